@@ -0,0 +1,300 @@
+//! Fetching GDScript addon dependencies into the godot project, see `GodotRunner::addon`.
+//!
+//! Each addon is fetched from a git repository or the Godot Asset Library, and its
+//! `addons/<name>/` folder is copied into the project's `addons/` folder before the import, so a
+//! fresh clone works without installing addons by hand. The fetched sources are recorded in
+//! `.godot/cargo_godot_lib/addons.json`, and an addon is only fetched again when its source
+//! changes or its folder is missing.
+//!
+//! Git sources need `git` on the `PATH`. Asset Library sources need the `download` feature.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let runner = runner
+//!     .addon(Addon::git("gut", "https://github.com/bitwes/Gut.git", "v9.5.0"))
+//!     .addon(Addon::asset_lib("dialogic", 2519, Some("2.0-alpha-17")));
+//! ```
+use crate::autoload::GENERATED_DIR;
+use anyhow::{Context, Result, anyhow};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// The file recording the fetched addon sources, relative to the godot project.
+const STATE_FILE: &str = "addons.json";
+
+/// Where an addon is fetched from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AddonSource {
+    /// A git repository at a branch, tag or commit.
+    Git { url: String, rev: String },
+    /// An asset of the Godot Asset Library. The library only serves the latest version of an
+    /// asset, so a pinned `version` fails once the asset is updated.
+    AssetLib { id: u32, version: Option<String> },
+}
+
+impl AddonSource {
+    /// A description of the source, recorded to detect changes.
+    fn key(&self) -> String {
+        match self {
+            Self::Git { url, rev } => format!("git {url}@{rev}"),
+            Self::AssetLib { id, version } => {
+                format!("asset-lib {id}@{}", version.as_deref().unwrap_or("latest"))
+            }
+        }
+    }
+}
+
+/// An addon dependency, installed into `addons/<name>/` of the godot project.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Addon {
+    name: String,
+    source: AddonSource,
+}
+
+impl Addon {
+    /// The addon `name` from the git repository at `url`, checked out at `rev`.
+    pub fn git(name: &str, url: &str, rev: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            source: AddonSource::Git {
+                url: url.to_string(),
+                rev: rev.to_string(),
+            },
+        }
+    }
+
+    /// The addon `name` from the Godot Asset Library asset `id`, optionally pinned to the
+    /// asset's `version`, e.g. `9.5.0`.
+    pub fn asset_lib(name: &str, id: u32, version: Option<&str>) -> Self {
+        Self {
+            name: name.to_string(),
+            source: AddonSource::AssetLib {
+                id,
+                version: version.map(str::to_string),
+            },
+        }
+    }
+
+    /// The name of the addon's folder in `addons/`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The source of the addon.
+    pub fn source(&self) -> &AddonSource {
+        &self.source
+    }
+
+    /// The folder of the addon in the godot project.
+    pub fn dir(&self, godot_project_path: &Path) -> PathBuf {
+        godot_project_path.join("addons").join(&self.name)
+    }
+
+    /// Fetch the addon and replace its folder in the godot project.
+    fn install(&self, godot_project_path: &Path) -> Result<()> {
+        let download = tempfile::tempdir().context("Failed to create temporary directory")?;
+        match &self.source {
+            AddonSource::Git { url, rev } => fetch_git(url, rev, download.path())?,
+            AddonSource::AssetLib { id, version } => {
+                fetch_asset_lib(*id, version.as_deref(), download.path())?
+            }
+        }
+        let source = find_addon_dir(download.path(), &self.name).with_context(|| {
+            format!(
+                "The fetched sources of addon {:?} contain no `addons/{}` folder",
+                self.name, self.name
+            )
+        })?;
+        let target = self.dir(godot_project_path);
+        if target.exists() {
+            std::fs::remove_dir_all(&target)
+                .with_context(|| format!("Failed to remove {target:?}"))?;
+        }
+        copy_dir(&source, &target)
+            .with_context(|| format!("Failed to copy addon {:?} to {target:?}", self.name))
+    }
+}
+
+/// Fetch the `addons` whose source changed or whose folder is missing.
+/// Returns the folders of the fetched addons.
+pub fn fetch(addons: &[Addon], godot_project_path: &Path) -> Result<Vec<PathBuf>> {
+    let state_path = godot_project_path.join(GENERATED_DIR).join(STATE_FILE);
+    let mut state: BTreeMap<String, String> = std::fs::read_to_string(&state_path)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default();
+    let mut fetched = vec![];
+    for addon in addons {
+        let key = addon.source.key();
+        let dir = addon.dir(godot_project_path);
+        if dir.is_dir() && state.get(&addon.name) == Some(&key) {
+            continue;
+        }
+        addon
+            .install(godot_project_path)
+            .with_context(|| format!("Failed to fetch addon {:?} ({key})", addon.name))?;
+        state.insert(addon.name.clone(), key);
+        fetched.push(dir);
+    }
+    if !fetched.is_empty() {
+        if let Some(parent) = state_path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {parent:?}"))?;
+        }
+        std::fs::write(&state_path, serde_json::to_string_pretty(&state)?)
+            .with_context(|| format!("Failed to write {state_path:?}"))?;
+    }
+    Ok(fetched)
+}
+
+/// Check out `rev` of the repository at `url` into `dir` without its history.
+fn fetch_git(url: &str, rev: &str, dir: &Path) -> Result<()> {
+    for args in [
+        vec!["init", "--quiet"],
+        vec!["fetch", "--quiet", "--depth", "1", url, rev],
+        vec!["checkout", "--quiet", "FETCH_HEAD"],
+    ] {
+        let mut command = Command::new("git");
+        command.arg("-C").arg(dir).args(&args).stdin(Stdio::null());
+        let status = command
+            .status()
+            .with_context(|| format!("Failed to run git: {command:?}"))?;
+        if !status.success() {
+            return Err(anyhow!("git {} failed with status `{status}`", args[0]));
+        }
+    }
+    Ok(())
+}
+
+/// Download and extract the Asset Library asset `id` into `dir`.
+#[cfg(feature = "download")]
+fn fetch_asset_lib(id: u32, version: Option<&str>, dir: &Path) -> Result<()> {
+    #[derive(serde::Deserialize)]
+    struct Asset {
+        version_string: String,
+        download_url: String,
+    }
+
+    let url = format!("https://godotengine.org/asset-library/api/asset/{id}");
+    let response = ureq::get(&url)
+        .call()
+        .with_context(|| format!("Failed to query the Asset Library: {url}"))?
+        .body_mut()
+        .read_to_string()
+        .with_context(|| format!("Failed to query the Asset Library: {url}"))?;
+    let asset: Asset = serde_json::from_str(&response)
+        .with_context(|| format!("Unexpected Asset Library response: {url}"))?;
+    if let Some(version) = version
+        && version != asset.version_string
+    {
+        return Err(anyhow!(
+            "The Asset Library serves version {:?} of asset {id}, not the pinned {version:?}. \
+            Update the pinned version or use a git source for older versions.",
+            asset.version_string
+        ));
+    }
+
+    let archive_path = dir.join("asset.zip");
+    let mut response = ureq::get(&asset.download_url)
+        .call()
+        .with_context(|| format!("Failed to download {}", asset.download_url))?;
+    let mut archive_file = std::fs::File::create(&archive_path)
+        .with_context(|| format!("Failed to create file: {archive_path:?}"))?;
+    std::io::copy(&mut response.body_mut().as_reader(), &mut archive_file)
+        .with_context(|| format!("Failed to download {}", asset.download_url))?;
+    drop(archive_file);
+
+    let archive_file = std::fs::File::open(&archive_path)
+        .with_context(|| format!("Failed to open archive: {archive_path:?}"))?;
+    zip::ZipArchive::new(archive_file)
+        .and_then(|mut archive| archive.extract(dir))
+        .with_context(|| format!("Failed to extract {}", asset.download_url))
+}
+
+#[cfg(not(feature = "download"))]
+fn fetch_asset_lib(id: u32, _version: Option<&str>, _dir: &Path) -> Result<()> {
+    Err(anyhow!(
+        "Fetching asset {id} from the Godot Asset Library requires the `download` feature \
+        of cargo-godot-lib"
+    ))
+}
+
+/// Find `addons/<name>` in `dir` or in one of its subfolders, e.g. the top-level folder of a
+/// downloaded archive.
+fn find_addon_dir(dir: &Path, name: &str) -> Option<PathBuf> {
+    let candidate = dir.join("addons").join(name);
+    if candidate.is_dir() {
+        return Some(candidate);
+    }
+    let mut subdirs: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    subdirs.sort();
+    subdirs.into_iter().find_map(|subdir| {
+        let candidate = subdir.join("addons").join(name);
+        candidate.is_dir().then_some(candidate)
+    })
+}
+
+fn copy_dir(source: &Path, target: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(target)?;
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.is_dir() {
+            copy_dir(&path, &target.join(entry.file_name()))?;
+        } else {
+            std::fs::copy(&path, target.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    #[test]
+    fn test_fetch_git() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        std::fs::create_dir_all(repo.join("addons/tool/icons")).unwrap();
+        std::fs::write(repo.join("addons/tool/plugin.cfg"), "[plugin]\n").unwrap();
+        std::fs::write(repo.join("addons/tool/icons/icon.svg"), "<svg/>").unwrap();
+        git(&repo, &["init", "--quiet"]);
+        git(&repo, &["add", "-A"]);
+        git(&repo, &["commit", "--quiet", "-m", "Add tool"]);
+        git(&repo, &["tag", "v1"]);
+
+        let project = dir.path().join("godot");
+        std::fs::create_dir_all(&project).unwrap();
+        let url = format!("file://{}", repo.display());
+        let addons = [Addon::git("tool", &url, "v1")];
+        assert_eq!(
+            fetch(&addons, &project).unwrap(),
+            vec![project.join("addons/tool")]
+        );
+        assert!(project.join("addons/tool/icons/icon.svg").exists());
+        assert!(fetch(&addons, &project).unwrap().is_empty());
+
+        std::fs::remove_dir_all(project.join("addons/tool")).unwrap();
+        assert_eq!(fetch(&addons, &project).unwrap().len(), 1);
+        assert!(fetch(&[Addon::git("missing", &url, "v1")], &project).is_err());
+    }
+}
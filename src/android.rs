@@ -0,0 +1,250 @@
+//! Building the extension for Android with the NDK, see `AndroidBuild`.
+//!
+//! The NDK is found from `ANDROID_NDK_HOME`, `ANDROID_NDK_ROOT` or `NDK_HOME`, or as the newest
+//! NDK installed in the SDK of `ANDROID_HOME` or `ANDROID_SDK_ROOT`. Cargo is run with the NDK's
+//! clang as the C compiler and linker of each target, so crates with C code build too.
+//! The libraries are added to the `android.*` entries of the `.gdextension` file with
+//! `CrossBuildOutput::gdextension_config`, which requires `Platform::Android`.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let output = AndroidBuild::new(CargoBuild::default().release())
+//!     .targets(["aarch64-linux-android", "x86_64-linux-android"])
+//!     .build(Path::new("rust/Cargo.toml"))?;
+//! let runner = GodotRunner::create("game", Path::new("godot")).gdextension_config(move |config| {
+//!     let config = config.platforms(&[Platform::Linux, Platform::Android]);
+//!     output.gdextension_config("game", config)
+//! });
+//! ```
+use crate::cargo::{self, CargoBuild};
+use crate::cross_build::CrossBuildOutput;
+use anyhow::{Context, Result, anyhow};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The Android target triples, in the order of the `android.*` `.gdextension` entries.
+pub const ANDROID_TRIPLES: [&str; 4] = [
+    "aarch64-linux-android",
+    "armv7-linux-androideabi",
+    "x86_64-linux-android",
+    "i686-linux-android",
+];
+
+/// The default minimum Android API level, the minimum SDK of Godot 4.3 and newer.
+pub const DEFAULT_API_LEVEL: u32 = 24;
+
+/// Options for building the extension for Android, see the module documentation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AndroidBuild {
+    cargo_build: CargoBuild,
+    ndk: Option<PathBuf>,
+    api_level: u32,
+    targets: Vec<String>,
+}
+
+impl AndroidBuild {
+    /// Build every target with the options of `cargo_build`. Its `target` is overridden.
+    pub fn new(cargo_build: CargoBuild) -> Self {
+        Self {
+            cargo_build: cargo_build.macos_universal(false),
+            ndk: None,
+            api_level: DEFAULT_API_LEVEL,
+            targets: vec![],
+        }
+    }
+
+    /// The NDK to build with. Default: found with `ndk_path`.
+    pub fn ndk(self, ndk: &Path) -> Self {
+        Self {
+            ndk: Some(ndk.to_path_buf()),
+            ..self
+        }
+    }
+
+    /// The minimum Android API level to link against. Default: `DEFAULT_API_LEVEL`.
+    pub fn api_level(self, api_level: u32) -> Self {
+        Self { api_level, ..self }
+    }
+
+    /// Add target triples to build for, see `ANDROID_TRIPLES`.
+    /// Default: `aarch64-linux-android`, the architecture Godot exports by default.
+    pub fn targets<S: Into<String>>(mut self, targets: impl IntoIterator<Item = S>) -> Self {
+        self.targets.extend(targets.into_iter().map(Into::into));
+        self
+    }
+
+    /// Build the package at `manifest_path` for every target, one after another.
+    /// Requires the rustup targets, e.g. `rustup target add aarch64-linux-android`.
+    pub fn build(&self, manifest_path: &Path) -> Result<CrossBuildOutput> {
+        let ndk = match &self.ndk {
+            Some(ndk) => ndk.clone(),
+            None => ndk_path()?,
+        };
+        let toolchain = toolchain_bin(&ndk);
+        if !toolchain.is_dir() {
+            return Err(anyhow!(
+                "The NDK toolchain {toolchain:?} does not exist, is {ndk:?} an Android NDK?"
+            ));
+        }
+        let targets = if self.targets.is_empty() {
+            vec![ANDROID_TRIPLES[0].to_string()]
+        } else {
+            self.targets.clone()
+        };
+        let mut artifacts = vec![];
+        for target in &targets {
+            if !ANDROID_TRIPLES.contains(&target.as_str()) {
+                return Err(anyhow!(
+                    "Unknown Android target {target:?}, expected one of {ANDROID_TRIPLES:?}"
+                ));
+            }
+            let mut command =
+                Command::new(std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into()));
+            command
+                .args(
+                    self.cargo_build
+                        .clone()
+                        .target(target)
+                        .cli_arguments(manifest_path),
+                )
+                .envs(self.cargo_build.env())
+                .envs(toolchain_env(&toolchain, target, self.api_level));
+            artifacts.extend(
+                cargo::run_build(command, "cargo build", target)
+                    .with_context(|| format!("Failed to build for {target}"))?,
+            );
+        }
+        Ok(CrossBuildOutput {
+            build: self.cargo_build.gdextension_build(),
+            artifacts,
+        })
+    }
+}
+
+/// The Android NDK from the environment, see the module documentation.
+pub fn ndk_path() -> Result<PathBuf> {
+    for var in ["ANDROID_NDK_HOME", "ANDROID_NDK_ROOT", "NDK_HOME"] {
+        if let Some(ndk) = std::env::var_os(var) {
+            return Ok(PathBuf::from(ndk));
+        }
+    }
+    for var in ["ANDROID_HOME", "ANDROID_SDK_ROOT"] {
+        if let Some(sdk) = std::env::var_os(var)
+            && let Some(ndk) = newest_ndk(Path::new(&sdk))
+        {
+            return Ok(ndk);
+        }
+    }
+    Err(anyhow!(
+        "Android NDK not found. Set ANDROID_NDK_HOME, or install the NDK with the SDK manager \
+        and set ANDROID_HOME"
+    ))
+}
+
+/// The newest NDK in `sdk/ndk/<version>`, or the legacy `sdk/ndk-bundle`.
+fn newest_ndk(sdk: &Path) -> Option<PathBuf> {
+    let version = |path: &Path| -> Vec<u64> {
+        path.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    std::fs::read_dir(sdk.join("ndk"))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.is_dir())
+        .max_by_key(|path| version(path))
+        .or_else(|| Some(sdk.join("ndk-bundle")).filter(|path| path.is_dir()))
+}
+
+/// The directory of the NDK's clang for this host.
+fn toolchain_bin(ndk: &Path) -> PathBuf {
+    // The NDK only ships x86_64 host toolchains, which run on Apple Silicon with Rosetta.
+    let host = if cfg!(target_os = "windows") {
+        "windows-x86_64"
+    } else if cfg!(target_os = "macos") {
+        "darwin-x86_64"
+    } else {
+        "linux-x86_64"
+    };
+    ndk.join("toolchains/llvm/prebuilt").join(host).join("bin")
+}
+
+/// The environment variables selecting the NDK's compilers and linker for `target`, for cargo
+/// and the `cc` crate.
+fn toolchain_env(toolchain: &Path, target: &str, api_level: u32) -> Vec<(String, OsString)> {
+    // Clang's target for 32-bit ARM differs from rustc's.
+    let clang_target = match target {
+        "armv7-linux-androideabi" => "armv7a-linux-androideabi",
+        target => target,
+    };
+    let script = if cfg!(target_os = "windows") {
+        ".cmd"
+    } else {
+        ""
+    };
+    let clang = |name: &str| -> OsString {
+        toolchain
+            .join(format!("{clang_target}{api_level}-{name}{script}"))
+            .into()
+    };
+    let env_target = target.replace('-', "_");
+    vec![
+        (format!("CC_{env_target}"), clang("clang")),
+        (format!("CXX_{env_target}"), clang("clang++")),
+        (
+            format!("AR_{env_target}"),
+            toolchain
+                .join(format!("llvm-ar{}", std::env::consts::EXE_SUFFIX))
+                .into(),
+        ),
+        (
+            format!("CARGO_TARGET_{}_LINKER", env_target.to_uppercase()),
+            clang("clang"),
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_android_toolchain() {
+        let dir = tempfile::tempdir().unwrap();
+        let sdk = dir.path();
+        assert_eq!(newest_ndk(sdk), None);
+        for version in ["26.3.11579264", "27.0.12077973", "9.0.0"] {
+            std::fs::create_dir_all(sdk.join("ndk").join(version)).unwrap();
+        }
+        let ndk = newest_ndk(sdk).unwrap();
+        assert_eq!(ndk, sdk.join("ndk/27.0.12077973"));
+
+        let toolchain = toolchain_bin(&ndk);
+        let env = toolchain_env(&toolchain, "armv7-linux-androideabi", 24);
+        let script = if cfg!(target_os = "windows") {
+            ".cmd"
+        } else {
+            ""
+        };
+        let clang = toolchain.join(format!("armv7a-linux-androideabi24-clang{script}"));
+        assert!(env.contains(&(
+            "CC_armv7_linux_androideabi".to_string(),
+            clang.clone().into()
+        )));
+        assert!(env.contains(&(
+            "CARGO_TARGET_ARMV7_LINUX_ANDROIDEABI_LINKER".to_string(),
+            clang.into()
+        )));
+
+        let error = AndroidBuild::new(CargoBuild::default())
+            .ndk(&ndk)
+            .build(Path::new("Cargo.toml"))
+            .unwrap_err();
+        assert!(format!("{error:#}").contains("an Android NDK?"));
+    }
+}
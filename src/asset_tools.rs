@@ -0,0 +1,193 @@
+//! Built-in `pipeline` steps exporting the sources of common asset tools into the godot project:
+//! Aseprite spritesheets with `aseprite --batch` and Blender scenes as glTF with
+//! `blender --background`.
+//!
+//! Every source file in the source directory is exported to the same relative path in the output
+//! directory, both relative to the godot project. The steps are skipped while the sources are
+//! unchanged, see `pipeline`. The tools are found with the `ASEPRITE` and `BLENDER` environment
+//! variables, or in the `PATH`.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let pipeline = Pipeline::default()
+//!     .step(asset_tools::aseprite("sprites", "../art/sprites", "sprites"))
+//!     .step(asset_tools::blender("models", "../art/models", "models"));
+//! let runner = GodotRunner::create("game", Path::new("godot")).pipeline(pipeline);
+//! ```
+use crate::pipeline::PipelineStep;
+use crate::state;
+use anyhow::{Context, Result, anyhow};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// The Blender script exporting the open scene to the path after `--`.
+const BLENDER_EXPORT_SCRIPT: &str = "import bpy, sys; \
+    bpy.ops.export_scene.gltf(filepath=sys.argv[sys.argv.index('--') + 1], export_format='GLB')";
+
+/// A step exporting every `.aseprite` and `.ase` file in `source_dir` to a `.png` spritesheet
+/// with a `.json` file of the frames and tags in `output_dir`.
+pub fn aseprite(name: &str, source_dir: &str, output_dir: &str) -> PipelineStep {
+    let (source, output) = (PathBuf::from(source_dir), PathBuf::from(output_dir));
+    PipelineStep::new(name, move |project| {
+        let tool = find_tool("ASEPRITE", "aseprite")?;
+        export_all(
+            project,
+            &source,
+            &output,
+            &["aseprite", "ase"],
+            |file, output| aseprite_command(&tool, file, output),
+        )
+    })
+    .inputs([source_dir])
+    .outputs([output_dir])
+}
+
+/// A step exporting every `.blend` file in `source_dir` to a binary glTF `.glb` file in
+/// `output_dir`.
+pub fn blender(name: &str, source_dir: &str, output_dir: &str) -> PipelineStep {
+    let (source, output) = (PathBuf::from(source_dir), PathBuf::from(output_dir));
+    PipelineStep::new(name, move |project| {
+        let tool = find_tool("BLENDER", "blender")?;
+        export_all(project, &source, &output, &["blend"], |file, output| {
+            blender_command(&tool, file, output)
+        })
+    })
+    .inputs([source_dir])
+    .outputs([output_dir])
+}
+
+/// The tool from the environment variable `env_var`, or `name` in the `PATH`.
+fn find_tool(env_var: &str, name: &str) -> Result<PathBuf> {
+    match std::env::var_os(env_var) {
+        Some(tool) => Ok(PathBuf::from(tool)),
+        None => which::which(name)
+            .with_context(|| format!("{name} not found, add it to the PATH or set {env_var}")),
+    }
+}
+
+/// Run the command of `command` for every source file with one of the `extensions`, creating
+/// the output directories. `command` gets the source file and its output path without extension.
+fn export_all(
+    project: &Path,
+    source_dir: &Path,
+    output_dir: &Path,
+    extensions: &[&str],
+    command: impl Fn(&Path, &Path) -> Command,
+) -> Result<()> {
+    let source_dir = project.join(source_dir);
+    let mut files = vec![];
+    state::find_project_files(&source_dir, &mut files)?;
+    for file in files.iter().filter(|file| {
+        file.extension()
+            .is_some_and(|extension| extensions.iter().any(|e| extension == *e))
+    }) {
+        let relative = file.strip_prefix(&source_dir).unwrap_or(file);
+        let output = project.join(output_dir).join(relative).with_extension("");
+        if let Some(parent) = output.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {parent:?}"))?;
+        }
+        let mut command = command(file, &output);
+        command.stdin(Stdio::null());
+        let status = command
+            .status()
+            .with_context(|| format!("Failed to run {command:?}"))?;
+        if !status.success() {
+            return Err(anyhow!(
+                "Failed to export {file:?}, {command:?} exited with {status}"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// `path` with `suffix` appended, unlike `with_extension` keeping dots in the file name.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// The command exporting the spritesheet `<output>.png` and its data `<output>.json`.
+fn aseprite_command(tool: &Path, file: &Path, output: &Path) -> Command {
+    let mut command = Command::new(tool);
+    command
+        .arg("--batch")
+        .arg(file)
+        .arg("--sheet")
+        .arg(with_suffix(output, ".png"))
+        .arg("--data")
+        .arg(with_suffix(output, ".json"))
+        .args(["--format", "json-array", "--list-tags"]);
+    command
+}
+
+/// The command exporting the scene to `<output>.glb`.
+fn blender_command(tool: &Path, file: &Path, output: &Path) -> Command {
+    let mut command = Command::new(tool);
+    command
+        .arg("--background")
+        .arg(file)
+        .args(["--python-expr", BLENDER_EXPORT_SCRIPT, "--"])
+        .arg(with_suffix(output, ".glb"));
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_asset_tools() {
+        let args = |command: &Command| -> Vec<String> {
+            command
+                .get_args()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect()
+        };
+        let output = Path::new("sprites/hero");
+        let command = aseprite_command(Path::new("aseprite"), Path::new("hero.aseprite"), output);
+        assert_eq!(
+            args(&command)[..5],
+            [
+                "--batch",
+                "hero.aseprite",
+                "--sheet",
+                "sprites/hero.png",
+                "--data"
+            ]
+        );
+        let output = Path::new("models/ship.v2");
+        let command = blender_command(Path::new("blender"), Path::new("ship.v2.blend"), output);
+        assert_eq!(args(&command).last().unwrap(), "models/ship.v2.glb");
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("art/characters")).unwrap();
+        std::fs::write(dir.path().join("art/characters/hero.ase"), "").unwrap();
+        std::fs::write(dir.path().join("art/notes.txt"), "").unwrap();
+        let exported = std::cell::RefCell::new(vec![]);
+        export_all(
+            dir.path(),
+            Path::new("art"),
+            Path::new("sprites"),
+            &["ase"],
+            |file, output| {
+                exported
+                    .borrow_mut()
+                    .push((file.to_path_buf(), output.to_path_buf()));
+                let mut command = Command::new(env!("CARGO"));
+                command.arg("--version").stdout(Stdio::null());
+                command
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            exported.into_inner(),
+            [(
+                dir.path().join("art/characters/hero.ase"),
+                dir.path().join("sprites/characters/hero")
+            )]
+        );
+        assert!(dir.path().join("sprites/characters").is_dir());
+    }
+}
@@ -0,0 +1,223 @@
+//! A static audit of the `res://` references in a godot project, without running Godot.
+//!
+//! Scenes, resources, scripts, shaders and `project.godot` are scanned for `"res://..."`
+//! strings to build a dependency graph of the project. The audit reports references to files
+//! which don't exist, e.g. after moving an asset outside the editor, `.import` files whose
+//! source asset is gone, and orphaned assets which no file references. Scripts can be used via
+//! `class_name` without being referenced, so they are never reported as orphaned. References by
+//! `uid://` alone are not followed.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let report = audit::audit(Path::new("godot"))?;
+//! for missing in &report.missing {
+//!     eprintln!("{missing}");
+//! }
+//! ```
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+/// Files scanned for references, by extension.
+const SCANNED_EXTENSIONS: [&str; 10] = [
+    "tscn",
+    "tres",
+    "gd",
+    "cs",
+    "gdshader",
+    "gdshaderinc",
+    "godot",
+    "gdextension",
+    "cfg",
+    "import",
+];
+
+/// Files reported as orphaned if nothing references them, by extension.
+const ASSET_EXTENSIONS: [&str; 22] = [
+    "tscn", "scn", "tres", "res", "gdshader", "png", "jpg", "jpeg", "webp", "svg", "wav", "ogg",
+    "mp3", "glb", "gltf", "fbx", "obj", "blend", "ttf", "otf", "woff", "woff2",
+];
+
+/// A `res://` reference to a file which doesn't exist.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize)]
+pub struct MissingReference {
+    /// The `res://` path of the referencing file.
+    pub file: String,
+    /// The missing `res://` path.
+    pub reference: String,
+}
+
+impl Display for MissingReference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} references missing {}", self.file, self.reference)
+    }
+}
+
+/// The result of `audit`.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct AuditReport {
+    /// The `res://` paths referenced by each scanned file.
+    pub dependencies: BTreeMap<String, BTreeSet<String>>,
+    /// References to files which don't exist.
+    pub missing: Vec<MissingReference>,
+    /// Assets which no file references.
+    pub orphaned: Vec<String>,
+}
+
+impl AuditReport {
+    /// Returns true if there are neither missing references nor orphaned assets.
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.orphaned.is_empty()
+    }
+}
+
+/// Audit the `res://` references of the godot project. Hidden folders such as `.godot` are
+/// skipped.
+pub fn audit(godot_project_path: &Path) -> Result<AuditReport> {
+    let mut files = vec![];
+    find_files(godot_project_path, "", &mut files)?;
+    let existing: BTreeSet<&str> = files.iter().map(String::as_str).collect();
+
+    let mut report = AuditReport::default();
+    let mut referenced = BTreeSet::new();
+    for file in &files {
+        if !has_extension(file, &SCANNED_EXTENSIONS) {
+            continue;
+        }
+        let path = godot_project_path.join(file.trim_start_matches("res://"));
+        // Skip binary files, e.g. a `.res` renamed to `.tres`.
+        let Ok(contents) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let references = find_references(&contents);
+        for reference in &references {
+            if !existing.contains(reference.as_str()) {
+                report.missing.push(MissingReference {
+                    file: file.clone(),
+                    reference: reference.clone(),
+                });
+            }
+        }
+        // An `.import` file belongs to its asset and doesn't keep it in use.
+        if !file.ends_with(".import") {
+            referenced.extend(references.iter().cloned());
+            report.dependencies.insert(file.clone(), references);
+        }
+    }
+    report.orphaned = files
+        .iter()
+        .filter(|file| has_extension(file, &ASSET_EXTENSIONS) && !referenced.contains(*file))
+        .cloned()
+        .collect();
+    Ok(report)
+}
+
+/// The `res://` paths of the files in `dir`, relative to the project root `prefix`.
+pub(crate) fn find_files(dir: &Path, prefix: &str, files: &mut Vec<String>) -> Result<()> {
+    let mut entries = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory: {dir:?}"))?
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        let relative = format!("{prefix}{name}");
+        if entry.path().is_dir() {
+            find_files(&entry.path(), &format!("{relative}/"), files)?;
+        } else if !name.ends_with(".uid") {
+            files.push(format!("res://{relative}"));
+        }
+    }
+    Ok(())
+}
+
+/// The quoted `res://` paths in `contents`, without sub-resource suffixes like `::1`.
+/// Paths into hidden folders, e.g. the imported files in `.godot`, are skipped.
+fn find_references(contents: &str) -> BTreeSet<String> {
+    contents
+        .split("\"res://")
+        .skip(1)
+        .filter_map(|rest| {
+            let path = &rest[..rest.find('"')?];
+            let path = path.split("::").next().unwrap_or(path);
+            let hidden = path.split('/').any(|component| component.starts_with('.'));
+            (!path.is_empty() && !hidden).then(|| format!("res://{path}"))
+        })
+        .collect()
+}
+
+fn has_extension(file: &str, extensions: &[&str]) -> bool {
+    Path::new(file)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.contains(&ext.to_lowercase().as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path();
+        std::fs::create_dir_all(project.join("scenes")).unwrap();
+        std::fs::create_dir_all(project.join(".godot/imported")).unwrap();
+        let files = [
+            (
+                "project.godot",
+                "[application]\n\nrun/main_scene=\"res://scenes/main.tscn\"\n",
+            ),
+            (
+                "scenes/main.tscn",
+                "[gd_scene format=3]\n\n\
+                [ext_resource type=\"Texture2D\" uid=\"uid://abc\" path=\"res://icon.svg\" id=\"1\"]\n\
+                [ext_resource type=\"Script\" path=\"res://scenes/main.gd\" id=\"2\"]\n\
+                [ext_resource type=\"AudioStream\" path=\"res://sounds/jump.ogg\" id=\"3\"]\n",
+            ),
+            (
+                "scenes/main.gd",
+                "const ENEMY = preload(\"res://scenes/enemy.tscn::1\")\n",
+            ),
+            ("scenes/enemy.tscn", "[gd_scene format=3]\n"),
+            ("scenes/unused.gd", "extends Node\n"),
+            ("icon.svg", "<svg/>"),
+            (
+                "icon.svg.import",
+                "[deps]\n\nsource_file=\"res://icon.svg\"\n\
+                dest_files=[\"res://.godot/imported/icon.svg-218a.ctex\"]\n",
+            ),
+            (
+                "old.png.import",
+                "[deps]\n\nsource_file=\"res://old.png\"\n",
+            ),
+            ("unused.png", ""),
+            (".godot/imported/cache.tscn", "\"res://gone.tscn\""),
+        ];
+        for (file, contents) in files {
+            std::fs::write(project.join(file), contents).unwrap();
+        }
+
+        let report = audit(project).unwrap();
+        assert_eq!(
+            report.missing,
+            vec![
+                MissingReference {
+                    file: "res://old.png.import".to_string(),
+                    reference: "res://old.png".to_string(),
+                },
+                MissingReference {
+                    file: "res://scenes/main.tscn".to_string(),
+                    reference: "res://sounds/jump.ogg".to_string(),
+                },
+            ]
+        );
+        assert_eq!(report.orphaned, vec!["res://unused.png"]);
+        assert!(report.dependencies["res://scenes/main.gd"].contains("res://scenes/enemy.tscn"));
+        assert!(!report.is_clean());
+    }
+}
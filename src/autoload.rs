@@ -0,0 +1,219 @@
+//! Temporary autoloads injected into a project for a single run, see
+//! `GodotRunner::temporary_autoload`.
+//!
+//! Scripts and scenes which are not part of the project are copied to
+//! `.godot/cargo_godot_lib/autoloads/` and registered in the project's `override.cfg`, so the
+//! project files are not modified. Both are removed again once Godot exits.
+use crate::project_overrides::ProjectOverrides;
+use anyhow::{Context, Result, anyhow};
+use std::path::{Path, PathBuf};
+
+/// The directory for files generated by this crate, relative to the godot project.
+pub(crate) const GENERATED_DIR: &str = ".godot/cargo_godot_lib";
+
+/// Prints the scene tree after `--dump-scene-tree-frames` frames and quits.
+const SCENE_TREE_DUMP_SCRIPT: &str = r#"extends Node
+
+# Injected by cargo-godot-lib to dump the scene tree.
+
+func _ready() -> void:
+	var frames := 1
+	for arg in OS.get_cmdline_user_args():
+		if arg.begins_with("--dump-scene-tree-frames="):
+			frames = int(arg.trim_prefix("--dump-scene-tree-frames="))
+	for i in frames:
+		await get_tree().process_frame
+	get_tree().root.print_tree_pretty()
+	get_tree().quit()
+"#;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum AutoloadSource {
+    /// GDScript source code.
+    Script(String),
+    /// A `.gd` script or `.tscn` scene file outside of the project.
+    File(PathBuf),
+    /// A script or scene in the project.
+    ResPath(String),
+}
+
+/// An autoload registered for a single run.
+///
+/// Example usage:
+/// ```rust,ignore
+/// let runner = runner.temporary_autoload(TemporaryAutoload::script(
+///     "TestDriver",
+///     "extends Node\n\nfunc _ready():\n\tprint(get_tree().current_scene.name)\n\tget_tree().quit()\n",
+/// ));
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TemporaryAutoload {
+    name: String,
+    source: AutoloadSource,
+}
+
+impl TemporaryAutoload {
+    /// An autoload named `name` running the GDScript `source`, which must extend `Node`.
+    pub fn script(name: impl Into<String>, source: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            source: AutoloadSource::Script(source.into()),
+        }
+    }
+
+    /// An autoload named `name` for a `.gd` script or `.tscn` scene file outside of the project.
+    /// Scenes may only reference resources of the project by their `res://` path.
+    pub fn file(name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            name: name.into(),
+            source: AutoloadSource::File(path.into()),
+        }
+    }
+
+    /// An autoload named `name` for a script or scene already in the project,
+    /// e.g. `res_path("TestDriver", "res://tests/driver.gd")`.
+    pub fn res_path(name: impl Into<String>, res_path: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            source: AutoloadSource::ResPath(res_path.into()),
+        }
+    }
+
+    /// A bundled autoload which prints the scene tree once the main scene ran for `frames`
+    /// frames, then quits. Add `--dump-scene-tree-frames=N` to the user arguments to override
+    /// `frames` without rebuilding the runner.
+    pub fn scene_tree_dump(frames: u32) -> Self {
+        Self::script(
+            "CargoGodotLibSceneTreeDump",
+            SCENE_TREE_DUMP_SCRIPT.replace("var frames := 1", &format!("var frames := {frames}")),
+        )
+    }
+
+    /// The name of the autoload node, e.g. `/root/TestDriver`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Write the autoload's file to the project if needed and return its `res://` path.
+    fn install(&self, godot_project_path: &Path, files: &mut AutoloadFiles) -> Result<String> {
+        let (contents, extension) = match &self.source {
+            AutoloadSource::ResPath(res_path) => return Ok(res_path.clone()),
+            AutoloadSource::Script(source) => (source.clone().into_bytes(), "gd".to_string()),
+            AutoloadSource::File(path) => {
+                let extension = path
+                    .extension()
+                    .map(|ext| ext.to_string_lossy().into_owned())
+                    .ok_or_else(|| anyhow!("Autoload file has no extension: {path:?}"))?;
+                let contents = std::fs::read(path)
+                    .with_context(|| format!("Failed to read autoload file: {path:?}"))?;
+                (contents, extension)
+            }
+        };
+
+        // Include the process id so concurrent runs don't remove each other's files.
+        let relative_path = format!(
+            "{GENERATED_DIR}/autoloads/{}_{}.{extension}",
+            self.name,
+            std::process::id()
+        );
+        let path = godot_project_path.join(&relative_path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {parent:?}"))?;
+        }
+        std::fs::write(&path, contents)
+            .with_context(|| format!("Failed to write autoload {}: {path:?}", self.name))?;
+        files.paths.push(path);
+        Ok(format!("res://{relative_path}"))
+    }
+}
+
+/// Removes the files written for temporary autoloads when dropped.
+#[derive(Debug, Default)]
+pub struct AutoloadFiles {
+    paths: Vec<PathBuf>,
+}
+
+impl AutoloadFiles {
+    /// The written files.
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+}
+
+impl Drop for AutoloadFiles {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            if let Err(e) = std::fs::remove_file(path) {
+                eprintln!("Warning: Failed to remove autoload file {path:?}: {e}");
+            }
+        }
+    }
+}
+
+/// Write the files of `autoloads` to the godot project. Returns the overrides registering
+/// them and a guard removing the files again.
+pub fn install(
+    autoloads: &[TemporaryAutoload],
+    godot_project_path: &Path,
+) -> Result<(ProjectOverrides, AutoloadFiles)> {
+    let mut files = AutoloadFiles::default();
+    let mut overrides = ProjectOverrides::default();
+    for autoload in autoloads {
+        let res_path = autoload.install(godot_project_path, &mut files)?;
+        overrides = overrides.autoload(&autoload.name, &res_path);
+    }
+    Ok((overrides, files))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_install() {
+        let dir = tempfile::tempdir().unwrap();
+        let scene = dir.path().join("driver.tscn");
+        std::fs::write(
+            &scene,
+            "[gd_scene format=3]\n\n[node name=\"Driver\" type=\"Node\"]\n",
+        )
+        .unwrap();
+
+        let autoloads = [
+            TemporaryAutoload::script("Driver", "extends Node\n"),
+            TemporaryAutoload::file("Scene", &scene),
+            TemporaryAutoload::res_path("Existing", "res://existing.gd"),
+            TemporaryAutoload::scene_tree_dump(5),
+        ];
+        let (overrides, files) = install(&autoloads, dir.path()).unwrap();
+        assert_eq!(files.paths().len(), 3);
+        let id = std::process::id();
+        let driver = dir
+            .path()
+            .join(format!("{GENERATED_DIR}/autoloads/Driver_{id}.gd"));
+        assert_eq!(std::fs::read_to_string(&driver).unwrap(), "extends Node\n");
+        let dump = std::fs::read_to_string(&files.paths()[2]).unwrap();
+        assert!(dump.contains("var frames := 5"));
+
+        let config = overrides.create();
+        assert!(config.contains(&format!(
+            "Driver=\"*res://{GENERATED_DIR}/autoloads/Driver_{id}.gd\""
+        )));
+        assert!(config.contains(&format!(
+            "Scene=\"*res://{GENERATED_DIR}/autoloads/Scene_{id}.tscn\""
+        )));
+        assert!(config.contains("Existing=\"*res://existing.gd\""));
+
+        let paths = files.paths().to_vec();
+        drop(files);
+        assert!(paths.iter().all(|path| !path.exists()));
+        assert!(
+            install(
+                &[TemporaryAutoload::file("Missing", "missing.gd")],
+                dir.path()
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,251 @@
+//! Startup benchmarking of a Godot project, see `GodotRunner::benchmark`.
+//!
+//! Godot is launched with `--benchmark-file` to record its own startup timings, and its output is
+//! watched for a marker line printed by the project, e.g. from the first `_process` call:
+//! ```rust,ignore
+//! godot_print!("{}", cargo_godot_lib::benchmark::DEFAULT_FIRST_FRAME_MARKER);
+//! ```
+use crate::exit_status::GodotExitStatus;
+use crate::output::OutputCallback;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The default line the project prints once its first frame is rendered.
+pub const DEFAULT_FIRST_FRAME_MARKER: &str = "cargo-godot-lib: first frame";
+
+/// Options for `GodotRunner::benchmark`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BenchmarkOptions {
+    profiling: bool,
+    gpu_profile: bool,
+    startup_benchmark: bool,
+    quit_after: Option<u32>,
+    first_frame_marker: String,
+}
+
+impl Default for BenchmarkOptions {
+    fn default() -> Self {
+        Self {
+            profiling: false,
+            gpu_profile: false,
+            startup_benchmark: true,
+            quit_after: Some(1),
+            first_frame_marker: DEFAULT_FIRST_FRAME_MARKER.to_string(),
+        }
+    }
+}
+
+impl BenchmarkOptions {
+    /// Enable profiling in the script debugger (`--profiling`). Default: false.
+    pub fn profiling(self, profiling: bool) -> Self {
+        Self { profiling, ..self }
+    }
+
+    /// Print the GPU tasks that took the most time each frame (`--gpu-profile`). Default: false.
+    pub fn gpu_profile(self, gpu_profile: bool) -> Self {
+        Self {
+            gpu_profile,
+            ..self
+        }
+    }
+
+    /// Record Godot's own startup timings (`--benchmark-file`, Godot 4.2+). Default: true.
+    pub fn startup_benchmark(self, startup_benchmark: bool) -> Self {
+        Self {
+            startup_benchmark,
+            ..self
+        }
+    }
+
+    /// Quit after the given number of frames (`--quit-after`), or run until Godot exits
+    /// if `None`. Default: `Some(1)`, so the total duration is the time to the first frame.
+    pub fn quit_after(self, quit_after: Option<u32>) -> Self {
+        Self { quit_after, ..self }
+    }
+
+    /// The output line which marks the first frame. Default: `DEFAULT_FIRST_FRAME_MARKER`.
+    pub fn first_frame_marker(self, first_frame_marker: impl Into<String>) -> Self {
+        Self {
+            first_frame_marker: first_frame_marker.into(),
+            ..self
+        }
+    }
+
+    /// The Godot CLI flags and their values for these options.
+    pub(crate) fn cli_arguments(&self, benchmark_file: &Path) -> Vec<Vec<String>> {
+        let mut flags = vec![];
+        if self.profiling {
+            flags.push(vec!["--profiling".to_string()]);
+        }
+        if self.gpu_profile {
+            flags.push(vec!["--gpu-profile".to_string()]);
+        }
+        if self.startup_benchmark {
+            flags.push(vec![
+                "--benchmark-file".to_string(),
+                benchmark_file.to_string_lossy().into_owned(),
+            ]);
+        }
+        if let Some(frames) = self.quit_after {
+            flags.push(vec!["--quit-after".to_string(), frames.to_string()]);
+        }
+        flags
+    }
+}
+
+/// Timing data of a benchmark run.
+#[derive(Clone, Debug, Serialize)]
+pub struct BenchmarkReport {
+    /// How Godot exited.
+    pub exit_status: GodotExitStatus,
+    /// Time from launch until Godot printed its first line of output.
+    pub time_to_first_output: Option<Duration>,
+    /// Time from launch until the first frame marker was printed.
+    pub time_to_first_frame: Option<Duration>,
+    /// Time from launch until Godot exited.
+    pub total: Duration,
+    /// Godot's startup timings in seconds, keyed by benchmark mark, e.g. `[Startup] Servers`.
+    pub startup_marks: BTreeMap<String, f64>,
+}
+
+/// Records when lines of interest appear in Godot's output.
+#[derive(Clone, Debug)]
+pub(crate) struct OutputTimer {
+    start: Instant,
+    first_output: Arc<Mutex<Option<Duration>>>,
+    first_frame: Arc<Mutex<Option<Duration>>>,
+}
+
+impl OutputTimer {
+    pub(crate) fn start() -> Self {
+        Self {
+            start: Instant::now(),
+            first_output: Arc::default(),
+            first_frame: Arc::default(),
+        }
+    }
+
+    /// A callback recording the time of the first line and the first frame marker of `options`.
+    pub(crate) fn callback(&self, options: &BenchmarkOptions) -> OutputCallback {
+        let timer = self.clone();
+        let marker = options.first_frame_marker.clone();
+        Arc::new(move |line| {
+            let elapsed = timer.start.elapsed();
+            if let Ok(mut first_output) = timer.first_output.lock() {
+                first_output.get_or_insert(elapsed);
+            }
+            if line.contains(&marker)
+                && let Ok(mut first_frame) = timer.first_frame.lock()
+            {
+                first_frame.get_or_insert(elapsed);
+            }
+        })
+    }
+
+    /// Create the report once Godot exited.
+    pub(crate) fn finish(
+        &self,
+        exit_status: GodotExitStatus,
+        benchmark_file: &Path,
+    ) -> Result<BenchmarkReport> {
+        let total = self.start.elapsed();
+        let startup_marks = if benchmark_file.exists() {
+            let marks = read_startup_marks(benchmark_file);
+            let _ = std::fs::remove_file(benchmark_file);
+            marks?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(BenchmarkReport {
+            exit_status,
+            time_to_first_output: *self.first_output.lock().unwrap_or_else(|e| e.into_inner()),
+            time_to_first_frame: *self.first_frame.lock().unwrap_or_else(|e| e.into_inner()),
+            total,
+            startup_marks,
+        })
+    }
+}
+
+/// A unique path for Godot's `--benchmark-file`. Removes a stale file left at that path.
+pub(crate) fn benchmark_file_path() -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "cargo-godot-lib-benchmark-{}.json",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+/// Read the JSON object written by `--benchmark-file`. Godot writes the timings as strings.
+fn read_startup_marks(path: &Path) -> Result<BTreeMap<String, f64>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read Godot benchmark file: {path:?}"))?;
+    let marks: BTreeMap<String, serde_json::Value> = serde_json::from_str(&contents)
+        .with_context(|| format!("Failed to parse Godot benchmark file: {path:?}"))?;
+    Ok(marks
+        .into_iter()
+        .filter_map(|(mark, value)| {
+            let seconds = match value {
+                serde_json::Value::Number(n) => n.as_f64(),
+                serde_json::Value::String(s) => s.parse().ok(),
+                _ => None,
+            }?;
+            Some((mark, seconds))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_arguments() {
+        let path = Path::new("bench.json");
+        assert_eq!(
+            BenchmarkOptions::default().cli_arguments(path).concat(),
+            vec!["--benchmark-file", "bench.json", "--quit-after", "1"]
+        );
+        assert_eq!(
+            BenchmarkOptions::default()
+                .profiling(true)
+                .gpu_profile(true)
+                .startup_benchmark(false)
+                .quit_after(None)
+                .cli_arguments(path)
+                .concat(),
+            vec!["--profiling", "--gpu-profile"]
+        );
+    }
+
+    #[test]
+    fn test_output_timer() {
+        let dir = tempfile::tempdir().unwrap();
+        let benchmark_file = dir.path().join("bench.json");
+        std::fs::write(
+            &benchmark_file,
+            r#"{ "[Startup] Servers": "0.125", "[Startup] Scene": 0.5, "Invalid": [] }"#,
+        )
+        .unwrap();
+
+        let timer = OutputTimer::start();
+        let callback =
+            timer.callback(&BenchmarkOptions::default().first_frame_marker("first frame"));
+        callback("Godot Engine v4.5.1.stable.official");
+        callback("first frame");
+        let report = timer
+            .finish(GodotExitStatus::Success, &benchmark_file)
+            .unwrap();
+
+        assert!(report.time_to_first_output.unwrap() <= report.time_to_first_frame.unwrap());
+        assert!(report.time_to_first_frame.unwrap() <= report.total);
+        assert_eq!(report.startup_marks["[Startup] Servers"], 0.125);
+        assert_eq!(report.startup_marks["[Startup] Scene"], 0.5);
+        assert_eq!(report.startup_marks.len(), 2);
+        assert!(!benchmark_file.exists());
+    }
+}
@@ -0,0 +1,323 @@
+//! Assembling a distributable folder and zip archive per platform after an export.
+//!
+//! `export::export_project` writes the executable, the `.pck` file and the GDExtension libraries
+//! Godot copies next to it into one directory. A bundle collects these files, plus extra files
+//! such as a `steam_appid.txt` for local Steam testing, into `<output>/<bundle name>/` and zips
+//! the folder for upload to Steam, itch.io or a release page. Bundles can include a
+//! `SHA256SUMS` file and a CycloneDX SBOM, see `provenance`.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let exported = export_project(project, None, "Linux", ExportMode::Release, Path::new("build/linux/game.x86_64"))?;
+//! let bundle = Bundle::new("game", env!("CARGO_PKG_VERSION"), Platform::Linux)
+//!     .exported(&exported)
+//!     .file(Path::new("README.txt"), "README.txt")
+//!     .create(Path::new("dist"))?;
+//! println!("Upload {:?}", bundle.archive);
+//! ```
+use crate::gdextension_config::Platform;
+use crate::provenance::{self, Sbom};
+use anyhow::{Context, Result, anyhow};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
+
+/// The default `Bundle::name_template`.
+pub const DEFAULT_NAME_TEMPLATE: &str = "{name}-{version}-{platform}";
+
+/// Placeholders supported by `Bundle::name_template`.
+const NAME_PLACEHOLDERS: &[&str] = &["name", "version", "platform"];
+
+/// The file extensions of shared libraries copied by `Bundle::exported`.
+const LIBRARY_EXTENSIONS: &[&str] = &["so", "dll", "dylib"];
+
+/// A distributable bundle of an exported project for one platform.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Bundle {
+    name: String,
+    version: String,
+    platform: Platform,
+    name_template: String,
+    files: Vec<(PathBuf, String)>,
+    steam_app_id: Option<u32>,
+    checksums: bool,
+    sbom: Option<Sbom>,
+    zip: bool,
+}
+
+impl Bundle {
+    /// A bundle of the game `name` in version `version` for `platform`.
+    pub fn new(name: &str, version: &str, platform: Platform) -> Self {
+        Self {
+            name: name.to_string(),
+            version: version.to_string(),
+            platform,
+            name_template: DEFAULT_NAME_TEMPLATE.to_string(),
+            files: vec![],
+            steam_app_id: None,
+            checksums: false,
+            sbom: None,
+            zip: true,
+        }
+    }
+
+    /// Add the exported file, e.g. the executable returned by `export::export_project`, and the
+    /// `.pck` file and shared libraries next to it.
+    pub fn exported(mut self, exported_file: &Path) -> Self {
+        self.add_file(exported_file);
+        let Some(dir) = exported_file.parent() else {
+            return self;
+        };
+        let pck = exported_file.with_extension("pck");
+        if pck.is_file() {
+            self.add_file(&pck);
+        }
+        let mut libraries: Vec<PathBuf> = std::fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .and_then(|ext| ext.to_str())
+                        .is_some_and(|ext| LIBRARY_EXTENSIONS.contains(&ext))
+            })
+            .collect();
+        libraries.sort();
+        for library in libraries {
+            self.add_file(&library);
+        }
+        self
+    }
+
+    /// Add the file at `source` as `name`, which may contain slashes for subdirectories.
+    pub fn file(mut self, source: &Path, name: &str) -> Self {
+        self.files.push((source.to_path_buf(), name.to_string()));
+        self
+    }
+
+    /// Write a `steam_appid.txt` with `app_id` into the bundle, which lets the Steam API
+    /// initialize when the game is started outside of Steam.
+    pub fn steam_app_id(self, app_id: u32) -> Self {
+        Self {
+            steam_app_id: Some(app_id),
+            ..self
+        }
+    }
+
+    /// The name of the bundle folder and archive. Supports the placeholders `{name}`,
+    /// `{version}` and `{platform}`. Default: `{name}-{version}-{platform}`.
+    pub fn name_template(self, template: &str) -> Self {
+        Self {
+            name_template: template.to_string(),
+            ..self
+        }
+    }
+
+    /// Write a `SHA256SUMS` file of the bundled files into the bundle. Default: false.
+    pub fn checksums(self, checksums: bool) -> Self {
+        Self { checksums, ..self }
+    }
+
+    /// Write the CycloneDX SBOM `sbom` into the bundle as `sbom.cdx.json`.
+    pub fn sbom(self, sbom: Sbom) -> Self {
+        Self {
+            sbom: Some(sbom),
+            ..self
+        }
+    }
+
+    /// Zip the bundle folder. Default: true.
+    pub fn zip(self, zip: bool) -> Self {
+        Self { zip, ..self }
+    }
+
+    /// The expanded `name_template`.
+    pub fn bundle_name(&self) -> Result<String> {
+        let mut rest = self.name_template.as_str();
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..].find('}').with_context(|| {
+                format!(
+                    "Unterminated placeholder in bundle name template: {:?}",
+                    self.name_template
+                )
+            })?;
+            let placeholder = &rest[start + 1..start + end];
+            if !NAME_PLACEHOLDERS.contains(&placeholder) {
+                return Err(anyhow!(
+                    "Unknown placeholder `{{{placeholder}}}` in bundle name template {:?}",
+                    self.name_template
+                ));
+            }
+            rest = &rest[start + end + 1..];
+        }
+        Ok(self
+            .name_template
+            .replace("{name}", &self.name)
+            .replace("{version}", &self.version)
+            .replace("{platform}", self.platform.os_name()))
+    }
+
+    /// Assemble the bundle folder in `output_dir`, replacing an existing one, and zip it.
+    pub fn create(&self, output_dir: &Path) -> Result<BundleOutput> {
+        let name = self.bundle_name()?;
+        let dir = output_dir.join(&name);
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir)
+                .with_context(|| format!("Failed to remove old bundle: {dir:?}"))?;
+        }
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create bundle directory: {dir:?}"))?;
+
+        let mut files = vec![];
+        for (source, file_name) in &self.files {
+            let target = dir.join(file_name);
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create directory: {parent:?}"))?;
+            }
+            std::fs::copy(source, &target)
+                .with_context(|| format!("Failed to copy {source:?} to {target:?}"))?;
+            files.push(file_name.clone());
+        }
+        if let Some(app_id) = self.steam_app_id {
+            std::fs::write(dir.join("steam_appid.txt"), app_id.to_string())
+                .context("Failed to write steam_appid.txt")?;
+            files.push("steam_appid.txt".to_string());
+        }
+        if let Some(sbom) = &self.sbom {
+            sbom.write(&dir.join("sbom.cdx.json"))?;
+            files.push("sbom.cdx.json".to_string());
+        }
+        if self.checksums {
+            provenance::write_sha256sums(&dir, &files)?;
+            files.push(provenance::SHA256SUMS.to_string());
+        }
+
+        let archive = if self.zip {
+            let archive = output_dir.join(format!("{name}.zip"));
+            zip_files(&dir, &files, &archive)?;
+            Some(archive)
+        } else {
+            None
+        };
+        Ok(BundleOutput { dir, archive })
+    }
+
+    fn add_file(&mut self, path: &Path) {
+        if let Some(file_name) = path.file_name() {
+            self.files
+                .push((path.to_path_buf(), file_name.to_string_lossy().into_owned()));
+        }
+    }
+}
+
+/// The results of `Bundle::create`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BundleOutput {
+    /// The bundle folder.
+    pub dir: PathBuf,
+    /// The zip archive of the bundle folder, if enabled.
+    pub archive: Option<PathBuf>,
+}
+
+/// Zip the `files` of `dir`, keeping the executable permission of Unix executables.
+fn zip_files(dir: &Path, files: &[String], archive: &Path) -> Result<()> {
+    let file =
+        File::create(archive).with_context(|| format!("Failed to create archive: {archive:?}"))?;
+    let mut zip = ZipWriter::new(file);
+    for name in files {
+        let path = dir.join(name);
+        let mut options = SimpleFileOptions::default();
+        if is_executable(&path) {
+            options = options.unix_permissions(0o755);
+        }
+        zip.start_file(name.as_str(), options)
+            .with_context(|| format!("Failed to add {name:?} to {archive:?}"))?;
+        let mut source = File::open(&path).with_context(|| format!("Failed to open {path:?}"))?;
+        std::io::copy(&mut source, &mut zip)
+            .with_context(|| format!("Failed to add {name:?} to {archive:?}"))?;
+    }
+    zip.finish()
+        .with_context(|| format!("Failed to write archive: {archive:?}"))?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "x86_64" || ext == "arm64")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create() {
+        let dir = tempfile::tempdir().unwrap();
+        let export_dir = dir.path().join("build");
+        std::fs::create_dir_all(&export_dir).unwrap();
+        std::fs::write(export_dir.join("game.x86_64"), "executable").unwrap();
+        std::fs::write(export_dir.join("game.pck"), "pck").unwrap();
+        std::fs::write(export_dir.join("libgame.so"), "library").unwrap();
+        std::fs::write(export_dir.join("export.log"), "").unwrap();
+        std::fs::write(dir.path().join("README.txt"), "readme").unwrap();
+
+        let bundle = Bundle::new("game", "1.2.0", Platform::Linux)
+            .exported(&export_dir.join("game.x86_64"))
+            .file(&dir.path().join("README.txt"), "docs/README.txt")
+            .steam_app_id(480);
+        let output = bundle.create(&dir.path().join("dist")).unwrap();
+        assert_eq!(output.dir, dir.path().join("dist/game-1.2.0-linux"));
+        for file in [
+            "game.x86_64",
+            "game.pck",
+            "libgame.so",
+            "docs/README.txt",
+            "steam_appid.txt",
+        ] {
+            assert!(output.dir.join(file).exists(), "{file}");
+        }
+        assert!(!output.dir.join("export.log").exists());
+
+        let archive = output.archive.unwrap();
+        assert_eq!(archive, dir.path().join("dist/game-1.2.0-linux.zip"));
+        let archive = zip::ZipArchive::new(File::open(archive).unwrap()).unwrap();
+        assert_eq!(archive.len(), 5);
+
+        assert_eq!(
+            bundle
+                .clone()
+                .name_template("{name}_{platform}")
+                .bundle_name()
+                .unwrap(),
+            "game_linux"
+        );
+        assert!(
+            bundle
+                .clone()
+                .name_template("{target}")
+                .bundle_name()
+                .is_err()
+        );
+
+        let output = bundle
+            .checksums(true)
+            .zip(false)
+            .create(&dir.path().join("dist"))
+            .unwrap();
+        let sums = std::fs::read_to_string(output.dir.join("SHA256SUMS")).unwrap();
+        assert_eq!(sums.lines().count(), 5);
+        assert!(sums.contains("  docs/README.txt\n"));
+    }
+}
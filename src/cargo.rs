@@ -0,0 +1,629 @@
+//! Building the extension with cargo and locating the exact library files it produced,
+//! see `GodotRunner::cargo_build`.
+//!
+//! Cargo is run with `--message-format=json`, and the paths of the cdylib artifacts are taken
+//! from its `compiler-artifact` messages, so unusual profiles, target triples, target
+//! directories and renamed libraries need no path guessing.
+use anyhow::{Context, Result, anyhow};
+use cargo_metadata::Message;
+use std::ffi::OsString;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Options for building the extension library with `cargo build`.
+///
+/// Example usage:
+/// ```rust,ignore
+/// let artifacts = CargoBuild::default()
+///     .profile("dist")
+///     .features(["tracing"])
+///     .build(Path::new("rust/Cargo.toml"))?;
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CargoBuild {
+    package: Option<String>,
+    profile: Option<String>,
+    target: Option<String>,
+    features: Vec<String>,
+    no_default_features: bool,
+    all_features: bool,
+    args: Vec<String>,
+    unstable_flags: Vec<String>,
+    rustflags: Vec<String>,
+    macos_universal: bool,
+    macos_deployment_target: Option<String>,
+}
+
+/// The target triples combined into a macOS universal library.
+pub const MACOS_UNIVERSAL_TRIPLES: [&str; 2] = ["x86_64-apple-darwin", "aarch64-apple-darwin"];
+
+/// The lowest macOS version supported by `aarch64-apple-darwin`.
+const AARCH64_MIN_DEPLOYMENT_TARGET: &str = "11.0";
+
+/// The pseudo target triple of macOS universal libraries, used for both macOS
+/// `[libraries]` entries by `GdExtensionConfig::library_file`.
+pub const MACOS_UNIVERSAL_TRIPLE: &str = "universal-apple-darwin";
+
+impl CargoBuild {
+    /// Build this package of a workspace (`--package`). Default: the package of the manifest.
+    pub fn package(self, package: &str) -> Self {
+        Self {
+            package: Some(package.to_string()),
+            ..self
+        }
+    }
+
+    /// Build with the `release` profile (`--release`).
+    pub fn release(self) -> Self {
+        self.profile("release")
+    }
+
+    /// Build with a cargo profile (`--profile`). Default: `dev`.
+    pub fn profile(self, profile: &str) -> Self {
+        Self {
+            profile: Some(profile.to_string()),
+            ..self
+        }
+    }
+
+    /// Build for a target triple (`--target`). Default: the host.
+    pub fn target(self, triple: &str) -> Self {
+        Self {
+            target: Some(triple.to_string()),
+            ..self
+        }
+    }
+
+    /// Enable cargo features (`--features`).
+    pub fn features<S: Into<String>>(mut self, features: impl IntoIterator<Item = S>) -> Self {
+        self.features.extend(features.into_iter().map(Into::into));
+        self
+    }
+
+    /// Disable the default features (`--no-default-features`). Default: false.
+    pub fn no_default_features(self, no_default_features: bool) -> Self {
+        Self {
+            no_default_features,
+            ..self
+        }
+    }
+
+    /// Enable all features (`--all-features`). Default: false.
+    pub fn all_features(self, all_features: bool) -> Self {
+        Self {
+            all_features,
+            ..self
+        }
+    }
+
+    /// Add other arguments to `cargo build`, e.g. `--locked`.
+    pub fn args<S: Into<String>>(mut self, args: impl IntoIterator<Item = S>) -> Self {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Add unstable flags of nightly cargo (`-Z`), e.g. `build-std=std,panic_abort` for targets
+    /// without a prebuilt standard library. Requires a nightly toolchain, e.g. from
+    /// `rust-toolchain.toml`.
+    pub fn unstable_flags<S: Into<String>>(mut self, flags: impl IntoIterator<Item = S>) -> Self {
+        self.unstable_flags
+            .extend(flags.into_iter().map(Into::into));
+        self
+    }
+
+    /// Add flags passed to rustc (`RUSTFLAGS`), e.g. `-C target-feature=+atomics`. They are
+    /// appended to the `RUSTFLAGS` environment variable, and like it replace `build.rustflags`
+    /// of cargo's config.
+    pub fn rustflags<S: Into<String>>(mut self, flags: impl IntoIterator<Item = S>) -> Self {
+        self.rustflags.extend(flags.into_iter().map(Into::into));
+        self
+    }
+
+    /// Build for both `x86_64-apple-darwin` and `aarch64-apple-darwin` and combine the libraries
+    /// into a universal library with `lipo`, as required for notarized distribution.
+    /// The library is written to `<target>/universal-apple-darwin/<profile>/` and overrides
+    /// `target`. Requires both rustup targets and the Xcode command line tools. Default: false.
+    pub fn macos_universal(self, macos_universal: bool) -> Self {
+        Self {
+            macos_universal,
+            ..self
+        }
+    }
+
+    /// The oldest macOS version the library runs on (`MACOSX_DEPLOYMENT_TARGET`), e.g. `10.13`.
+    /// It is raised to `11.0` for `aarch64-apple-darwin`, the first version on Apple Silicon.
+    /// Default: the default of rustc, or the environment variable if set.
+    pub fn macos_deployment_target(self, version: &str) -> Self {
+        Self {
+            macos_deployment_target: Some(version.to_string()),
+            ..self
+        }
+    }
+
+    /// The name of the cargo profile, `dev` by default.
+    pub fn profile_name(&self) -> &str {
+        self.profile.as_deref().unwrap_or("dev")
+    }
+
+    /// The target triples built for, none for the host.
+    pub(crate) fn target_triples(&self) -> Vec<&str> {
+        if self.macos_universal {
+            MACOS_UNIVERSAL_TRIPLES.to_vec()
+        } else {
+            self.target.as_deref().into_iter().collect()
+        }
+    }
+
+    /// The `.gdextension` build the library is used for: `"debug"` for the `dev` profile,
+    /// `"release"` for all others.
+    pub fn gdextension_build(&self) -> &'static str {
+        match self.profile.as_deref() {
+            None | Some("dev") | Some("test") => "debug",
+            Some(_) => "release",
+        }
+    }
+
+    /// The arguments passed to cargo.
+    pub(crate) fn cli_arguments(&self, manifest_path: &Path) -> Vec<OsString> {
+        let mut args: Vec<OsString> = vec![
+            "build".into(),
+            "--lib".into(),
+            "--message-format=json-render-diagnostics".into(),
+            "--manifest-path".into(),
+            manifest_path.into(),
+        ];
+        if let Some(package) = &self.package {
+            args.extend(["--package".into(), package.into()]);
+        }
+        if let Some(profile) = &self.profile {
+            args.extend(["--profile".into(), profile.into()]);
+        }
+        if let Some(target) = &self.target {
+            args.extend(["--target".into(), target.into()]);
+        }
+        if !self.features.is_empty() {
+            args.extend(["--features".into(), self.features.join(",").into()]);
+        }
+        if self.no_default_features {
+            args.push("--no-default-features".into());
+        }
+        if self.all_features {
+            args.push("--all-features".into());
+        }
+        for flag in &self.unstable_flags {
+            args.extend(["-Z".into(), flag.into()]);
+        }
+        args.extend(self.args.iter().map(OsString::from));
+        args
+    }
+
+    /// The environment variables passed to cargo.
+    pub(crate) fn env(&self) -> Vec<(&'static str, OsString)> {
+        if self.rustflags.is_empty() {
+            return vec![];
+        }
+        let mut rustflags: Vec<String> = std::env::var("RUSTFLAGS")
+            .ok()
+            .filter(|flags| !flags.trim().is_empty())
+            .into_iter()
+            .collect();
+        rustflags.extend(self.rustflags.iter().cloned());
+        vec![("RUSTFLAGS", rustflags.join(" ").into())]
+    }
+
+    /// Run `cargo build` for the package at `manifest_path` and return the cdylibs it built.
+    /// Compiler diagnostics are printed as usual.
+    pub fn build(&self, manifest_path: &Path) -> Result<Vec<CdylibArtifact>> {
+        if self.macos_universal {
+            return self.build_macos_targets(manifest_path, true);
+        }
+        let mut command = Command::new(std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into()));
+        command
+            .args(self.cli_arguments(manifest_path))
+            .envs(self.env());
+        let target = match &self.target {
+            Some(target) if target.contains("apple-darwin") => {
+                command.envs(self.macos_env(target)?);
+                target.clone()
+            }
+            Some(target) => target.clone(),
+            // Native builds for the host need no SDK setup.
+            None => host_triple()?,
+        };
+        run_build(command, "cargo build", &target)
+    }
+
+    /// Build for both `x86_64-apple-darwin` and `aarch64-apple-darwin`, and combine each pair of
+    /// libraries into a universal library with `lipo` if `universal` is true.
+    /// Each target is built with its own `MACOSX_DEPLOYMENT_TARGET`, and `SDKROOT` is set to the
+    /// macOS SDK of the Xcode command line tools unless it is already set.
+    pub fn build_macos_targets(
+        &self,
+        manifest_path: &Path,
+        universal: bool,
+    ) -> Result<Vec<CdylibArtifact>> {
+        let [x86_64, aarch64] = MACOS_UNIVERSAL_TRIPLES.map(|triple| {
+            Self {
+                macos_universal: false,
+                ..self.clone()
+            }
+            .target(triple)
+            .build(manifest_path)
+        });
+        let (x86_64, aarch64) = (x86_64?, aarch64?);
+        if !universal {
+            return Ok(x86_64.into_iter().chain(aarch64).collect());
+        }
+        let mut libraries = vec![];
+        for library in &x86_64 {
+            let other = find_library(&aarch64, &library.name).with_context(|| {
+                format!(
+                    "No aarch64-apple-darwin library {:?} to combine with {:?}",
+                    library.name, library.path
+                )
+            })?;
+            let artifact = universal_artifact(library)?;
+            lipo(&[&library.path, &other.path], &artifact.path)?;
+            libraries.push(artifact);
+        }
+        Ok(libraries)
+    }
+
+    /// The environment variables for building for the apple `target`.
+    fn macos_env(&self, target: &str) -> Result<Vec<(&'static str, OsString)>> {
+        let mut env = vec![];
+        let requested = self
+            .macos_deployment_target
+            .clone()
+            .or_else(|| std::env::var("MACOSX_DEPLOYMENT_TARGET").ok());
+        if let Some(version) = deployment_target(requested.as_deref(), target) {
+            env.push(("MACOSX_DEPLOYMENT_TARGET", version.into()));
+        }
+        if cfg!(target_os = "macos") && std::env::var_os("SDKROOT").is_none() {
+            env.push(("SDKROOT", macos_sdk_path()?));
+        }
+        Ok(env)
+    }
+}
+
+/// The deployment target for `target`: the `requested` version, raised to the minimum of
+/// `aarch64-apple-darwin`.
+fn deployment_target(requested: Option<&str>, target: &str) -> Option<String> {
+    let requested = requested?;
+    let version = |version: &str| -> Vec<u32> {
+        version
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    if target.starts_with("aarch64") && version(requested) < version(AARCH64_MIN_DEPLOYMENT_TARGET)
+    {
+        Some(AARCH64_MIN_DEPLOYMENT_TARGET.to_string())
+    } else {
+        Some(requested.to_string())
+    }
+}
+
+/// The path of the macOS SDK from `xcrun --sdk macosx --show-sdk-path`.
+fn macos_sdk_path() -> Result<OsString> {
+    let mut command = Command::new("xcrun");
+    command
+        .args(["--sdk", "macosx", "--show-sdk-path"])
+        .stdin(Stdio::null());
+    let output = command.output().with_context(|| {
+        format!("Failed to run xcrun, are the Xcode command line tools installed? {command:?}")
+    })?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "xcrun failed with status `{}`: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().into())
+}
+
+/// Run a `command` printing cargo's JSON messages, e.g. `cargo build`, and return the cdylibs
+/// it built for `target`. `name` describes the command in errors.
+pub(crate) fn run_build(command: Command, name: &str, target: &str) -> Result<Vec<CdylibArtifact>> {
+    run_build_with_progress(command, name, target, &|_| {})
+}
+
+/// `run_build`, calling `on_compiled` with the name of every crate cargo compiled.
+pub(crate) fn run_build_with_progress(
+    mut command: Command,
+    name: &str,
+    target: &str,
+    on_compiled: &dyn Fn(&str),
+) -> Result<Vec<CdylibArtifact>> {
+    command.stdin(Stdio::null()).stdout(Stdio::piped());
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("Failed to run {name}: {command:?}"))?;
+    let stdout = child
+        .stdout
+        .take()
+        .with_context(|| format!("Failed to read {name} output"))?;
+    let artifacts = parse_messages(BufReader::new(stdout), on_compiled);
+    let status = child
+        .wait()
+        .with_context(|| format!("Failed to wait for {name}"))?;
+    if !status.success() {
+        return Err(anyhow!("{name} failed with status `{status}`"));
+    }
+    Ok(artifacts?
+        .into_iter()
+        .map(|artifact| CdylibArtifact {
+            target: target.to_string(),
+            ..artifact
+        })
+        .collect())
+}
+
+/// The universal library combining `library`, built for one of the macOS targets, with its
+/// counterpart, e.g. `target/universal-apple-darwin/debug/libgame.dylib` for
+/// `target/x86_64-apple-darwin/debug/libgame.dylib`.
+fn universal_artifact(library: &CdylibArtifact) -> Result<CdylibArtifact> {
+    let unexpected = || format!("Unexpected library path: {:?}", library.path);
+    let file_name = library.path.file_name().with_context(unexpected)?;
+    let profile_dir = library
+        .path
+        .parent()
+        .and_then(Path::file_name)
+        .with_context(unexpected)?;
+    let target_directory = library.target_directory().with_context(unexpected)?;
+    Ok(CdylibArtifact {
+        name: library.name.clone(),
+        path: target_directory
+            .join(MACOS_UNIVERSAL_TRIPLE)
+            .join(profile_dir)
+            .join(file_name),
+        target: MACOS_UNIVERSAL_TRIPLE.to_string(),
+    })
+}
+
+/// Combine single architecture libraries into the universal library `output` with `lipo`.
+pub fn lipo(inputs: &[&Path], output: &Path) -> Result<()> {
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {parent:?}"))?;
+    }
+    let mut command = Command::new("lipo");
+    command
+        .arg("-create")
+        .args(inputs)
+        .arg("-output")
+        .arg(output)
+        .stdin(Stdio::null());
+    let status = command.status().with_context(|| {
+        format!("Failed to run lipo, are the Xcode command line tools installed? {command:?}")
+    })?;
+    if !status.success() {
+        return Err(anyhow!("lipo failed with status `{status}`: {command:?}"));
+    }
+    Ok(())
+}
+
+/// A shared library built by cargo.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CdylibArtifact {
+    /// The library name, e.g. `my_crate` for the package `my-crate`.
+    pub name: String,
+    /// The path of the `.so`, `.dylib`, `.dll` or `.wasm` file.
+    pub path: PathBuf,
+    /// The target triple the library was built for.
+    pub target: String,
+}
+
+impl CdylibArtifact {
+    /// The target directory the library was built in, e.g. `target/` for
+    /// `target/debug/libmy_crate.so` or `target/x86_64-pc-windows-gnu/release/my_crate.dll`.
+    pub fn target_directory(&self) -> Option<&Path> {
+        let target_dir = self.path.parent()?.parent()?;
+        if target_dir
+            .file_name()
+            .is_some_and(|name| name == self.target.as_str())
+        {
+            target_dir.parent()
+        } else {
+            Some(target_dir)
+        }
+    }
+}
+
+/// The cdylibs in cargo's JSON messages. The target triple is left empty.
+pub fn parse_cdylib_artifacts(messages: impl BufRead) -> Result<Vec<CdylibArtifact>> {
+    parse_messages(messages, &|_| {})
+}
+
+/// `parse_cdylib_artifacts`, calling `on_compiled` with the name of every compiled crate.
+fn parse_messages(
+    messages: impl BufRead,
+    on_compiled: &dyn Fn(&str),
+) -> Result<Vec<CdylibArtifact>> {
+    let mut artifacts = vec![];
+    for message in Message::parse_stream(messages) {
+        let Message::CompilerArtifact(artifact) =
+            message.context("Failed to parse cargo output")?
+        else {
+            continue;
+        };
+        on_compiled(&artifact.target.name);
+        if artifact.target.is_cdylib()
+            && let Some(path) = artifact
+                .filenames
+                .into_iter()
+                .find(|file| matches!(file.extension(), Some("so" | "dylib" | "dll" | "wasm")))
+        {
+            artifacts.push(CdylibArtifact {
+                name: artifact.target.name,
+                path: path.into_std_path_buf(),
+                target: String::new(),
+            });
+        }
+    }
+    Ok(artifacts)
+}
+
+/// Find the cdylib of `crate_name` in `artifacts`.
+pub fn find_library<'a>(
+    artifacts: &'a [CdylibArtifact],
+    crate_name: &str,
+) -> Option<&'a CdylibArtifact> {
+    let library_name = crate_name.replace('-', "_");
+    artifacts
+        .iter()
+        .find(|artifact| artifact.name == library_name)
+}
+
+/// The host target triple from `rustc -vV`.
+fn host_triple() -> Result<String> {
+    let mut command = Command::new(std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into()));
+    command.arg("-vV").stdin(Stdio::null());
+    let output = command
+        .output()
+        .with_context(|| format!("Failed to run rustc: {command:?}"))?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .map(str::to_string)
+        .context("Failed to detect the host target triple from `rustc -vV`")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGES: &str = r#"{"reason":"compiler-artifact","package_id":"path+file:///w/my-crate#0.1.0","manifest_path":"/w/my-crate/Cargo.toml","target":{"kind":["lib","cdylib"],"crate_types":["lib","cdylib"],"name":"my_crate","src_path":"/w/my-crate/src/lib.rs","edition":"2024","doc":true,"doctest":true,"test":true},"profile":{"opt_level":"0","debuginfo":2,"debug_assertions":true,"overflow_checks":true,"test":false},"features":[],"filenames":["/w/my-crate/target/debug/libmy_crate.rlib","/w/my-crate/target/debug/libmy_crate.so"],"executable":null,"fresh":true}
+{"reason":"compiler-artifact","package_id":"path+file:///w/util#0.1.0","manifest_path":"/w/util/Cargo.toml","target":{"kind":["lib"],"crate_types":["lib"],"name":"util","src_path":"/w/util/src/lib.rs","edition":"2024","doc":true,"doctest":true,"test":true},"profile":{"opt_level":"0","debuginfo":2,"debug_assertions":true,"overflow_checks":true,"test":false},"features":[],"filenames":["/w/target/debug/libutil.rlib"],"executable":null,"fresh":true}
+Compiling my-crate
+{"reason":"build-finished","success":true}
+"#;
+
+    #[test]
+    fn test_parse_cdylib_artifacts() {
+        let artifacts = parse_cdylib_artifacts(MESSAGES.as_bytes()).unwrap();
+        assert_eq!(
+            artifacts,
+            vec![CdylibArtifact {
+                name: "my_crate".to_string(),
+                path: PathBuf::from("/w/my-crate/target/debug/libmy_crate.so"),
+                target: String::new(),
+            }]
+        );
+        let library = find_library(&artifacts, "my-crate").unwrap();
+        assert_eq!(
+            library.target_directory(),
+            Some(Path::new("/w/my-crate/target"))
+        );
+        let cross_compiled = CdylibArtifact {
+            name: "my_crate".to_string(),
+            path: PathBuf::from("/w/target/x86_64-pc-windows-gnu/release/my_crate.dll"),
+            target: "x86_64-pc-windows-gnu".to_string(),
+        };
+        assert_eq!(
+            cross_compiled.target_directory(),
+            Some(Path::new("/w/target"))
+        );
+        assert!(find_library(&artifacts, "util").is_none());
+    }
+
+    #[test]
+    fn test_universal_artifact() {
+        let library = CdylibArtifact {
+            name: "game".to_string(),
+            path: PathBuf::from("/w/target/x86_64-apple-darwin/dist/libgame.dylib"),
+            target: "x86_64-apple-darwin".to_string(),
+        };
+        let universal = universal_artifact(&library).unwrap();
+        assert_eq!(
+            universal.path,
+            PathBuf::from("/w/target/universal-apple-darwin/dist/libgame.dylib")
+        );
+        assert_eq!(universal.target, MACOS_UNIVERSAL_TRIPLE);
+        assert_eq!(universal.target_directory(), Some(Path::new("/w/target")));
+    }
+
+    #[test]
+    fn test_cli_arguments() {
+        let build = CargoBuild::default()
+            .package("game")
+            .profile("dist")
+            .target("x86_64-pc-windows-gnu")
+            .features(["a", "b"])
+            .no_default_features(true)
+            .unstable_flags(["build-std=std,panic_abort"])
+            .args(["--locked"]);
+        assert_eq!(
+            build.cli_arguments(Path::new("Cargo.toml")),
+            [
+                "build",
+                "--lib",
+                "--message-format=json-render-diagnostics",
+                "--manifest-path",
+                "Cargo.toml",
+                "--package",
+                "game",
+                "--profile",
+                "dist",
+                "--target",
+                "x86_64-pc-windows-gnu",
+                "--features",
+                "a,b",
+                "--no-default-features",
+                "-Z",
+                "build-std=std,panic_abort",
+                "--locked"
+            ]
+            .map(OsString::from)
+        );
+        assert_eq!(
+            CargoBuild::default()
+                .all_features(true)
+                .cli_arguments(Path::new("Cargo.toml"))
+                .last()
+                .unwrap(),
+            "--all-features"
+        );
+        assert!(build.env().is_empty());
+        let rustflags = CargoBuild::default()
+            .rustflags(["-C", "target-feature=+atomics"])
+            .env();
+        assert_eq!(rustflags[0].0, "RUSTFLAGS");
+        assert!(
+            rustflags[0]
+                .1
+                .to_string_lossy()
+                .ends_with("-C target-feature=+atomics")
+        );
+        assert_eq!(build.target_triples(), ["x86_64-pc-windows-gnu"]);
+        assert_eq!(
+            CargoBuild::default().macos_universal(true).target_triples(),
+            MACOS_UNIVERSAL_TRIPLES
+        );
+        assert_eq!(build.gdextension_build(), "release");
+        assert_eq!(CargoBuild::default().gdextension_build(), "debug");
+        assert_eq!(build.profile_name(), "dist");
+        assert_eq!(CargoBuild::default().profile_name(), "dev");
+    }
+
+    #[test]
+    fn test_deployment_target() {
+        let build = CargoBuild::default().macos_deployment_target("10.13");
+        assert_eq!(build.macos_deployment_target.as_deref(), Some("10.13"));
+        assert_eq!(
+            deployment_target(Some("10.13"), "x86_64-apple-darwin").as_deref(),
+            Some("10.13")
+        );
+        assert_eq!(
+            deployment_target(Some("10.13"), "aarch64-apple-darwin").as_deref(),
+            Some("11.0")
+        );
+        assert_eq!(
+            deployment_target(Some("12.3"), "aarch64-apple-darwin").as_deref(),
+            Some("12.3")
+        );
+        assert_eq!(deployment_target(None, "aarch64-apple-darwin"), None);
+    }
+}
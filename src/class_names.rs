@@ -0,0 +1,187 @@
+//! A pre-launch check for extension classes named like engine classes,
+//! see `GodotRunner::check_class_names`.
+//!
+//! Godot refuses to register a class whose name is already taken, and the resulting
+//! "Class already exists" error doesn't point at the cause. The names are compared to the engine
+//! classes in `extension_api.json`, which is dumped once per Godot version and cached in the
+//! project's `.godot` folder.
+//!
+//! Shared libraries don't export symbols for individual classes, so class names are discovered
+//! from the crate's source instead: every struct deriving `GodotClass`, using the name given by
+//! `#[class(rename = ...)]` if present.
+use crate::autoload::GENERATED_DIR;
+use crate::extension_api::{self, ExtensionApi};
+use crate::godot_commands::GodotBinary;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// The names of the structs deriving `GodotClass` in the `.rs` files below `src_dir`.
+pub fn discover_class_names(src_dir: &Path) -> Result<Vec<String>> {
+    let mut files = vec![];
+    find_rust_files(src_dir, &mut files)?;
+    files.sort();
+
+    let mut names = vec![];
+    for file in files {
+        let source = std::fs::read_to_string(&file)
+            .with_context(|| format!("Failed to read source file: {file:?}"))?;
+        for name in parse_class_names(&source) {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// The names of `names` which are engine classes or builtin types of `api`.
+pub fn find_collisions(api: &ExtensionApi, names: &[String]) -> Vec<String> {
+    let mut engine_names = api.class_names();
+    engine_names.extend(api.builtin_class_names());
+    names
+        .iter()
+        .filter(|name| engine_names.contains(name.as_str()))
+        .cloned()
+        .collect()
+}
+
+/// The warning printed for a colliding class name.
+pub fn collision_warning(name: &str) -> String {
+    format!(
+        "Class `{name}` has the same name as a built-in Godot class and will fail to register. \
+        Rename it, e.g. with `#[class(rename = My{name})]`."
+    )
+}
+
+/// The extension API of the Godot binary, dumped into the project's `.godot` folder on first use.
+/// Dumps of `gdenv` versions are cached separately.
+pub(crate) fn cached_extension_api(
+    godot_project_path: &Path,
+    godot_binary: GodotBinary,
+) -> Result<ExtensionApi> {
+    let version = match godot_binary {
+        GodotBinary::Version(version) => version,
+        GodotBinary::Discover | GodotBinary::Path(_) => "default",
+    };
+    let cache_dir = godot_project_path
+        .join(GENERATED_DIR)
+        .join(format!("extension_api_{version}"));
+    let path = cache_dir.join(extension_api::EXTENSION_API_FILE);
+    if !path.exists() {
+        extension_api::dump(godot_binary, &cache_dir, false)?;
+    }
+    ExtensionApi::load(&path)
+}
+
+/// The class names declared in a Rust source file.
+fn parse_class_names(source: &str) -> Vec<String> {
+    let mut names = vec![];
+    let mut rest = source;
+    while let Some(derive) = rest.find("#[derive(") {
+        rest = &rest[derive + "#[derive(".len()..];
+        let Some(end) = rest.find(")]") else {
+            break;
+        };
+        let derives = &rest[..end];
+        if !derives
+            .split(',')
+            .any(|derive| derive.trim().rsplit("::").next() == Some("GodotClass"))
+        {
+            continue;
+        }
+
+        // The attributes between the derive and the struct may rename the class.
+        let Some(item) = rest.find("struct ") else {
+            break;
+        };
+        let attributes = &rest[..item];
+        let struct_name = identifier(&rest[item + "struct ".len()..]);
+        let rename = attributes
+            .find("rename")
+            .and_then(|rename| {
+                attributes[rename + "rename".len()..]
+                    .trim_start()
+                    .strip_prefix('=')
+            })
+            .and_then(identifier);
+        if let Some(name) = rename.or(struct_name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+fn identifier(text: &str) -> Option<String> {
+    let text = text.trim_start();
+    let end = text
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(text.len());
+    (end > 0).then(|| text[..end].to_string())
+}
+
+pub(crate) fn find_rust_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries =
+        std::fs::read_dir(dir).with_context(|| format!("Failed to read directory: {dir:?}"))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_rust_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_class_names() {
+        let source = r#"
+            #[derive(GodotClass)]
+            #[class(base = Node)]
+            struct Player {
+                base: Base<Node>,
+            }
+
+            #[derive(Debug, Clone)]
+            struct Stats;
+
+            #[derive(godot::prelude::GodotClass)]
+            #[class(init, rename = GameTimer, base = Node)]
+            pub struct Timer {}
+
+            #[derive(GodotClass)]
+            #[class(base = Resource)]
+            pub(crate) struct Camera2D;
+        "#;
+        assert_eq!(
+            parse_class_names(source),
+            vec!["Player", "GameTimer", "Camera2D"]
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("nodes")).unwrap();
+        std::fs::write(dir.path().join("lib.rs"), source).unwrap();
+        std::fs::write(dir.path().join("nodes/player.rs"), source).unwrap();
+        assert_eq!(
+            discover_class_names(dir.path()).unwrap(),
+            vec!["Player", "GameTimer", "Camera2D"]
+        );
+    }
+
+    #[test]
+    fn test_find_collisions() {
+        let api: ExtensionApi = r#"{
+            "builtin_classes": [{ "name": "Vector2" }],
+            "classes": [{ "name": "Node" }, { "name": "Camera2D" }]
+        }"#
+        .parse()
+        .unwrap();
+        let names = ["Player", "Camera2D", "Vector2"].map(String::from);
+        assert_eq!(find_collisions(&api, &names), vec!["Camera2D", "Vector2"]);
+        assert!(collision_warning("Camera2D").contains("MyCamera2D"));
+    }
+}
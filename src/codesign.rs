@@ -0,0 +1,159 @@
+//! Signing the extension's macOS library with `codesign`, see `GodotRunner::codesign`.
+//!
+//! Gatekeeper blocks unsigned dylibs once the project is exported and downloaded, so the library
+//! must be signed before it is exported. Signing with the ad-hoc identity `-` is enough for local
+//! runs; distribution requires a "Developer ID Application" identity and the hardened runtime.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! Codesign::new("Developer ID Application: Example (TEAMID)")
+//!     .entitlements(Path::new("entitlements.plist"))
+//!     .hardened_runtime(true)
+//!     .sign(Path::new("target/release/libgame.dylib"))?;
+//! ```
+use anyhow::{Context, Result, anyhow};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Options for signing a library with `codesign`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Codesign {
+    identity: String,
+    entitlements: Option<PathBuf>,
+    hardened_runtime: bool,
+    timestamp: bool,
+}
+
+impl Codesign {
+    /// Sign with the keychain identity `identity`, e.g. `Developer ID Application: Name (TEAMID)`.
+    pub fn new(identity: &str) -> Self {
+        Self {
+            identity: identity.to_string(),
+            entitlements: None,
+            hardened_runtime: false,
+            timestamp: false,
+        }
+    }
+
+    /// Sign with the ad-hoc identity `-`, which needs no certificate but can't be notarized.
+    pub fn ad_hoc() -> Self {
+        Self::new("-")
+    }
+
+    /// Embed the entitlements of the `.plist` file at `path` (`--entitlements`).
+    pub fn entitlements(self, path: &Path) -> Self {
+        Self {
+            entitlements: Some(path.to_path_buf()),
+            ..self
+        }
+    }
+
+    /// Enable the hardened runtime (`--options runtime`), required for notarization.
+    /// Default: false.
+    pub fn hardened_runtime(self, hardened_runtime: bool) -> Self {
+        Self {
+            hardened_runtime,
+            ..self
+        }
+    }
+
+    /// Request a secure timestamp from Apple (`--timestamp`), required for notarization.
+    /// Needs network access. Default: false.
+    pub fn timestamp(self, timestamp: bool) -> Self {
+        Self { timestamp, ..self }
+    }
+
+    /// The arguments passed to `codesign`.
+    fn cli_arguments(&self, library: &Path) -> Vec<OsString> {
+        let mut args: Vec<OsString> = vec![
+            "--force".into(),
+            "--sign".into(),
+            self.identity.as_str().into(),
+        ];
+        if let Some(entitlements) = &self.entitlements {
+            args.extend(["--entitlements".into(), entitlements.into()]);
+        }
+        if self.hardened_runtime {
+            args.extend(["--options".into(), "runtime".into()]);
+        }
+        if self.timestamp {
+            args.push("--timestamp".into());
+        }
+        args.push(library.into());
+        args
+    }
+
+    /// Sign the library at `library`, replacing any existing signature.
+    pub fn sign(&self, library: &Path) -> Result<()> {
+        let mut command = Command::new("codesign");
+        command
+            .args(self.cli_arguments(library))
+            .stdin(Stdio::null());
+        let status = command.status().with_context(|| {
+            format!("Failed to run codesign, which is only available on macOS: {command:?}")
+        })?;
+        if !status.success() {
+            return Err(anyhow!(
+                "codesign failed with status `{status}` for {library:?}"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Sign the existing `.dylib` files of `files`, skipping other files.
+    /// Returns the signed files.
+    pub fn sign_dylibs(&self, files: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        let dylibs: Vec<PathBuf> = files
+            .iter()
+            .filter(|file| file.extension().is_some_and(|ext| ext == "dylib") && file.exists())
+            .cloned()
+            .collect();
+        for dylib in &dylibs {
+            self.sign(dylib)?;
+        }
+        Ok(dylibs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_arguments() {
+        let codesign = Codesign::new("Developer ID Application: Example (TEAMID)")
+            .entitlements(Path::new("entitlements.plist"))
+            .hardened_runtime(true)
+            .timestamp(true);
+        assert_eq!(
+            codesign.cli_arguments(Path::new("libgame.dylib")),
+            [
+                "--force",
+                "--sign",
+                "Developer ID Application: Example (TEAMID)",
+                "--entitlements",
+                "entitlements.plist",
+                "--options",
+                "runtime",
+                "--timestamp",
+                "libgame.dylib"
+            ]
+            .map(OsString::from)
+        );
+        assert_eq!(
+            Codesign::ad_hoc().cli_arguments(Path::new("libgame.dylib")),
+            ["--force", "--sign", "-", "libgame.dylib"].map(OsString::from)
+        );
+    }
+
+    #[test]
+    fn test_sign_dylibs_skips_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let library = dir.path().join("libgame.so");
+        std::fs::write(&library, "").unwrap();
+        let missing = dir.path().join("libgame.dylib");
+        let signed = Codesign::ad_hoc().sign_dylibs(&[library, missing]).unwrap();
+        assert!(signed.is_empty());
+    }
+}
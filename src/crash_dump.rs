@@ -0,0 +1,248 @@
+//! Collecting crash dumps of Godot processes launched by `GodotRunner`,
+//! see `GodotRunner::crash_dumps`.
+//!
+//! On Unix, Godot is launched through `sh` with `ulimit -c unlimited`, and after a crash the core
+//! file is moved into the configured folder. Linux `core_pattern`s are followed, and cores piped to
+//! `systemd-coredump` are exported with `coredumpctl`. On Windows, Windows Error Reporting is
+//! configured to write full dumps of the Godot executable into the folder, which requires
+//! administrator rights once per executable.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let runner = runner.crash_dumps(CrashDumps::new(Path::new("target/crash-dumps")));
+//! if let Err(e) = runner.execute() {
+//!     eprintln!("{e:?}"); // Includes the path of the dump if Godot crashed.
+//! }
+//! ```
+use anyhow::{Context, Result, anyhow};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::SystemTime;
+
+/// Where crash dumps of the launched Godot process are collected.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CrashDumps {
+    dir: PathBuf,
+}
+
+impl CrashDumps {
+    /// Collect crash dumps into `dir`, which is created if needed.
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    /// The folder crash dumps are collected into.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Enable crash dumps for the Godot executable `program`.
+    /// Returns the wrapper Godot must be launched through, see `GodotRunner::wrapper_command`.
+    pub fn setup(&self, program: &OsStr) -> Result<Vec<String>> {
+        let dir = self.absolute_dir()?;
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create directory: {dir:?}"))?;
+        if cfg!(windows) {
+            enable_local_dumps(program, &dir)?;
+            return Ok(vec![]);
+        }
+        Ok([
+            "sh",
+            "-c",
+            "ulimit -c unlimited 2>/dev/null; exec \"$@\"",
+            "sh",
+        ]
+        .map(str::to_string)
+        .to_vec())
+    }
+
+    /// Find the dump of the process `pid` which crashed after `since`, running in
+    /// `working_dir`, and move it into the dump folder.
+    /// Returns an error describing where to look instead if no dump was found.
+    pub fn collect(&self, pid: u32, since: SystemTime, working_dir: &Path) -> Result<PathBuf> {
+        let dir = self.absolute_dir()?;
+        if cfg!(windows) {
+            return find_newest(&dir, since, |name| name.ends_with(&format!(".{pid}.dmp")))
+                .with_context(|| {
+                    format!("Windows Error Reporting wrote no dump of process {pid} to {dir:?}")
+                });
+        }
+        let target = dir.join(format!("core.{pid}"));
+        let core_pattern = std::fs::read_to_string("/proc/sys/kernel/core_pattern")
+            .map(|pattern| pattern.trim().to_string())
+            .unwrap_or_default();
+        if let Some(handler) = core_pattern.strip_prefix('|') {
+            if !handler.contains("systemd-coredump") {
+                return Err(anyhow!(
+                    "Core dumps are piped to `{handler}`, so they can't be collected. \
+                    Find the dump of process {pid} with that tool."
+                ));
+            }
+            let mut command = Command::new("coredumpctl");
+            command
+                .arg(format!("--output={}", target.display()))
+                .arg("dump")
+                .arg(pid.to_string())
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null());
+            let status = command
+                .status()
+                .with_context(|| format!("Failed to run coredumpctl: {command:?}"))?;
+            if !status.success() {
+                return Err(anyhow!(
+                    "coredumpctl found no core dump of process {pid}, check `ulimit -c` and \
+                    `coredumpctl list`"
+                ));
+            }
+            return Ok(target);
+        }
+
+        let (search_dir, prefix) = if cfg!(target_os = "macos") {
+            (PathBuf::from("/cores"), "core".to_string())
+        } else {
+            core_pattern_location(&core_pattern, working_dir)
+        };
+        let core = find_newest(&search_dir, since, |name| name.starts_with(&prefix)).with_context(
+            || {
+                format!(
+                    "No core dump of process {pid} found in {search_dir:?}, check `ulimit -c` \
+                    and the core dump settings of the system"
+                )
+            },
+        )?;
+        if std::fs::rename(&core, &target).is_err() {
+            std::fs::copy(&core, &target)
+                .with_context(|| format!("Failed to copy {core:?} to {target:?}"))?;
+            let _ = std::fs::remove_file(&core);
+        }
+        Ok(target)
+    }
+
+    fn absolute_dir(&self) -> Result<PathBuf> {
+        std::path::absolute(&self.dir)
+            .with_context(|| format!("Failed to make {:?} absolute", self.dir))
+    }
+}
+
+/// The folder and file name prefix of core files written for the Linux `core_pattern`,
+/// e.g. `core.%p` writes `core.<pid>` into the working directory of the process.
+fn core_pattern_location(core_pattern: &str, working_dir: &Path) -> (PathBuf, String) {
+    let pattern = Path::new(core_pattern);
+    let dir = match pattern.parent() {
+        Some(parent) if pattern.is_absolute() => parent.to_path_buf(),
+        Some(parent) => working_dir.join(parent),
+        None => working_dir.to_path_buf(),
+    };
+    let file_name = pattern
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let prefix = file_name.split('%').next().unwrap_or_default();
+    let prefix = if prefix.is_empty() { "core" } else { prefix };
+    (dir, prefix.to_string())
+}
+
+/// The newest file in `dir` modified after `since` whose name matches.
+fn find_newest(dir: &Path, since: SystemTime, matches: impl Fn(&str) -> bool) -> Option<PathBuf> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|entry| matches(&entry.file_name().to_string_lossy()))
+        .filter_map(|entry| {
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            (entry.path().is_file() && modified >= since).then(|| (modified, entry.path()))
+        })
+        .max()
+        .map(|(_, path)| path)
+}
+
+/// Configure Windows Error Reporting to write full dumps of `program` into `dir`.
+fn enable_local_dumps(program: &OsStr, dir: &Path) -> Result<()> {
+    let exe_name = Path::new(program)
+        .file_name()
+        .with_context(|| format!("Unexpected Godot executable: {program:?}"))?
+        .to_string_lossy()
+        .to_string();
+    let exe_name = if exe_name.ends_with(".exe") {
+        exe_name
+    } else {
+        format!("{exe_name}.exe")
+    };
+    let key =
+        format!(r"HKLM\SOFTWARE\Microsoft\Windows\Windows Error Reporting\LocalDumps\{exe_name}");
+    let dir = dir.to_string_lossy().to_string();
+    let configured = Command::new("reg")
+        .args(["query", &key, "/v", "DumpFolder"])
+        .stdin(Stdio::null())
+        .output()
+        .is_ok_and(|output| {
+            output.status.success() && String::from_utf8_lossy(&output.stdout).contains(&dir)
+        });
+    if configured {
+        return Ok(());
+    }
+    for (name, kind, value) in [
+        ("DumpFolder", "REG_EXPAND_SZ", dir.as_str()),
+        ("DumpType", "REG_DWORD", "2"),
+        ("DumpCount", "REG_DWORD", "10"),
+    ] {
+        let mut command = Command::new("reg");
+        command
+            .args(["add", &key, "/v", name, "/t", kind, "/d", value, "/f"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null());
+        let status = command
+            .status()
+            .with_context(|| format!("Failed to run reg: {command:?}"))?;
+        if !status.success() {
+            return Err(anyhow!(
+                "Failed to enable crash dumps of {exe_name} in {key}, \
+                which requires administrator rights once"
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_core_pattern_location() {
+        let cwd = Path::new("/project");
+        assert_eq!(
+            core_pattern_location("core", cwd),
+            (PathBuf::from("/project"), "core".to_string())
+        );
+        assert_eq!(
+            core_pattern_location("/var/crash/core.%e.%p", cwd),
+            (PathBuf::from("/var/crash"), "core.".to_string())
+        );
+        assert_eq!(
+            core_pattern_location("%e.core", cwd),
+            (PathBuf::from("/project"), "core".to_string())
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let since = SystemTime::now() - std::time::Duration::from_secs(1);
+        std::fs::write(dir.path().join("core.42"), "").unwrap();
+        std::fs::write(dir.path().join("other"), "").unwrap();
+        assert_eq!(
+            find_newest(dir.path(), since, |name| name.starts_with("core")),
+            Some(dir.path().join("core.42"))
+        );
+        assert_eq!(
+            find_newest(
+                dir.path(),
+                SystemTime::now() + std::time::Duration::from_secs(60),
+                |_| true
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,412 @@
+//! Building the extension for several target triples from one host with
+//! [`cross`](https://github.com/cross-rs/cross) or
+//! [`cargo-zigbuild`](https://github.com/rust-cross/cargo-zigbuild), see `CrossBuild`.
+//!
+//! Each target is built with the options of a `CargoBuild`, and the libraries are added to the
+//! `.gdextension` file with `CrossBuildOutput::gdextension_config`, so exports of every platform,
+//! e.g. with an `ExportMatrix`, include their library. Targets can be built in parallel with
+//! `CrossBuild::parallel`, and with the `progress` feature, their progress is shown in one
+//! display instead of cargo's interleaved output.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let output = CrossBuild::new(CargoBuild::default().release())
+//!     .tool(CrossTool::Zigbuild)
+//!     .targets(["x86_64-unknown-linux-gnu.2.17", "x86_64-pc-windows-gnu"])
+//!     .parallel(2)
+//!     .build(Path::new("rust/Cargo.toml"))?;
+//! let runner = GodotRunner::create("game", Path::new("godot"))
+//!     .gdextension_config(move |config| output.gdextension_config("game", config));
+//! ```
+use crate::cargo::{self, CargoBuild, CdylibArtifact};
+use crate::gdextension_config::GdExtensionConfig;
+use anyhow::{Context, Result, anyhow};
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+/// The tool building for other targets.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CrossTool {
+    /// `cross build`, building in a container with the target's toolchain. Requires Docker
+    /// or Podman.
+    #[default]
+    Cross,
+    /// `cargo zigbuild`, linking with zig. A glibc version can be appended to linux targets,
+    /// e.g. `x86_64-unknown-linux-gnu.2.17`.
+    Zigbuild,
+}
+
+impl CrossTool {
+    /// The command, e.g. `cross build`, as shown in errors.
+    fn name(&self) -> &'static str {
+        match self {
+            CrossTool::Cross => "cross build",
+            CrossTool::Zigbuild => "cargo zigbuild",
+        }
+    }
+}
+
+/// Options for building the extension for several targets, see the module documentation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CrossBuild {
+    cargo_build: CargoBuild,
+    tool: CrossTool,
+    targets: Vec<String>,
+    parallel: usize,
+    #[cfg(feature = "progress")]
+    progress: bool,
+}
+
+impl CrossBuild {
+    /// Build every target with the options of `cargo_build`. Its `target` is overridden.
+    pub fn new(cargo_build: CargoBuild) -> Self {
+        Self {
+            cargo_build: cargo_build.macos_universal(false),
+            tool: CrossTool::default(),
+            targets: vec![],
+            parallel: 1,
+            #[cfg(feature = "progress")]
+            progress: false,
+        }
+    }
+
+    /// The tool building for other targets. Default: `CrossTool::Cross`.
+    pub fn tool(self, tool: CrossTool) -> Self {
+        Self { tool, ..self }
+    }
+
+    /// Enable cargo features for every target (`--features`).
+    pub fn features<S: Into<String>>(self, features: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            cargo_build: self.cargo_build.features(features),
+            ..self
+        }
+    }
+
+    /// Disable the default features for every target (`--no-default-features`). Default: false.
+    pub fn no_default_features(self, no_default_features: bool) -> Self {
+        Self {
+            cargo_build: self.cargo_build.no_default_features(no_default_features),
+            ..self
+        }
+    }
+
+    /// Enable all features for every target (`--all-features`). Default: false.
+    pub fn all_features(self, all_features: bool) -> Self {
+        Self {
+            cargo_build: self.cargo_build.all_features(all_features),
+            ..self
+        }
+    }
+
+    /// Add target triples to build for.
+    pub fn targets<S: Into<String>>(mut self, targets: impl IntoIterator<Item = S>) -> Self {
+        self.targets.extend(targets.into_iter().map(Into::into));
+        self
+    }
+
+    /// Build up to `jobs` targets at the same time. Each target of a parallel build uses its own
+    /// target directory, `<target>/cross/<triple>`, since cargo locks the target directory for
+    /// the whole build. Defaults to 1, i.e. one after another in the usual target directory.
+    pub fn parallel(self, jobs: usize) -> Self {
+        Self {
+            parallel: jobs.max(1),
+            ..self
+        }
+    }
+
+    /// Show the progress of all targets in one display instead of cargo's output. Cargo's
+    /// output of a failed build is included in the error. Default: false.
+    #[cfg(feature = "progress")]
+    pub fn progress(self, progress: bool) -> Self {
+        Self { progress, ..self }
+    }
+
+    /// Build the package at `manifest_path` for every target.
+    /// Returns the cdylibs built for all targets, or the error of the first failed target.
+    pub fn build(&self, manifest_path: &Path) -> Result<CrossBuildOutput> {
+        let target_directory = if self.tool == CrossTool::Cross || self.parallel > 1 {
+            Some(
+                cargo_metadata::MetadataCommand::new()
+                    .manifest_path(manifest_path)
+                    .exec()
+                    .context("Failed to read cargo metadata")?
+                    .target_directory
+                    .into_std_path_buf(),
+            )
+        } else {
+            None
+        };
+        #[cfg(feature = "progress")]
+        let progress = self.progress.then(indicatif::MultiProgress::new);
+        let next = Mutex::new(self.targets.iter().enumerate());
+        let results = Mutex::new(vec![]);
+        std::thread::scope(|scope| {
+            for _ in 0..self.parallel.min(self.targets.len()) {
+                scope.spawn(|| {
+                    loop {
+                        let Some((index, target)) =
+                            next.lock().unwrap_or_else(|e| e.into_inner()).next()
+                        else {
+                            break;
+                        };
+                        #[cfg(feature = "progress")]
+                        let bar = progress
+                            .as_ref()
+                            .map(|progress| progress_bar(progress, target));
+                        let on_compiled = |_name: &str| {
+                            #[cfg(feature = "progress")]
+                            if let Some(bar) = &bar {
+                                bar.inc(1);
+                                bar.set_message(_name.to_string());
+                            }
+                        };
+                        let result = self
+                            .build_target(
+                                target,
+                                manifest_path,
+                                target_directory.as_deref(),
+                                &on_compiled,
+                            )
+                            .with_context(|| format!("Failed to build for {target}"));
+                        #[cfg(feature = "progress")]
+                        if let Some(bar) = bar {
+                            bar.finish_with_message(if result.is_ok() { "done" } else { "failed" });
+                        }
+                        results
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .push((index, result));
+                    }
+                });
+            }
+        });
+        let mut results = results.into_inner().unwrap_or_else(|e| e.into_inner());
+        results.sort_by_key(|(index, _)| *index);
+        let mut artifacts = vec![];
+        for (_, result) in results {
+            artifacts.extend(result?);
+        }
+        Ok(CrossBuildOutput {
+            build: self.cargo_build.gdextension_build(),
+            artifacts,
+        })
+    }
+
+    /// Build for `target`, with the target directory `target_directory` from cargo metadata if
+    /// it is needed.
+    fn build_target(
+        &self,
+        target: &str,
+        manifest_path: &Path,
+        target_directory: Option<&Path>,
+        on_compiled: &dyn Fn(&str),
+    ) -> Result<Vec<CdylibArtifact>> {
+        let mut command = self.command(target, manifest_path);
+        let target_directory = self.target_directory(target, target_directory);
+        if self.parallel > 1
+            && let Some(target_directory) = &target_directory
+        {
+            command.env("CARGO_TARGET_DIR", target_directory);
+        }
+        // Cargo's output would garble the progress display, so it's kept for errors instead.
+        #[cfg(feature = "progress")]
+        let stderr = if self.progress {
+            let file = tempfile::tempfile().context("Failed to create a temporary file")?;
+            command.stderr(file.try_clone()?);
+            Some(file)
+        } else {
+            None
+        };
+        #[cfg(not(feature = "progress"))]
+        let stderr: Option<std::fs::File> = None;
+        let built = cargo::run_build_with_progress(
+            command,
+            self.tool.name(),
+            target_triple(target),
+            on_compiled,
+        );
+        let built = match (built, stderr) {
+            (Err(e), Some(mut stderr)) => {
+                let mut output = String::new();
+                stderr.rewind()?;
+                stderr.read_to_string(&mut output)?;
+                Err(anyhow!("{e:#}\n{}", output.trim_end()))
+            }
+            (built, _) => built,
+        }?;
+        // `cross` reports the paths inside its container, where the target directory is mounted
+        // at `/target`.
+        Ok(built
+            .into_iter()
+            .map(|artifact| CdylibArtifact {
+                path: match (&target_directory, self.tool) {
+                    (Some(target_directory), CrossTool::Cross) => {
+                        host_path(&artifact.path, target_directory)
+                    }
+                    _ => artifact.path,
+                },
+                ..artifact
+            })
+            .collect())
+    }
+
+    /// The target directory of the build for `target` in the cargo `target_directory`.
+    fn target_directory(&self, target: &str, target_directory: Option<&Path>) -> Option<PathBuf> {
+        let target_directory = target_directory?;
+        if self.parallel > 1 {
+            Some(target_directory.join("cross").join(target_triple(target)))
+        } else {
+            Some(target_directory.to_path_buf())
+        }
+    }
+
+    /// The command building for `target`.
+    fn command(&self, target: &str, manifest_path: &Path) -> Command {
+        let mut args = self
+            .cargo_build
+            .clone()
+            .target(target)
+            .cli_arguments(manifest_path);
+        let mut command = match self.tool {
+            CrossTool::Cross => Command::new("cross"),
+            CrossTool::Zigbuild => {
+                args[0] = "zigbuild".into();
+                Command::new(std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into()))
+            }
+        };
+        command.args(args).envs(self.cargo_build.env());
+        command
+    }
+}
+
+/// The libraries built by `CrossBuild::build`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CrossBuildOutput {
+    /// The `.gdextension` build of the libraries, `"debug"` or `"release"`.
+    pub build: &'static str,
+    /// The cdylibs of all targets.
+    pub artifacts: Vec<CdylibArtifact>,
+}
+
+impl CrossBuildOutput {
+    /// The library of `crate_name` built for `target`.
+    pub fn library(&self, crate_name: &str, target: &str) -> Option<&CdylibArtifact> {
+        let library_name = crate_name.replace('-', "_");
+        self.artifacts.iter().find(|artifact| {
+            artifact.name == library_name && artifact.target == target_triple(target)
+        })
+    }
+
+    /// Add the libraries of `crate_name` for all targets to `config` with
+    /// `GdExtensionConfig::library_file`.
+    pub fn gdextension_config(
+        &self,
+        crate_name: &str,
+        config: GdExtensionConfig,
+    ) -> GdExtensionConfig {
+        let library_name = crate_name.replace('-', "_");
+        self.artifacts
+            .iter()
+            .filter(|artifact| artifact.name == library_name)
+            .fold(config, |config, artifact| {
+                config.library_file(self.build, &artifact.target, &artifact.path)
+            })
+    }
+}
+
+/// A line of the progress display for `target`.
+#[cfg(feature = "progress")]
+fn progress_bar(progress: &indicatif::MultiProgress, target: &str) -> indicatif::ProgressBar {
+    let bar = progress.add(indicatif::ProgressBar::new_spinner());
+    if let Ok(style) =
+        indicatif::ProgressStyle::with_template("{spinner} {prefix:.bold} {pos} crates {wide_msg}")
+    {
+        bar.set_style(style);
+    }
+    bar.set_prefix(target.to_string());
+    bar.enable_steady_tick(std::time::Duration::from_millis(100));
+    bar
+}
+
+/// The target triple without the glibc version of `cargo zigbuild`, e.g.
+/// `x86_64-unknown-linux-gnu` for `x86_64-unknown-linux-gnu.2.17`.
+fn target_triple(target: &str) -> &str {
+    target.split('.').next().unwrap_or(target)
+}
+
+/// The host path of a `path` in the target directory of a `cross` container.
+fn host_path(path: &Path, target_directory: &Path) -> PathBuf {
+    match path.strip_prefix("/target") {
+        Ok(relative) => target_directory.join(relative),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_cross_build() {
+        let build = CrossBuild::new(CargoBuild::default().release())
+            .tool(CrossTool::Zigbuild)
+            .targets(["x86_64-unknown-linux-gnu.2.17"]);
+        let command = build.command("x86_64-unknown-linux-gnu.2.17", Path::new("Cargo.toml"));
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args[0], "zigbuild");
+        assert!(args.contains(&OsStr::new("x86_64-unknown-linux-gnu.2.17")));
+        let command = build
+            .clone()
+            .tool(CrossTool::Cross)
+            .command("x86_64-pc-windows-gnu", Path::new("Cargo.toml"));
+        assert_eq!(command.get_program(), "cross");
+        assert_eq!(command.get_args().next().unwrap(), "build");
+        let command = build
+            .clone()
+            .features(["steam"])
+            .no_default_features(true)
+            .command("x86_64-pc-windows-gnu", Path::new("Cargo.toml"));
+        let args: Vec<_> = command.get_args().collect();
+        assert!(args.windows(2).any(|args| args == ["--features", "steam"]));
+        assert!(args.contains(&OsStr::new("--no-default-features")));
+
+        let parallel = build.clone().parallel(2);
+        assert_eq!(
+            parallel.target_directory(
+                "x86_64-unknown-linux-gnu.2.17",
+                Some(Path::new("/w/target"))
+            ),
+            Some(PathBuf::from("/w/target/cross/x86_64-unknown-linux-gnu"))
+        );
+        assert_eq!(
+            build.target_directory("x86_64-pc-windows-gnu", Some(Path::new("/w/target"))),
+            Some(PathBuf::from("/w/target"))
+        );
+
+        assert_eq!(
+            host_path(
+                Path::new("/target/x86_64-pc-windows-gnu/release/game.dll"),
+                Path::new("/w/target")
+            ),
+            PathBuf::from("/w/target/x86_64-pc-windows-gnu/release/game.dll")
+        );
+        let output = CrossBuildOutput {
+            build: "release",
+            artifacts: vec![CdylibArtifact {
+                name: "game".to_string(),
+                path: PathBuf::from("/w/target/x86_64-unknown-linux-gnu/release/libgame.so"),
+                target: "x86_64-unknown-linux-gnu".to_string(),
+            }],
+        };
+        assert!(
+            output
+                .library("game", "x86_64-unknown-linux-gnu.2.17")
+                .is_some()
+        );
+        assert!(output.library("game", "x86_64-pc-windows-gnu").is_none());
+    }
+}
@@ -0,0 +1,236 @@
+//! Utilities for attaching debuggers and IDEs to a Godot instance launched by `GodotRunner`.
+use crate::exit_status::GodotExitStatus;
+use crate::godot_commands::GodotProcess;
+use anyhow::{Context, Result, anyhow};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+/// How Godot should expose itself to an external debugger.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DebugServer {
+    /// The game connects to a debugger (e.g. the Godot editor or an IDE) listening at `host:port`
+    /// using `--remote-debug tcp://host:port`.
+    Remote { host: String, port: u16 },
+    /// The editor is launched with its GDScript Debug Adapter Protocol server listening on `port`
+    /// using `--editor --dap-port port`.
+    Dap { port: u16 },
+}
+
+/// Debugger configuration for `GodotRunner::debug`.
+///
+/// Example usage:
+/// ```rust,ignore
+/// let debug = DebugConfig::dap(0).wait_for_port(Duration::from_secs(30));
+/// println!("DAP listening on port {}", debug.port());
+/// let godot = runner.debug(debug).spawn()?;
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DebugConfig {
+    server: DebugServer,
+    wait_timeout: Option<Duration>,
+}
+
+impl DebugConfig {
+    /// Connect the launched game to a debugger listening at `host:port`.
+    /// A `port` of `0` picks a free local port.
+    pub fn remote(host: &str, port: u16) -> Self {
+        Self {
+            server: DebugServer::Remote {
+                host: host.to_string(),
+                port: resolve_port(port),
+            },
+            wait_timeout: None,
+        }
+    }
+
+    /// Launch the editor with its Debug Adapter Protocol server on `port`.
+    /// A `port` of `0` picks a free local port.
+    pub fn dap(port: u16) -> Self {
+        Self {
+            server: DebugServer::Dap {
+                port: resolve_port(port),
+            },
+            wait_timeout: None,
+        }
+    }
+
+    /// Wait up to `timeout` for the debug port to accept connections.
+    /// For `Remote` this happens before Godot is launched (waiting for the debugger to listen),
+    /// for `Dap` this happens after the editor is launched (waiting for the editor to listen).
+    pub fn wait_for_port(self, timeout: Duration) -> Self {
+        Self {
+            wait_timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// The debug server configuration.
+    pub fn server(&self) -> &DebugServer {
+        &self.server
+    }
+
+    /// The host the debug port is reachable on.
+    pub fn host(&self) -> &str {
+        match &self.server {
+            DebugServer::Remote { host, .. } => host,
+            DebugServer::Dap { .. } => "127.0.0.1",
+        }
+    }
+
+    /// The chosen debug port, after resolving a requested port of `0`.
+    pub fn port(&self) -> u16 {
+        match &self.server {
+            DebugServer::Remote { port, .. } | DebugServer::Dap { port } => *port,
+        }
+    }
+
+    /// The Godot CLI arguments enabling this debug configuration.
+    pub fn cli_arguments(&self) -> Vec<String> {
+        match &self.server {
+            DebugServer::Remote { host, port } => {
+                vec!["--remote-debug".to_string(), format!("tcp://{host}:{port}")]
+            }
+            DebugServer::Dap { port } => {
+                vec![
+                    "--editor".to_string(),
+                    "--dap-port".to_string(),
+                    port.to_string(),
+                ]
+            }
+        }
+    }
+
+    /// Block until the debug port is open if `wait_for_port` was configured.
+    pub(crate) fn wait_before_launch(&self) -> Result<()> {
+        match (&self.server, self.wait_timeout) {
+            (DebugServer::Remote { .. }, Some(timeout)) => {
+                wait_for_port(self.host(), self.port(), timeout)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Block until the debug port is open if `wait_for_port` was configured.
+    pub(crate) fn wait_after_launch(&self) -> Result<()> {
+        match (&self.server, self.wait_timeout) {
+            (DebugServer::Dap { .. }, Some(timeout)) => {
+                wait_for_port(self.host(), self.port(), timeout)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A Godot editor running the GDScript language server.
+/// Returned by `GodotRunner::with_language_server`.
+#[derive(Debug)]
+pub struct LanguageServer {
+    host: String,
+    port: u16,
+    process: GodotProcess,
+}
+
+impl LanguageServer {
+    pub(crate) fn new(host: &str, port: u16, process: GodotProcess) -> Self {
+        Self {
+            host: host.to_string(),
+            port,
+            process,
+        }
+    }
+
+    /// The host the language server is listening on.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// The port the language server is listening on.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// The language server address as `host:port`, e.g. for editor LSP client configuration.
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    /// The editor process hosting the language server.
+    pub fn process(&self) -> &GodotProcess {
+        &self.process
+    }
+
+    /// Wait for the editor to exit.
+    pub fn wait(self) -> Result<GodotExitStatus> {
+        self.process.wait()
+    }
+
+    /// Stop the editor and its language server.
+    pub fn kill(self) -> Result<()> {
+        self.process.kill()
+    }
+}
+
+/// Poll `host:port` until it accepts TCP connections or `timeout` elapses.
+pub fn wait_for_port(host: &str, port: u16, timeout: Duration) -> Result<()> {
+    let start = Instant::now();
+    let addresses = (host, port)
+        .to_socket_addrs()
+        .with_context(|| format!("Failed to resolve debug address: {host}:{port}"))?
+        .collect::<Vec<_>>();
+
+    while start.elapsed() < timeout {
+        for address in &addresses {
+            if TcpStream::connect_timeout(address, Duration::from_millis(250)).is_ok() {
+                return Ok(());
+            }
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    Err(anyhow!(
+        "Timed out after {timeout:?} waiting for {host}:{port} to accept connections"
+    ))
+}
+
+/// Returns `port`, or a currently free local port if `port` is `0`.
+pub(crate) fn resolve_port(port: u16) -> u16 {
+    if port != 0 {
+        return port;
+    }
+    TcpListener::bind(("127.0.0.1", 0))
+        .and_then(|listener| listener.local_addr())
+        .map(|address| address.port())
+        .unwrap_or(port)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_arguments() {
+        assert_eq!(
+            DebugConfig::remote("localhost", 6007).cli_arguments(),
+            vec!["--remote-debug", "tcp://localhost:6007"]
+        );
+        assert_eq!(
+            DebugConfig::dap(6006).cli_arguments(),
+            vec!["--editor", "--dap-port", "6006"]
+        );
+    }
+
+    #[test]
+    fn test_free_port() {
+        assert_ne!(DebugConfig::dap(0).port(), 0);
+    }
+
+    #[test]
+    fn test_wait_for_port() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        wait_for_port("127.0.0.1", port, Duration::from_secs(1)).unwrap();
+        drop(listener);
+
+        assert!(wait_for_port("127.0.0.1", port, Duration::from_millis(200)).is_err());
+    }
+}
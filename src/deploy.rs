@@ -0,0 +1,229 @@
+//! Copying the built extension library into the godot project, see `GodotRunner::deploy`.
+//!
+//! Debuggers find the symbols of a library next to it, so the debug symbol files cargo writes
+//! alongside the library are copied as well: `.pdb` files on Windows, `.dSYM` bundles on macOS
+//! and `.dwp` or `.debug` files of `split-debuginfo` builds on Linux.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let runner = runner
+//!     .cargo_build(CargoBuild::default().release())
+//!     .deploy(Deploy::new("bin").symbols(false).strip(true));
+//! ```
+use anyhow::{Context, Result, anyhow};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Options for copying the library into the godot project.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Deploy {
+    dir: PathBuf,
+    symbols: bool,
+    strip: bool,
+}
+
+impl Deploy {
+    /// Copy the library into `dir`, relative to the godot project, e.g. `bin`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            symbols: true,
+            strip: false,
+        }
+    }
+
+    /// Copy the debug symbol files of the library as well. Default: true.
+    pub fn symbols(self, symbols: bool) -> Self {
+        Self { symbols, ..self }
+    }
+
+    /// Strip the debug info from the copied library with `strip`, e.g. for release builds.
+    /// Windows libraries keep their debug info in the `.pdb` file and are not stripped.
+    /// Default: false.
+    pub fn strip(self, strip: bool) -> Self {
+        Self { strip, ..self }
+    }
+
+    /// The directory the library is copied to.
+    pub fn dir(&self, godot_project_path: &Path) -> PathBuf {
+        godot_project_path.join(&self.dir)
+    }
+
+    /// The existing libraries named `library_name` in the deploy directory and their symbol
+    /// files, for all platforms.
+    pub fn deployed_files(&self, godot_project_path: &Path, library_name: &str) -> Vec<PathBuf> {
+        let dir = self.dir(godot_project_path);
+        let mut files = vec![];
+        for file_name in [
+            format!("lib{library_name}.so"),
+            format!("lib{library_name}.dylib"),
+            format!("{library_name}.dll"),
+        ] {
+            let library = dir.join(file_name);
+            if library.exists() {
+                files.push(library.clone());
+            }
+            files.extend(find_symbol_files(&library));
+        }
+        files
+    }
+
+    /// Copy `library` and, if enabled, its symbol files into the deploy directory of the project.
+    pub fn deploy(&self, library: &Path, godot_project_path: &Path) -> Result<DeployedLibrary> {
+        let dir = self.dir(godot_project_path);
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create deploy directory: {dir:?}"))?;
+        let file_name = library
+            .file_name()
+            .with_context(|| format!("Unexpected library path: {library:?}"))?;
+        let deployed = dir.join(file_name);
+        std::fs::copy(library, &deployed)
+            .with_context(|| format!("Failed to copy {library:?} to {deployed:?}"))?;
+        if self.strip {
+            strip(&deployed)?;
+        }
+
+        let mut symbol_files = vec![];
+        if self.symbols {
+            for source in find_symbol_files(library) {
+                let Some(file_name) = source.file_name() else {
+                    continue;
+                };
+                let target = dir.join(file_name);
+                copy_all(&source, &target)
+                    .with_context(|| format!("Failed to copy {source:?} to {target:?}"))?;
+                symbol_files.push(target);
+            }
+        }
+        Ok(DeployedLibrary {
+            library: deployed,
+            symbol_files,
+        })
+    }
+}
+
+/// The files written by `Deploy::deploy`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeployedLibrary {
+    /// The copied library.
+    pub library: PathBuf,
+    /// The copied debug symbol files and bundles.
+    pub symbol_files: Vec<PathBuf>,
+}
+
+/// The existing debug symbol files of `library`, e.g. `my_crate.pdb` for `my_crate.dll`
+/// or `libmy_crate.dylib.dSYM` for `libmy_crate.dylib`.
+pub fn find_symbol_files(library: &Path) -> Vec<PathBuf> {
+    let mut candidates = vec![library.with_extension("pdb")];
+    if let Some(file_name) = library.file_name() {
+        for extension in [".dSYM", ".dwp", ".debug"] {
+            let mut name = file_name.to_os_string();
+            name.push(extension);
+            candidates.push(library.with_file_name(name));
+        }
+    }
+    candidates
+        .into_iter()
+        .filter(|candidate| candidate != library && candidate.exists())
+        .collect()
+}
+
+/// Strip the debug info from `library` in place. Does nothing for Windows libraries.
+fn strip(library: &Path) -> Result<()> {
+    let flag = match library.extension().and_then(|ext| ext.to_str()) {
+        Some("dll") => return Ok(()),
+        Some("dylib") => "-S",
+        _ => "--strip-debug",
+    };
+    let mut command = Command::new("strip");
+    command.arg(flag).arg(library).stdin(Stdio::null());
+    let status = command
+        .status()
+        .with_context(|| format!("Failed to run strip: {command:?}"))?;
+    if !status.success() {
+        return Err(anyhow!(
+            "strip failed with status `{status}` for {library:?}"
+        ));
+    }
+    Ok(())
+}
+
+/// Copy a file, or a directory such as a `.dSYM` bundle recursively.
+fn copy_all(source: &Path, target: &Path) -> Result<()> {
+    if source.is_dir() {
+        std::fs::create_dir_all(target)?;
+        for entry in std::fs::read_dir(source)? {
+            let entry = entry?;
+            copy_all(&entry.path(), &target.join(entry.file_name()))?;
+        }
+    } else {
+        std::fs::copy(source, target)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deploy() {
+        let dir = tempfile::tempdir().unwrap();
+        let build_dir = dir.path().join("target/debug");
+        let project = dir.path().join("godot");
+        std::fs::create_dir_all(build_dir.join("libgame.dylib.dSYM/Contents")).unwrap();
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(build_dir.join("game.dll"), "dll").unwrap();
+        std::fs::write(build_dir.join("game.pdb"), "pdb").unwrap();
+        std::fs::write(build_dir.join("libgame.dylib"), "dylib").unwrap();
+        std::fs::write(build_dir.join("libgame.dylib.dSYM/Contents/Info.plist"), "").unwrap();
+        std::fs::write(build_dir.join("libgame.so"), "so").unwrap();
+
+        assert_eq!(
+            find_symbol_files(&build_dir.join("game.dll")),
+            vec![build_dir.join("game.pdb")]
+        );
+        assert_eq!(
+            find_symbol_files(&build_dir.join("libgame.dylib")),
+            vec![build_dir.join("libgame.dylib.dSYM")]
+        );
+        assert!(find_symbol_files(&build_dir.join("libgame.so")).is_empty());
+
+        let deployed = Deploy::new("bin")
+            .deploy(&build_dir.join("game.dll"), &project)
+            .unwrap();
+        assert_eq!(deployed.library, project.join("bin/game.dll"));
+        assert_eq!(deployed.symbol_files, vec![project.join("bin/game.pdb")]);
+        assert_eq!(
+            std::fs::read_to_string(project.join("bin/game.pdb")).unwrap(),
+            "pdb"
+        );
+
+        let deployed = Deploy::new("bin")
+            .deploy(&build_dir.join("libgame.dylib"), &project)
+            .unwrap();
+        assert!(
+            project
+                .join("bin/libgame.dylib.dSYM/Contents/Info.plist")
+                .exists()
+        );
+        assert_eq!(deployed.symbol_files.len(), 1);
+
+        let deployed = Deploy::new("bin")
+            .symbols(false)
+            .strip(true)
+            .deploy(&build_dir.join("game.dll"), &project)
+            .unwrap();
+        assert!(deployed.symbol_files.is_empty());
+
+        assert_eq!(
+            Deploy::new("bin").deployed_files(&project, "game"),
+            vec![
+                project.join("bin/libgame.dylib"),
+                project.join("bin/libgame.dylib.dSYM"),
+                project.join("bin/game.dll"),
+                project.join("bin/game.pdb"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//! XML class reference generation for the classes registered by the extension,
+//! see `GodotRunner::generate_docs`.
+//!
+//! Godot 4.3+ generates the class reference of all GDExtension classes of a project with
+//! `--doctool <path> --gdextension-docs`. The generated XML files use the same format as the
+//! engine's own class reference and are typically kept in the project's `doc_classes` directory.
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// The conventional documentation directory of a project.
+pub const DEFAULT_DOC_DIR: &str = "doc_classes";
+
+/// The files changed by `update_doc_dir`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DocsUpdate {
+    /// Files which didn't exist before.
+    pub created: Vec<PathBuf>,
+    /// Files whose contents changed.
+    pub updated: Vec<PathBuf>,
+    /// Files which were already up to date.
+    pub unchanged: Vec<PathBuf>,
+}
+
+impl DocsUpdate {
+    /// Returns true if any file was created or updated.
+    pub fn changed(&self) -> bool {
+        !self.created.is_empty() || !self.updated.is_empty()
+    }
+}
+
+/// The Godot CLI arguments to generate the extension class reference into `output_dir`.
+pub(crate) fn cli_arguments(output_dir: &Path) -> Vec<String> {
+    vec![
+        "--headless".to_string(),
+        "--doctool".to_string(),
+        output_dir.to_string_lossy().into_owned(),
+        "--gdextension-docs".to_string(),
+    ]
+}
+
+/// Copy the XML files Godot generated anywhere below `generated_dir` into `doc_dir`,
+/// only writing files whose contents changed so their timestamps stay stable.
+/// Files in `doc_dir` which were not generated are kept.
+pub fn update_doc_dir(generated_dir: &Path, doc_dir: &Path) -> Result<DocsUpdate> {
+    let mut generated = vec![];
+    find_xml_files(generated_dir, &mut generated)?;
+    generated.sort();
+
+    std::fs::create_dir_all(doc_dir)
+        .with_context(|| format!("Failed to create documentation directory: {doc_dir:?}"))?;
+    let mut update = DocsUpdate::default();
+    for source in generated {
+        let Some(file_name) = source.file_name() else {
+            continue;
+        };
+        let target = doc_dir.join(file_name);
+        let contents = std::fs::read(&source)
+            .with_context(|| format!("Failed to read generated documentation: {source:?}"))?;
+        let list = match std::fs::read(&target) {
+            Ok(existing) if existing == contents => {
+                update.unchanged.push(target);
+                continue;
+            }
+            Ok(_) => &mut update.updated,
+            Err(_) => &mut update.created,
+        };
+        std::fs::write(&target, contents)
+            .with_context(|| format!("Failed to write documentation: {target:?}"))?;
+        list.push(target);
+    }
+    Ok(update)
+}
+
+fn find_xml_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries =
+        std::fs::read_dir(dir).with_context(|| format!("Failed to read directory: {dir:?}"))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_xml_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "xml") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_doc_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let generated = dir.path().join("generated");
+        let doc_dir = dir.path().join("doc_classes");
+        std::fs::create_dir_all(generated.join("doc_classes")).unwrap();
+        std::fs::create_dir_all(&doc_dir).unwrap();
+        std::fs::write(
+            generated.join("doc_classes/Player.xml"),
+            "<class name=\"Player\"/>",
+        )
+        .unwrap();
+        std::fs::write(
+            generated.join("doc_classes/Enemy.xml"),
+            "<class name=\"Enemy\"/>",
+        )
+        .unwrap();
+        std::fs::write(generated.join("log.txt"), "").unwrap();
+        std::fs::write(doc_dir.join("Enemy.xml"), "<class/>").unwrap();
+        std::fs::write(doc_dir.join("Manual.xml"), "<class/>").unwrap();
+
+        let update = update_doc_dir(&generated, &doc_dir).unwrap();
+        assert!(update.changed());
+        assert_eq!(update.created, vec![doc_dir.join("Player.xml")]);
+        assert_eq!(update.updated, vec![doc_dir.join("Enemy.xml")]);
+        assert!(doc_dir.join("Manual.xml").exists());
+        assert!(!doc_dir.join("log.txt").exists());
+
+        let update = update_doc_dir(&generated, &doc_dir).unwrap();
+        assert!(!update.changed());
+        assert_eq!(update.unchanged.len(), 2);
+    }
+}
@@ -0,0 +1,611 @@
+//! Environment diagnostics for a `GodotRunner` configuration.
+use crate::GodotRunner;
+use crate::export_templates;
+use crate::gdextension_config::GdExtensionConfig;
+use crate::godot_commands::{GodotBinary, GodotVersion, godot_binary_path};
+use crate::project_config::ProjectConfig;
+use anyhow::{Context, Result, anyhow};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The outcome of a single diagnostic check.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// A single diagnostic check with an optional suggestion on how to fix it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DoctorCheck {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+    pub fix: Option<String>,
+}
+
+/// The result of running `doctor`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DoctorReport {
+    pub checks: Vec<DoctorCheck>,
+}
+
+impl DoctorReport {
+    /// Returns true if no check reported an error.
+    pub fn is_healthy(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.status != CheckStatus::Error)
+    }
+
+    /// Returns the checks which did not pass.
+    pub fn problems(&self) -> impl Iterator<Item = &DoctorCheck> {
+        self.checks
+            .iter()
+            .filter(|check| check.status != CheckStatus::Ok)
+    }
+
+    fn ok(&mut self, name: &'static str, message: impl Into<String>) {
+        self.push(name, CheckStatus::Ok, message, None::<String>);
+    }
+
+    fn push(
+        &mut self,
+        name: &'static str,
+        status: CheckStatus,
+        message: impl Into<String>,
+        fix: Option<impl Into<String>>,
+    ) {
+        self.checks.push(DoctorCheck {
+            name,
+            status,
+            message: message.into(),
+            fix: fix.map(Into::into),
+        });
+    }
+}
+
+impl Display for DoctorReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for check in &self.checks {
+            let status = match check.status {
+                CheckStatus::Ok => "ok",
+                CheckStatus::Warning => "warning",
+                CheckStatus::Error => "error",
+            };
+            writeln!(f, "[{status}] {}: {}", check.name, check.message)?;
+            if let Some(fix) = &check.fix {
+                writeln!(f, "    fix: {fix}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check the environment `runner` will execute in and report problems with suggested fixes:
+/// - Godot binary presence and version.
+/// - Export templates for the detected Godot version.
+/// - The godot project's `project.godot`.
+/// - The crate being built as a `cdylib`.
+/// - Validity of the `.gdextension` file.
+/// - Existence of the `.godot` import folder.
+/// - Installed Rust target toolchains.
+///
+/// Example usage:
+/// ```rust,ignore
+/// let report = cargo_godot_lib::doctor(&runner);
+/// print!("{report}");
+/// ```
+pub fn doctor(runner: &GodotRunner) -> DoctorReport {
+    let mut report = DoctorReport::default();
+
+    let version = check_godot(runner, &mut report);
+    if let Some(version) = &version {
+        check_export_templates(version, &mut report);
+    }
+    check_project(runner, &mut report);
+    check_cdylib(runner, &mut report);
+    check_gdextension(runner, &mut report);
+    check_import(runner, &mut report);
+    check_rust_targets(runner, &mut report);
+
+    report
+}
+
+fn check_godot(runner: &GodotRunner, report: &mut DoctorReport) -> Option<GodotVersion> {
+    const NAME: &str = "Godot binary";
+    match runner.godot_binary() {
+        GodotBinary::Path(binary) => {
+            if binary.is_file() {
+                report.ok(NAME, format!("Using {binary:?}"));
+            } else {
+                report.push(
+                    NAME,
+                    CheckStatus::Error,
+                    format!("{binary:?} does not exist"),
+                    Some("Check the `godot_binary_path` of the runner"),
+                );
+                return None;
+            }
+        }
+        GodotBinary::Version(godot_version) => match which::which("gdenv") {
+            Ok(path) => report.ok(
+                NAME,
+                format!("Using gdenv ({path:?}) with Godot {godot_version}"),
+            ),
+            Err(_) => {
+                report.push(
+                    NAME,
+                    CheckStatus::Error,
+                    format!("Godot {godot_version} was requested but `gdenv` is not in PATH"),
+                    Some("Install gdenv (https://github.com/bytemeadow/gdenv) or remove `godot_version` from the runner"),
+                );
+                return None;
+            }
+        },
+        GodotBinary::Discover => match godot_binary_path() {
+            Ok(path) => report.ok(NAME, format!("Found {path:?}")),
+            Err(e) => {
+                report.push(
+                    NAME,
+                    CheckStatus::Error,
+                    "Couldn't find the godot binary",
+                    Some(format!("{e}")),
+                );
+                return None;
+            }
+        },
+    }
+
+    match runner.detected_godot_version() {
+        Ok(version) => {
+            report.ok("Godot version", version.to_string());
+            Some(version)
+        }
+        Err(e) => {
+            report.push(
+                "Godot version",
+                CheckStatus::Error,
+                format!("{e:#}"),
+                Some("Check that the binary is a Godot 4 executable and runs on this machine"),
+            );
+            None
+        }
+    }
+}
+
+fn check_export_templates(version: &GodotVersion, report: &mut DoctorReport) {
+    const NAME: &str = "Export templates";
+    match export_templates::is_installed(version) {
+        Ok(true) => report.ok(
+            NAME,
+            format!("Installed for {}", version.template_dir_name()),
+        ),
+        Ok(false) => report.push(
+            NAME,
+            CheckStatus::Warning,
+            format!(
+                "Not installed for {} (only needed for exports)",
+                version.template_dir_name()
+            ),
+            Some(format!(
+                "Install them from the editor (Editor > Manage Export Templates) or download {}",
+                export_templates::download_url(version)
+            )),
+        ),
+        Err(e) => report.push(NAME, CheckStatus::Warning, format!("{e:#}"), None::<String>),
+    }
+}
+
+/// The path of the godot project chosen with `GodotRunner::select_project`, as configured.
+fn selected_project_path(runner: &GodotRunner) -> Result<PathBuf> {
+    Ok(runner.selected_project()?.path)
+}
+
+fn check_project(runner: &GodotRunner, report: &mut DoctorReport) {
+    const NAME: &str = "project.godot";
+    let project_path = match selected_project_path(runner) {
+        Ok(project_path) => project_path,
+        Err(e) => {
+            report.push(
+                NAME,
+                CheckStatus::Error,
+                format!("{e:#}"),
+                Some("Check the project passed to `select_project`"),
+            );
+            return;
+        }
+    };
+    match ProjectConfig::load(&project_path).and_then(|config| {
+        config.validate()?;
+        Ok(config)
+    }) {
+        Ok(config) => report.ok(
+            NAME,
+            format!(
+                "{:?} (Godot {})",
+                config.name().unwrap_or_default(),
+                config.engine_version().as_deref().unwrap_or("unknown")
+            ),
+        ),
+        Err(e) => report.push(
+            NAME,
+            CheckStatus::Error,
+            format!("{e:#}"),
+            Some(format!(
+                "Check that the godot project path points at the folder containing `project.godot`: {project_path:?}"
+            )),
+        ),
+    }
+}
+
+fn check_cdylib(runner: &GodotRunner, report: &mut DoctorReport) {
+    const NAME: &str = "cdylib crate type";
+    let result = cargo_metadata::MetadataCommand::new()
+        .manifest_path(&runner.cargo_manifest_path)
+        .no_deps()
+        .exec();
+    let metadata = match result {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            report.push(
+                NAME,
+                CheckStatus::Error,
+                format!("Failed to read cargo metadata: {e}"),
+                Some(format!(
+                    "Check the cargo manifest path: {:?}",
+                    runner.cargo_manifest_path
+                )),
+            );
+            return;
+        }
+    };
+
+    let library_name = runner.crate_name.replace('-', "_");
+    let is_cdylib = metadata
+        .packages
+        .iter()
+        .flat_map(|package| &package.targets)
+        .filter(|target| target.name.replace('-', "_") == library_name)
+        .any(|target| target.is_kind(cargo_metadata::TargetKind::CDyLib));
+    if is_cdylib {
+        report.ok(NAME, format!("`{}` builds a cdylib", runner.crate_name));
+    } else {
+        report.push(
+            NAME,
+            CheckStatus::Error,
+            format!("`{}` does not build a cdylib", runner.crate_name),
+            Some("Add `[lib] crate-type = [\"cdylib\"]` to the crate's Cargo.toml"),
+        );
+    }
+}
+
+fn check_gdextension(runner: &GodotRunner, report: &mut DoctorReport) {
+    const NAME: &str = ".gdextension file";
+    let (config_path, project_path) = match gdextension_config_path(runner) {
+        Ok(paths) => paths,
+        Err(e) => {
+            report.push(
+                NAME,
+                CheckStatus::Error,
+                format!("{e:#}"),
+                Some("Check the godot project path and the cargo target directory"),
+            );
+            return;
+        }
+    };
+
+    match std::fs::read_to_string(&config_path) {
+        Ok(contents) => check_gdextension_contents(&contents, &config_path, &project_path, report),
+        Err(_) if runner.write_gdextension_config => {
+            report.ok(NAME, format!("{config_path:?} will be generated"));
+        }
+        Err(_) => report.push(
+            NAME,
+            CheckStatus::Error,
+            format!("{config_path:?} does not exist"),
+            Some("Enable `write_gdextension_config` to generate it"),
+        ),
+    }
+}
+
+/// Check the `entry_symbol` and the libraries of the host platform of a `.gdextension` file.
+fn check_gdextension_contents(
+    contents: &str,
+    config_path: &Path,
+    project_path: &Path,
+    report: &mut DoctorReport,
+) {
+    const NAME: &str = ".gdextension file";
+    const FIX: &str = "Enable `write_gdextension_config` or fix the file by hand";
+    let config = match ProjectConfig::parse(contents) {
+        Ok(config) => config,
+        Err(e) => {
+            report.push(
+                NAME,
+                CheckStatus::Error,
+                format!("Failed to parse {config_path:?}: {e:#}"),
+                Some(FIX),
+            );
+            return;
+        }
+    };
+    if config.get_string("configuration", "entry_symbol").is_none() {
+        report.push(
+            NAME,
+            CheckStatus::Error,
+            format!("{config_path:?} has no `entry_symbol` in `[configuration]`"),
+            Some(FIX),
+        );
+        return;
+    }
+
+    let libraries: Vec<PathBuf> = config
+        .keys("libraries")
+        .filter(|key| is_host_library(key))
+        .filter_map(|key| config.get_string("libraries", key))
+        .map(|path| match path.strip_prefix("res://") {
+            Some(relative) => project_path.join(relative),
+            // Godot resolves relative paths from the directory of the `.gdextension` file.
+            None => config_path.parent().unwrap_or(project_path).join(path),
+        })
+        .collect();
+    let os = std::env::consts::OS;
+    if libraries.is_empty() {
+        report.push(
+            NAME,
+            CheckStatus::Error,
+            format!("{config_path:?} has no `[libraries]` entry for {os}"),
+            Some(FIX),
+        );
+        return;
+    }
+    let missing: Vec<&PathBuf> = libraries.iter().filter(|path| !path.is_file()).collect();
+    if missing.is_empty() {
+        report.ok(NAME, format!("{config_path:?} is valid"));
+    } else {
+        report.push(
+            NAME,
+            CheckStatus::Warning,
+            format!("The {os} libraries {missing:?} of {config_path:?} don't exist"),
+            Some("Build the crate, e.g. by running the `GodotRunner`"),
+        );
+    }
+}
+
+/// Returns true if the `[libraries]` key, e.g. `linux.debug.x86_64`, matches the host platform.
+/// Keys without an architecture, e.g. `macos.debug`, match every architecture.
+fn is_host_library(key: &str) -> bool {
+    let arch = match std::env::consts::ARCH {
+        "aarch64" => "arm64",
+        "arm" => "arm32",
+        "x86" => "x86_32",
+        arch => arch,
+    };
+    let mut tags = key.split('.');
+    tags.next() == Some(std::env::consts::OS)
+        && tags
+            .filter(|tag| !matches!(*tag, "debug" | "release" | "editor"))
+            .all(|tag| tag == arch)
+}
+
+/// The paths of the `.gdextension` file and of the godot project it is in.
+fn gdextension_config_path(runner: &GodotRunner) -> Result<(PathBuf, PathBuf)> {
+    let metadata = cargo_metadata::MetadataCommand::new()
+        .manifest_path(&runner.cargo_manifest_path)
+        .no_deps()
+        .exec()
+        .context("Failed to read cargo metadata")?;
+    let project_path = selected_project_path(runner)?;
+    let config = (runner.gdextension_config)(GdExtensionConfig::start(
+        &runner.crate_name,
+        &project_path,
+        metadata.target_directory.as_std_path(),
+    ))
+    .build()?;
+    Ok((config.full_config_path(), project_path))
+}
+
+fn check_import(runner: &GodotRunner, report: &mut DoctorReport) {
+    const NAME: &str = ".godot folder";
+    let Ok(project_path) = selected_project_path(runner) else {
+        // Reported by `check_project`.
+        return;
+    };
+    if project_path.join(".godot").exists() {
+        report.ok(NAME, "Project has been imported");
+    } else if runner.pre_import {
+        report.ok(NAME, "Missing, it will be created by the pre-import step");
+    } else {
+        report.push(
+            NAME,
+            CheckStatus::Warning,
+            "Missing and `pre_import` is disabled, GDExtension classes may fail to load",
+            Some("Enable `pre_import` or open the project in the Godot editor once"),
+        );
+    }
+}
+
+/// Checks the rustup targets of `rust_targets`.
+fn check_rust_targets(runner: &GodotRunner, report: &mut DoctorReport) {
+    const NAME: &str = "Rust targets";
+    let (required, platforms) = match host_target() {
+        Ok(host) => rust_targets(runner, host),
+        Err(e) => {
+            report.push(NAME, CheckStatus::Warning, format!("{e:#}"), None::<String>);
+            return;
+        }
+    };
+
+    let installed = match Command::new("rustup")
+        .args(["target", "list", "--installed"])
+        .output()
+    {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect::<Vec<_>>(),
+        _ => {
+            report.push(
+                NAME,
+                CheckStatus::Warning,
+                "Couldn't list installed targets, `rustup` is not available",
+                None::<String>,
+            );
+            return;
+        }
+    };
+
+    let missing = |targets: &[String]| {
+        targets
+            .iter()
+            .filter(|target| !installed.contains(target))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let (missing_required, missing_platforms) = (missing(&required), missing(&platforms));
+    let all_missing = [missing_required.as_slice(), &missing_platforms].concat();
+    let hint = Some(format!("rustup target add {}", all_missing.join(" ")));
+    if !missing_required.is_empty() {
+        report.push(
+            NAME,
+            CheckStatus::Error,
+            format!("Missing: {}", all_missing.join(", ")),
+            hint,
+        );
+    } else if !missing_platforms.is_empty() {
+        report.push(
+            NAME,
+            CheckStatus::Warning,
+            format!(
+                "Missing for the configured platforms: {} (only needed to build for them)",
+                missing_platforms.join(", ")
+            ),
+            hint,
+        );
+    } else {
+        let all = [required, platforms].concat();
+        report.ok(NAME, format!("Installed: {}", all.join(", ")));
+    }
+}
+
+/// The targets needed to launch: the `host` and the targets of the runner's `cargo_build`, and
+/// the other targets of the platforms of the `.gdextension` configs, needed to build for them.
+fn rust_targets(runner: &GodotRunner, host: String) -> (Vec<String>, Vec<String>) {
+    let mut required = vec![host];
+    if let Some(cargo_build) = runner.effective_cargo_build() {
+        for triple in cargo_build.target_triples() {
+            if !required.iter().any(|required| required == triple) {
+                required.push(triple.to_string());
+            }
+        }
+    }
+    let mut platforms: Vec<String> = vec![];
+    for config in runner.gdextension_configs() {
+        for triple in config(GdExtensionConfig::default()).target_triples() {
+            if !required
+                .iter()
+                .chain(&platforms)
+                .any(|known| known == triple)
+            {
+                platforms.push(triple.to_string());
+            }
+        }
+    }
+    (required, platforms)
+}
+
+/// The target triple of the installed `rustc`.
+fn host_target() -> Result<String> {
+    let output = Command::new("rustc")
+        .arg("-vV")
+        .output()
+        .context("Failed to run `rustc -vV`")?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("Failed to determine the rustc host target"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cargo::CargoBuild;
+    use crate::gdextension_config::Platform;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_doctor() {
+        let runner = GodotRunner::create("cargo-godot-lib", Path::new("mock_godot_project"));
+        let report = doctor(&runner);
+
+        let cdylib = report
+            .checks
+            .iter()
+            .find(|check| check.name == "cdylib crate type")
+            .unwrap();
+        assert_eq!(cdylib.status, CheckStatus::Ok);
+        assert!(
+            report
+                .checks
+                .iter()
+                .any(|check| check.name == "Godot binary")
+        );
+        assert!(report.to_string().contains("[ok] cdylib crate type"));
+
+        let runner = runner
+            .cargo_build(CargoBuild::default().target("x86_64-pc-windows-gnu"))
+            .gdextension_config(|config| config.platforms(&[Platform::Linux, Platform::Web]));
+        let (required, platforms) = rust_targets(&runner, "x86_64-unknown-linux-gnu".to_string());
+        assert_eq!(
+            required,
+            ["x86_64-unknown-linux-gnu", "x86_64-pc-windows-gnu"]
+        );
+        assert_eq!(platforms, ["wasm32-unknown-emscripten"]);
+    }
+
+    #[test]
+    fn test_check_gdextension() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("game.gdextension");
+        let library = format!("{}.debug.{}", std::env::consts::OS, std::env::consts::ARCH)
+            .replace("aarch64", "arm64");
+        let check = |contents: &str| {
+            let mut report = DoctorReport::default();
+            check_gdextension_contents(contents, &config_path, dir.path(), &mut report);
+            report.checks.pop().unwrap()
+        };
+
+        // A comment mentioning the keys doesn't make the file valid.
+        let check_result = check("; [configuration] entry_symbol [libraries]\n");
+        assert_eq!(check_result.status, CheckStatus::Error);
+        assert!(check_result.message.contains("entry_symbol"));
+
+        let contents = format!(
+            "[configuration]\nentry_symbol = \"gdext_rust_init\"\n\n\
+            [libraries]\nweb.debug.wasm32 = \"res://game.wasm\"\n{library} = \"res://libgame\"\n"
+        );
+        let check_result = check(&contents);
+        assert_eq!(check_result.status, CheckStatus::Warning);
+        assert!(check_result.message.contains("libgame"));
+        assert!(!check_result.message.contains("game.wasm"));
+
+        fs::write(dir.path().join("libgame"), "").unwrap();
+        assert_eq!(check(&contents).status, CheckStatus::Ok);
+
+        let check_result = check("[configuration]\nentry_symbol = \"gdext_rust_init\"\n");
+        assert_eq!(check_result.status, CheckStatus::Error);
+        assert!(check_result.message.contains("[libraries]"));
+
+        assert!(is_host_library(&library));
+        assert!(is_host_library(&format!(
+            "{}.release",
+            std::env::consts::OS
+        )));
+        assert!(!is_host_library("web.debug.wasm32"));
+    }
+}
@@ -0,0 +1,158 @@
+//! Support for godot projects mixing C# and Rust, see `GodotRunner::dotnet`.
+//!
+//! C# scripts only load in the .NET build of Godot, and only after the project's `.csproj` was
+//! built. With `Dotnet`, the runner builds the `.csproj` in the project root with
+//! `dotnet build` before every launch and prefers a .NET Godot binary found on the `PATH`,
+//! e.g. `godot-mono`, over a plain `godot`.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let runner = runner.dotnet(Dotnet::default().configuration("ExportDebug"));
+//! ```
+use anyhow::{Context, Result, anyhow};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use which::{which, which_in_global};
+
+/// Names of .NET Godot binaries used by package managers and the official downloads.
+const DOTNET_BINARY_NAMES: [&str; 4] = ["godot-mono", "godot-dotnet", "godot_mono", "godot4-mono"];
+
+/// Locations of .NET Godot binaries outside the `PATH`.
+const DOTNET_SEARCH_PATHS: &str = "/Applications/Godot_mono.app/Contents/MacOS";
+
+/// Options for C#/.NET hybrid projects.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Dotnet {
+    build: bool,
+    configuration: String,
+    prefer_dotnet_editor: bool,
+}
+
+impl Default for Dotnet {
+    fn default() -> Self {
+        Self {
+            build: true,
+            configuration: "Debug".to_string(),
+            prefer_dotnet_editor: true,
+        }
+    }
+}
+
+impl Dotnet {
+    /// Run `dotnet build` on the project's `.csproj` before every launch. Default: true.
+    pub fn build(self, build: bool) -> Self {
+        Self { build, ..self }
+    }
+
+    /// The build configuration (`--configuration`), e.g. `ExportRelease`. Default: `Debug`.
+    pub fn configuration(self, configuration: &str) -> Self {
+        Self {
+            configuration: configuration.to_string(),
+            ..self
+        }
+    }
+
+    /// Run a .NET Godot binary found by `find_dotnet_binary` instead of the default binary.
+    /// The `godot` and `GODOT` environment variables and `GodotRunner::godot_version` still
+    /// take precedence. Default: true.
+    pub fn prefer_dotnet_editor(self, prefer_dotnet_editor: bool) -> Self {
+        Self {
+            prefer_dotnet_editor,
+            ..self
+        }
+    }
+
+    /// Whether a .NET Godot binary should be preferred.
+    pub(crate) fn prefers_dotnet_editor(&self) -> bool {
+        self.prefer_dotnet_editor
+    }
+
+    /// Build the `.csproj` of the godot project if configured.
+    /// Returns an error if the project has no `.csproj` or the build failed.
+    pub fn prepare(&self, godot_project_path: &Path) -> Result<()> {
+        if !self.build {
+            return Ok(());
+        }
+        let csproj = find_csproj(godot_project_path)?.with_context(|| {
+            format!(
+                "No .csproj file found in {godot_project_path:?}. \
+                Create the C# solution in the .NET Godot editor first."
+            )
+        })?;
+        let mut command = Command::new("dotnet");
+        command
+            .arg("build")
+            .arg(&csproj)
+            .args(["--nologo", "--configuration", &self.configuration])
+            .stdin(Stdio::null());
+        let status = command.status().with_context(|| {
+            format!(
+                "Failed to run dotnet, which is required to build the C# scripts \
+                (https://dotnet.microsoft.com/download): {command:?}"
+            )
+        })?;
+        if !status.success() {
+            return Err(anyhow!(
+                "dotnet build of {csproj:?} failed with status `{status}`"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The `.csproj` file in the root of the godot project, which Godot names after the project.
+/// Returns the first one by name if there are several.
+pub fn find_csproj(godot_project_path: &Path) -> Result<Option<PathBuf>> {
+    let mut csproj_files: Vec<PathBuf> = std::fs::read_dir(godot_project_path)
+        .with_context(|| format!("Failed to read directory: {godot_project_path:?}"))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "csproj"))
+        .collect();
+    csproj_files.sort();
+    Ok(csproj_files.into_iter().next())
+}
+
+/// Whether the godot project uses C#, i.e. has a `.csproj` file.
+pub fn is_dotnet_project(godot_project_path: &Path) -> bool {
+    find_csproj(godot_project_path).is_ok_and(|csproj| csproj.is_some())
+}
+
+/// Looks for a .NET Godot binary on the `PATH` and in the default macOS location, unless the
+/// `godot` or `GODOT` environment variables select a binary explicitly.
+pub fn find_dotnet_binary() -> Option<PathBuf> {
+    if std::env::var_os("godot").is_some() || std::env::var_os("GODOT").is_some() {
+        return None;
+    }
+    DOTNET_BINARY_NAMES
+        .iter()
+        .find_map(|name| which(name).ok())
+        .or_else(|| {
+            which_in_global("Godot", Some(DOTNET_SEARCH_PATHS))
+                .ok()
+                .and_then(|mut paths| paths.next())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_csproj() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(find_csproj(dir.path()).unwrap(), None);
+        assert!(!is_dotnet_project(dir.path()));
+        assert!(Dotnet::default().prepare(dir.path()).is_err());
+        Dotnet::default().build(false).prepare(dir.path()).unwrap();
+
+        std::fs::write(dir.path().join("My Game.csproj"), "<Project/>").unwrap();
+        std::fs::write(dir.path().join("Other.csproj"), "<Project/>").unwrap();
+        std::fs::create_dir(dir.path().join("addons.csproj")).unwrap();
+        assert_eq!(
+            find_csproj(dir.path()).unwrap(),
+            Some(dir.path().join("My Game.csproj"))
+        );
+        assert!(is_dotnet_project(dir.path()));
+    }
+}
@@ -0,0 +1,108 @@
+//! Detection of a Godot editor launched by `GodotRunner` that is still running on a project.
+//!
+//! Godot itself doesn't lock a project, so opening the editor twice leads to confusing errors.
+//! When the runner launches the editor it records the editor's process id in
+//! `.godot/editor/cargo-godot-lib.pid`, and refuses to launch a second editor while that process
+//! is alive.
+use anyhow::{Context, Result, anyhow};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// The path of the editor lock file of a godot project.
+pub fn lock_path(godot_project_path: &Path) -> PathBuf {
+    godot_project_path.join(".godot/editor/cargo-godot-lib.pid")
+}
+
+/// Returns the process id of an editor launched by `GodotRunner` that is still running
+/// on the godot project, if any.
+pub fn running_editor(godot_project_path: &Path) -> Option<u32> {
+    let pid = std::fs::read_to_string(lock_path(godot_project_path))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    is_process_running(pid).then_some(pid)
+}
+
+/// Returns true if `args` launch the Godot editor.
+pub(crate) fn is_editor_launch(args: &[String]) -> bool {
+    args.iter().any(|arg| arg == "-e" || arg == "--editor")
+}
+
+/// Fail if another editor launched by `GodotRunner` is running on the godot project.
+pub(crate) fn ensure_no_running_editor(godot_project_path: &Path) -> Result<()> {
+    match running_editor(godot_project_path) {
+        Some(pid) => Err(anyhow!(
+            "A Godot editor (pid {pid}) is already running on {:?}.\n\
+            Close it first, or use `GodotRunner::force_editor_launch(true)` to launch another one.\n\
+            Lock file: {:?}",
+            godot_project_path,
+            lock_path(godot_project_path)
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Record `pid` as the editor running on the godot project. Returns the lock file path.
+pub(crate) fn write_lock(godot_project_path: &Path, pid: u32) -> Result<PathBuf> {
+    let path = lock_path(godot_project_path);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {parent:?}"))?;
+    }
+    std::fs::write(&path, pid.to_string())
+        .with_context(|| format!("Failed to write editor lock file: {path:?}"))?;
+    Ok(path)
+}
+
+#[cfg(unix)]
+fn is_process_running(pid: u32) -> bool {
+    Command::new("kill")
+        .arg("-0")
+        .arg(pid.to_string())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[cfg(windows)]
+fn is_process_running(pid: u32) -> bool {
+    Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {pid}"), "/NH"])
+        .stderr(Stdio::null())
+        .output()
+        .is_ok_and(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .split_whitespace()
+                .any(|word| word == pid.to_string())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_running_editor() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(running_editor(dir.path()), None);
+
+        write_lock(dir.path(), std::process::id()).unwrap();
+        assert_eq!(running_editor(dir.path()), Some(std::process::id()));
+        assert!(ensure_no_running_editor(dir.path()).is_err());
+
+        // A stale lock file of an exited process is ignored.
+        let mut child = Command::new("cargo").arg("--version").spawn().unwrap();
+        child.wait().unwrap();
+        write_lock(dir.path(), child.id()).unwrap();
+        assert_eq!(running_editor(dir.path()), None);
+    }
+
+    #[test]
+    fn test_is_editor_launch() {
+        assert!(is_editor_launch(&["--editor".to_string()]));
+        assert!(is_editor_launch(&["-e".to_string()]));
+        assert!(!is_editor_launch(&["--headless".to_string()]));
+    }
+}
@@ -0,0 +1,240 @@
+//! A Godot editor plugin with a toolbar button that rebuilds the extension,
+//! see `GodotRunner::editor_plugin`.
+//!
+//! The plugin is written to `addons/cargo_godot_lib/` and enabled in `project.godot`. Its button
+//! runs `cargo build` for the crate and rescans the project, after which Godot reloads a
+//! `reloadable` extension, so designers can rebuild the Rust code without leaving the editor.
+//! The plugin is meant to be committed, so it keeps working for teammates who only use the editor.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! EditorPlugin::new(Path::new("rust/Cargo.toml"))
+//!     .cargo_args(["--features", "tools"])
+//!     .install(Path::new("godot"))?;
+//! ```
+use crate::project_config::{self, ProjectConfig, SettingValue};
+use anyhow::{Context, Result};
+use pathdiff::diff_paths;
+use std::path::{Path, PathBuf};
+
+/// The directory of the plugin, relative to the godot project.
+pub const PLUGIN_DIR: &str = "addons/cargo_godot_lib";
+
+/// The `res://` path of the plugin's `plugin.cfg`.
+const PLUGIN_CFG: &str = "res://addons/cargo_godot_lib/plugin.cfg";
+
+const PLUGIN_SCRIPT: &str = r#"@tool
+extends EditorPlugin
+## Generated by cargo-godot-lib, changes are overwritten.
+
+const MANIFEST_PATH := {manifest_path}
+const CARGO_ARGS := [{cargo_args}]
+
+var _button: Button
+
+
+func _enter_tree() -> void:
+	_button = Button.new()
+	_button.text = {button_text}
+	_button.tooltip_text = "Run `cargo build` and reload the Rust extension"
+	_button.pressed.connect(_build)
+	add_control_to_container(CONTAINER_TOOLBAR, _button)
+
+
+func _exit_tree() -> void:
+	remove_control_from_container(CONTAINER_TOOLBAR, _button)
+	_button.queue_free()
+
+
+func _build() -> void:
+	var args := ["build", "--manifest-path", ProjectSettings.globalize_path("res://").path_join(MANIFEST_PATH)]
+	args.append_array(CARGO_ARGS)
+	var output := []
+	_button.disabled = true
+	var exit_code := OS.execute("cargo", args, output, true)
+	_button.disabled = false
+	for text in output:
+		print(text)
+	if exit_code == 0:
+		print_rich("[color=green]cargo build finished[/color]")
+		EditorInterface.get_resource_filesystem().scan()
+	else:
+		push_error("cargo build failed with exit code %d" % exit_code)
+"#;
+
+/// Options for the generated editor plugin.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EditorPlugin {
+    manifest_path: PathBuf,
+    cargo_args: Vec<String>,
+    button_text: String,
+}
+
+impl EditorPlugin {
+    /// A plugin building the crate of the `Cargo.toml` at `manifest_path`.
+    pub fn new(manifest_path: &Path) -> Self {
+        Self {
+            manifest_path: manifest_path.to_path_buf(),
+            cargo_args: vec![],
+            button_text: "Build Rust".to_string(),
+        }
+    }
+
+    /// Add arguments to `cargo build`, e.g. `--release`.
+    pub fn cargo_args<S: Into<String>>(mut self, args: impl IntoIterator<Item = S>) -> Self {
+        self.cargo_args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// The text of the toolbar button. Default: `Build Rust`.
+    pub fn button_text(self, text: &str) -> Self {
+        Self {
+            button_text: text.to_string(),
+            ..self
+        }
+    }
+
+    /// The contents of the plugin's script, with the manifest path relative to the project.
+    fn script(&self, relative_manifest_path: &str) -> String {
+        let quote = |value: &str| SettingValue::from(value).to_variant_string();
+        PLUGIN_SCRIPT
+            .replace("{manifest_path}", &quote(relative_manifest_path))
+            .replace(
+                "{cargo_args}",
+                &self
+                    .cargo_args
+                    .iter()
+                    .map(|arg| quote(arg))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )
+            .replace("{button_text}", &quote(&self.button_text))
+    }
+
+    /// Write the plugin into the godot project and enable it, updating files whose contents
+    /// changed. Returns the written files.
+    pub fn install(&self, godot_project_path: &Path) -> Result<Vec<PathBuf>> {
+        let manifest_path = self.manifest_path.canonicalize().with_context(|| {
+            format!(
+                "Failed to canonicalize manifest path: {:?}",
+                self.manifest_path
+            )
+        })?;
+        let project_path = godot_project_path.canonicalize().with_context(|| {
+            format!("Failed to canonicalize godot project path: {godot_project_path:?}")
+        })?;
+        let relative_manifest_path = diff_paths(&manifest_path, &project_path)
+            .with_context(|| format!("Failed to make {manifest_path:?} relative to the project"))?
+            .to_string_lossy()
+            .replace('\\', "/");
+
+        let dir = godot_project_path.join(PLUGIN_DIR);
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create plugin directory: {dir:?}"))?;
+        let plugin_cfg = format!(
+            "[plugin]\n\nname=\"Cargo Godot Lib\"\n\
+            description=\"Build and reload the Rust extension from the editor.\"\n\
+            author=\"cargo-godot-lib\"\nversion=\"{}\"\nscript=\"plugin.gd\"\n",
+            env!("CARGO_PKG_VERSION")
+        );
+        let mut written = vec![];
+        for (name, contents) in [
+            ("plugin.cfg", plugin_cfg),
+            ("plugin.gd", self.script(&relative_manifest_path)),
+        ] {
+            let path = dir.join(name);
+            if std::fs::read_to_string(&path).is_ok_and(|existing| existing == contents) {
+                continue;
+            }
+            std::fs::write(&path, contents).with_context(|| format!("Failed to write {path:?}"))?;
+            written.push(path);
+        }
+
+        let mut enabled = enabled_plugins(godot_project_path)?;
+        if !enabled.iter().any(|plugin| plugin == PLUGIN_CFG) {
+            enabled.push(PLUGIN_CFG.to_string());
+            set_enabled_plugins(godot_project_path, &enabled)?;
+            written.push(ProjectConfig::path(godot_project_path));
+        }
+        Ok(written)
+    }
+}
+
+/// Disable the plugin and remove it from the godot project.
+/// Returns true if anything was removed.
+pub fn uninstall(godot_project_path: &Path) -> Result<bool> {
+    let mut removed = false;
+    let dir = godot_project_path.join(PLUGIN_DIR);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).with_context(|| format!("Failed to remove {dir:?}"))?;
+        removed = true;
+    }
+    let mut enabled = enabled_plugins(godot_project_path)?;
+    if enabled.iter().any(|plugin| plugin == PLUGIN_CFG) {
+        enabled.retain(|plugin| plugin != PLUGIN_CFG);
+        set_enabled_plugins(godot_project_path, &enabled)?;
+        removed = true;
+    }
+    Ok(removed)
+}
+
+/// The `plugin.cfg` paths of the enabled editor plugins.
+fn enabled_plugins(godot_project_path: &Path) -> Result<Vec<String>> {
+    Ok(ProjectConfig::load(godot_project_path)?
+        .get_string_array("editor_plugins", "enabled")
+        .unwrap_or_default())
+}
+
+fn set_enabled_plugins(godot_project_path: &Path, enabled: &[String]) -> Result<()> {
+    let value = SettingValue::Raw(format!(
+        "PackedStringArray({})",
+        enabled
+            .iter()
+            .map(|plugin| SettingValue::from(plugin.as_str()).to_variant_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    ));
+    let value = (!enabled.is_empty()).then_some(&value);
+    project_config::write_setting(godot_project_path, "editor_plugins", "enabled", value)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_install_and_uninstall() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("godot");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::create_dir_all(dir.path().join("rust")).unwrap();
+        std::fs::write(dir.path().join("rust/Cargo.toml"), "[package]").unwrap();
+        std::fs::write(
+            project.join("project.godot"),
+            "config_version=5\n\n[editor_plugins]\n\nenabled=PackedStringArray(\"res://addons/other/plugin.cfg\")\n",
+        )
+        .unwrap();
+
+        let plugin =
+            EditorPlugin::new(&dir.path().join("rust/Cargo.toml")).cargo_args(["--release"]);
+        let written = plugin.install(&project).unwrap();
+        assert_eq!(written.len(), 3);
+        let script = std::fs::read_to_string(project.join(PLUGIN_DIR).join("plugin.gd")).unwrap();
+        assert!(script.contains("const MANIFEST_PATH := \"../rust/Cargo.toml\""));
+        assert!(script.contains("const CARGO_ARGS := [\"--release\"]"));
+        assert_eq!(
+            enabled_plugins(&project).unwrap(),
+            vec!["res://addons/other/plugin.cfg", PLUGIN_CFG]
+        );
+        assert!(plugin.install(&project).unwrap().is_empty());
+
+        assert!(uninstall(&project).unwrap());
+        assert!(!project.join(PLUGIN_DIR).exists());
+        assert_eq!(
+            enabled_plugins(&project).unwrap(),
+            vec!["res://addons/other/plugin.cfg"]
+        );
+        assert!(!uninstall(&project).unwrap());
+    }
+}
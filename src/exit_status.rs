@@ -0,0 +1,212 @@
+//! Structured exit status of a Godot process.
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use std::process::ExitStatus;
+
+/// How a Godot process exited.
+///
+/// Example usage:
+/// ```rust,ignore
+/// match runner.execute_status()? {
+///     GodotExitStatus::Success => {}
+///     GodotExitStatus::Crashed(signal) => eprintln!("Engine crashed with signal {signal}"),
+///     status => std::process::exit(status.exit_code()),
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[must_use = "an unsuccessful exit is not an error, see `GodotExitStatus::into_result`"]
+pub enum GodotExitStatus {
+    /// Godot exited with code `0`.
+    Success,
+    /// Godot exited with `EXIT_FAILURE` (`1`), which it uses when the main script or scene
+    /// fails to load, or when `--check-only` finds script errors.
+    ScriptError(i32),
+    /// Godot was terminated by a signal (Unix) or an unhandled exception (Windows).
+    Crashed(i32),
+    /// The headless `--import` step failed.
+    ImportFailed,
+    /// Godot exited with a code set by the project, e.g. `get_tree().quit(code)`.
+    Custom(i32),
+}
+
+impl GodotExitStatus {
+    /// Classify the exit status of a Godot process.
+    pub fn from_exit_status(status: ExitStatus) -> Self {
+        match status.code() {
+            Some(0) => Self::Success,
+            Some(1) => Self::ScriptError(1),
+            // Windows reports crashes as NTSTATUS exception codes, e.g. 0xC0000005.
+            Some(code) if cfg!(windows) && (code as u32) & 0xC000_0000 == 0xC000_0000 => {
+                Self::Crashed(code)
+            }
+            Some(code) => Self::Custom(code),
+            None => Self::Crashed(signal(status).unwrap_or(-1)),
+        }
+    }
+
+    /// Returns true if Godot exited successfully.
+    pub fn is_success(&self) -> bool {
+        *self == Self::Success
+    }
+
+    /// An exit code suitable for passing on with `std::process::exit`.
+    /// Signals are reported as `128 + signal` like Unix shells do.
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            Self::Success => 0,
+            Self::ScriptError(code) | Self::Custom(code) => code,
+            Self::Crashed(signal) if signal > 0 && cfg!(unix) => 128 + signal,
+            Self::Crashed(code) => code,
+            Self::ImportFailed => 1,
+        }
+    }
+
+    /// Convert an unsuccessful exit into an error describing it.
+    pub fn into_result(self) -> Result<()> {
+        if self.is_success() {
+            Ok(())
+        } else {
+            Err(anyhow!("{self}"))
+        }
+    }
+}
+
+impl Display for GodotExitStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Success => write!(f, "Godot exited successfully"),
+            Self::ScriptError(code) => write!(
+                f,
+                "Godot exited with exit code {code}, check the output for script errors"
+            ),
+            Self::Crashed(code) if cfg!(windows) => write!(
+                f,
+                "Godot crashed with exception code {:#010X}{}.\n{RELOADABLE_HINT}",
+                *code as u32,
+                exception_name(*code)
+                    .map(|name| format!(" ({name})"))
+                    .unwrap_or_default()
+            ),
+            Self::Crashed(signal) => write!(
+                f,
+                "Godot was terminated by signal {signal}{}.\n{RELOADABLE_HINT}",
+                signal_name(*signal)
+                    .map(|name| format!(" ({name})"))
+                    .unwrap_or_default()
+            ),
+            Self::ImportFailed => write!(
+                f,
+                "Godot import process failed.\n\
+                Possible cause: Known bug in Godot 4.5.1: \"Headless import of project with GDExtensions crashes\"\n\
+                See: https://github.com/godotengine/godot/issues/111645\n\
+                Try re-running if `.godot` folder was generated successfully, \
+                or configure `ImportOptions::retry` to do so automatically."
+            ),
+            Self::Custom(code) => write!(f, "Godot exited with exit code {code}"),
+        }
+    }
+}
+
+const RELOADABLE_HINT: &str = "Hint: Crashes after rebuilding the extension while Godot is running \
+    are often caused by hot reloading. Try `GdExtensionConfig::reloadable(false)`.";
+
+/// The name of common Unix signals that terminate a crashing process.
+pub fn signal_name(signal: i32) -> Option<&'static str> {
+    Some(match signal {
+        1 => "SIGHUP",
+        2 => "SIGINT",
+        3 => "SIGQUIT",
+        4 => "SIGILL",
+        5 => "SIGTRAP",
+        6 => "SIGABRT",
+        7 if cfg!(target_os = "linux") => "SIGBUS",
+        10 if !cfg!(target_os = "linux") => "SIGBUS",
+        8 => "SIGFPE",
+        9 => "SIGKILL",
+        11 => "SIGSEGV",
+        13 => "SIGPIPE",
+        15 => "SIGTERM",
+        _ => return None,
+    })
+}
+
+/// The name of common Windows exception codes that terminate a crashing process.
+fn exception_name(code: i32) -> Option<&'static str> {
+    Some(match code as u32 {
+        0xC000_0005 => "EXCEPTION_ACCESS_VIOLATION",
+        0xC000_001D => "EXCEPTION_ILLEGAL_INSTRUCTION",
+        0xC000_0094 => "EXCEPTION_INT_DIVIDE_BY_ZERO",
+        0xC000_00FD => "EXCEPTION_STACK_OVERFLOW",
+        0xC000_0409 => "STATUS_STACK_BUFFER_OVERRUN",
+        0xC000_0374 => "STATUS_HEAP_CORRUPTION",
+        _ => return None,
+    })
+}
+
+#[cfg(unix)]
+fn signal(status: ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn signal(_status: ExitStatus) -> Option<i32> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    fn exit_status(raw: i32) -> ExitStatus {
+        use std::os::unix::process::ExitStatusExt;
+        ExitStatus::from_raw(raw)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_from_exit_status() {
+        // Raw wait statuses: exit codes are stored in the second byte, signals in the first.
+        assert_eq!(
+            GodotExitStatus::from_exit_status(exit_status(0)),
+            GodotExitStatus::Success
+        );
+        assert_eq!(
+            GodotExitStatus::from_exit_status(exit_status(1 << 8)),
+            GodotExitStatus::ScriptError(1)
+        );
+        assert_eq!(
+            GodotExitStatus::from_exit_status(exit_status(42 << 8)),
+            GodotExitStatus::Custom(42)
+        );
+        assert_eq!(
+            GodotExitStatus::from_exit_status(exit_status(11)),
+            GodotExitStatus::Crashed(11)
+        );
+        assert_eq!(GodotExitStatus::Crashed(11).exit_code(), 139);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_crashed_message() {
+        let message = GodotExitStatus::Crashed(11).to_string();
+        assert!(message.contains("signal 11 (SIGSEGV)"));
+        assert!(message.contains("reloadable(false)"));
+        assert_eq!(signal_name(6), Some("SIGABRT"));
+        assert_eq!(signal_name(64), None);
+    }
+
+    #[test]
+    fn test_into_result() {
+        assert!(GodotExitStatus::Success.into_result().is_ok());
+        assert!(
+            GodotExitStatus::ImportFailed
+                .into_result()
+                .unwrap_err()
+                .to_string()
+                .contains("Godot import process failed")
+        );
+    }
+}
@@ -0,0 +1,462 @@
+//! Utilities for exporting a Godot project using `godot --headless --export-*`.
+//!
+//! Exports use the presets defined in the project's `export_presets.cfg`.
+//! Several presets and modes can be exported at once with an `ExportMatrix`.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let summary = ExportMatrix::new()
+//!     .presets(
+//!         [("Linux", "game.x86_64"), ("Windows Desktop", "game.exe"), ("Web", "index.html")],
+//!         &[ExportMode::Debug, ExportMode::Release],
+//!         Path::new("build"),
+//!     )
+//!     .parallel(2)
+//!     .run(Path::new("godot"), None)?;
+//! println!("{summary}");
+//! ```
+use crate::export_templates;
+use crate::godot_commands::{GodotBinary, run_godot};
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The kind of export to run.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum ExportMode {
+    /// Export a release build (`--export-release`). Requires export templates.
+    Release,
+    /// Export a debug build (`--export-debug`). Requires export templates.
+    Debug,
+    /// Export only the project data as a `.pck` or `.zip` (`--export-pack`).
+    Pack,
+}
+
+impl ExportMode {
+    /// The Godot CLI flag for this export mode.
+    pub fn flag(&self) -> &'static str {
+        match self {
+            ExportMode::Release => "--export-release",
+            ExportMode::Debug => "--export-debug",
+            ExportMode::Pack => "--export-pack",
+        }
+    }
+
+    /// The lowercase name of this export mode, e.g. `release`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportMode::Release => "release",
+            ExportMode::Debug => "debug",
+            ExportMode::Pack => "pack",
+        }
+    }
+}
+
+/// Export the project using the export preset named `preset`.
+/// Export templates are checked (and installed if possible) before `Release` and `Debug` exports.
+/// Returns the absolute path of the exported file.
+pub fn export_project<'a>(
+    godot_project_path: &Path,
+    godot_binary: impl Into<GodotBinary<'a>>,
+    preset: &str,
+    mode: ExportMode,
+    output_path: &Path,
+) -> Result<PathBuf> {
+    let godot_binary = godot_binary.into();
+    if mode != ExportMode::Pack {
+        export_templates::ensure_installed(godot_binary)
+            .context("Export templates are required to export a project")?;
+    }
+    export_with_templates(godot_project_path, godot_binary, preset, mode, output_path)
+}
+
+/// `export_project` without checking the export templates.
+fn export_with_templates<'a>(
+    godot_project_path: &Path,
+    godot_binary: impl Into<GodotBinary<'a>>,
+    preset: &str,
+    mode: ExportMode,
+    output_path: &Path,
+) -> Result<PathBuf> {
+    // Godot resolves relative export paths against the project directory,
+    // so resolve them against the current directory instead to avoid surprises.
+    let output_path = std::path::absolute(output_path)
+        .with_context(|| format!("Failed to resolve export path: {output_path:?}"))?;
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create export directory: {parent:?}"))?;
+    }
+    let output_str = output_path
+        .to_str()
+        .context("Failed to convert export path to string")?;
+
+    run_godot(
+        godot_project_path,
+        godot_binary,
+        &[
+            "--headless".to_string(),
+            mode.flag().to_string(),
+            preset.to_string(),
+            output_str.to_string(),
+        ],
+    )
+    .and_then(|status| status.into_result())
+    .with_context(|| format!("Failed to export preset {preset:?} to {output_path:?}"))?;
+
+    if !output_path.exists() {
+        return Err(anyhow!(
+            "Godot finished exporting preset {preset:?} but {output_path:?} was not created.\n\
+            Check that the preset exists in `export_presets.cfg`."
+        ));
+    }
+    Ok(output_path)
+}
+
+/// Export only the project data of preset `preset` into a `.pck` or `.zip` at `output_path`,
+/// e.g. for DLC or patch workflows. The file extension selects the pack format.
+/// Returns the absolute path of the produced pack.
+pub fn export_pack<'a>(
+    godot_project_path: &Path,
+    godot_binary: impl Into<GodotBinary<'a>>,
+    preset: &str,
+    output_path: &Path,
+) -> Result<PathBuf> {
+    match output_path.extension().and_then(|ext| ext.to_str()) {
+        Some("pck") | Some("zip") => {}
+        _ => {
+            return Err(anyhow!(
+                "Pack output path must end in `.pck` or `.zip`: {output_path:?}"
+            ));
+        }
+    }
+    export_project(
+        godot_project_path,
+        godot_binary,
+        preset,
+        ExportMode::Pack,
+        output_path,
+    )
+}
+
+/// One export of an `ExportMatrix`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct ExportJob {
+    /// The name of the export preset.
+    pub preset: String,
+    pub mode: ExportMode,
+    /// Where the export is written.
+    pub output_path: PathBuf,
+}
+
+/// A set of exports run one after another or in parallel, see `ExportMatrix::run`.
+#[derive(Clone, Debug)]
+pub struct ExportMatrix {
+    jobs: Vec<ExportJob>,
+    parallel: usize,
+}
+
+impl Default for ExportMatrix {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExportMatrix {
+    /// Create an empty matrix, exporting one preset at a time.
+    pub fn new() -> Self {
+        Self {
+            jobs: vec![],
+            parallel: 1,
+        }
+    }
+
+    /// Export `preset` in `mode` to `output_path`.
+    pub fn job(mut self, preset: &str, mode: ExportMode, output_path: &Path) -> Self {
+        self.jobs.push(ExportJob {
+            preset: preset.to_string(),
+            mode,
+            output_path: output_path.to_path_buf(),
+        });
+        self
+    }
+
+    /// Export every preset, given with the file name of its export, in every mode.
+    /// Exports are written to `<output_dir>/<mode>/<file name>`, e.g. `build/release/game.exe`.
+    pub fn presets<P: AsRef<str>, F: AsRef<Path>>(
+        mut self,
+        presets: impl IntoIterator<Item = (P, F)>,
+        modes: &[ExportMode],
+        output_dir: &Path,
+    ) -> Self {
+        for (preset, file_name) in presets {
+            for mode in modes {
+                self.jobs.push(ExportJob {
+                    preset: preset.as_ref().to_string(),
+                    mode: *mode,
+                    output_path: output_dir.join(mode.name()).join(file_name.as_ref()),
+                });
+            }
+        }
+        self
+    }
+
+    /// Run up to `jobs` exports at the same time. Defaults to 1, i.e. one after another.
+    pub fn parallel(self, jobs: usize) -> Self {
+        Self {
+            parallel: jobs.max(1),
+            ..self
+        }
+    }
+
+    /// The exports of the matrix in order.
+    pub fn jobs(&self) -> &[ExportJob] {
+        &self.jobs
+    }
+
+    /// Run all exports, continuing after failed ones.
+    /// Export templates are checked once before the first `Release` or `Debug` export.
+    /// Returns an error only if the export templates are missing.
+    pub fn run<'a>(
+        &self,
+        godot_project_path: &Path,
+        godot_binary: impl Into<GodotBinary<'a>>,
+    ) -> Result<ExportSummary> {
+        let godot_binary = godot_binary.into();
+        if self.jobs.iter().any(|job| job.mode != ExportMode::Pack) {
+            export_templates::ensure_installed(godot_binary)
+                .context("Export templates are required to export a project")?;
+        }
+        let start = Instant::now();
+        let next = Mutex::new(self.jobs.iter().enumerate());
+        let results = Mutex::new(vec![]);
+        std::thread::scope(|scope| {
+            for _ in 0..self.parallel.min(self.jobs.len()) {
+                scope.spawn(|| {
+                    loop {
+                        let Some((index, job)) =
+                            next.lock().unwrap_or_else(|e| e.into_inner()).next()
+                        else {
+                            break;
+                        };
+                        let result = run_job(godot_project_path, godot_binary, job);
+                        results
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .push((index, result));
+                    }
+                });
+            }
+        });
+        let mut results = results.into_inner().unwrap_or_else(|e| e.into_inner());
+        results.sort_by_key(|(index, _)| *index);
+        Ok(ExportSummary {
+            results: results.into_iter().map(|(_, result)| result).collect(),
+            duration: start.elapsed(),
+        })
+    }
+}
+
+fn run_job<'a>(
+    godot_project_path: &Path,
+    godot_binary: impl Into<GodotBinary<'a>>,
+    job: &ExportJob,
+) -> ExportResult {
+    let start = Instant::now();
+    let exported = export_with_templates(
+        godot_project_path,
+        godot_binary,
+        &job.preset,
+        job.mode,
+        &job.output_path,
+    );
+    let duration = start.elapsed();
+    match exported {
+        Ok(output_path) => ExportResult {
+            size: Some(export_size(&output_path)),
+            job: ExportJob {
+                output_path,
+                ..job.clone()
+            },
+            duration,
+            error: None,
+        },
+        Err(e) => ExportResult {
+            job: job.clone(),
+            duration,
+            size: None,
+            error: Some(format!("{e:#}")),
+        },
+    }
+}
+
+/// The size of the exported file and the files exported next to it with the same name, e.g.
+/// `game.pck` for `game.exe` or `index.wasm` for `index.html`.
+fn export_size(output_path: &Path) -> u64 {
+    let (Some(dir), Some(stem)) = (output_path.parent(), output_path.file_stem()) else {
+        return 0;
+    };
+    let prefix = format!("{}.", stem.to_string_lossy());
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// The outcome of one export of an `ExportMatrix`.
+#[derive(Clone, Debug, Serialize)]
+pub struct ExportResult {
+    /// The export, with the absolute output path if it succeeded.
+    pub job: ExportJob,
+    pub duration: Duration,
+    /// The size in bytes of the exported files, if the export succeeded.
+    pub size: Option<u64>,
+    /// Why the export failed.
+    pub error: Option<String>,
+}
+
+impl ExportResult {
+    /// Returns true if the export succeeded.
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+impl Display for ExportResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({}): ", self.job.preset, self.job.mode.name())?;
+        match (&self.error, self.size) {
+            (Some(error), _) => write!(f, "failed after {:.1?}: {error}", self.duration),
+            (None, size) => write!(
+                f,
+                "{:?}, {} bytes in {:.1?}",
+                self.job.output_path,
+                size.unwrap_or_default(),
+                self.duration
+            ),
+        }
+    }
+}
+
+/// The results of `ExportMatrix::run`, serializable for CI tooling.
+#[derive(Clone, Debug, Serialize)]
+pub struct ExportSummary {
+    /// The result of every export, in the order of the matrix.
+    pub results: Vec<ExportResult>,
+    /// The wall-clock time of all exports.
+    pub duration: Duration,
+}
+
+impl ExportSummary {
+    /// Returns true if all exports succeeded.
+    pub fn is_success(&self) -> bool {
+        self.results.iter().all(ExportResult::is_success)
+    }
+
+    /// The exports which failed.
+    pub fn failures(&self) -> impl Iterator<Item = &ExportResult> {
+        self.results.iter().filter(|result| !result.is_success())
+    }
+
+    /// Turn failed exports into an error listing them.
+    pub fn into_result(self) -> Result<Self> {
+        if self.is_success() {
+            return Ok(self);
+        }
+        let failures: Vec<String> = self
+            .failures()
+            .map(|result| format!("  - {result}"))
+            .collect();
+        Err(anyhow!(
+            "{} of {} export(s) failed:\n{}",
+            failures.len(),
+            self.results.len(),
+            failures.join("\n")
+        ))
+    }
+}
+
+impl Display for ExportSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let succeeded = self
+            .results
+            .iter()
+            .filter(|result| result.is_success())
+            .count();
+        write!(
+            f,
+            "{succeeded} of {} export(s) succeeded in {:.1?}",
+            self.results.len(),
+            self.duration
+        )?;
+        for result in &self.results {
+            write!(f, "\n  - {result}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_pack_invalid_extension() {
+        let result = export_pack(Path::new("."), None, "Linux", Path::new("out/game.exe"));
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("must end in `.pck` or `.zip`")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_export_matrix() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        // A fake Godot failing to export the `Broken` preset.
+        let godot = dir.path().join("godot");
+        std::fs::write(
+            &godot,
+            "#!/bin/sh\ntest \"$3\" = Broken && exit 1\nprintf data > \"$4\"\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&godot, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let output_dir = dir.path().join("build");
+
+        let matrix = ExportMatrix::new()
+            .presets(
+                [("DLC", "dlc.pck"), ("Broken", "broken.pck")],
+                &[ExportMode::Pack],
+                &output_dir,
+            )
+            .job("DLC", ExportMode::Pack, &output_dir.join("dlc.zip"))
+            .parallel(2);
+        assert_eq!(
+            matrix.jobs()[0].output_path,
+            output_dir.join("pack/dlc.pck")
+        );
+
+        let summary = matrix.run(dir.path(), godot.as_path()).unwrap();
+        let sizes: Vec<_> = summary.results.iter().map(|result| result.size).collect();
+        assert_eq!(sizes, [Some(4), None, Some(4)]);
+        assert_eq!(summary.failures().count(), 1);
+        assert!(
+            summary
+                .to_string()
+                .starts_with("2 of 3 export(s) succeeded")
+        );
+        let error = summary.into_result().unwrap_err().to_string();
+        assert!(error.contains("Broken (pack): failed"));
+    }
+}
@@ -0,0 +1,166 @@
+//! Utilities for detecting and installing Godot export templates.
+//!
+//! Export templates are required to export a Godot project. Godot looks for them in a
+//! per-user directory named after the engine version, e.g.
+//! `~/.local/share/godot/export_templates/4.5.1.stable` on Linux.
+use crate::godot_commands::{GodotBinary, GodotVersion, detect_godot_version};
+use anyhow::{Context, Result, anyhow};
+use std::path::PathBuf;
+
+/// The directory Godot installs export templates into for the current OS:
+/// - Linux: `$XDG_DATA_HOME/godot/export_templates` (default `~/.local/share/godot/export_templates`).
+/// - macOS: `~/Library/Application Support/Godot/export_templates`.
+/// - Windows: `%APPDATA%\Godot\export_templates`.
+pub fn templates_root() -> Result<PathBuf> {
+    if cfg!(target_os = "windows") {
+        let app_data = std::env::var("APPDATA").context("Missing APPDATA environment variable")?;
+        Ok(PathBuf::from(app_data).join("Godot/export_templates"))
+    } else if cfg!(target_os = "macos") {
+        let home = std::env::var("HOME").context("Missing HOME environment variable")?;
+        Ok(PathBuf::from(home).join("Library/Application Support/Godot/export_templates"))
+    } else {
+        let data_home = match std::env::var("XDG_DATA_HOME") {
+            Ok(data_home) if !data_home.is_empty() => PathBuf::from(data_home),
+            _ => PathBuf::from(std::env::var("HOME").context("Missing HOME environment variable")?)
+                .join(".local/share"),
+        };
+        Ok(data_home.join("godot/export_templates"))
+    }
+}
+
+/// The directory containing the export templates for `version`.
+pub fn templates_dir(version: &GodotVersion) -> Result<PathBuf> {
+    Ok(templates_root()?.join(version.template_dir_name()))
+}
+
+/// Whether export templates for `version` are installed.
+pub fn is_installed(version: &GodotVersion) -> Result<bool> {
+    Ok(templates_dir(version)?.join("version.txt").exists())
+}
+
+/// The official download URL of the export templates (`.tpz`) for `version`.
+pub fn download_url(version: &GodotVersion) -> String {
+    let mono = if version.mono { "_mono" } else { "" };
+    format!(
+        "https://github.com/godotengine/godot/releases/download/{tag}/Godot_v{tag}{mono}_export_templates.tpz",
+        tag = version.release_tag(),
+    )
+}
+
+/// Detect the Godot version and make sure its export templates are installed,
+/// downloading them if the `download` feature is enabled.
+/// Returns the export templates directory.
+pub fn ensure_installed<'a>(godot_binary: impl Into<GodotBinary<'a>>) -> Result<PathBuf> {
+    let version = detect_godot_version(godot_binary)?;
+    if is_installed(&version)? {
+        return templates_dir(&version);
+    }
+
+    #[cfg(feature = "download")]
+    {
+        install(&version)
+    }
+    #[cfg(not(feature = "download"))]
+    {
+        Err(anyhow!(
+            "Export templates for Godot {version} are not installed in {:?}.\n\
+            Install them from the Godot editor (Editor > Manage Export Templates),\n\
+            download them from {},\n\
+            or enable the `download` feature of cargo-godot-lib to install them automatically.",
+            templates_dir(&version)?,
+            download_url(&version),
+        ))
+    }
+}
+
+/// Download and install the export templates for `version`.
+/// Returns the export templates directory.
+#[cfg(feature = "download")]
+pub fn install(version: &GodotVersion) -> Result<PathBuf> {
+    let url = download_url(version);
+    let root = templates_root()?;
+    std::fs::create_dir_all(&root)
+        .with_context(|| format!("Failed to create export templates directory: {root:?}"))?;
+
+    let archive_path = root.join(format!("{}.tpz.part", version.template_dir_name()));
+    let mut response = ureq::get(&url)
+        .call()
+        .with_context(|| format!("Failed to download export templates: {url}"))?;
+    let mut archive_file = std::fs::File::create(&archive_path)
+        .with_context(|| format!("Failed to create file: {archive_path:?}"))?;
+    std::io::copy(&mut response.body_mut().as_reader(), &mut archive_file)
+        .with_context(|| format!("Failed to download export templates: {url}"))?;
+    drop(archive_file);
+
+    let dir = templates_dir(version)?;
+    let result = extract_templates(&archive_path, &dir);
+    std::fs::remove_file(&archive_path)
+        .with_context(|| format!("Failed to remove file: {archive_path:?}"))?;
+    result?;
+
+    if !is_installed(version)? {
+        return Err(anyhow!(
+            "Export templates archive {url} did not contain a `templates/version.txt`"
+        ));
+    }
+    Ok(dir)
+}
+
+/// Extract the `templates/` folder of a `.tpz` archive into `dir`.
+#[cfg(feature = "download")]
+fn extract_templates(archive_path: &std::path::Path, dir: &std::path::Path) -> Result<()> {
+    let archive_file = std::fs::File::open(archive_path)
+        .with_context(|| format!("Failed to open export templates archive: {archive_path:?}"))?;
+    let mut archive = zip::ZipArchive::new(archive_file)
+        .with_context(|| format!("Failed to read export templates archive: {archive_path:?}"))?;
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let Some(name) = entry.enclosed_name() else {
+            continue;
+        };
+        let Ok(relative_path) = name.strip_prefix("templates") else {
+            continue;
+        };
+        let output_path = dir.join(relative_path);
+        if entry.is_dir() {
+            std::fs::create_dir_all(&output_path)?;
+        } else {
+            if let Some(parent) = output_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut output_file = std::fs::File::create(&output_path)
+                .with_context(|| format!("Failed to create file: {output_path:?}"))?;
+            std::io::copy(&mut entry, &mut output_file)
+                .with_context(|| format!("Failed to extract file: {output_path:?}"))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_download_url() {
+        let version: GodotVersion = "4.5.1.stable.official.f62fdbde1".parse().unwrap();
+        assert_eq!(
+            download_url(&version),
+            "https://github.com/godotengine/godot/releases/download/4.5.1-stable/Godot_v4.5.1-stable_export_templates.tpz"
+        );
+
+        let version: GodotVersion = "4.5.stable.mono.official.876b29033".parse().unwrap();
+        assert_eq!(
+            download_url(&version),
+            "https://github.com/godotengine/godot/releases/download/4.5-stable/Godot_v4.5-stable_mono_export_templates.tpz"
+        );
+    }
+
+    #[test]
+    fn test_templates_dir() {
+        let version: GodotVersion = "4.5.1.stable.official.f62fdbde1".parse().unwrap();
+        let dir = templates_dir(&version).unwrap();
+        assert!(dir.ends_with("export_templates/4.5.1.stable"));
+    }
+}
@@ -0,0 +1,367 @@
+//! Dumping and comparing Godot's `extension_api.json`, which describes the engine API that
+//! GDExtension bindings are generated from.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let previous = Path::new("api/extension_api.json");
+//! let (_, diff) = extension_api::dump_and_diff(Some("4.5"), Path::new("api"), Some(previous))?;
+//! if let Some(diff) = diff {
+//!     println!("{diff}");
+//! }
+//! ```
+use crate::godot_commands::{GodotBinary, godot_command};
+use anyhow::{Context, Result, anyhow};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+/// The file name Godot writes the API to.
+pub const EXTENSION_API_FILE: &str = "extension_api.json";
+
+/// Run `godot --dump-extension-api` in `output_dir` and return the path of the written
+/// `extension_api.json`. With `with_docs`, the class reference descriptions are included
+/// (`--dump-extension-api-with-docs`, Godot 4.2+). An existing dump is replaced.
+pub fn dump<'a>(
+    godot_binary: impl Into<GodotBinary<'a>>,
+    output_dir: &Path,
+    with_docs: bool,
+) -> Result<PathBuf> {
+    std::fs::create_dir_all(output_dir)
+        .with_context(|| format!("Failed to create directory: {output_dir:?}"))?;
+    let flag = if with_docs {
+        "--dump-extension-api-with-docs"
+    } else {
+        "--dump-extension-api"
+    };
+
+    let mut command = godot_command(godot_binary)?;
+    command
+        .stdin(Stdio::null())
+        .current_dir(output_dir)
+        .arg("--headless")
+        .arg(flag);
+    let output = command
+        .output()
+        .with_context(|| format!("Failed to dump the extension API: {command:?}"))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Dumping the extension API failed with status `{}`\nCommand: {:?}\n{}",
+            output.status,
+            command,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    let path = output_dir.join(EXTENSION_API_FILE);
+    if !path.exists() {
+        return Err(anyhow!("Godot did not write {path:?}"));
+    }
+    Ok(path)
+}
+
+/// Like `dump`, also comparing the new dump to the `extension_api.json` at `previous` if given.
+/// `previous` is read before dumping, so it may be the file which is replaced.
+/// If `previous` doesn't exist yet, there is nothing to compare and no diff is returned.
+pub fn dump_and_diff<'a>(
+    godot_binary: impl Into<GodotBinary<'a>>,
+    output_dir: &Path,
+    previous: Option<&Path>,
+) -> Result<(PathBuf, Option<ExtensionApiDiff>)> {
+    let previous = match previous {
+        Some(previous) if previous.exists() => Some(ExtensionApi::load(previous)?),
+        _ => None,
+    };
+    let path = dump(godot_binary, output_dir, false)?;
+    let diff = match previous {
+        Some(previous) => Some(previous.diff(&ExtensionApi::load(&path)?)),
+        None => None,
+    };
+    Ok((path, diff))
+}
+
+/// A parsed `extension_api.json`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExtensionApi {
+    json: Value,
+}
+
+impl ExtensionApi {
+    /// Read and parse an `extension_api.json` file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read extension API: {path:?}"))?;
+        contents
+            .parse()
+            .with_context(|| format!("Failed to parse extension API: {path:?}"))
+    }
+
+    /// The full engine version the API was dumped from, e.g. `Godot Engine v4.5.1.stable.official`.
+    pub fn version(&self) -> Option<&str> {
+        self.json["header"]["version_full_name"].as_str()
+    }
+
+    /// The names of all engine classes, e.g. `Node` and `RefCounted`.
+    pub fn class_names(&self) -> BTreeSet<String> {
+        names(&self.json["classes"]).into_keys().collect()
+    }
+
+    /// The names of all builtin types, e.g. `Vector2` and `String`.
+    pub fn builtin_class_names(&self) -> BTreeSet<String> {
+        names(&self.json["builtin_classes"]).into_keys().collect()
+    }
+
+    /// The names of the global utility functions, e.g. `print`.
+    pub fn utility_function_names(&self) -> BTreeSet<String> {
+        names(&self.json["utility_functions"]).into_keys().collect()
+    }
+
+    /// The differences between this API and a newer one.
+    pub fn diff(&self, new: &ExtensionApi) -> ExtensionApiDiff {
+        let old_classes = classes(&self.json);
+        let new_classes = classes(&new.json);
+        let (added_classes, removed_classes) = added_removed(
+            &old_classes.keys().cloned().collect(),
+            &new_classes.keys().cloned().collect(),
+        );
+
+        let mut changed_classes = BTreeMap::new();
+        for (name, old_class) in &old_classes {
+            let Some(new_class) = new_classes.get(name) else {
+                continue;
+            };
+            let mut class_diff = ClassDiff::default();
+            (class_diff.added_methods, class_diff.removed_methods) = added_removed(
+                &member_names(old_class, "methods"),
+                &member_names(new_class, "methods"),
+            );
+            (class_diff.added_properties, class_diff.removed_properties) = added_removed(
+                &member_names(old_class, "properties"),
+                &member_names(new_class, "properties"),
+            );
+            (class_diff.added_signals, class_diff.removed_signals) = added_removed(
+                &member_names(old_class, "signals"),
+                &member_names(new_class, "signals"),
+            );
+            if !class_diff.is_empty() {
+                changed_classes.insert(name.clone(), class_diff);
+            }
+        }
+
+        let (added_utility_functions, removed_utility_functions) = added_removed(
+            &self.utility_function_names(),
+            &new.utility_function_names(),
+        );
+
+        ExtensionApiDiff {
+            old_version: self.version().map(str::to_string),
+            new_version: new.version().map(str::to_string),
+            added_classes,
+            removed_classes,
+            changed_classes,
+            added_utility_functions,
+            removed_utility_functions,
+        }
+    }
+}
+
+impl std::str::FromStr for ExtensionApi {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let json: Value = serde_json::from_str(s)?;
+        if !json.is_object() {
+            return Err(anyhow!("Expected a JSON object"));
+        }
+        Ok(Self { json })
+    }
+}
+
+/// The changes of a class between two APIs.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct ClassDiff {
+    pub added_methods: Vec<String>,
+    pub removed_methods: Vec<String>,
+    pub added_properties: Vec<String>,
+    pub removed_properties: Vec<String>,
+    pub added_signals: Vec<String>,
+    pub removed_signals: Vec<String>,
+}
+
+impl ClassDiff {
+    /// Returns true if nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.added_methods.is_empty()
+            && self.removed_methods.is_empty()
+            && self.added_properties.is_empty()
+            && self.removed_properties.is_empty()
+            && self.added_signals.is_empty()
+            && self.removed_signals.is_empty()
+    }
+}
+
+/// The differences between two extension APIs, see `ExtensionApi::diff`.
+/// Engine classes and builtin types are compared together.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct ExtensionApiDiff {
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+    pub added_classes: Vec<String>,
+    pub removed_classes: Vec<String>,
+    /// Classes present in both APIs whose members changed.
+    pub changed_classes: BTreeMap<String, ClassDiff>,
+    pub added_utility_functions: Vec<String>,
+    pub removed_utility_functions: Vec<String>,
+}
+
+impl ExtensionApiDiff {
+    /// Returns true if nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.added_classes.is_empty()
+            && self.removed_classes.is_empty()
+            && self.changed_classes.is_empty()
+            && self.added_utility_functions.is_empty()
+            && self.removed_utility_functions.is_empty()
+    }
+
+    /// Returns true if anything was removed, which may break existing bindings.
+    pub fn has_removals(&self) -> bool {
+        !self.removed_classes.is_empty()
+            || !self.removed_utility_functions.is_empty()
+            || self.changed_classes.values().any(|class| {
+                !class.removed_methods.is_empty()
+                    || !class.removed_properties.is_empty()
+                    || !class.removed_signals.is_empty()
+            })
+    }
+}
+
+impl Display for ExtensionApiDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let unknown = "unknown version";
+        writeln!(
+            f,
+            "Extension API changes from {} to {}:",
+            self.old_version.as_deref().unwrap_or(unknown),
+            self.new_version.as_deref().unwrap_or(unknown)
+        )?;
+        if self.is_empty() {
+            return writeln!(f, "  No changes.");
+        }
+        for class in &self.added_classes {
+            writeln!(f, "  + class {class}")?;
+        }
+        for class in &self.removed_classes {
+            writeln!(f, "  - class {class}")?;
+        }
+        for (class, diff) in &self.changed_classes {
+            writeln!(f, "  ~ class {class}")?;
+            let members = [
+                ("+", "method", &diff.added_methods),
+                ("-", "method", &diff.removed_methods),
+                ("+", "property", &diff.added_properties),
+                ("-", "property", &diff.removed_properties),
+                ("+", "signal", &diff.added_signals),
+                ("-", "signal", &diff.removed_signals),
+            ];
+            for (sign, kind, names) in members {
+                for name in names {
+                    writeln!(f, "    {sign} {kind} {name}")?;
+                }
+            }
+        }
+        for function in &self.added_utility_functions {
+            writeln!(f, "  + utility function {function}")?;
+        }
+        for function in &self.removed_utility_functions {
+            writeln!(f, "  - utility function {function}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The entries of a JSON array of objects with a `name`, keyed by name.
+fn names(array: &Value) -> BTreeMap<String, &Value> {
+    array
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| Some((entry["name"].as_str()?.to_string(), entry)))
+        .collect()
+}
+
+fn classes(json: &Value) -> BTreeMap<String, &Value> {
+    let mut classes = names(&json["builtin_classes"]);
+    classes.extend(names(&json["classes"]));
+    classes
+}
+
+fn member_names(class: &Value, members: &str) -> BTreeSet<String> {
+    names(&class[members]).into_keys().collect()
+}
+
+fn added_removed(old: &BTreeSet<String>, new: &BTreeSet<String>) -> (Vec<String>, Vec<String>) {
+    (
+        new.difference(old).cloned().collect(),
+        old.difference(new).cloned().collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = r#"{
+        "header": { "version_full_name": "Godot Engine v4.4.stable.official" },
+        "builtin_classes": [{ "name": "Vector2", "methods": [{ "name": "length" }] }],
+        "classes": [
+            { "name": "Node", "methods": [{ "name": "get_name" }, { "name": "old_method" }],
+              "signals": [{ "name": "ready" }] },
+            { "name": "Removed" }
+        ],
+        "utility_functions": [{ "name": "print" }]
+    }"#;
+
+    const NEW: &str = r#"{
+        "header": { "version_full_name": "Godot Engine v4.5.stable.official" },
+        "builtin_classes": [{ "name": "Vector2", "methods": [{ "name": "length" }] }],
+        "classes": [
+            { "name": "Node", "methods": [{ "name": "get_name" }, { "name": "new_method" }],
+              "properties": [{ "name": "name" }], "signals": [{ "name": "ready" }] },
+            { "name": "Added" }
+        ],
+        "utility_functions": [{ "name": "print" }, { "name": "print_rich" }]
+    }"#;
+
+    #[test]
+    fn test_extension_api() {
+        let old: ExtensionApi = OLD.parse().unwrap();
+        let new: ExtensionApi = NEW.parse().unwrap();
+        assert_eq!(old.version(), Some("Godot Engine v4.4.stable.official"));
+        assert_eq!(
+            old.class_names(),
+            BTreeSet::from(["Node".into(), "Removed".into()])
+        );
+        assert_eq!(
+            old.builtin_class_names(),
+            BTreeSet::from(["Vector2".into()])
+        );
+        assert!("[]".parse::<ExtensionApi>().is_err());
+
+        assert!(old.diff(&old).is_empty());
+        let diff = old.diff(&new);
+        assert_eq!(diff.added_classes, vec!["Added"]);
+        assert_eq!(diff.removed_classes, vec!["Removed"]);
+        assert_eq!(diff.added_utility_functions, vec!["print_rich"]);
+        assert_eq!(diff.changed_classes.len(), 1);
+        let node = &diff.changed_classes["Node"];
+        assert_eq!(node.added_methods, vec!["new_method"]);
+        assert_eq!(node.removed_methods, vec!["old_method"]);
+        assert_eq!(node.added_properties, vec!["name"]);
+        assert!(node.added_signals.is_empty());
+        assert!(diff.has_removals());
+        assert!(diff.to_string().contains("    - method old_method\n"));
+    }
+}
@@ -1,8 +1,182 @@
 //! Utilities for generating a `.gdextension` file for Godot.
-use anyhow::{Context, Result};
-use pathdiff::diff_paths;
+use crate::cargo::MACOS_UNIVERSAL_TRIPLE;
+use crate::paths::{CanonicalizeMode, canonicalize, path_root, project_path_string};
+use crate::project_config::SettingValue;
+use anyhow::{Context, Result, anyhow};
+use std::collections::BTreeMap;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
+/// The default `library_path_template`.
+pub const DEFAULT_LIBRARY_PATH_TEMPLATE: &str = "res://{target}/{profile}/{prefix}{name}{ext}";
+
+/// The first line of written `.gdextension` files, marking them as generated.
+pub const GENERATED_HEADER: &str = "; Generated by cargo-godot-lib, changes are overwritten.";
+
+/// Keys of the `[configuration]` section set by `GdExtensionConfig`'s own methods.
+const CONFIGURATION_KEYS: &[&str] = &["entry_symbol", "compatibility_minimum", "reloadable"];
+
+/// Placeholders supported by `GdExtensionConfig::library_path_template`.
+const LIBRARY_PATH_PLACEHOLDERS: &[&str] =
+    &["target", "triple", "profile", "prefix", "name", "ext"];
+
+/// A platform with entries in the `[libraries]` section, see `GdExtensionConfig::platforms`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Platform {
+    /// `linux.*.x86_64`
+    Linux,
+    /// `windows.*.x86_64`
+    Windows,
+    /// `macos.*` and `macos.*.arm64`
+    MacOS,
+    /// `android.*.arm64`, `android.*.arm32`, `android.*.x86_64` and `android.*.x86_32`,
+    /// see `android::AndroidBuild`.
+    Android,
+    /// `web.*.wasm32`, used by both threaded and single-threaded web exports, see
+    /// `web::WebBuild`.
+    Web,
+}
+
+impl Platform {
+    /// The desktop platforms, the default of `GdExtensionConfig::platforms`.
+    /// Mobile and web platforms are opt-in.
+    pub const ALL: [Platform; 3] = [Platform::Linux, Platform::Windows, Platform::MacOS];
+
+    /// The OS name used in `[libraries]` keys.
+    pub fn os_name(&self) -> &'static str {
+        match self {
+            Platform::Linux => "linux",
+            Platform::Windows => "windows",
+            Platform::MacOS => "macos",
+            Platform::Android => "android",
+            Platform::Web => "web",
+        }
+    }
+}
+
+/// The layout of generated `.gdextension` files, see `GdExtensionConfig::format_version`.
+///
+/// The output of a format version is fixed: a crate update which changes the generated file for
+/// the same configuration adds a new version instead, and the default only changes in breaking
+/// releases. This keeps generated files from churning in git diffs between crate versions.
+#[derive(Clone, Copy, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub enum FormatVersion {
+    /// Sections added with `raw_section` in the order they were first added, and their keys in
+    /// the order they were added.
+    #[default]
+    V1,
+    /// Sections added with `raw_section` and their keys sorted by name, so the output doesn't
+    /// depend on the order of builder calls. A key added twice keeps its last value.
+    V2,
+}
+
+impl FormatVersion {
+    /// The newest format version.
+    pub const LATEST: FormatVersion = FormatVersion::V2;
+}
+
+/// A `[libraries]` entry of the generated `.gdextension` file, e.g. `linux.release.x86_64`.
+struct LibraryEntry {
+    os: &'static str,
+    arch: Option<&'static str>,
+    triple: &'static str,
+}
+
+/// The `[libraries]` entries in the order they are generated.
+const LIBRARY_ENTRIES: &[LibraryEntry] = &[
+    LibraryEntry {
+        os: "linux",
+        arch: Some("x86_64"),
+        triple: "x86_64-unknown-linux-gnu",
+    },
+    LibraryEntry {
+        os: "windows",
+        arch: Some("x86_64"),
+        triple: "x86_64-pc-windows-msvc",
+    },
+    LibraryEntry {
+        os: "macos",
+        arch: None,
+        triple: "x86_64-apple-darwin",
+    },
+    LibraryEntry {
+        os: "macos",
+        arch: Some("arm64"),
+        triple: "aarch64-apple-darwin",
+    },
+    LibraryEntry {
+        os: "android",
+        arch: Some("arm64"),
+        triple: "aarch64-linux-android",
+    },
+    LibraryEntry {
+        os: "android",
+        arch: Some("arm32"),
+        triple: "armv7-linux-androideabi",
+    },
+    LibraryEntry {
+        os: "android",
+        arch: Some("x86_64"),
+        triple: "x86_64-linux-android",
+    },
+    LibraryEntry {
+        os: "android",
+        arch: Some("x86_32"),
+        triple: "i686-linux-android",
+    },
+    LibraryEntry {
+        os: "web",
+        arch: Some("wasm32"),
+        triple: "wasm32-unknown-emscripten",
+    },
+];
+
+impl LibraryEntry {
+    /// The `[libraries]` key for the given build, e.g. `linux.release.x86_64`.
+    fn key(&self, build: &str) -> String {
+        match self.arch {
+            Some(arch) => format!("{}.{build}.{arch}", self.os),
+            None => format!("{}.{build}", self.os),
+        }
+    }
+
+    /// Returns true if libraries built for the target `triple` run on this entry's platform,
+    /// e.g. `x86_64-pc-windows-gnu` for `windows.release.x86_64`, or
+    /// `universal-apple-darwin` for both macOS entries.
+    fn matches_triple(&self, triple: &str) -> bool {
+        if triple == MACOS_UNIVERSAL_TRIPLE {
+            return self.os == "macos";
+        }
+        let os = |triple: &str| {
+            if triple.contains("android") {
+                Some("android")
+            } else if triple.contains("windows") {
+                Some("windows")
+            } else if triple.contains("apple-darwin") {
+                Some("macos")
+            } else if triple.contains("linux") {
+                Some("linux")
+            } else if triple.contains("emscripten") {
+                Some("web")
+            } else {
+                None
+            }
+        };
+        let arch = |triple: &str| triple.split('-').next().map(str::to_string);
+        os(triple).is_some() && os(self.triple) == os(triple) && arch(self.triple) == arch(triple)
+    }
+
+    /// The shared library file name prefix and extension for this entry's OS.
+    fn prefix_and_extension(&self) -> (&'static str, &'static str) {
+        match self.os {
+            "windows" => ("", ".dll"),
+            "macos" => ("lib", ".dylib"),
+            "web" => ("", ".wasm"),
+            _ => ("lib", ".so"),
+        }
+    }
+}
+
 /// A validated GDExtension configuration ready to be writen to a `.gdextension` file.
 /// Construct me using the builder `GdExtensionConfig::start`.
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -14,8 +188,17 @@ pub struct ValidGdExtensionConfig {
     release_target: Option<String>,
     debug_target: Option<String>,
     godot_project_path: PathBuf,
-    relative_target_path: String,
+    library_target_path: String,
     library_name: String,
+    library_path_template: String,
+    absolute_paths: bool,
+    /// Exact library paths by build and entry triple, see `GdExtensionConfig::library_file`.
+    library_files: Vec<(String, &'static str, String)>,
+    platforms: Vec<Platform>,
+    raw_sections: Vec<(String, Vec<(String, String)>)>,
+    format_version: FormatVersion,
+    force: bool,
+    warnings: Vec<String>,
 }
 
 /// Used to configure a `.gdextension` file for Godot that can be written to disk.
@@ -42,6 +225,14 @@ pub struct GdExtensionConfig {
     target_path: Option<PathBuf>,
     godot_project_path: Option<PathBuf>,
     library_name: Option<String>,
+    library_path_template: String,
+    absolute_paths: bool,
+    library_files: Vec<(String, String, PathBuf)>,
+    platforms: Vec<Platform>,
+    raw_sections: Vec<(String, Vec<(String, String)>)>,
+    format_version: FormatVersion,
+    canonicalize_mode: CanonicalizeMode,
+    force: bool,
 }
 
 impl Default for GdExtensionConfig {
@@ -56,6 +247,14 @@ impl Default for GdExtensionConfig {
             target_path: None,
             godot_project_path: None,
             library_name: None,
+            library_path_template: DEFAULT_LIBRARY_PATH_TEMPLATE.to_string(),
+            absolute_paths: false,
+            library_files: vec![],
+            platforms: Platform::ALL.to_vec(),
+            raw_sections: vec![],
+            format_version: FormatVersion::default(),
+            canonicalize_mode: CanonicalizeMode::default(),
+            force: false,
         }
     }
 }
@@ -75,38 +274,141 @@ impl GdExtensionConfig {
     }
 
     /// Validate builder parameters and return a `ValidGdExtensionConfig`.
+    /// The error lists all problems found, not just the first.
     pub fn build(&self) -> Result<ValidGdExtensionConfig> {
-        let target_path = self
-            .target_path
-            .as_ref()
-            .context("Missing target path")?
-            .canonicalize()
-            .with_context(|| {
-                format!("Failed to canonicalize target path: {:?}", self.target_path)
-            })?;
-        let godot_project_path = self
-            .godot_project_path
-            .as_ref()
-            .context("Missing godot project path")?
-            .canonicalize()
-            .with_context(|| {
-                format!(
-                    "Failed to canonicalize godot project path: {:?}",
-                    self.godot_project_path
-                )
-            })?;
-        let library_name = self.library_name.as_ref().context("Missing library name")?;
-        let relative_target_path = diff_paths(&target_path, &godot_project_path)
-            .with_context(|| {
-                format!(
-                    "Failed to calculate relative target path: target={:?} -> godot_project={:?}",
-                    target_path, godot_project_path
-                )
-            })?
-            .to_str()
-            .context("Failed to convert relative target path to string")?
-            .to_string()
-            .replace('\\', "/"); // Godot res:// paths are always forward slashes.
+        // Collect all problems instead of failing on the first, so they can be fixed at once.
+        let mut problems: Vec<String> = vec![];
+        let mut canonical_dir = |path: &Option<PathBuf>, description: &str| match path {
+            None => {
+                problems.push(format!("Missing {description}"));
+                None
+            }
+            Some(path) => match canonicalize(path, self.canonicalize_mode) {
+                Ok(path) => Some(path),
+                Err(e) => {
+                    problems.push(format!("The {description} {path:?} does not exist: {e}"));
+                    None
+                }
+            },
+        };
+        let target_path = canonical_dir(&self.target_path, "target path");
+        let godot_project_path = canonical_dir(&self.godot_project_path, "godot project path");
+        if self.library_name.is_none() {
+            problems.push("Missing library name".to_string());
+        }
+        if let Err(e) = validate_library_path_template(&self.library_path_template) {
+            problems.push(format!("{e:#}"));
+        }
+        if self.platforms.is_empty() {
+            problems.push("No platforms configured for the `[libraries]` section".to_string());
+        }
+
+        for (name, entries) in &self.raw_sections {
+            if name.is_empty() || name.contains([']', '[', '\n']) {
+                problems.push(format!("Invalid .gdextension section name {name:?}"));
+                continue;
+            }
+            for (key, _) in entries {
+                if key.is_empty() || key.contains(['=', '\n']) {
+                    problems.push(format!("Invalid key {key:?} in section [{name}]"));
+                } else if name == "configuration" && CONFIGURATION_KEYS.contains(&key.as_str()) {
+                    problems.push(format!(
+                        "The [configuration] key {key:?} is generated, \
+                        use the corresponding `GdExtensionConfig` method instead"
+                    ));
+                }
+            }
+        }
+
+        let mut warnings = vec![];
+        let different_roots = match (&target_path, &godot_project_path) {
+            (Some(target_path), Some(godot_project_path)) => {
+                path_root(target_path) != path_root(godot_project_path)
+            }
+            _ => false,
+        };
+        if different_roots && !self.absolute_paths {
+            warnings.push(format!(
+                "The target directory {:?} and godot project {:?} are on different drives, \
+                so a relative `res://` path can't be generated. Using absolute paths instead.",
+                target_path, godot_project_path
+            ));
+        }
+        let absolute_paths = self.absolute_paths || different_roots;
+        let project_path = |path: &Path| -> Option<String> {
+            let godot_project_path = godot_project_path.as_ref()?;
+            project_path_string(path, godot_project_path, absolute_paths).ok()
+        };
+
+        let library_target_path = target_path.as_ref().and_then(|target_path| {
+            let path = project_path(target_path);
+            if path.is_none() && godot_project_path.is_some() {
+                problems.push(format!(
+                    "Failed to make the target path {target_path:?} relative to the godot project"
+                ));
+            }
+            path
+        });
+
+        let mut library_files = vec![];
+        for (build, triple, path) in &self.library_files {
+            if build != "release" && build != "debug" {
+                problems.push(format!(
+                    "Unknown build {build:?} for library file {path:?}, expected \"release\" or \"debug\""
+                ));
+                continue;
+            }
+            let entries: Vec<_> = LIBRARY_ENTRIES
+                .iter()
+                .filter(|entry| self.platforms.iter().any(|p| p.os_name() == entry.os))
+                .filter(|entry| entry.matches_triple(triple))
+                .collect();
+            if entries.is_empty() {
+                problems.push(format!(
+                    "No `[libraries]` entry for target {triple:?} of library file {path:?}"
+                ));
+                continue;
+            }
+            let canonical = match canonicalize(path, self.canonicalize_mode) {
+                Ok(canonical) => canonical,
+                Err(e) => {
+                    problems.push(format!("The library file {path:?} does not exist: {e}"));
+                    continue;
+                }
+            };
+            let Some(path) = project_path(&canonical) else {
+                if godot_project_path.is_some() {
+                    problems.push(format!(
+                        "Failed to make the library file {canonical:?} relative to the godot project"
+                    ));
+                }
+                continue;
+            };
+            let path = if absolute_paths {
+                path
+            } else {
+                format!("res://{path}")
+            };
+            for entry in entries {
+                library_files.push((build.clone(), entry.triple, path.clone()));
+            }
+        }
+
+        let (Some(godot_project_path), Some(library_target_path), Some(library_name), true) = (
+            godot_project_path,
+            library_target_path,
+            self.library_name.as_ref(),
+            problems.is_empty(),
+        ) else {
+            return Err(anyhow!(
+                "Invalid .gdextension config:\n{}",
+                problems
+                    .iter()
+                    .map(|problem| format!("  - {problem}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ));
+        };
 
         Ok(ValidGdExtensionConfig {
             config_file_name: self.config_file_name.clone(),
@@ -116,8 +418,16 @@ impl GdExtensionConfig {
             release_target: self.release_target.clone(),
             debug_target: self.debug_target.clone(),
             godot_project_path,
-            relative_target_path,
+            library_target_path,
             library_name: library_name.clone(),
+            library_path_template: self.library_path_template.clone(),
+            absolute_paths,
+            library_files,
+            platforms: self.platforms.clone(),
+            raw_sections: self.raw_sections.clone(),
+            format_version: self.format_version,
+            force: self.force,
+            warnings,
         })
     }
 
@@ -166,60 +476,236 @@ impl GdExtensionConfig {
         }
     }
 
+    /// The name of the generated `.gdextension` file.
+    pub fn file_name(&self) -> &str {
+        &self.config_file_name
+    }
+
+    /// Configure the library name used in the library paths, e.g. for a library renamed
+    /// by a feature-gated build. Dashes are replaced with underscores.
+    /// The default is the `crate_name` given to `start`.
+    pub fn library_name(self, name: &str) -> Self {
+        Self {
+            library_name: Some(name.replace("-", "_")),
+            ..self
+        }
+    }
+
     /// Configure whether the `.gdextension` library is hot reloadable.
     /// The default is `true`.
     pub fn reloadable(self, reloadable: bool) -> Self {
         Self { reloadable, ..self }
     }
+
+    /// Configure the path written for each library in the `[libraries]` section,
+    /// for build layouts such as `cross`, `cargo-zigbuild` or Nix.
+    /// The default is `res://{target}/{profile}/{prefix}{name}{ext}`.
+    ///
+    /// Supported placeholders:
+    /// - `{target}`: The cargo target directory relative to the godot project,
+    ///   or the absolute target directory if `absolute_paths` is enabled.
+    /// - `{triple}`: The target triple of the entry, e.g. `x86_64-unknown-linux-gnu`.
+    /// - `{profile}`: The release or debug target name, e.g. `release`.
+    /// - `{prefix}`: The library file name prefix of the entry's platform, e.g. `lib`.
+    /// - `{name}`: The library name.
+    /// - `{ext}`: The library file extension of the entry's platform, e.g. `.so`.
+    ///
+    /// Example usage:
+    /// ```rust,ignore
+    /// config.library_path_template("res://{target}/{triple}/{profile}/{prefix}{name}{ext}")
+    /// ```
+    pub fn library_path_template(self, template: &str) -> Self {
+        Self {
+            library_path_template: template.to_string(),
+            ..self
+        }
+    }
+
+    /// Use the library file at `path`, built for the target `triple`, for the `build`
+    /// (`"release"` or `"debug"`) entry of the matching platform instead of a path from the
+    /// `library_path_template`. See `cargo::CargoBuild` to get the exact paths from cargo.
+    ///
+    /// Example usage:
+    /// ```rust,ignore
+    /// config.library_file("debug", "x86_64-unknown-linux-gnu", Path::new("target/dist/libgame.so"))
+    /// ```
+    pub fn library_file(mut self, build: &str, triple: &str, path: &Path) -> Self {
+        self.library_files
+            .push((build.to_string(), triple.to_string(), path.to_path_buf()));
+        self
+    }
+
+    /// Only generate `[libraries]` entries for `platforms`, e.g. to avoid paths to libraries which
+    /// are never built. The default is all platforms.
+    ///
+    /// Example usage:
+    /// ```rust,ignore
+    /// config.platforms(&[Platform::Linux, Platform::Windows])
+    /// ```
+    pub fn platforms(self, platforms: &[Platform]) -> Self {
+        Self {
+            platforms: platforms.to_vec(),
+            ..self
+        }
+    }
+
+    /// Add keys to a section of the generated file, e.g. for Godot keys without a dedicated method
+    /// or tool-specific metadata. Values are written in Godot's variant text format, see
+    /// `SettingValue`. Keys for the `configuration` and `libraries` sections are added to the
+    /// generated sections, other sections are appended in the order they were first added.
+    ///
+    /// Example usage:
+    /// ```rust,ignore
+    /// config
+    ///     .raw_section("libraries", [("android.debug.arm64", "res://bin/libgame.so")])
+    ///     .raw_section("icons", [("Player", "res://icons/player.svg")])
+    /// ```
+    pub fn raw_section<K: Into<String>, V: Into<SettingValue>>(
+        mut self,
+        name: &str,
+        key_values: impl IntoIterator<Item = (K, V)>,
+    ) -> Self {
+        let entries = key_values
+            .into_iter()
+            .map(|(key, value)| (key.into(), value.into().to_variant_string()));
+        match self.raw_sections.iter_mut().find(|(n, _)| n == name) {
+            Some((_, existing)) => existing.extend(entries),
+            None => self
+                .raw_sections
+                .push((name.to_string(), entries.collect())),
+        }
+        self
+    }
+
+    /// The layout of the generated file. Pin it to keep the output byte-identical across crate
+    /// updates, or use `FormatVersion::LATEST`. The default is `FormatVersion::V1`.
+    pub fn format_version(self, format_version: FormatVersion) -> Self {
+        Self {
+            format_version,
+            ..self
+        }
+    }
+
+    /// How the godot project, target and library paths are made absolute before the relative
+    /// `res://` paths are computed. Use `CanonicalizeMode::Logical` if the godot project is reached
+    /// through a symlinked directory. The default is `CanonicalizeMode::Physical`.
+    pub fn canonicalize_mode(self, canonicalize_mode: CanonicalizeMode) -> Self {
+        Self {
+            canonicalize_mode,
+            ..self
+        }
+    }
+
+    /// Overwrite an existing `.gdextension` file which was not generated by this crate without
+    /// backing it up first. The default is `false`.
+    pub fn force(self, force: bool) -> Self {
+        Self { force, ..self }
+    }
+
+    /// Write absolute filesystem paths into the `[libraries]` section instead of `res://` paths
+    /// relative to the godot project, e.g. when the target directory lives in the Nix store.
+    /// A leading `res://` in the `library_path_template` is dropped in this mode.
+    /// The default is `false`.
+    pub fn absolute_paths(self, absolute_paths: bool) -> Self {
+        Self {
+            absolute_paths,
+            ..self
+        }
+    }
+
+    /// The target triples of the `[libraries]` entries of the configured `platforms`.
+    pub(crate) fn target_triples(&self) -> Vec<&'static str> {
+        LIBRARY_ENTRIES
+            .iter()
+            .filter(|entry| self.platforms.iter().any(|p| p.os_name() == entry.os))
+            .map(|entry| entry.triple)
+            .collect()
+    }
+}
+
+/// Write `contents` to a temporary file next to `path` and rename it to `path`, so readers see
+/// either the old or the new contents.
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    #[cfg_attr(not(unix), allow(unused_mut))]
+    let mut builder = tempfile::Builder::new();
+    // Temporary files are private, but the config should get the permissions of the file it
+    // replaces, or those of `std::fs::write`.
+    #[cfg(unix)]
+    builder.permissions(match std::fs::metadata(path) {
+        Ok(metadata) => metadata.permissions(),
+        Err(_) => std::os::unix::fs::PermissionsExt::from_mode(0o666),
+    });
+    let mut file = builder.tempfile_in(dir)?;
+    file.write_all(contents)?;
+    file.as_file().sync_all()?;
+    file.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// The `index`th backup path of `path`: `<path>.bak`, `<path>.1.bak`, `<path>.2.bak`, ...
+fn numbered_backup_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    if index == 0 {
+        name.push(".bak");
+    } else {
+        name.push(format!(".{index}.bak"));
+    }
+    path.with_file_name(name)
+}
+
+/// The first unused backup path of `path`.
+fn backup_path(path: &Path) -> PathBuf {
+    (0..)
+        .map(|index| numbered_backup_path(path, index))
+        .find(|backup_path| !backup_path.exists())
+        .unwrap_or_default()
+}
+
+/// The latest backup of `path` written before overwriting it, if any.
+pub(crate) fn latest_backup(path: &Path) -> Option<PathBuf> {
+    (0..)
+        .map(|index| numbered_backup_path(path, index))
+        .take_while(|backup_path| backup_path.exists())
+        .last()
+}
+
+/// Check that `template` only uses known placeholders.
+fn validate_library_path_template(template: &str) -> Result<()> {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..].find('}').with_context(|| {
+            format!("Unterminated placeholder in library path template: {template:?}")
+        })?;
+        let placeholder = &rest[start + 1..start + end];
+        if !LIBRARY_PATH_PLACEHOLDERS.contains(&placeholder) {
+            return Err(anyhow!(
+                "Unknown placeholder `{{{placeholder}}}` in library path template {template:?}. \
+                Supported placeholders: {}",
+                LIBRARY_PATH_PLACEHOLDERS
+                    .iter()
+                    .map(|p| format!("{{{p}}}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    Ok(())
 }
 
 impl ValidGdExtensionConfig {
     /// Generate a `.gdextension` file as a string.
     pub fn create(&self) -> String {
-        let release = if let Some(release_target) = &self.release_target {
-            format!(
-                r#"
-linux.release.x86_64 =   "res://{target}/{release_target}/lib{pkgname}.so"
-windows.release.x86_64 = "res://{target}/{release_target}/{pkgname}.dll"
-macos.release =          "res://{target}/{release_target}/lib{pkgname}.dylib"
-macos.release.arm64 =    "res://{target}/{release_target}/lib{pkgname}.dylib"
-"#,
-                target = self.relative_target_path,
-                release_target = release_target,
-                pkgname = self.library_name,
-            )
-            .trim_start()
-            .to_string()
-        } else {
-            "".to_string()
-        };
-
-        let debug = if let Some(debug_target) = &self.debug_target {
-            format!(
-                r#"
-linux.debug.x86_64 =     "res://{target}/{debug_target}/lib{pkgname}.so"
-windows.debug.x86_64 =   "res://{target}/{debug_target}/{pkgname}.dll"
-macos.debug =            "res://{target}/{debug_target}/lib{pkgname}.dylib"
-macos.debug.arm64 =      "res://{target}/{debug_target}/lib{pkgname}.dylib"
-"#,
-                target = self.relative_target_path,
-                debug_target = debug_target,
-                pkgname = self.library_name,
-            )
-            .trim_start()
-            .to_string()
-        } else {
-            "".to_string()
-        };
-
-        let preamble = format!(
+        let configuration = format!(
             r#"
 [configuration]
 entry_symbol = "{entry_symbol}"
 compatibility_minimum = {compatability_version}
 reloadable = {reloadable}
-
-[libraries]
 "#,
             entry_symbol = self.entry_symbol,
             compatability_version = self.compatability_version,
@@ -228,7 +714,138 @@ reloadable = {reloadable}
         .trim_start()
         .to_string();
 
-        preamble + &release + &debug
+        let builds = [
+            ("release", &self.release_target),
+            ("debug", &self.debug_target),
+        ];
+        let libraries = builds
+            .into_iter()
+            .filter_map(|(build, profile)| Some((build, profile.as_ref()?)))
+            .flat_map(|(build, profile)| {
+                self.library_entries().map(move |entry| {
+                    format!(
+                        "{:<24} \"{}\"\n",
+                        format!("{} =", entry.key(build)),
+                        self.library_path(entry, build, profile)
+                    )
+                })
+            })
+            .collect::<String>();
+
+        let mut output = configuration + &self.raw_keys("configuration");
+        output += "\n[libraries]\n";
+        output += &libraries;
+        output += &self.raw_keys("libraries");
+        let mut names: Vec<&str> = self
+            .raw_sections
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .filter(|name| *name != "configuration" && *name != "libraries")
+            .collect();
+        if self.format_version >= FormatVersion::V2 {
+            names.sort();
+        }
+        for name in names {
+            output += &format!("\n[{name}]\n{}", self.raw_keys(name));
+        }
+        output
+    }
+
+    /// The keys added to section `name` by `GdExtensionConfig::raw_section`, one per line.
+    fn raw_keys(&self, name: &str) -> String {
+        let mut entries: Vec<(&str, &str)> = self
+            .raw_sections
+            .iter()
+            .filter(|(n, _)| n == name)
+            .flat_map(|(_, entries)| entries)
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        if self.format_version >= FormatVersion::V2 {
+            let sorted: BTreeMap<&str, &str> = entries.into_iter().collect();
+            entries = sorted.into_iter().collect();
+        }
+        entries
+            .into_iter()
+            .map(|(key, value)| match name {
+                "libraries" => format!("{:<24} {value}\n", format!("{key} =")),
+                _ => format!("{key} = {value}\n"),
+            })
+            .collect()
+    }
+
+    /// The `[libraries]` entries of the configured platforms.
+    fn library_entries(&self) -> impl Iterator<Item = &'static LibraryEntry> {
+        LIBRARY_ENTRIES.iter().filter(|entry| {
+            self.platforms
+                .iter()
+                .any(|platform| platform.os_name() == entry.os)
+        })
+    }
+
+    /// The exact library file for `entry`, or the expanded library path template.
+    fn library_path(&self, entry: &LibraryEntry, build: &str, profile: &str) -> String {
+        if let Some((_, _, path)) = self
+            .library_files
+            .iter()
+            .find(|(b, triple, _)| b == build && *triple == entry.triple)
+        {
+            return path.clone();
+        }
+        let (prefix, ext) = entry.prefix_and_extension();
+        let template = if self.absolute_paths {
+            self.library_path_template
+                .strip_prefix("res://")
+                .unwrap_or(&self.library_path_template)
+        } else {
+            &self.library_path_template
+        };
+        template
+            .replace("{target}", &self.library_target_path)
+            .replace("{triple}", entry.triple)
+            .replace("{profile}", profile)
+            .replace("{prefix}", prefix)
+            .replace("{name}", &self.library_name)
+            .replace("{ext}", ext)
+    }
+
+    /// The filesystem paths of the libraries of all `[libraries]` entries, without duplicates.
+    pub fn library_files(&self) -> Vec<PathBuf> {
+        let builds = [
+            ("release", &self.release_target),
+            ("debug", &self.debug_target),
+        ];
+        let mut files = vec![];
+        for (build, profile) in builds {
+            let Some(profile) = profile else {
+                continue;
+            };
+            for entry in self.library_entries() {
+                let path = self.library_path(entry, build, profile);
+                let file = match path.strip_prefix("res://") {
+                    Some(relative) => self.godot_project_path.join(relative),
+                    None => PathBuf::from(path),
+                };
+                if !files.contains(&file) {
+                    files.push(file);
+                }
+            }
+        }
+        files
+    }
+
+    /// Returns true if Godot reloads the library when it changes.
+    pub fn reloadable(&self) -> bool {
+        self.reloadable
+    }
+
+    /// The name of the entry symbol Godot looks up in the library.
+    pub fn entry_symbol(&self) -> &str {
+        &self.entry_symbol
+    }
+
+    /// Problems detected while building the configuration that did not prevent generating it.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
     }
 
     /// The full path to the generated `.gdextension` file including the file name.
@@ -236,15 +853,32 @@ reloadable = {reloadable}
         self.godot_project_path.join(&self.config_file_name)
     }
 
-    /// Write a generated `.gdextension` file to disk.
-    pub fn write(&self) -> std::io::Result<()> {
-        std::fs::write(self.full_config_path(), self.create())
+    /// Write a generated `.gdextension` file to disk, starting with the `GENERATED_HEADER`.
+    /// An existing file without the header and with different contents, e.g. a hand-written one,
+    /// is backed up to `<name>.bak` first unless `force` is set. Returns the path of the backup.
+    /// The file is replaced atomically, so a running editor never reads a half-written config.
+    pub fn write(&self) -> std::io::Result<Option<PathBuf>> {
+        let path = self.full_config_path();
+        let contents = format!("{GENERATED_HEADER}\n{}", self.create());
+        let mut backup = None;
+        match std::fs::read_to_string(&path) {
+            Ok(existing) if existing == contents => return Ok(None),
+            Ok(existing) if !self.force && !existing.starts_with(GENERATED_HEADER) => {
+                let backup_path = backup_path(&path);
+                std::fs::copy(&path, &backup_path)?;
+                backup = Some(backup_path);
+            }
+            _ => {}
+        }
+        write_atomic(&path, contents.as_bytes())?;
+        Ok(backup)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::paths::absolute_path_string;
     use tempfile::{TempDir, tempdir};
 
     fn create_test_directories() -> (TempDir, PathBuf, PathBuf) {
@@ -381,4 +1015,418 @@ macos.debug.arm64 =      "res://../../.cache/cargo/target/debug/libtest_library.
             .to_string()
         );
     }
+
+    #[test]
+    fn test_library_path_template() {
+        let (_tempdir, godot_project_path, target_path) = create_test_directories();
+        let config = GdExtensionConfig::start("test_library", &godot_project_path, &target_path)
+            .debug_target(None)
+            .library_path_template("res://{target}/{triple}/{profile}/{prefix}{name}{ext}")
+            .build()
+            .expect("Successful build");
+        let file_string = config.create();
+
+        assert_eq!(
+            file_string,
+            r#"
+[configuration]
+entry_symbol = "gdext_rust_init"
+compatibility_minimum = 4.1
+reloadable = true
+
+[libraries]
+linux.release.x86_64 =   "res://../../.cache/cargo/target/x86_64-unknown-linux-gnu/release/libtest_library.so"
+windows.release.x86_64 = "res://../../.cache/cargo/target/x86_64-pc-windows-msvc/release/test_library.dll"
+macos.release =          "res://../../.cache/cargo/target/x86_64-apple-darwin/release/libtest_library.dylib"
+macos.release.arm64 =    "res://../../.cache/cargo/target/aarch64-apple-darwin/release/libtest_library.dylib"
+"#
+            .trim_start()
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn test_library_path_template_unknown_placeholder() {
+        let (_tempdir, godot_project_path, target_path) = create_test_directories();
+        let result = GdExtensionConfig::start("test_library", &godot_project_path, &target_path)
+            .library_path_template("res://{target}/{arch}/{name}")
+            .build();
+
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Unknown placeholder `{arch}`")
+        );
+    }
+
+    #[test]
+    fn test_build_collects_problems() {
+        let (tempdir, godot_project_path, _target_path) = create_test_directories();
+        let error = GdExtensionConfig::start(
+            "test_library",
+            &godot_project_path,
+            &tempdir.path().join("missing"),
+        )
+        .library_path_template("{arch}")
+        .platforms(&[])
+        .library_file("beta", "x86_64-unknown-linux-gnu", Path::new("lib.so"))
+        .build()
+        .unwrap_err()
+        .to_string();
+
+        assert!(error.starts_with("Invalid .gdextension config:\n"));
+        assert!(error.contains("missing\" does not exist"));
+        assert!(error.contains("Unknown placeholder `{arch}`"));
+        assert!(error.contains("No platforms configured"));
+        assert!(error.contains("Unknown build \"beta\""));
+        assert_eq!(
+            error
+                .lines()
+                .filter(|line| line.starts_with("  - "))
+                .count(),
+            4
+        );
+
+        let error = GdExtensionConfig::default()
+            .build()
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Missing target path"));
+        assert!(error.contains("Missing godot project path"));
+        assert!(error.contains("Missing library name"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_canonicalize_mode() {
+        let tempdir = tempdir().unwrap();
+        let real = tempdir.path().join("real");
+        std::fs::create_dir_all(real.join("godot")).unwrap();
+        std::fs::create_dir_all(real.join("target")).unwrap();
+        std::fs::create_dir_all(tempdir.path().join("projects")).unwrap();
+        let project = tempdir.path().join("projects/godot");
+        std::os::unix::fs::symlink(real.join("godot"), &project).unwrap();
+
+        let library_path = |mode| {
+            GdExtensionConfig::start("game", &project, &real.join("target"))
+                .canonicalize_mode(mode)
+                .build()
+                .unwrap()
+                .library_files()[0]
+                .clone()
+        };
+        assert_eq!(
+            library_path(CanonicalizeMode::Physical),
+            real.join("godot")
+                .canonicalize()
+                .unwrap()
+                .join("../target/release/libgame.so")
+        );
+        assert_eq!(
+            library_path(CanonicalizeMode::Logical),
+            project.join("../../real/target/release/libgame.so")
+        );
+    }
+
+    #[test]
+    fn test_absolute_paths() {
+        let (_tempdir, godot_project_path, target_path) = create_test_directories();
+        let config = GdExtensionConfig::start("test_library", &godot_project_path, &target_path)
+            .debug_target(None)
+            .absolute_paths(true)
+            .build()
+            .expect("Successful build");
+        let file_string = config.create();
+        let target = absolute_path_string(&target_path.canonicalize().unwrap()).unwrap();
+
+        assert!(!file_string.contains("res://"));
+        assert!(file_string.contains(&format!(
+            "linux.release.x86_64 =   \"{target}/release/libtest_library.so\"\n"
+        )));
+    }
+
+    #[test]
+    fn test_library_file() {
+        let (_tempdir, godot_project_path, target_path) = create_test_directories();
+        let library = target_path.join("dist/libtest_library.so");
+        std::fs::create_dir_all(library.parent().unwrap()).unwrap();
+        std::fs::write(&library, "").unwrap();
+
+        let config = GdExtensionConfig::start("test_library", &godot_project_path, &target_path)
+            .library_file("release", "x86_64-unknown-linux-gnu", &library)
+            .build()
+            .unwrap();
+        let file_string = config.create();
+        assert!(file_string.contains(
+            "linux.release.x86_64 =   \"res://../../.cache/cargo/target/dist/libtest_library.so\""
+        ));
+        assert!(file_string.contains(
+            "linux.debug.x86_64 =     \"res://../../.cache/cargo/target/debug/libtest_library.so\""
+        ));
+
+        let start = || GdExtensionConfig::start("test_library", &godot_project_path, &target_path);
+        assert!(
+            start()
+                .library_file("dist", "x86_64-unknown-linux-gnu", &library)
+                .build()
+                .is_err()
+        );
+        assert!(
+            start()
+                .library_file("debug", "wasm32-unknown-unknown", &library)
+                .build()
+                .is_err()
+        );
+        assert!(LIBRARY_ENTRIES[1].matches_triple("x86_64-pc-windows-gnu"));
+        assert!(LIBRARY_ENTRIES[3].matches_triple("aarch64-apple-darwin"));
+        assert!(!LIBRARY_ENTRIES[2].matches_triple("aarch64-apple-darwin"));
+        assert!(LIBRARY_ENTRIES[4].matches_triple("aarch64-linux-android"));
+        assert!(!LIBRARY_ENTRIES[0].matches_triple("x86_64-linux-android"));
+
+        let universal = target_path.join("universal-apple-darwin/release/libtest_library.dylib");
+        std::fs::create_dir_all(universal.parent().unwrap()).unwrap();
+        std::fs::write(&universal, "").unwrap();
+        let file_string = start()
+            .library_file("release", MACOS_UNIVERSAL_TRIPLE, &universal)
+            .build()
+            .unwrap()
+            .create();
+        let path = "\"res://../../.cache/cargo/target/universal-apple-darwin/release/libtest_library.dylib\"";
+        assert!(file_string.contains(&format!("macos.release =          {path}")));
+        assert!(file_string.contains(&format!("macos.release.arm64 =    {path}")));
+    }
+
+    #[test]
+    fn test_library_files() {
+        let (_tempdir, godot_project_path, target_path) = create_test_directories();
+        let config = GdExtensionConfig::start("test_library", &godot_project_path, &target_path)
+            .release_target(None)
+            .build()
+            .unwrap();
+        let files = config.library_files();
+        assert_eq!(files.len(), 3);
+        assert_eq!(
+            files[0],
+            config
+                .godot_project_path
+                .join("../../.cache/cargo/target/debug/libtest_library.so")
+        );
+    }
+
+    #[test]
+    fn test_platforms() {
+        let (_tempdir, godot_project_path, target_path) = create_test_directories();
+        let start = || GdExtensionConfig::start("test_library", &godot_project_path, &target_path);
+        let config = start()
+            .platforms(&[Platform::Linux, Platform::MacOS])
+            .build()
+            .unwrap();
+        let file_string = config.create();
+        assert!(file_string.contains("linux.release.x86_64 ="));
+        assert!(file_string.contains("macos.debug.arm64 ="));
+        assert!(!file_string.contains("windows"));
+        assert_eq!(config.library_files().len(), 4);
+
+        assert!(start().platforms(&[]).build().is_err());
+        let library = target_path.join("release/test_library.dll");
+        std::fs::create_dir_all(library.parent().unwrap()).unwrap();
+        std::fs::write(&library, "").unwrap();
+        assert!(
+            start()
+                .platforms(&[Platform::Linux])
+                .library_file("release", "x86_64-pc-windows-msvc", &library)
+                .build()
+                .is_err()
+        );
+
+        let file_string = start()
+            .platforms(&[Platform::Android])
+            .build()
+            .unwrap()
+            .create();
+        assert!(file_string.contains(
+            "android.debug.arm32 =    \"res://../../.cache/cargo/target/debug/libtest_library.so\""
+        ));
+        assert!(!file_string.contains("linux."));
+
+        let file_string = start()
+            .platforms(&[Platform::Web])
+            .build()
+            .unwrap()
+            .create();
+        assert!(file_string.contains(
+            "web.release.wasm32 =     \"res://../../.cache/cargo/target/release/test_library.wasm\""
+        ));
+    }
+
+    #[test]
+    fn test_raw_section() {
+        let (_tempdir, godot_project_path, target_path) = create_test_directories();
+        let start = || GdExtensionConfig::start("test_library", &godot_project_path, &target_path);
+        let file_string = start()
+            .platforms(&[Platform::Linux])
+            .release_target(None)
+            .raw_section("icons", [("Player", "res://icons/player.svg")])
+            .raw_section("configuration", [("android_aar_plugin", true)])
+            .raw_section(
+                "libraries",
+                [("android.debug.arm64", "res://bin/libgame.so")],
+            )
+            .raw_section("icons", [("Enemy", "res://icons/enemy.svg")])
+            .build()
+            .unwrap()
+            .create();
+        assert_eq!(
+            file_string,
+            r#"[configuration]
+entry_symbol = "gdext_rust_init"
+compatibility_minimum = 4.1
+reloadable = true
+android_aar_plugin = true
+
+[libraries]
+linux.debug.x86_64 =     "res://../../.cache/cargo/target/debug/libtest_library.so"
+android.debug.arm64 =    "res://bin/libgame.so"
+
+[icons]
+Player = "res://icons/player.svg"
+Enemy = "res://icons/enemy.svg"
+"#
+        );
+
+        assert!(
+            start()
+                .raw_section("configuration", [("entry_symbol", "init")])
+                .build()
+                .is_err()
+        );
+        assert!(start().raw_section("a]b", [("key", 1)]).build().is_err());
+    }
+
+    #[test]
+    fn test_format_versions() {
+        let (_tempdir, godot_project_path, target_path) = create_test_directories();
+        let create = |format_version| {
+            GdExtensionConfig::start("test_library", &godot_project_path, &target_path)
+                .platforms(&[Platform::Linux])
+                .release_target(None)
+                .raw_section("icons", [("Player", "res://player.svg")])
+                .raw_section("dependencies", [("linux.debug", "{}")])
+                .raw_section("icons", [("Enemy", "res://enemy.svg")])
+                .raw_section("icons", [("Player", "res://player2.svg")])
+                .format_version(format_version)
+                .build()
+                .unwrap()
+                .create()
+        };
+        // The output of each format version must never change, see `FormatVersion`.
+        assert_eq!(
+            create(FormatVersion::V1),
+            r#"[configuration]
+entry_symbol = "gdext_rust_init"
+compatibility_minimum = 4.1
+reloadable = true
+
+[libraries]
+linux.debug.x86_64 =     "res://../../.cache/cargo/target/debug/libtest_library.so"
+
+[icons]
+Player = "res://player.svg"
+Enemy = "res://enemy.svg"
+Player = "res://player2.svg"
+
+[dependencies]
+linux.debug = "{}"
+"#
+        );
+        assert_eq!(
+            create(FormatVersion::V2),
+            r#"[configuration]
+entry_symbol = "gdext_rust_init"
+compatibility_minimum = 4.1
+reloadable = true
+
+[libraries]
+linux.debug.x86_64 =     "res://../../.cache/cargo/target/debug/libtest_library.so"
+
+[dependencies]
+linux.debug = "{}"
+
+[icons]
+Enemy = "res://enemy.svg"
+Player = "res://player2.svg"
+"#
+        );
+        assert_eq!(create(FormatVersion::default()), create(FormatVersion::V1));
+    }
+
+    #[test]
+    fn test_write_backs_up_hand_written_file() {
+        let (_tempdir, godot_project_path, target_path) = create_test_directories();
+        let path = godot_project_path.join("rust.gdextension");
+        std::fs::write(&path, "[configuration]\nentry_symbol = \"custom\"").unwrap();
+        let config = GdExtensionConfig::start("test_library", &godot_project_path, &target_path)
+            .build()
+            .unwrap();
+
+        let backup = config.write().unwrap().unwrap();
+        assert_eq!(backup, godot_project_path.join("rust.gdextension.bak"));
+        assert!(std::fs::read_to_string(&backup).unwrap().contains("custom"));
+        assert!(
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .starts_with(GENERATED_HEADER)
+        );
+        assert_eq!(config.write().unwrap(), None);
+        // Only the config and its backup remain, no temporary file.
+        assert_eq!(std::fs::read_dir(&godot_project_path).unwrap().count(), 2);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o044, 0o044);
+        }
+
+        std::fs::write(&path, "hand-written").unwrap();
+        assert_eq!(
+            config.write().unwrap(),
+            Some(godot_project_path.join("rust.gdextension.1.bak"))
+        );
+        std::fs::write(&path, "hand-written").unwrap();
+        let forced = GdExtensionConfig::start("test_library", &godot_project_path, &target_path)
+            .force(true)
+            .build()
+            .unwrap();
+        assert_eq!(forced.write().unwrap(), None);
+        assert!(!godot_project_path.join("rust.gdextension.2.bak").exists());
+        assert_eq!(
+            latest_backup(&godot_project_path.join("rust.gdextension")),
+            Some(godot_project_path.join("rust.gdextension.1.bak"))
+        );
+        assert_eq!(
+            latest_backup(&godot_project_path.join("other.gdextension")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_path_root() {
+        let (_tempdir, godot_project_path, target_path) = create_test_directories();
+        assert_eq!(path_root(&godot_project_path), path_root(&target_path));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_path_root_different_drives() {
+        assert_eq!(path_root(Path::new(r"c:\project")), Some("C:".to_string()));
+        assert_eq!(
+            path_root(Path::new(r"\\?\C:\project")),
+            path_root(Path::new(r"C:\target"))
+        );
+        assert_ne!(
+            path_root(Path::new(r"C:\project")),
+            path_root(Path::new(r"D:\target"))
+        );
+    }
 }
@@ -0,0 +1,435 @@
+//! Generating the `.gdnlib` and `.gdns` files of a Godot 3.x GDNative project.
+//!
+//! Godot 3 loads Rust libraries built with the `gdnative` crate through a `GDNativeLibrary`
+//! resource (`.gdnlib`), and each exported class through a `NativeScript` resource (`.gdns`)
+//! pointing at it. `GdNativeConfig` mirrors the builder of `GdExtensionConfig`, so both
+//! extension systems are configured the same way.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! GdNativeConfig::start("game", Path::new("godot"), Path::new("target"))
+//!     .native_script("Player", "scripts/player.gdns")
+//!     .build()?
+//!     .write()?;
+//! ```
+use crate::gdextension_config::{GENERATED_HEADER, Platform};
+use crate::paths::{CanonicalizeMode, canonicalize, project_path_string};
+use crate::state::find_project_files;
+use anyhow::{Result, anyhow};
+use std::path::{Path, PathBuf};
+
+/// The `.gdns` file of the class `class_name`, loaded from the `.gdnlib` file
+/// `res://<config_file_name>`.
+fn gdns_contents(config_file_name: &str, class_name: &str) -> String {
+    format!(
+        "[gd_resource type=\"NativeScript\" load_steps=2 format=2]\n\n\
+        [ext_resource path=\"res://{config_file_name}\" type=\"GDNativeLibrary\" id=1]\n\n\
+        [resource]\n\n\
+        resource_name = \"{class_name}\"\n\
+        class_name = \"{class_name}\"\n\
+        library = ExtResource( 1 )\n"
+    )
+}
+
+/// The files of the project written by `ValidGdNativeConfig::write`: the `.gdnlib` files starting
+/// with the generated header, and the unmodified `.gdns` files loaded from them.
+pub(crate) fn generated_files(godot_project_path: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    find_project_files(godot_project_path, &mut files)?;
+    let has_extension = |path: &Path, extension: &str| path.extension() == Some(extension.as_ref());
+    let libraries: Vec<PathBuf> = files
+        .iter()
+        .filter(|path| has_extension(path, "gdnlib"))
+        .filter(|path| {
+            std::fs::read_to_string(path)
+                .is_ok_and(|contents| contents.starts_with(GENERATED_HEADER))
+        })
+        .cloned()
+        .collect();
+    let library_names: Vec<String> = libraries
+        .iter()
+        .filter_map(|path| path.strip_prefix(godot_project_path).ok())
+        .map(|path| path.to_string_lossy().replace('\\', "/"))
+        .collect();
+    let is_generated_gdns = |path: &Path| {
+        let Ok(contents) = std::fs::read_to_string(path) else {
+            return false;
+        };
+        let class_name = contents
+            .lines()
+            .find_map(|line| line.strip_prefix("class_name = \""))
+            .and_then(|name| name.strip_suffix('"'));
+        class_name.is_some_and(|class_name| {
+            library_names
+                .iter()
+                .any(|library| gdns_contents(library, class_name) == contents)
+        })
+    };
+    let scripts = files
+        .into_iter()
+        .filter(|path| has_extension(path, "gdns") && is_generated_gdns(path));
+    Ok(libraries.into_iter().chain(scripts).collect())
+}
+
+/// The entry of a platform in the `.gdnlib` file, its library prefix and extension.
+fn platform_entry(platform: Platform) -> (&'static str, &'static str, &'static str) {
+    match platform {
+        Platform::Linux => ("X11.64", "lib", ".so"),
+        Platform::Windows => ("Windows.64", "", ".dll"),
+        Platform::MacOS => ("OSX.64", "lib", ".dylib"),
+        Platform::Android => ("Android.arm64-v8a", "lib", ".so"),
+        Platform::Web => ("HTML5.wasm32", "", ".wasm"),
+    }
+}
+
+/// Builder for `ValidGdNativeConfig`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GdNativeConfig {
+    config_file_name: String,
+    library_name: Option<String>,
+    target_path: Option<PathBuf>,
+    godot_project_path: Option<PathBuf>,
+    profile: String,
+    symbol_prefix: String,
+    reloadable: bool,
+    singleton: bool,
+    load_once: bool,
+    platforms: Vec<Platform>,
+    native_scripts: Vec<(String, String)>,
+    canonicalize_mode: CanonicalizeMode,
+}
+
+impl Default for GdNativeConfig {
+    fn default() -> Self {
+        Self {
+            config_file_name: "rust.gdnlib".to_string(),
+            library_name: None,
+            target_path: None,
+            godot_project_path: None,
+            profile: "debug".to_string(),
+            symbol_prefix: "godot_".to_string(),
+            reloadable: true,
+            singleton: false,
+            load_once: true,
+            platforms: Platform::ALL.to_vec(),
+            native_scripts: vec![],
+            canonicalize_mode: CanonicalizeMode::default(),
+        }
+    }
+}
+
+impl GdNativeConfig {
+    /// Start building a `ValidGdNativeConfig` from the given parameters.
+    ///
+    /// Note: `crate_name` will have dashes replaced with underscores
+    /// to match cargo file naming conventions.
+    pub fn start(crate_name: &str, godot_project_path: &Path, target_directory: &Path) -> Self {
+        Self {
+            library_name: Some(crate_name.replace('-', "_")),
+            target_path: Some(target_directory.to_path_buf()),
+            godot_project_path: Some(godot_project_path.to_path_buf()),
+            ..Self::default()
+        }
+    }
+
+    /// Set the `.gdnlib` file name, relative to the godot project.
+    /// The default is `rust.gdnlib`.
+    pub fn config_file_name(self, name: &str) -> Self {
+        Self {
+            config_file_name: name.to_string(),
+            ..self
+        }
+    }
+
+    /// The cargo profile directory the libraries are loaded from, since `.gdnlib` files have no
+    /// separate debug and release entries. The default is `debug`.
+    pub fn profile(self, profile: &str) -> Self {
+        Self {
+            profile: profile.to_string(),
+            ..self
+        }
+    }
+
+    /// The prefix of the library's entry symbols, `godot_` unless `godot_init!` was given a
+    /// custom prefix. The default is `godot_`.
+    pub fn symbol_prefix(self, symbol_prefix: &str) -> Self {
+        Self {
+            symbol_prefix: symbol_prefix.to_string(),
+            ..self
+        }
+    }
+
+    /// Allow Godot to reload the library when it changes. The default is `true`.
+    pub fn reloadable(self, reloadable: bool) -> Self {
+        Self { reloadable, ..self }
+    }
+
+    /// Load the library as a singleton when Godot starts. The default is `false`.
+    pub fn singleton(self, singleton: bool) -> Self {
+        Self { singleton, ..self }
+    }
+
+    /// Load the library only once for all scripts using it. The default is `true`.
+    pub fn load_once(self, load_once: bool) -> Self {
+        Self { load_once, ..self }
+    }
+
+    /// Only generate entries for `platforms`. The default is all platforms.
+    pub fn platforms(self, platforms: &[Platform]) -> Self {
+        Self {
+            platforms: platforms.to_vec(),
+            ..self
+        }
+    }
+
+    /// Generate a `.gdns` file at `path`, relative to the godot project, for the class
+    /// `class_name` registered by the library.
+    pub fn native_script(mut self, class_name: &str, path: &str) -> Self {
+        self.native_scripts
+            .push((class_name.to_string(), path.to_string()));
+        self
+    }
+
+    /// How the godot project and target paths are made absolute before the relative `res://`
+    /// paths are computed. The default is `CanonicalizeMode::Physical`.
+    pub fn canonicalize_mode(self, canonicalize_mode: CanonicalizeMode) -> Self {
+        Self {
+            canonicalize_mode,
+            ..self
+        }
+    }
+
+    /// Validate builder parameters and return a `ValidGdNativeConfig`.
+    /// The error lists all problems found, not just the first.
+    pub fn build(&self) -> Result<ValidGdNativeConfig> {
+        let mut problems: Vec<String> = vec![];
+        let mut canonical_dir = |path: &Option<PathBuf>, description: &str| match path {
+            None => {
+                problems.push(format!("Missing {description}"));
+                None
+            }
+            Some(path) => match canonicalize(path, self.canonicalize_mode) {
+                Ok(path) => Some(path),
+                Err(e) => {
+                    problems.push(format!("The {description} {path:?} does not exist: {e}"));
+                    None
+                }
+            },
+        };
+        let target_path = canonical_dir(&self.target_path, "target path");
+        let godot_project_path = canonical_dir(&self.godot_project_path, "godot project path");
+        if self.library_name.is_none() {
+            problems.push("Missing library name".to_string());
+        }
+        if self.platforms.is_empty() {
+            problems.push("No platforms configured for the `[entry]` section".to_string());
+        }
+        for (class_name, path) in &self.native_scripts {
+            if !path.ends_with(".gdns") {
+                problems.push(format!(
+                    "The NativeScript path {path:?} of class {class_name} must end with `.gdns`"
+                ));
+            }
+        }
+        let library_target_path = match (&target_path, &godot_project_path) {
+            (Some(target_path), Some(godot_project_path)) => {
+                match project_path_string(target_path, godot_project_path, false) {
+                    Ok(path) => Some(path),
+                    Err(e) => {
+                        problems.push(format!("{e:#}"));
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        let (Some(godot_project_path), Some(library_target_path), Some(library_name), true) = (
+            godot_project_path,
+            library_target_path,
+            self.library_name.clone(),
+            problems.is_empty(),
+        ) else {
+            return Err(anyhow!(
+                "Invalid .gdnlib config:\n{}",
+                problems
+                    .iter()
+                    .map(|problem| format!("  - {problem}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ));
+        };
+        Ok(ValidGdNativeConfig {
+            config_file_name: self.config_file_name.clone(),
+            library_name,
+            godot_project_path,
+            library_target_path,
+            profile: self.profile.clone(),
+            symbol_prefix: self.symbol_prefix.clone(),
+            reloadable: self.reloadable,
+            singleton: self.singleton,
+            load_once: self.load_once,
+            platforms: self.platforms.clone(),
+            native_scripts: self.native_scripts.clone(),
+        })
+    }
+}
+
+/// A validated GDNative configuration ready to be written to `.gdnlib` and `.gdns` files.
+/// Construct me using the builder `GdNativeConfig::start`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ValidGdNativeConfig {
+    config_file_name: String,
+    library_name: String,
+    godot_project_path: PathBuf,
+    library_target_path: String,
+    profile: String,
+    symbol_prefix: String,
+    reloadable: bool,
+    singleton: bool,
+    load_once: bool,
+    platforms: Vec<Platform>,
+    native_scripts: Vec<(String, String)>,
+}
+
+impl ValidGdNativeConfig {
+    /// Generate the `.gdnlib` file as a string.
+    pub fn create_gdnlib(&self) -> String {
+        let entries: Vec<(&str, String)> = self
+            .platforms
+            .iter()
+            .map(|platform| {
+                let (key, prefix, ext) = platform_entry(*platform);
+                let path = format!(
+                    "res://{}/{}/{prefix}{}{ext}",
+                    self.library_target_path, self.profile, self.library_name
+                );
+                (key, path)
+            })
+            .collect();
+        let mut output = format!(
+            "[general]\n\nsingleton={}\nload_once={}\nsymbol_prefix=\"{}\"\nreloadable={}\n",
+            self.singleton, self.load_once, self.symbol_prefix, self.reloadable
+        );
+        output += "\n[entry]\n\n";
+        for (key, path) in &entries {
+            output += &format!("{key}=\"{path}\"\n");
+        }
+        output += "\n[dependencies]\n\n";
+        for (key, _) in &entries {
+            output += &format!("{key}=[  ]\n");
+        }
+        output
+    }
+
+    /// Generate the `.gdns` file of the class `class_name` as a string.
+    pub fn create_gdns(&self, class_name: &str) -> String {
+        gdns_contents(&self.config_file_name, class_name)
+    }
+
+    /// The full path of the `.gdnlib` file.
+    pub fn full_config_path(&self) -> PathBuf {
+        self.godot_project_path.join(&self.config_file_name)
+    }
+
+    /// Write the `.gdnlib` file and the `.gdns` files whose contents changed.
+    /// Returns the written files.
+    pub fn write(&self) -> std::io::Result<Vec<PathBuf>> {
+        let mut files = vec![(
+            self.full_config_path(),
+            format!("{GENERATED_HEADER}\n{}", self.create_gdnlib()),
+        )];
+        for (class_name, path) in &self.native_scripts {
+            files.push((
+                self.godot_project_path.join(path),
+                self.create_gdns(class_name),
+            ));
+        }
+        let mut written = vec![];
+        for (path, contents) in files {
+            if std::fs::read_to_string(&path).is_ok_and(|existing| existing == contents) {
+                continue;
+            }
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, contents)?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_and_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("godot");
+        let target = dir.path().join("target");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::create_dir_all(&target).unwrap();
+
+        let config = GdNativeConfig::start("my-game", &project, &target)
+            .platforms(&[Platform::Linux, Platform::Windows])
+            .native_script("Player", "scripts/player.gdns")
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.create_gdnlib(),
+            r#"[general]
+
+singleton=false
+load_once=true
+symbol_prefix="godot_"
+reloadable=true
+
+[entry]
+
+X11.64="res://../target/debug/libmy_game.so"
+Windows.64="res://../target/debug/my_game.dll"
+
+[dependencies]
+
+X11.64=[  ]
+Windows.64=[  ]
+"#
+        );
+        assert_eq!(
+            config.create_gdns("Player"),
+            r#"[gd_resource type="NativeScript" load_steps=2 format=2]
+
+[ext_resource path="res://rust.gdnlib" type="GDNativeLibrary" id=1]
+
+[resource]
+
+resource_name = "Player"
+class_name = "Player"
+library = ExtResource( 1 )
+"#
+        );
+
+        assert_eq!(config.write().unwrap().len(), 2);
+        assert!(project.join("scripts/player.gdns").exists());
+        assert!(config.write().unwrap().is_empty());
+        std::fs::write(project.join("scripts/enemy.gdns"), "hand-written").unwrap();
+        std::fs::write(project.join("other.gdnlib"), "hand-written").unwrap();
+        assert_eq!(
+            generated_files(&project).unwrap(),
+            vec![
+                project.join("rust.gdnlib"),
+                project.join("scripts/player.gdns")
+            ]
+        );
+
+        let error = GdNativeConfig::start("game", &project, &dir.path().join("missing"))
+            .native_script("Enemy", "enemy.tres")
+            .build()
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("does not exist"));
+        assert!(error.contains("must end with `.gdns`"));
+    }
+}
@@ -0,0 +1,216 @@
+//! The files this crate writes into a godot project, see `GodotRunner::generated_files_manifest`.
+//!
+//! The generated `.gdextension` file is rewritten before every launch, so it is usually ignored
+//! by version control rather than committed. `update_gitignore` appends the entries of all
+//! generated files to the project's `.gitignore`.
+//!
+//! `GodotRunner::clean` removes the generated files again and reports what it removed.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let files = runner.generated_files_manifest();
+//! update_gitignore(Path::new("godot"), &files)?;
+//! ```
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// The comment above the entries appended by `update_gitignore`.
+pub const GITIGNORE_HEADER: &str = "# Generated by cargo-godot-lib";
+
+/// A file or directory written into the godot project.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GeneratedFile {
+    /// The path relative to the godot project with forward slashes.
+    /// Directories end with a slash.
+    pub path: String,
+    /// What the file is for.
+    pub description: &'static str,
+    /// Returns true if the file only exists during a run.
+    pub temporary: bool,
+    /// Returns true if the file should be ignored by version control. Files which may also be
+    /// written by users, such as `override.cfg`, are not ignored.
+    pub gitignore: bool,
+}
+
+impl GeneratedFile {
+    /// The `.gitignore` entry matching the file, anchored to the project directory.
+    pub fn gitignore_entry(&self) -> String {
+        format!("/{}", self.path)
+    }
+}
+
+/// Append the entries of `files` missing from the project's `.gitignore`, creating it if needed.
+/// Returns the added entries.
+pub fn update_gitignore(godot_project_path: &Path, files: &[GeneratedFile]) -> Result<Vec<String>> {
+    let path = godot_project_path.join(".gitignore");
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {path:?}")),
+    };
+    let existing: Vec<&str> = contents.lines().map(str::trim).collect();
+    let mut added: Vec<String> = vec![];
+    for entry in files
+        .iter()
+        .filter(|file| file.gitignore)
+        .map(GeneratedFile::gitignore_entry)
+    {
+        if !existing.contains(&entry.as_str()) && !added.contains(&entry) {
+            added.push(entry);
+        }
+    }
+    if added.is_empty() {
+        return Ok(added);
+    }
+
+    let mut updated = contents.clone();
+    if !updated.is_empty() && !updated.ends_with('\n') {
+        updated.push('\n');
+    }
+    if !existing.contains(&GITIGNORE_HEADER) {
+        if !updated.is_empty() {
+            updated.push('\n');
+        }
+        updated.push_str(GITIGNORE_HEADER);
+        updated.push('\n');
+    }
+    for entry in &added {
+        updated.push_str(entry);
+        updated.push('\n');
+    }
+    std::fs::write(&path, updated).with_context(|| format!("Failed to write {path:?}"))?;
+    Ok(added)
+}
+
+/// Remove the entries of `files` and the `GITIGNORE_HEADER` from the project's `.gitignore`,
+/// deleting it if nothing else is left. Returns true if the file was changed.
+pub fn remove_gitignore_entries(
+    godot_project_path: &Path,
+    files: &[GeneratedFile],
+) -> Result<bool> {
+    let path = godot_project_path.join(".gitignore");
+    let Ok(contents) = std::fs::read_to_string(&path) else {
+        return Ok(false);
+    };
+    let entries: Vec<String> = files.iter().map(GeneratedFile::gitignore_entry).collect();
+    let kept: Vec<&str> = contents
+        .lines()
+        .filter(|line| {
+            let line = line.trim();
+            line != GITIGNORE_HEADER && !entries.iter().any(|entry| entry == line)
+        })
+        .collect();
+    if kept.len() == contents.lines().count() {
+        return Ok(false);
+    }
+    if kept.iter().all(|line| line.trim().is_empty()) {
+        std::fs::remove_file(&path).with_context(|| format!("Failed to remove {path:?}"))?;
+    } else {
+        let updated = kept.join("\n").trim_end().to_string() + "\n";
+        std::fs::write(&path, updated).with_context(|| format!("Failed to write {path:?}"))?;
+    }
+    Ok(true)
+}
+
+/// The files changed by `GodotRunner::clean`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CleanReport {
+    /// Removed files and directories.
+    pub removed: Vec<PathBuf>,
+    /// Files restored from a backup left behind by an interrupted run.
+    pub restored: Vec<PathBuf>,
+    /// Files edited to remove generated entries, e.g. `.gitignore`.
+    pub edited: Vec<PathBuf>,
+}
+
+impl CleanReport {
+    /// Returns true if nothing was changed.
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.restored.is_empty() && self.edited.is_empty()
+    }
+
+    /// Remove the file or directory at `path` if it exists, without following symbolic links.
+    pub(crate) fn remove(&mut self, path: &Path) -> Result<()> {
+        let result = match std::fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(path),
+            Ok(_) => std::fs::remove_file(path),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => Err(e),
+        };
+        result.with_context(|| format!("Failed to remove {path:?}"))?;
+        self.removed.push(path.to_path_buf());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, gitignore: bool) -> GeneratedFile {
+        GeneratedFile {
+            path: path.to_string(),
+            description: "",
+            temporary: false,
+            gitignore,
+        }
+    }
+
+    #[test]
+    fn test_update_gitignore() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = [
+            file("rust.gdextension", true),
+            file("bin/", true),
+            file("override.cfg", false),
+        ];
+        assert_eq!(
+            update_gitignore(dir.path(), &files).unwrap(),
+            vec!["/rust.gdextension", "/bin/"]
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join(".gitignore")).unwrap(),
+            "# Generated by cargo-godot-lib\n/rust.gdextension\n/bin/\n"
+        );
+        assert!(update_gitignore(dir.path(), &files).unwrap().is_empty());
+
+        std::fs::write(dir.path().join(".gitignore"), ".godot/").unwrap();
+        update_gitignore(dir.path(), &files[..1]).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join(".gitignore")).unwrap(),
+            ".godot/\n\n# Generated by cargo-godot-lib\n/rust.gdextension\n"
+        );
+
+        assert!(remove_gitignore_entries(dir.path(), &files).unwrap());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join(".gitignore")).unwrap(),
+            ".godot/\n"
+        );
+        std::fs::write(
+            dir.path().join(".gitignore"),
+            "# Generated by cargo-godot-lib\n/bin/\n",
+        )
+        .unwrap();
+        assert!(remove_gitignore_entries(dir.path(), &files).unwrap());
+        assert!(!dir.path().join(".gitignore").exists());
+        assert!(!remove_gitignore_entries(dir.path(), &files).unwrap());
+    }
+
+    #[test]
+    fn test_clean_report_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("bin")).unwrap();
+        std::fs::write(dir.path().join("bin/libgame.so"), "").unwrap();
+        std::fs::write(dir.path().join("rust.gdextension"), "").unwrap();
+
+        let mut report = CleanReport::default();
+        report.remove(&dir.path().join("rust.gdextension")).unwrap();
+        report.remove(&dir.path().join("bin")).unwrap();
+        report.remove(&dir.path().join("missing")).unwrap();
+        assert_eq!(
+            report.removed,
+            vec![dir.path().join("rust.gdextension"), dir.path().join("bin")]
+        );
+        assert!(!dir.path().join("bin").exists());
+    }
+}
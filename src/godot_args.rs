@@ -0,0 +1,132 @@
+//! Checking Godot CLI arguments before launch, for the typed argument builders of
+//! `GodotRunner` such as `arg_resolution` and arguments set with `godot_cli_arguments`.
+//!
+//! Godot silently lets the last of conflicting window modes win and ignores malformed values,
+//! so mistakes like `--fullscreen` together with `--windowed` are reported as errors instead.
+//! Arguments after `--` or `++` are passed to the game and not checked.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! godot_args::validate(&["--fullscreen".to_string(), "--resolution".to_string(), "1280x720".to_string()])?;
+//! ```
+use anyhow::{Result, anyhow};
+
+/// Groups of flags of which at most one may be given, as long and short forms.
+const EXCLUSIVE_FLAGS: [&[(&str, &str)]; 2] = [
+    &[
+        ("--fullscreen", "-f"),
+        ("--maximized", "-m"),
+        ("--windowed", "-w"),
+    ],
+    &[("--verbose", "-v"), ("--quiet", "-q")],
+];
+
+/// Options with a value that may only be given once, and the expected format of the value.
+const VALUE_OPTIONS: [(&str, &str); 3] = [
+    ("--resolution", "<width>x<height>"),
+    ("--position", "<x>,<y>"),
+    ("--quit-after", "<frames>"),
+];
+
+/// The `--resolution` argument and value.
+pub fn resolution(width: u32, height: u32) -> [String; 2] {
+    ["--resolution".to_string(), format!("{width}x{height}")]
+}
+
+/// The `--position` argument and value.
+pub fn position(x: i32, y: i32) -> [String; 2] {
+    ["--position".to_string(), format!("{x},{y}")]
+}
+
+/// The `--quit-after` argument and value.
+pub fn quit_after(frames: u32) -> [String; 2] {
+    ["--quit-after".to_string(), frames.to_string()]
+}
+
+/// Check that no mutually exclusive flags are combined, and that the options of `VALUE_OPTIONS`
+/// are given at most once with a well-formed value.
+pub fn validate(args: &[String]) -> Result<()> {
+    let godot_args: Vec<&str> = args
+        .iter()
+        .map(String::as_str)
+        .take_while(|arg| *arg != "--" && *arg != "++")
+        .collect();
+    for group in EXCLUSIVE_FLAGS {
+        let given: Vec<&str> = group
+            .iter()
+            .filter(|(long, short)| godot_args.contains(long) || godot_args.contains(short))
+            .map(|(long, _)| *long)
+            .collect();
+        if given.len() > 1 {
+            return Err(anyhow!(
+                "The Godot arguments {given:?} are mutually exclusive"
+            ));
+        }
+    }
+    for (option, format) in VALUE_OPTIONS {
+        let positions: Vec<usize> = godot_args
+            .iter()
+            .enumerate()
+            .filter(|(_, arg)| **arg == option)
+            .map(|(index, _)| index)
+            .collect();
+        if positions.len() > 1 {
+            return Err(anyhow!(
+                "The Godot argument {option} is given more than once"
+            ));
+        }
+        if let Some(&index) = positions.first() {
+            let value = godot_args.get(index + 1).copied().unwrap_or_default();
+            if !valid_value(option, value) {
+                return Err(anyhow!(
+                    "Invalid value {value:?} of the Godot argument {option}, expected {format}"
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether `value` has the format `VALUE_OPTIONS` expects for `option`.
+fn valid_value(option: &str, value: &str) -> bool {
+    match option {
+        "--resolution" => value
+            .split_once('x')
+            .and_then(|(width, height)| {
+                Some((width.parse::<u32>().ok()?, height.parse::<u32>().ok()?))
+            })
+            .is_some_and(|(width, height)| width > 0 && height > 0),
+        "--position" => value
+            .split_once(',')
+            .is_some_and(|(x, y)| x.parse::<i32>().is_ok() && y.parse::<i32>().is_ok()),
+        _ => value.parse::<u32>().is_ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let args =
+            |args: &[&str]| -> Vec<String> { args.iter().map(|arg| arg.to_string()).collect() };
+        let mut valid = args(&["--fullscreen", "--verbose"]);
+        valid.extend(resolution(1280, 720));
+        valid.extend(position(-10, 20));
+        valid.extend(quit_after(60));
+        validate(&valid).unwrap();
+        validate(&args(&["-f", "--", "--windowed", "--quit-after", "x"])).unwrap();
+
+        let error = validate(&args(&["--windowed", "-f", "--fullscreen"])).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"The Godot arguments ["--fullscreen", "--windowed"] are mutually exclusive"#
+        );
+        assert!(validate(&args(&["--verbose", "--quiet"])).is_err());
+        assert!(validate(&args(&["--quit-after", "1", "--quit-after", "2"])).is_err());
+        assert!(validate(&args(&["--resolution", "0x720"])).is_err());
+        assert!(validate(&args(&["--position", "10"])).is_err());
+        assert!(validate(&args(&["--quit-after"])).is_err());
+    }
+}
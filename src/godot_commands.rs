@@ -1,21 +1,229 @@
+use crate::autoload::GENERATED_DIR;
+use crate::exit_status::GodotExitStatus;
+use crate::output::{self, GodotError, GodotErrorKind, OutputCallback};
+use crate::paths;
+use crate::verbosity::Verbosity;
 use anyhow::{Context, Result, anyhow};
+use std::any::Any;
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use which::{which, which_in_global};
 
-pub fn run_godot_import_if_needed(
+/// Options for `run_godot_import_with_options`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ImportOptions {
+    pub(crate) force: bool,
+    timeout: Option<Duration>,
+    retry: ImportRetry,
+    verbosity: Verbosity,
+}
+
+/// How `run_godot_import_with_options` handles a failed import, working around the known
+/// Godot 4.5.1 headless import crash (https://github.com/godotengine/godot/issues/111645).
+///
+/// Example usage:
+/// ```rust,ignore
+/// let options = ImportOptions::default().retry(ImportRetry::default().attempts(2).verify_godot_dir(true));
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ImportRetry {
+    attempts: u32,
+    verify_godot_dir: bool,
+}
+
+impl ImportRetry {
+    /// Retry a failed import up to `attempts` times. Default: 0.
+    pub fn attempts(self, attempts: u32) -> Self {
+        Self { attempts, ..self }
+    }
+
+    /// Treat a failed import as successful if it created the `.godot` folder anyway,
+    /// which is what the crash usually leaves behind. Default: false.
+    pub fn verify_godot_dir(self, verify_godot_dir: bool) -> Self {
+        Self {
+            verify_godot_dir,
+            ..self
+        }
+    }
+}
+
+impl ImportOptions {
+    /// Re-import even if the `.godot` folder already exists. Default: false.
+    pub fn force(self, force: bool) -> Self {
+        Self { force, ..self }
+    }
+
+    /// Kill the import process if it takes longer than `timeout`. Default: no timeout.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Retry a failed import once, working around the known Godot 4.5.1 headless import crash
+    /// (https://github.com/godotengine/godot/issues/111645). Default: false.
+    pub fn retry_once(self, retry_once: bool) -> Self {
+        self.retry(ImportRetry::default().attempts(retry_once as u32))
+    }
+
+    /// Retry failed imports according to `retry`. Default: no retries.
+    pub fn retry(self, retry: ImportRetry) -> Self {
+        Self { retry, ..self }
+    }
+
+    /// How much the import prints about failures and retries. `GodotRunner` uses its own
+    /// `verbosity`. Default: `Verbosity::Normal`.
+    pub fn verbosity(self, verbosity: Verbosity) -> Self {
+        Self { verbosity, ..self }
+    }
+}
+
+pub fn run_godot_import_if_needed<'a>(
     godot_project_path: &Path,
-    godot_version: Option<&str>,
-) -> Result<()> {
-    if !godot_project_path.join(".godot").exists() {
-        run_godot_import(godot_project_path, godot_version)
-    } else {
-        Ok(())
+    godot_binary: impl Into<GodotBinary<'a>>,
+) -> Result<GodotExitStatus> {
+    run_godot_import_with_options(godot_project_path, godot_binary, &ImportOptions::default())
+}
+
+pub fn run_godot_import<'a>(
+    godot_project_path: &Path,
+    godot_binary: impl Into<GodotBinary<'a>>,
+) -> Result<GodotExitStatus> {
+    run_godot_import_once(
+        godot_project_path,
+        godot_binary.into(),
+        None,
+        Verbosity::default(),
+    )
+}
+
+/// Run `godot --import --headless` according to `options`.
+/// Without `force`, nothing is done if the `.godot` folder already exists.
+/// Returns `GodotExitStatus::ImportFailed` if the import process exited unsuccessfully.
+pub fn run_godot_import_with_options<'a>(
+    godot_project_path: &Path,
+    godot_binary: impl Into<GodotBinary<'a>>,
+    options: &ImportOptions,
+) -> Result<GodotExitStatus> {
+    let godot_binary = godot_binary.into();
+    if !options.force && godot_project_path.join(".godot").exists() {
+        return Ok(GodotExitStatus::Success);
+    }
+
+    let godot_dir = godot_project_path.join(".godot");
+    let existed = godot_dir.exists();
+    retry_import(&options.retry, options.verbosity, || {
+        let status = run_godot_import_once(
+            godot_project_path,
+            godot_binary,
+            options.timeout,
+            options.verbosity,
+        );
+        (status, !existed && godot_dir.exists())
+    })
+}
+
+/// Run `import` according to `retry`, printing retries according to `verbosity`. `import`
+/// returns the import status and whether it created the `.godot` folder.
+fn retry_import(
+    retry: &ImportRetry,
+    verbosity: Verbosity,
+    mut import: impl FnMut() -> (Result<GodotExitStatus>, bool),
+) -> Result<GodotExitStatus> {
+    let mut attempt = 0;
+    loop {
+        let (result, created_godot_dir) = import();
+        match result {
+            Ok(GodotExitStatus::Success) => return result,
+            _ if retry.verify_godot_dir && created_godot_dir => {
+                verbosity.log(
+                    Verbosity::Normal,
+                    "Godot import failed, but the `.godot` folder was created. Treating the import as successful.",
+                );
+                return Ok(GodotExitStatus::Success);
+            }
+            _ if attempt < retry.attempts => {
+                attempt += 1;
+                verbosity.log(
+                    Verbosity::Normal,
+                    format!(
+                        "Godot import failed, retrying ({attempt} of {}).",
+                        retry.attempts
+                    ),
+                );
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Import several godot projects concurrently like `run_godot_import_with_options`, running at
+/// most `parallelism` imports at a time. The output of concurrent imports is interleaved.
+/// Returns the import status of every project in order, or an error listing all projects which
+/// failed to import.
+pub fn run_godot_import_many<'a>(
+    godot_project_paths: &[PathBuf],
+    godot_binary: impl Into<GodotBinary<'a>>,
+    options: &ImportOptions,
+    parallelism: usize,
+) -> Result<Vec<(PathBuf, GodotExitStatus)>> {
+    let godot_binary = godot_binary.into();
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(vec![]);
+    std::thread::scope(|scope| {
+        for _ in 0..parallelism.clamp(1, godot_project_paths.len().max(1)) {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(path) = godot_project_paths.get(index) else {
+                        break;
+                    };
+                    let result = run_godot_import_with_options(path, godot_binary, options);
+                    results.lock().unwrap_or_else(|e| e.into_inner()).push((
+                        index,
+                        path.clone(),
+                        result,
+                    ));
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap_or_else(|e| e.into_inner());
+    results.sort_by_key(|(index, _, _)| *index);
+    let mut statuses = vec![];
+    let mut failures = vec![];
+    for (_, path, result) in results {
+        match result {
+            Ok(GodotExitStatus::Success) => statuses.push((path, GodotExitStatus::Success)),
+            Ok(status) => failures.push(format!("{path:?}: {status}")),
+            Err(e) => failures.push(format!("{path:?}: {e:#}")),
+        }
     }
+    if !failures.is_empty() {
+        return Err(anyhow!(
+            "Failed to import {} of {} Godot projects:\n{}",
+            failures.len(),
+            godot_project_paths.len(),
+            failures.join("\n")
+        ));
+    }
+    Ok(statuses)
 }
 
-pub fn run_godot_import(godot_project_path: &Path, godot_version: Option<&str>) -> Result<()> {
-    let mut command = godot_command(godot_version)?;
+fn run_godot_import_once(
+    godot_project_path: &Path,
+    godot_binary: GodotBinary,
+    timeout: Option<Duration>,
+    verbosity: Verbosity,
+) -> Result<GodotExitStatus> {
+    let mut command = godot_command(godot_binary)?;
 
     command
         .stdin(Stdio::inherit())
@@ -24,76 +232,973 @@ pub fn run_godot_import(godot_project_path: &Path, godot_version: Option<&str>)
         .current_dir(godot_project_path)
         .arg("--import")
         .arg("--headless");
-    let status = command
+    let mut child = command
         .spawn()
-        .with_context(|| format!("Failed to spawn Godot import process: {:?}", command))?
-        .wait()
+        .with_context(|| format!("Failed to spawn Godot import process: {:?}", command))?;
+    let status = wait_with_timeout(&mut child, timeout)
         .with_context(|| format!("Failed to wait for Godot import process: {:?}", command))?;
 
     if !status.success() {
-        Err(anyhow!(
-            "Godot import process failed with exit code `{}`.\n\
-            Possible cause: Known bug in Godot 4.5.1: \"Headless import of project with GDExtensions crashes\"\n\
-            See: https://github.com/godotengine/godot/issues/111645\n\
-            Try re-running if `.godot` folder was generated successfully.",
-            status
-                .code()
-                .map(|e| e.to_string())
-                .unwrap_or("unknown".to_string())
-        ))
+        verbosity.log(
+            Verbosity::Normal,
+            format!(
+                "Godot import process failed with exit code `{}`.",
+                status
+                    .code()
+                    .map(|e| e.to_string())
+                    .unwrap_or("unknown".to_string())
+            ),
+        );
+        Ok(GodotExitStatus::ImportFailed)
     } else {
-        Ok(())
+        Ok(GodotExitStatus::Success)
+    }
+}
+
+/// Which part of the `.godot` cache folder `clean_godot_cache` removes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CleanLevel {
+    /// `.godot/imported`: Imported assets, fixes most corrupted import caches.
+    Imported,
+    /// `.godot/editor`: Editor state such as open scenes and filesystem cache.
+    Editor,
+    /// The whole `.godot` folder.
+    All,
+}
+
+/// Remove the `.godot` cache folder, or part of it, of a godot project.
+/// Refuses to remove anything if `godot_project_path` doesn't contain a `project.godot` file,
+/// and never follows symbolic links.
+pub fn clean_godot_cache(godot_project_path: &Path, level: CleanLevel) -> Result<()> {
+    if !godot_project_path.join("project.godot").is_file() {
+        return Err(anyhow!(
+            "Refusing to clean Godot cache: {:?} does not contain a `project.godot` file",
+            godot_project_path
+        ));
+    }
+
+    let godot_dir = godot_project_path.join(".godot");
+    let path = match level {
+        CleanLevel::Imported => godot_dir.join("imported"),
+        CleanLevel::Editor => godot_dir.join("editor"),
+        CleanLevel::All => godot_dir,
+    };
+
+    match std::fs::symlink_metadata(&path) {
+        Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(&path)
+            .with_context(|| format!("Failed to remove Godot cache: {path:?}")),
+        Ok(_) => Err(anyhow!(
+            "Refusing to clean Godot cache: {path:?} is not a directory"
+        )),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read Godot cache: {path:?}")),
     }
 }
 
-pub fn run_godot(
+/// Wait for `child` to exit, killing it if `timeout` elapses first.
+fn wait_with_timeout(child: &mut Child, timeout: Option<Duration>) -> Result<ExitStatus> {
+    let Some(timeout) = timeout else {
+        return Ok(child.wait()?);
+    };
+
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if start.elapsed() >= timeout {
+            child.kill()?;
+            child.wait()?;
+            return Err(anyhow!("Timed out after {timeout:?}"));
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+pub fn run_godot<'a>(
     godot_project_path: &Path,
-    godot_version: Option<&str>,
+    godot_binary: impl Into<GodotBinary<'a>>,
     args: &[String],
-) -> Result<()> {
-    let mut command = godot_command(godot_version)?;
+) -> Result<GodotExitStatus> {
+    spawn_godot(godot_project_path, godot_binary, args)?.wait()
+}
+
+/// Run the GDScript `source` with `godot --headless --script` and return what it printed,
+/// e.g. to query project state from Rust tools. Godot's version banner is not included.
+///
+/// A `source` without an `extends` line is the body of a `SceneTree` script's `_initialize`
+/// function, and Godot quits once it returns. A full script must call `quit()` itself.
+///
+/// Example usage:
+/// ```rust,ignore
+/// let autoloads = run_script(
+///     Path::new("godot"),
+///     None,
+///     "for child in root.get_children():\n\tprint(child.name)",
+/// )?;
+/// ```
+pub fn run_script<'a>(
+    godot_project_path: &Path,
+    godot_binary: impl Into<GodotBinary<'a>>,
+    source: &str,
+) -> Result<String> {
+    let script = GeneratedScript::write(godot_project_path, "run_script", &script_source(source))?;
+    let mut command = script.command(godot_binary, &[])?;
+    let output = command
+        .output()
+        .with_context(|| format!("Failed to run Godot script: {command:?}"))?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Godot script failed with status `{}`\nCommand: {:?}\n{}",
+            output.status,
+            command,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(strip_banner(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// The marker of lines reporting a scene problem in the output of the validation script.
+const SCENE_PROBLEM_MARKER: &str = "CARGO_GODOT_SCENE_PROBLEM";
+
+/// A scene which failed validation, see `validate_scenes`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SceneProblem {
+    /// The `res://` path of the scene.
+    pub scene: String,
+    /// What is wrong, e.g. `missing dependency res://player.png`.
+    pub problem: String,
+}
+
+impl std::fmt::Display for SceneProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.scene, self.problem)
+    }
+}
+
+/// Load every `*.tscn` and `*.scn` scene of the godot project headlessly and report the scenes
+/// which fail to load, can't be instantiated, or have missing dependencies. Hidden folders such
+/// as `.godot` are skipped. Returns an empty list if all scenes are fine.
+///
+/// Example usage:
+/// ```rust,ignore
+/// let problems = validate_scenes(Path::new("godot"), None)?;
+/// for problem in &problems {
+///     eprintln!("{problem}");
+/// }
+/// ```
+pub fn validate_scenes<'a>(
+    godot_project_path: &Path,
+    godot_binary: impl Into<GodotBinary<'a>>,
+) -> Result<Vec<SceneProblem>> {
+    let mut scenes = vec![];
+    find_project_files(godot_project_path, &["tscn", "scn"], &mut scenes)?;
+    scenes.sort();
+    if scenes.is_empty() {
+        return Ok(vec![]);
+    }
+    let res_paths = scenes
+        .iter()
+        .map(|scene| paths::to_res_path(godot_project_path, scene))
+        .collect::<Result<Vec<_>>>()?;
+    let output = run_script(
+        godot_project_path,
+        godot_binary,
+        &validation_script(&res_paths)?,
+    )?;
+    Ok(parse_scene_problems(&output))
+}
 
+/// Find the files with one of the `extensions` in `dir`, skipping hidden folders.
+fn find_project_files(dir: &Path, extensions: &[&str], files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries =
+        std::fs::read_dir(dir).with_context(|| format!("Failed to read directory: {dir:?}"))?;
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            find_project_files(&path, extensions, files)?;
+        } else if path
+            .extension()
+            .is_some_and(|ext| extensions.iter().any(|extension| ext == *extension))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// An error found by `check_scripts`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScriptDiagnostic {
+    /// The `res://` path of the checked script.
+    pub script: String,
+    /// The error reported by Godot, e.g. a parse error with its line in `location`.
+    pub error: GodotError,
+}
+
+impl std::fmt::Display for ScriptDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.script, self.error)
+    }
+}
+
+/// Check every GDScript file of the godot project with `godot --headless --check-only --script`,
+/// one Godot process per script. Hidden folders such as `.godot` are skipped.
+/// Returns the errors found, so CI can fail if there are any.
+///
+/// Example usage:
+/// ```rust,ignore
+/// let diagnostics = check_scripts(Path::new("godot"), None)?;
+/// for diagnostic in &diagnostics {
+///     eprintln!("{diagnostic}");
+/// }
+/// std::process::exit(if diagnostics.is_empty() { 0 } else { 1 });
+/// ```
+pub fn check_scripts<'a>(
+    godot_project_path: &Path,
+    godot_binary: impl Into<GodotBinary<'a>>,
+) -> Result<Vec<ScriptDiagnostic>> {
+    let godot_binary = godot_binary.into();
+    let mut scripts = vec![];
+    find_project_files(godot_project_path, &["gd"], &mut scripts)?;
+    scripts.sort();
+    let mut diagnostics = vec![];
+    for script in scripts {
+        let res_path = paths::to_res_path(godot_project_path, &script)?;
+        let mut command = godot_command(godot_binary)?;
+        command
+            .stdin(Stdio::null())
+            .current_dir(godot_project_path)
+            .args(["--headless", "--check-only", "--script", &res_path]);
+        let output = command
+            .output()
+            .with_context(|| format!("Failed to run Godot script check: {command:?}"))?;
+        let errors = check_output_errors(&output, GodotErrorKind::ScriptError);
+        diagnostics.extend(errors.into_iter().map(|error| ScriptDiagnostic {
+            script: res_path.clone(),
+            error,
+        }));
+    }
+    Ok(diagnostics)
+}
+
+/// The script loading the shaders passed as user arguments, which makes Godot compile them.
+const SHADER_VALIDATION_SCRIPT: &str = "for path in OS.get_cmdline_user_args():\n\
+    \tvar shader = ResourceLoader.load(path)\n\
+    \tif shader is Shader:\n\
+    \t\tshader.get_shader_uniform_list()";
+
+/// An error found by `validate_shaders`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ShaderDiagnostic {
+    /// The `res://` path of the shader.
+    pub shader: String,
+    /// The error reported by Godot, e.g. a compile error with its line in `location`.
+    pub error: GodotError,
+}
+
+impl std::fmt::Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.shader, self.error)
+    }
+}
+
+/// Load every `*.gdshader` file of the godot project headlessly, one Godot process per shader,
+/// to find compile errors before a scene using the shader is opened. Hidden folders such as
+/// `.godot` are skipped, and `*.gdshaderinc` files are checked through the shaders including
+/// them. Returns the errors found, so CI can fail if there are any.
+///
+/// Example usage:
+/// ```rust,ignore
+/// for diagnostic in validate_shaders(Path::new("godot"), None)? {
+///     eprintln!("{diagnostic}");
+/// }
+/// ```
+pub fn validate_shaders<'a>(
+    godot_project_path: &Path,
+    godot_binary: impl Into<GodotBinary<'a>>,
+) -> Result<Vec<ShaderDiagnostic>> {
+    let godot_binary = godot_binary.into();
+    let mut shaders = vec![];
+    find_project_files(godot_project_path, &["gdshader"], &mut shaders)?;
+    shaders.sort();
+    if shaders.is_empty() {
+        return Ok(vec![]);
+    }
+    let script = GeneratedScript::write(
+        godot_project_path,
+        "validate_shaders",
+        &script_source(SHADER_VALIDATION_SCRIPT),
+    )?;
+    let diagnostics = shaders
+        .iter()
+        .map(|shader| {
+            let res_path = paths::to_res_path(godot_project_path, shader)?;
+            let mut command = script.command(godot_binary, &[])?;
+            command.args(["--", &res_path]);
+            let output = command
+                .output()
+                .with_context(|| format!("Failed to run Godot shader validation: {command:?}"))?;
+            let errors = check_output_errors(&output, GodotErrorKind::ShaderError);
+            Ok(errors.into_iter().map(move |error| ShaderDiagnostic {
+                shader: res_path.clone(),
+                error,
+            }))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(diagnostics.into_iter().flatten().collect())
+}
+
+/// The editor script reimporting the files passed as user arguments.
+const REIMPORT_SCRIPT: &str = r#"extends SceneTree
+
+# Injected by cargo-godot-lib to reimport the files passed after `--`.
+
+func _initialize() -> void:
+	_reimport.call_deferred()
+
+
+func _reimport() -> void:
+	# Wait for the editor to start and finish its first scan.
+	await process_frame
+	var filesystem := EditorInterface.get_resource_filesystem()
+	while filesystem.is_scanning():
+		await process_frame
+	var files := PackedStringArray(OS.get_cmdline_user_args())
+	filesystem.reimport_files(files)
+	print("Reimported %d files" % files.size())
+	quit()
+"#;
+
+/// Reimport only the `files` of the godot project, e.g. textures or audio changed by an asset
+/// pipeline, with `godot --headless --editor --script`. This is much faster than a full
+/// `--import` on large projects, but requires a project which has been imported before.
+/// `files` are paths relative to the project, absolute paths or `res://` paths.
+///
+/// Example usage:
+/// ```rust,ignore
+/// reimport_files(Path::new("godot"), None, &["sprites/hero.png", "audio/jump.wav"], Verbosity::Normal)?
+///     .into_result()?;
+/// ```
+pub fn reimport_files<'a>(
+    godot_project_path: &Path,
+    godot_binary: impl Into<GodotBinary<'a>>,
+    files: &[impl AsRef<Path>],
+    verbosity: Verbosity,
+) -> Result<GodotExitStatus> {
+    if files.is_empty() {
+        return Ok(GodotExitStatus::Success);
+    }
+    if !godot_project_path.join(".godot").exists() {
+        return Err(anyhow!(
+            "{godot_project_path:?} has not been imported yet, run a full import first"
+        ));
+    }
+    let res_paths = reimport_res_paths(godot_project_path, files)?;
+    let script = GeneratedScript::write(godot_project_path, "reimport", REIMPORT_SCRIPT)?;
+    let mut command = script.command(godot_binary, &["--editor"])?;
+    command.arg("--").args(&res_paths);
     let status = command
+        .status()
+        .with_context(|| format!("Failed to run Godot reimport: {command:?}"))?;
+    if status.success() {
+        Ok(GodotExitStatus::Success)
+    } else {
+        verbosity.log(
+            Verbosity::Normal,
+            format!("Godot reimport failed with status `{status}`."),
+        );
+        Ok(GodotExitStatus::ImportFailed)
+    }
+}
+
+/// The `res://` paths of the existing `files` to reimport.
+fn reimport_res_paths(
+    godot_project_path: &Path,
+    files: &[impl AsRef<Path>],
+) -> Result<Vec<String>> {
+    files
+        .iter()
+        .map(|file| {
+            let file = file.as_ref();
+            let path = match file.to_str().and_then(|file| file.strip_prefix("res://")) {
+                Some(relative) => godot_project_path.join(relative),
+                None => godot_project_path.join(file),
+            };
+            if !path.is_file() {
+                return Err(anyhow!("Can't reimport {file:?}, the file does not exist"));
+            }
+            paths::to_res_path(godot_project_path, &path)
+        })
+        .collect()
+}
+
+/// The errors printed by a Godot check, or an error of `kind` if Godot failed without
+/// printing one.
+fn check_output_errors(output: &std::process::Output, kind: GodotErrorKind) -> Vec<GodotError> {
+    let mut errors = output::scan_output(&format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    ));
+    if errors.is_empty() && !output.status.success() {
+        errors.push(GodotError {
+            kind,
+            message: format!("Godot failed with status `{}`", output.status),
+            location: None,
+        });
+    }
+    errors
+}
+
+/// The body of the script validating the `scenes`, printing a marked line per problem.
+fn validation_script(scenes: &[String]) -> Result<String> {
+    // JSON string arrays are valid GDScript array literals.
+    let scenes = serde_json::to_string(scenes)?;
+    let report = |problem: &str, values: &str| {
+        format!("print(\"{SCENE_PROBLEM_MARKER}\\t%s\\t{problem}\" % [{values}])")
+    };
+    Ok([
+        format!("for path in {scenes}:"),
+        "\tfor dependency in ResourceLoader.get_dependencies(path):".to_string(),
+        "\t\tvar dependency_path = dependency.get_slice(\"::\", 0)".to_string(),
+        "\t\tfor part in dependency.split(\"::\"):".to_string(),
+        "\t\t\tif part.begins_with(\"res://\"):".to_string(),
+        "\t\t\t\tdependency_path = part".to_string(),
+        "\t\tif not ResourceLoader.exists(dependency_path):".to_string(),
+        format!(
+            "\t\t\t{}",
+            report("missing dependency %s", "path, dependency_path")
+        ),
+        "\tvar scene = ResourceLoader.load(path)".to_string(),
+        "\tif scene == null:".to_string(),
+        format!("\t\t{}", report("failed to load", "path")),
+        "\telif scene is PackedScene and not scene.can_instantiate():".to_string(),
+        format!("\t\t{}", report("can't be instantiated", "path")),
+    ]
+    .join("\n"))
+}
+
+fn parse_scene_problems(output: &str) -> Vec<SceneProblem> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line
+                .trim()
+                .strip_prefix(SCENE_PROBLEM_MARKER)?
+                .strip_prefix('\t')?
+                .splitn(2, '\t');
+            Some(SceneProblem {
+                scene: parts.next()?.to_string(),
+                problem: parts.next()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// A script written into the generated folder of a godot project, removed when dropped so
+/// errors and panics don't leave it in the project.
+struct GeneratedScript {
+    godot_project_path: PathBuf,
+    relative_path: String,
+}
+
+impl GeneratedScript {
+    /// Write `source` to `<GENERATED_DIR>/<name>_<pid>.gd` in the godot project.
+    fn write(godot_project_path: &Path, name: &str, source: &str) -> Result<Self> {
+        // Include the process id so concurrent runs don't overwrite each other's scripts.
+        let script = Self {
+            godot_project_path: godot_project_path.to_path_buf(),
+            relative_path: format!("{GENERATED_DIR}/{name}_{}.gd", std::process::id()),
+        };
+        let script_path = script.path();
+        if let Some(parent) = script_path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {parent:?}"))?;
+        }
+        std::fs::write(&script_path, source)
+            .with_context(|| format!("Failed to write script: {script_path:?}"))?;
+        Ok(script)
+    }
+
+    fn path(&self) -> PathBuf {
+        self.godot_project_path.join(&self.relative_path)
+    }
+
+    /// A headless Godot command running the script in the godot project, with `flags` before
+    /// `--script`, e.g. `--editor`.
+    fn command<'a>(
+        &self,
+        godot_binary: impl Into<GodotBinary<'a>>,
+        flags: &[&str],
+    ) -> Result<Command> {
+        let mut command = godot_command(godot_binary)?;
+        command
+            .stdin(Stdio::null())
+            .current_dir(&self.godot_project_path)
+            .arg("--headless")
+            .args(flags)
+            .arg("--script")
+            .arg(format!("res://{}", self.relative_path));
+        Ok(command)
+    }
+}
+
+impl Drop for GeneratedScript {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(self.path());
+    }
+}
+
+/// Wrap `source` in a `SceneTree` script unless it is a full script.
+fn script_source(source: &str) -> String {
+    if source.lines().any(|line| line.starts_with("extends ")) {
+        return source.to_string();
+    }
+    let body: String = source.lines().map(|line| format!("\t{line}\n")).collect();
+    format!("extends SceneTree\n\nfunc _initialize() -> void:\n{body}\tquit()\n")
+}
+
+/// Remove the `Godot Engine v4.5.1.stable.official - https://godotengine.org` line.
+fn strip_banner(stdout: &str) -> String {
+    match stdout.split_once('\n') {
+        Some((first, rest)) if first.starts_with("Godot Engine v") => {
+            rest.trim_start_matches(['\r', '\n']).to_string()
+        }
+        _ => stdout.to_string(),
+    }
+}
+
+/// Launch Godot in the background without waiting for it to exit.
+pub fn spawn_godot<'a>(
+    godot_project_path: &Path,
+    godot_binary: impl Into<GodotBinary<'a>>,
+    args: &[String],
+) -> Result<GodotProcess> {
+    spawn_godot_process(godot_project_path, godot_binary, &[], args, &[], None, &[])
+}
+
+/// Launch Godot in the background like `spawn_godot`, passing its output through to the console
+/// while scanning it for errors. See `GodotProcess::wait_with_errors`.
+pub fn spawn_godot_scanned<'a>(
+    godot_project_path: &Path,
+    godot_binary: impl Into<GodotBinary<'a>>,
+    args: &[String],
+) -> Result<GodotProcess> {
+    spawn_godot_watched(godot_project_path, godot_binary, args, Arc::new(|_| {}))
+}
+
+/// Launch Godot in the background like `spawn_godot_scanned`, calling `on_line` for every line
+/// of output as it is printed, e.g. to measure when a line appears.
+pub fn spawn_godot_watched<'a>(
+    godot_project_path: &Path,
+    godot_binary: impl Into<GodotBinary<'a>>,
+    args: &[String],
+    on_line: OutputCallback,
+) -> Result<GodotProcess> {
+    spawn_godot_process(
+        godot_project_path,
+        godot_binary,
+        &[],
+        args,
+        &[],
+        Some(on_line),
+        &[],
+    )
+}
+
+/// A function customizing the Godot `Command` before it is spawned.
+pub type CommandHook = Box<dyn Fn(&mut Command) + Send + Sync>;
+
+/// A function called with the spawned command and the exit status once a `GodotProcess` exited.
+pub(crate) type ExitHook = Box<dyn FnOnce(&Command, GodotExitStatus) -> Result<()> + Send>;
+
+/// Launch Godot prefixed with the `wrapper` program and with additional environment variables
+/// `envs`, watching its output with `on_line` if given. The `hooks` run last before spawning.
+pub(crate) fn spawn_godot_process<'a>(
+    godot_project_path: &Path,
+    godot_binary: impl Into<GodotBinary<'a>>,
+    wrapper: &[String],
+    args: &[String],
+    envs: &[(OsString, OsString)],
+    on_line: Option<OutputCallback>,
+    hooks: &[CommandHook],
+) -> Result<GodotProcess> {
+    let command = godot_process_command(
+        godot_project_path,
+        godot_binary,
+        wrapper,
+        args,
+        envs,
+        on_line.is_some(),
+        hooks,
+    )?;
+    spawn_command(command, on_line, false, true)
+}
+
+/// The command of `spawn_godot_process`, with piped output if `piped`.
+pub(crate) fn godot_process_command<'a>(
+    godot_project_path: &Path,
+    godot_binary: impl Into<GodotBinary<'a>>,
+    wrapper: &[String],
+    args: &[String],
+    envs: &[(OsString, OsString)],
+    piped: bool,
+    hooks: &[CommandHook],
+) -> Result<Command> {
+    let mut command = wrap_command(godot_command(godot_binary)?, wrapper);
+    let output = || {
+        if piped {
+            Stdio::piped()
+        } else {
+            Stdio::inherit()
+        }
+    };
+
+    command
         .stdin(Stdio::inherit())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
+        .stdout(output())
+        .stderr(output())
         .current_dir(godot_project_path)
-        .args(args)
-        .spawn()
-        .context("Failed to spawn Godot process")?
-        .wait()
-        .context("Failed to wait for Godot process")?;
+        .envs(envs.iter().map(|(key, value)| (key, value)))
+        .args(args);
+    for hook in hooks {
+        hook(&mut command);
+    }
+    Ok(command)
+}
 
-    if !status.success() {
-        let code = status.code().context("Godot process exited")?;
-        Err(anyhow!(
-            "Godot process exited with exit code {}\nCommand: {:?}",
-            code,
-            command
-        ))
-    } else {
+/// Spawn a command of `godot_process_command`, watching its output with `on_line` if given.
+/// The watched output is passed through without ANSI escape codes if `strip_ansi`, and scanned
+/// for errors if `scan`.
+pub(crate) fn spawn_command(
+    mut command: Command,
+    on_line: Option<OutputCallback>,
+    strip_ansi: bool,
+    scan: bool,
+) -> Result<GodotProcess> {
+    let mut child = command.spawn().context("Failed to spawn Godot process")?;
+
+    let mut output_scanners = vec![];
+    if let Some(on_line) = on_line {
+        if let Some(stdout) = child.stdout.take() {
+            output_scanners.push(output::tee(
+                stdout,
+                std::io::stdout(),
+                on_line.clone(),
+                strip_ansi,
+                scan,
+            ));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            output_scanners.push(output::tee(
+                stderr,
+                std::io::stderr(),
+                on_line,
+                strip_ansi,
+                scan,
+            ));
+        }
+    }
+
+    Ok(GodotProcess {
+        child,
+        command,
+        editor_lock: None,
+        output_scanners,
+        guards: vec![],
+        exit_hooks: vec![],
+    })
+}
+
+/// A running Godot process started by `spawn_godot`.
+pub struct GodotProcess {
+    child: Child,
+    command: Command,
+    editor_lock: Option<PathBuf>,
+    output_scanners: Vec<JoinHandle<Vec<GodotError>>>,
+    /// Values dropped once the process exits, e.g. an `OverrideGuard`.
+    guards: Vec<Box<dyn Any + Send>>,
+    exit_hooks: Vec<ExitHook>,
+}
+
+impl std::fmt::Debug for GodotProcess {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GodotProcess")
+            .field("child", &self.child)
+            .field("command", &self.command)
+            .field("editor_lock", &self.editor_lock)
+            .field("output_scanners", &self.output_scanners)
+            .field("guards", &self.guards)
+            .field("exit_hooks", &self.exit_hooks.len())
+            .finish()
+    }
+}
+
+impl GodotProcess {
+    /// The OS-assigned process identifier.
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// The command the process was spawned with, including the wrapper and hooks.
+    pub fn command(&self) -> &Command {
+        &self.command
+    }
+
+    /// Remove `editor_lock` once this process exits.
+    pub(crate) fn set_editor_lock(&mut self, editor_lock: PathBuf) {
+        self.editor_lock = Some(editor_lock);
+    }
+
+    /// Keep `guard` alive until this process exits.
+    pub(crate) fn hold(&mut self, guard: impl Any + Send) {
+        self.guards.push(Box::new(guard));
+    }
+
+    /// Call `hook` once this process exits, after the guards are dropped.
+    pub(crate) fn on_exit(&mut self, hook: ExitHook) {
+        self.exit_hooks.push(hook);
+    }
+
+    /// Run the exit hooks in order, stopping at the first error.
+    fn run_exit_hooks(&mut self, status: GodotExitStatus) -> Result<()> {
+        for hook in self.exit_hooks.drain(..) {
+            hook(&self.command, status)?;
+        }
         Ok(())
     }
+
+    /// Remove the editor lock and drop the guards once the process exited.
+    fn cleanup(&mut self) {
+        if let Some(editor_lock) = self.editor_lock.take() {
+            let _ = std::fs::remove_file(editor_lock);
+        }
+        self.guards.clear();
+    }
+
+    /// Collect the errors found by the output scanners once the output streams are closed.
+    fn join_output_scanners(&mut self) -> Vec<GodotError> {
+        self.output_scanners
+            .drain(..)
+            .flat_map(|scanner| scanner.join().unwrap_or_default())
+            .collect()
+    }
+
+    /// Wait for Godot to exit.
+    pub fn wait(self) -> Result<GodotExitStatus> {
+        self.wait_with_errors().map(|(status, _)| status)
+    }
+
+    /// Wait for Godot to exit, returning the errors found in its output.
+    /// Errors are only collected if the process was started by `spawn_godot_scanned`.
+    pub fn wait_with_errors(mut self) -> Result<(GodotExitStatus, Vec<GodotError>)> {
+        let status = self.child.wait();
+        self.cleanup();
+        let status = status
+            .with_context(|| format!("Failed to wait for Godot process: {:?}", self.command))?;
+        let errors = self.join_output_scanners();
+        let status = GodotExitStatus::from_exit_status(status);
+        self.run_exit_hooks(status)?;
+        Ok((status, errors))
+    }
+
+    /// The exit status if Godot exited, without waiting. `wait` is still needed to clean up.
+    pub fn try_wait(&mut self) -> Result<Option<GodotExitStatus>> {
+        let status = self
+            .child
+            .try_wait()
+            .with_context(|| format!("Failed to check Godot process: {:?}", self.command))?;
+        Ok(status.map(GodotExitStatus::from_exit_status))
+    }
+
+    /// Forcefully stop the Godot process.
+    pub fn kill(mut self) -> Result<()> {
+        self.child.kill().context("Failed to kill Godot process")?;
+        let status = self.child.wait();
+        self.cleanup();
+        let status = status.context("Failed to wait for Godot process")?;
+        self.join_output_scanners();
+        self.run_exit_hooks(GodotExitStatus::from_exit_status(status))
+    }
 }
 
-/// Returns a Command for running godot with the specified version (using `gdenv run <version>`),
-/// or the default godot binary if no version is provided.
-fn godot_command(godot_version: Option<&str>) -> Result<Command> {
-    Ok(if let Some(version) = godot_version {
-        let mut cmd = Command::new("gdenv");
-        cmd.arg("run").arg(version);
-        cmd
-    } else {
-        Command::new(godot_binary_path()?)
+/// Run `godot --version` and parse the result.
+pub fn detect_godot_version<'a>(godot_binary: impl Into<GodotBinary<'a>>) -> Result<GodotVersion> {
+    run_version_check(godot_command(godot_binary)?)
+}
+
+/// Run `command`, a Godot command, with `--version` and parse the result.
+pub(crate) fn run_version_check(mut command: Command) -> Result<GodotVersion> {
+    command.arg("--version").stdin(Stdio::null());
+    let output = command
+        .output()
+        .with_context(|| format!("Failed to run Godot version check: {:?}", command))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Godot version check failed with status `{}`\nCommand: {:?}",
+            output.status,
+            command
+        ));
+    }
+
+    // Godot may print warnings before the version, so use the last line.
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = stdout.lines().last().unwrap_or_default();
+    version
+        .parse()
+        .with_context(|| format!("Failed to parse Godot version: {version:?}"))
+}
+
+/// A Godot engine version as reported by `godot --version`,
+/// e.g. `4.5.1.stable.official.f62fdbde1` or `4.5.stable.mono.official.876b29033`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GodotVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    /// Release status such as `stable`, `rc1`, `beta2` or `dev`.
+    pub status: String,
+    /// Whether this is a .NET (mono) build.
+    pub mono: bool,
+    raw: String,
+}
+
+impl GodotVersion {
+    /// The `major.minor` version, e.g. `4.5`.
+    pub fn compatibility(&self) -> String {
+        format!("{}.{}", self.major, self.minor)
+    }
+
+    /// The version number as Godot formats it, omitting a zero patch version, e.g. `4.5` or `4.5.1`.
+    pub fn number(&self) -> String {
+        if self.patch == 0 {
+            self.compatibility()
+        } else {
+            format!("{}.{}.{}", self.major, self.minor, self.patch)
+        }
+    }
+
+    /// The name of the export templates directory for this version, e.g. `4.5.1.stable`.
+    pub fn template_dir_name(&self) -> String {
+        let mono = if self.mono { ".mono" } else { "" };
+        format!("{}.{}{}", self.number(), self.status, mono)
+    }
+
+    /// The GitHub release tag for this version, e.g. `4.5.1-stable`.
+    pub fn release_tag(&self) -> String {
+        format!("{}-{}", self.number(), self.status)
+    }
+}
+
+impl std::fmt::Display for GodotVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+impl std::str::FromStr for GodotVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let raw = s.trim();
+        let mut parts = raw.split('.').peekable();
+        let mut numbers = vec![];
+        while let Some(number) = parts.peek().and_then(|part| part.parse::<u32>().ok()) {
+            numbers.push(number);
+            parts.next();
+        }
+        let status = parts.next().context("Missing release status")?.to_string();
+        let mono = parts.next() == Some("mono");
+
+        match numbers[..] {
+            [major, minor] => Ok(Self {
+                major,
+                minor,
+                patch: 0,
+                status,
+                mono,
+                raw: raw.to_string(),
+            }),
+            [major, minor, patch] => Ok(Self {
+                major,
+                minor,
+                patch,
+                status,
+                mono,
+                raw: raw.to_string(),
+            }),
+            _ => Err(anyhow!("Expected `major.minor[.patch]` version numbers")),
+        }
+    }
+}
+
+/// The Godot binary a command runs.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum GodotBinary<'a> {
+    /// The binary found by `godot_binary_path`.
+    #[default]
+    Discover,
+    /// A version run with `gdenv run <version>`.
+    Version(&'a str),
+    /// The binary at a path, e.g. located by the `GodotLocator`.
+    Path(&'a Path),
+}
+
+/// An optional `godot_version`: `None` discovers the binary, and a version is run with `gdenv`.
+impl<'a> From<Option<&'a str>> for GodotBinary<'a> {
+    fn from(godot_version: Option<&'a str>) -> Self {
+        match godot_version {
+            Some(version) => Self::Version(version),
+            None => Self::Discover,
+        }
+    }
+}
+
+impl<'a> From<&'a Path> for GodotBinary<'a> {
+    fn from(path: &'a Path) -> Self {
+        Self::Path(path)
+    }
+}
+
+/// Returns a Command for running the `godot_binary`.
+pub(crate) fn godot_command<'a>(godot_binary: impl Into<GodotBinary<'a>>) -> Result<Command> {
+    Ok(match godot_binary.into() {
+        GodotBinary::Discover => Command::new(godot_binary_path()?),
+        GodotBinary::Version(version) => {
+            let mut cmd = Command::new("gdenv");
+            cmd.arg("run").arg(version);
+            cmd
+        }
+        GodotBinary::Path(path) => Command::new(path),
     })
 }
 
+/// Prefix `command` with the `wrapper` program and its arguments, e.g. `gdb --args`.
+/// An empty `wrapper` returns `command` unchanged.
+fn wrap_command(command: Command, wrapper: &[String]) -> Command {
+    let Some((program, wrapper_args)) = wrapper.split_first() else {
+        return command;
+    };
+    let mut wrapped = Command::new(program);
+    wrapped
+        .args(wrapper_args)
+        .arg(command.get_program())
+        .args(command.get_args());
+    wrapped
+}
+
 /// Looks for a godot executable in the following places:
 /// - `godot` environment variable.
 /// - `GODOT` environment variable.
 /// - `godot` executable in the PATH.
 /// - `godot` executable in the following common paths for linux and osx: `/usr/local/bin:/usr/bin:/bin:/Applications/Godot.app/Contents/MacOS`.
-fn godot_binary_path() -> Result<PathBuf> {
+pub(crate) fn godot_binary_path() -> Result<PathBuf> {
     if let Ok(godot_binary_path) = std::env::var("godot") {
         return Ok(PathBuf::from(godot_binary_path));
     }
@@ -130,3 +1235,382 @@ fn godot_binary_path() -> Result<PathBuf> {
         godot_search_paths = godot_search_paths
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_godot_version() {
+        let version: GodotVersion = "4.5.1.stable.official.f62fdbde1".parse().unwrap();
+        assert_eq!((version.major, version.minor, version.patch), (4, 5, 1));
+        assert_eq!(version.compatibility(), "4.5");
+        assert_eq!(version.template_dir_name(), "4.5.1.stable");
+        assert_eq!(version.release_tag(), "4.5.1-stable");
+        assert_eq!(version.to_string(), "4.5.1.stable.official.f62fdbde1");
+
+        let version: GodotVersion = "4.5.stable.mono.official.876b29033".parse().unwrap();
+        assert!(version.mono);
+        assert_eq!(version.template_dir_name(), "4.5.stable.mono");
+        assert_eq!(version.release_tag(), "4.5-stable");
+
+        assert!("godot".parse::<GodotVersion>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_wrap_command() {
+        let mut command = Command::new("gdenv");
+        command.args(["run", "4.5"]);
+        let wrapped = wrap_command(command, &["gdb".to_string(), "--args".to_string()]);
+        assert_eq!(wrapped.get_program(), "gdb");
+        assert_eq!(
+            wrapped.get_args().collect::<Vec<_>>(),
+            ["--args", "gdenv", "run", "4.5"]
+        );
+        assert_eq!(
+            wrap_command(Command::new("godot"), &[]).get_program(),
+            "godot"
+        );
+    }
+
+    #[test]
+    fn test_godot_binary() {
+        assert_eq!(GodotBinary::from(None), GodotBinary::Discover);
+        assert_eq!(GodotBinary::from(Some("4.5")), GodotBinary::Version("4.5"));
+        let command = godot_command(Some("4.5")).unwrap();
+        assert_eq!(command.get_program(), "gdenv");
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["run", "4.5"]);
+        let command = godot_command(Path::new("/opt/godot")).unwrap();
+        assert_eq!(command.get_program(), "/opt/godot");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_wait_with_timeout() {
+        let mut child = Command::new("sleep").arg("5").spawn().unwrap();
+        let result = wait_with_timeout(&mut child, Some(Duration::from_millis(200)));
+        assert!(result.unwrap_err().to_string().contains("Timed out"));
+
+        let mut child = Command::new("true").spawn().unwrap();
+        assert!(
+            wait_with_timeout(&mut child, Some(Duration::from_secs(5)))
+                .unwrap()
+                .success()
+        );
+    }
+
+    #[test]
+    fn test_exit_hooks() {
+        let statuses = Arc::new(Mutex::new(vec![]));
+        let mut process = spawn_command(Command::new("false"), None, false, false).unwrap();
+        let recorded = statuses.clone();
+        process.on_exit(Box::new(move |command, status| {
+            recorded
+                .lock()
+                .unwrap()
+                .push((command.get_program().to_owned(), status));
+            Ok(())
+        }));
+        process.on_exit(Box::new(|_, status| status.into_result()));
+        assert!(process.wait().is_err());
+        assert_eq!(
+            *statuses.lock().unwrap(),
+            vec![("false".into(), GodotExitStatus::ScriptError(1))]
+        );
+    }
+
+    #[test]
+    fn test_validate_scenes_helpers() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("levels")).unwrap();
+        std::fs::create_dir_all(dir.path().join(".godot/imported")).unwrap();
+        for file in [
+            "main.tscn",
+            "levels/one.scn",
+            "icon.svg",
+            ".godot/imported/cache.tscn",
+        ] {
+            std::fs::write(dir.path().join(file), "").unwrap();
+        }
+        let mut scenes = vec![];
+        find_project_files(dir.path(), &["tscn", "scn"], &mut scenes).unwrap();
+        scenes.sort();
+        assert_eq!(
+            scenes,
+            vec![
+                dir.path().join("levels/one.scn"),
+                dir.path().join("main.tscn")
+            ]
+        );
+
+        let script = validation_script(&["res://main.tscn".to_string()]).unwrap();
+        assert!(script.starts_with("for path in [\"res://main.tscn\"]:\n"));
+
+        let output = format!(
+            "ERROR: Failed loading resource.\n\
+            {SCENE_PROBLEM_MARKER}\tres://main.tscn\tmissing dependency res://player.png\n\
+            {SCENE_PROBLEM_MARKER}\tres://main.tscn\tfailed to load\n"
+        );
+        assert_eq!(
+            parse_scene_problems(&output),
+            vec![
+                SceneProblem {
+                    scene: "res://main.tscn".to_string(),
+                    problem: "missing dependency res://player.png".to_string(),
+                },
+                SceneProblem {
+                    scene: "res://main.tscn".to_string(),
+                    problem: "failed to load".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_check_scripts() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        // A fake Godot failing the check of `broken.gd` only.
+        let godot = dir.path().join("godot");
+        std::fs::write(
+            &godot,
+            "#!/bin/sh\n\
+            case \"$4\" in\n\
+            *broken.gd) echo 'SCRIPT ERROR: Parse Error: Unexpected \"}\".' >&2; \
+            echo '          at: GDScript::reload (res://broken.gd:3)' >&2; exit 1;;\n\
+            *empty.gd) exit 1;;\n\
+            esac\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&godot, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let project = dir.path().join("project");
+        std::fs::create_dir_all(project.join(".godot")).unwrap();
+        for file in ["player.gd", "broken.gd", "empty.gd", ".godot/cache.gd"] {
+            std::fs::write(project.join(file), "extends Node\n").unwrap();
+        }
+
+        let diagnostics = check_scripts(&project, godot.as_path()).unwrap();
+        let scripts: Vec<_> = diagnostics.iter().map(|d| d.script.as_str()).collect();
+        assert_eq!(scripts, ["res://broken.gd", "res://empty.gd"]);
+        assert!(diagnostics[0].error.message.contains("Parse Error"));
+        assert!(diagnostics[1].error.message.contains("status"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_reimport_files() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        // A fake Godot recording its arguments.
+        let godot = dir.path().join("godot");
+        let log = dir.path().join("args.txt");
+        std::fs::write(&godot, format!("#!/bin/sh\necho \"$@\" > {log:?}\n")).unwrap();
+        std::fs::set_permissions(&godot, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let project = dir.path().join("project");
+        std::fs::create_dir_all(project.join("sprites")).unwrap();
+        std::fs::write(project.join("sprites/hero.png"), "").unwrap();
+        std::fs::write(project.join("jump.wav"), "").unwrap();
+
+        let files = ["sprites/hero.png", "res://jump.wav"];
+        assert!(reimport_files(&project, godot.as_path(), &files, Verbosity::Quiet).is_err());
+        std::fs::create_dir(project.join(".godot")).unwrap();
+        let status = reimport_files(&project, godot.as_path(), &files, Verbosity::Quiet).unwrap();
+        assert_eq!(status, GodotExitStatus::Success);
+        let args = std::fs::read_to_string(&log).unwrap();
+        assert!(args.starts_with("--headless --editor --script res://.godot/cargo_godot_lib/"));
+        assert!(args.ends_with("-- res://sprites/hero.png res://jump.wav\n"));
+        assert_eq!(
+            std::fs::read_dir(project.join(GENERATED_DIR))
+                .unwrap()
+                .count(),
+            0
+        );
+        assert!(
+            reimport_files(
+                &project,
+                godot.as_path(),
+                &["missing.png"],
+                Verbosity::Quiet
+            )
+            .is_err()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_validate_shaders() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        // A fake Godot failing to compile `broken.gdshader` only.
+        let godot = dir.path().join("godot");
+        std::fs::write(
+            &godot,
+            "#!/bin/sh\n\
+            test -f \"${3#res://}\" || exit 2\n\
+            case \"$5\" in\n\
+            *broken.gdshader) echo \"SHADER ERROR: Expected ';' after statement.\" >&2; \
+            echo '          at: (null) (res://broken.gdshader:3)' >&2;;\n\
+            esac\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&godot, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let project = dir.path().join("project");
+        std::fs::create_dir_all(project.join(".godot")).unwrap();
+        for file in [
+            "water.gdshader",
+            "broken.gdshader",
+            "common.gdshaderinc",
+            ".godot/cache.gdshader",
+        ] {
+            std::fs::write(project.join(file), "shader_type spatial;\n").unwrap();
+        }
+
+        let diagnostics = validate_shaders(&project, godot.as_path()).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].shader, "res://broken.gdshader");
+        assert_eq!(diagnostics[0].error.kind, GodotErrorKind::ShaderError);
+        assert_eq!(
+            diagnostics[0].error.location.as_deref(),
+            Some("(null) (res://broken.gdshader:3)")
+        );
+        // The validation script is removed again.
+        assert_eq!(
+            std::fs::read_dir(project.join(GENERATED_DIR))
+                .unwrap()
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_script_source() {
+        assert_eq!(
+            script_source("print(1)\nprint(2)"),
+            "extends SceneTree\n\nfunc _initialize() -> void:\n\tprint(1)\n\tprint(2)\n\tquit()\n"
+        );
+        let script = "extends MainLoop\n\nfunc _process(_delta):\n\treturn true\n";
+        assert_eq!(script_source(script), script);
+
+        let dir = tempfile::tempdir().unwrap();
+        let generated = GeneratedScript::write(dir.path(), "test", script).unwrap();
+        let path = generated.path();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), script);
+        let command = generated
+            .command(Path::new("/bin/godot"), &["--editor"])
+            .unwrap();
+        assert_eq!(
+            command.get_args().map(OsString::from).collect::<Vec<_>>(),
+            [
+                OsString::from("--headless"),
+                "--editor".into(),
+                "--script".into(),
+                OsString::from(format!("res://{}", generated.relative_path))
+            ]
+        );
+        drop(generated);
+        assert!(!path.exists());
+        assert_eq!(
+            strip_banner(
+                "Godot Engine v4.5.1.stable.official - https://godotengine.org\n\nres://\n"
+            ),
+            "res://\n"
+        );
+        assert_eq!(strip_banner("res://\n"), "res://\n");
+    }
+
+    #[test]
+    fn test_retry_import() {
+        let mut calls = 0;
+        let result = retry_import(
+            &ImportRetry::default().attempts(2),
+            Verbosity::Quiet,
+            || {
+                calls += 1;
+                (Ok(GodotExitStatus::ImportFailed), false)
+            },
+        );
+        assert_eq!(result.unwrap(), GodotExitStatus::ImportFailed);
+        assert_eq!(calls, 3);
+
+        let mut calls = 0;
+        let result = retry_import(
+            &ImportRetry::default().attempts(2),
+            Verbosity::Quiet,
+            || {
+                calls += 1;
+                let status = if calls == 2 {
+                    GodotExitStatus::Success
+                } else {
+                    GodotExitStatus::ImportFailed
+                };
+                (Ok(status), false)
+            },
+        );
+        assert_eq!(result.unwrap(), GodotExitStatus::Success);
+        assert_eq!(calls, 2);
+
+        let mut calls = 0;
+        let retry = ImportRetry::default().attempts(2).verify_godot_dir(true);
+        let result = retry_import(&retry, Verbosity::Quiet, || {
+            calls += 1;
+            (Err(anyhow!("crashed")), true)
+        });
+        assert_eq!(result.unwrap(), GodotExitStatus::Success);
+        assert_eq!(calls, 1);
+
+        assert_eq!(
+            ImportOptions::default().retry_once(true),
+            ImportOptions::default().retry(ImportRetry::default().attempts(1))
+        );
+    }
+
+    #[test]
+    fn test_run_godot_import_many() {
+        let dir = tempfile::tempdir().unwrap();
+        let projects: Vec<PathBuf> = (0..3)
+            .map(|i| {
+                let project = dir.path().join(format!("project_{i}"));
+                std::fs::create_dir_all(project.join(".godot")).unwrap();
+                project
+            })
+            .collect();
+
+        // Already imported projects are skipped without launching Godot.
+        let statuses =
+            run_godot_import_many(&projects, None, &ImportOptions::default(), 2).unwrap();
+        assert_eq!(
+            statuses,
+            projects
+                .iter()
+                .map(|project| (project.clone(), GodotExitStatus::Success))
+                .collect::<Vec<_>>()
+        );
+        assert!(
+            run_godot_import_many(&[], None, &ImportOptions::default(), 0)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_clean_godot_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path();
+        std::fs::create_dir_all(project.join(".godot/imported")).unwrap();
+        std::fs::create_dir_all(project.join(".godot/editor")).unwrap();
+
+        assert!(clean_godot_cache(project, CleanLevel::All).is_err());
+        assert!(project.join(".godot").exists());
+
+        std::fs::write(project.join("project.godot"), "config_version=5").unwrap();
+        clean_godot_cache(project, CleanLevel::Imported).unwrap();
+        assert!(!project.join(".godot/imported").exists());
+        assert!(project.join(".godot/editor").exists());
+
+        clean_godot_cache(project, CleanLevel::Imported).unwrap();
+        clean_godot_cache(project, CleanLevel::All).unwrap();
+        assert!(!project.join(".godot").exists());
+    }
+}
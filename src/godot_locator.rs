@@ -0,0 +1,175 @@
+//! Caching the Godot binary found by discovery, see `GodotRunner::godot_locator`, since probing
+//! the environment and the filesystem and running `godot --version` add noticeable latency,
+//! e.g. on network home directories.
+//!
+//! The binary, its version and its SHA-256 hash for the `godot.lock` are stored in the
+//! `BuildState` of the cargo target directory.
+//! They are located again once the `godot`, `GODOT` or `PATH` environment variables change, or
+//! the binary is replaced or removed.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let locator = GodotLocator::new("target");
+//! let godot = locator.locate()?;
+//! println!("Godot {} at {:?}", godot.version, godot.path);
+//! // After installing another Godot at the same path:
+//! locator.refresh()?;
+//! ```
+use crate::godot_commands::{GodotVersion, godot_binary_path, run_version_check};
+use crate::state::{BuildState, build_state_path, hash_file};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+
+/// The environment variables discovery depends on.
+const DISCOVERY_ENV: [&str; 3] = ["godot", "GODOT", "PATH"];
+
+/// A Godot binary found by discovery, as recorded in the `BuildState`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct LocatedGodot {
+    /// The absolute path of the binary.
+    pub path: PathBuf,
+    /// The version reported by `godot --version`, e.g. `4.5.1.stable.official.f62fdbde1`.
+    pub version: String,
+    /// The SHA-256 hash of the binary.
+    pub sha256: String,
+    /// The discovery environment variables when the binary was located.
+    env: BTreeMap<String, String>,
+    /// When the binary was last modified.
+    modified: Option<SystemTime>,
+}
+
+impl LocatedGodot {
+    /// Find the Godot binary like `GodotRunner` does without a `godot_version`, and detect its
+    /// version and hash.
+    pub fn discover() -> Result<Self> {
+        let path = godot_binary_path()?;
+        // An environment variable may name a binary in the `PATH`.
+        let path = which::which(&path).unwrap_or(path);
+        let path = std::path::absolute(&path)
+            .with_context(|| format!("Failed to resolve Godot binary path: {path:?}"))?;
+        let version = run_version_check(Command::new(&path))?;
+        Ok(Self {
+            modified: modified(&path),
+            version: version.to_string(),
+            sha256: hash_file(&path)?,
+            env: discovery_env(),
+            path,
+        })
+    }
+
+    /// The parsed `version`.
+    pub fn godot_version(&self) -> Result<GodotVersion> {
+        self.version.parse()
+    }
+
+    /// Whether discovery would still find this binary with this version: the environment is
+    /// unchanged and the binary is unmodified.
+    pub fn is_current(&self) -> bool {
+        self.env == discovery_env() && self.path.is_file() && modified(&self.path) == self.modified
+    }
+}
+
+/// The values of the `DISCOVERY_ENV` variables which are set.
+fn discovery_env() -> BTreeMap<String, String> {
+    DISCOVERY_ENV
+        .into_iter()
+        .filter_map(|name| {
+            let value = std::env::var_os(name)?;
+            Some((name.to_string(), value.to_string_lossy().into_owned()))
+        })
+        .collect()
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    path.metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Locates the Godot binary, cached in the `BuildState` of a cargo target directory.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GodotLocator {
+    target_directory: PathBuf,
+}
+
+impl GodotLocator {
+    /// Cache in the `BuildState` of the cargo `target_directory`.
+    pub fn new(target_directory: impl Into<PathBuf>) -> Self {
+        Self {
+            target_directory: target_directory.into(),
+        }
+    }
+
+    /// The cached binary if it is still current, otherwise the binary found by `refresh`.
+    pub fn locate(&self) -> Result<LocatedGodot> {
+        let cached = BuildState::load(&self.state_path()).and_then(|state| state.godot_binary);
+        match cached {
+            Some(located) if located.is_current() => Ok(located),
+            _ => self.refresh(),
+        }
+    }
+
+    /// Discover the binary and its version again and update the cache, e.g. after upgrading
+    /// Godot in place.
+    pub fn refresh(&self) -> Result<LocatedGodot> {
+        let located = LocatedGodot::discover()?;
+        BuildState::update(&self.state_path(), |state| {
+            state.godot_binary = Some(located.clone());
+            Ok(())
+        })?;
+        Ok(located)
+    }
+
+    fn state_path(&self) -> PathBuf {
+        build_state_path(&self.target_directory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::godot_lock::{GodotLock, current_platform};
+
+    #[test]
+    fn test_locate_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("godot");
+        std::fs::write(&binary, "").unwrap();
+        let located = LocatedGodot {
+            path: binary.clone(),
+            version: "4.5.1.stable.official.f62fdbde1".to_string(),
+            sha256: hash_file(&binary).unwrap(),
+            env: discovery_env(),
+            modified: modified(&binary),
+        };
+        assert!(located.is_current());
+        assert_eq!(located.godot_version().unwrap().number(), "4.5.1");
+        BuildState::update(&build_state_path(dir.path()), |state| {
+            state.godot_binary = Some(located.clone());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(GodotLocator::new(dir.path()).locate().unwrap(), located);
+        let lock = GodotLock::located(&located).unwrap();
+        assert_eq!(lock.sha256[&current_platform()], located.sha256);
+
+        let mut env = discovery_env();
+        env.insert("GODOT".to_string(), "/opt/other/godot".to_string());
+        let changed_env = LocatedGodot {
+            env,
+            ..located.clone()
+        };
+        assert!(!changed_env.is_current());
+        let replaced = LocatedGodot {
+            modified: Some(SystemTime::UNIX_EPOCH),
+            ..located.clone()
+        };
+        assert!(!replaced.is_current());
+        std::fs::remove_file(&binary).unwrap();
+        assert!(!located.is_current());
+    }
+}
@@ -0,0 +1,238 @@
+//! A `godot.lock` file pinning the Godot build of a project, see `GodotRunner::godot_lock`.
+//!
+//! The lock records the `godot --version` of the engine, where it came from and the SHA-256 hash
+//! of the binary per platform, so everyone on the team and CI runs the exact same build. It lives
+//! next to `project.godot` and is meant to be committed. Hashes are only recorded and checked for
+//! binaries found by path, not for versions run through `gdenv`.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let current = GodotLock::current(None)?;
+//! match GodotLock::load(Path::new("godot"))? {
+//!     Some(lock) => lock.check(&current)?,
+//!     None => current.save(Path::new("godot"))?,
+//! }
+//! ```
+use crate::godot_commands::{GodotBinary, GodotVersion, detect_godot_version, godot_binary_path};
+use crate::godot_locator::LocatedGodot;
+use crate::state::hash_file;
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// The name of the lock file in the godot project.
+pub const LOCK_FILE_NAME: &str = "godot.lock";
+
+/// How `GodotRunner` uses the `godot.lock` file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GodotLockMode {
+    /// Fail if the Godot binary doesn't match the lock. Writes the lock if there is none.
+    Verify,
+    /// Like `Verify`, but install the locked version with `gdenv` and run it instead of
+    /// failing if the Godot binary doesn't match.
+    Install,
+    /// Rewrite the lock for the current Godot binary, keeping the hashes of other platforms
+    /// if the version is unchanged.
+    Update,
+}
+
+/// The Godot build a project is locked to.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct GodotLock {
+    /// The output of `godot --version`, e.g. `4.5.1.stable.official.f62fdbde1`.
+    pub version: String,
+    /// Where the engine came from, e.g. the official release page or `gdenv`.
+    pub source: String,
+    /// The SHA-256 hashes of the binary by platform, e.g. `linux-x86_64`.
+    #[serde(default)]
+    pub sha256: BTreeMap<String, String>,
+}
+
+impl GodotLock {
+    /// The path of the lock file in the godot project.
+    pub fn path(godot_project_path: &Path) -> PathBuf {
+        godot_project_path.join(LOCK_FILE_NAME)
+    }
+
+    /// Load the lock of the godot project. Returns `None` if there is none.
+    pub fn load(godot_project_path: &Path) -> Result<Option<Self>> {
+        let path = Self::path(godot_project_path);
+        let Ok(contents) = std::fs::read_to_string(&path) else {
+            return Ok(None);
+        };
+        serde_json::from_str(&contents)
+            .map(Some)
+            .with_context(|| format!("Failed to parse {path:?}"))
+    }
+
+    /// Write the lock into the godot project.
+    pub fn save(&self, godot_project_path: &Path) -> Result<()> {
+        let path = Self::path(godot_project_path);
+        let contents = serde_json::to_string_pretty(self)? + "\n";
+        std::fs::write(&path, contents).with_context(|| format!("Failed to write {path:?}"))
+    }
+
+    /// The lock of the Godot binary the runner uses, see `GodotRunner::godot_version`.
+    pub fn current<'a>(godot_binary: impl Into<GodotBinary<'a>>) -> Result<Self> {
+        let godot_binary = godot_binary.into();
+        let version = detect_godot_version(godot_binary)?;
+        let binary = match godot_binary {
+            GodotBinary::Discover => godot_binary_path()?,
+            GodotBinary::Version(_) => {
+                return Ok(Self {
+                    version: version.to_string(),
+                    source: "gdenv".to_string(),
+                    sha256: BTreeMap::new(),
+                });
+            }
+            GodotBinary::Path(path) => path.to_path_buf(),
+        };
+        Ok(Self::binary(&version, hash_file(&binary)?))
+    }
+
+    /// The lock of a binary located by the `GodotLocator`, from its cached version and hash.
+    pub fn located(located: &LocatedGodot) -> Result<Self> {
+        Ok(Self::binary(
+            &located.godot_version()?,
+            located.sha256.clone(),
+        ))
+    }
+
+    /// The lock of a release binary with the hash `sha256` on the current platform.
+    fn binary(version: &GodotVersion, sha256: String) -> Self {
+        Self {
+            version: version.to_string(),
+            source: release_url(version),
+            sha256: BTreeMap::from([(current_platform(), sha256)]),
+        }
+    }
+
+    /// This lock updated to `current`, keeping the hashes of other platforms if the version
+    /// is unchanged.
+    pub fn updated(&self, current: &Self) -> Self {
+        let mut updated = current.clone();
+        if self.version == current.version {
+            for (platform, hash) in &self.sha256 {
+                updated
+                    .sha256
+                    .entry(platform.clone())
+                    .or_insert_with(|| hash.clone());
+            }
+        }
+        updated
+    }
+
+    /// Check that `current` matches the lock: the same version and, if both have one, the same
+    /// hash for the current platform.
+    pub fn check(&self, current: &Self) -> Result<()> {
+        if self.version != current.version {
+            return Err(anyhow!(
+                "Godot {} is locked in {LOCK_FILE_NAME}, but Godot {} was found.{}",
+                self.version,
+                current.version,
+                MISMATCH_HINT
+            ));
+        }
+        let platform = current_platform();
+        if let (Some(locked), Some(hash)) =
+            (self.sha256.get(&platform), current.sha256.get(&platform))
+            && locked != hash
+        {
+            return Err(anyhow!(
+                "The Godot {} binary differs from the one locked in {LOCK_FILE_NAME} for {platform} \
+                (SHA-256 {hash} instead of {locked}), e.g. a custom build.{}",
+                self.version,
+                MISMATCH_HINT
+            ));
+        }
+        Ok(())
+    }
+
+    /// The version argument for `gdenv`, e.g. `4.5.1` or `4.6-beta2`.
+    pub fn gdenv_version(&self) -> Result<String> {
+        let version: GodotVersion = self.version.parse()?;
+        Ok(if version.status == "stable" {
+            version.number()
+        } else {
+            version.release_tag()
+        })
+    }
+
+    /// Install the locked version with `gdenv install`.
+    pub fn install(&self) -> Result<()> {
+        let version = self.gdenv_version()?;
+        let mut command = Command::new("gdenv");
+        command.arg("install").arg(&version).stdin(Stdio::null());
+        let status = command.status().with_context(|| {
+            format!(
+                "Failed to run gdenv, which is required to install the locked Godot {version} \
+                (https://github.com/bytemeadow/gdenv): {command:?}"
+            )
+        })?;
+        if !status.success() {
+            return Err(anyhow!(
+                "gdenv failed to install Godot {version} with status `{status}`"
+            ));
+        }
+        Ok(())
+    }
+}
+
+const MISMATCH_HINT: &str = "\nInstall the locked version, set the `GODOT` environment variable \
+    to it, use `GodotLockMode::Install`, or update the lock with `GodotLockMode::Update`.";
+
+/// The platform key of the hashes, e.g. `linux-x86_64`.
+pub fn current_platform() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// The official release page of `version`.
+fn release_url(version: &GodotVersion) -> String {
+    format!(
+        "https://github.com/godotengine/godot/releases/tag/{}",
+        version.release_tag()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(GodotLock::load(dir.path()).unwrap(), None);
+
+        let platform = current_platform();
+        let lock = GodotLock {
+            version: "4.5.1.stable.official.f62fdbde1".to_string(),
+            source: "https://github.com/godotengine/godot/releases/tag/4.5.1-stable".to_string(),
+            sha256: BTreeMap::from([
+                (platform.clone(), "aaaa".to_string()),
+                ("other-arch".to_string(), "bbbb".to_string()),
+            ]),
+        };
+        lock.save(dir.path()).unwrap();
+        assert_eq!(GodotLock::load(dir.path()).unwrap(), Some(lock.clone()));
+        assert_eq!(lock.gdenv_version().unwrap(), "4.5.1");
+
+        let mut current = GodotLock {
+            sha256: BTreeMap::from([(platform.clone(), "aaaa".to_string())]),
+            ..lock.clone()
+        };
+        lock.check(&current).unwrap();
+        assert_eq!(lock.updated(&current), lock);
+
+        current.sha256.insert(platform.clone(), "cccc".to_string());
+        assert!(lock.check(&current).is_err());
+        current.sha256.clear();
+        lock.check(&current).unwrap();
+
+        current.version = "4.6.beta2.official.abcdef".to_string();
+        assert!(lock.check(&current).is_err());
+        assert!(lock.updated(&current).sha256.is_empty());
+        assert_eq!(current.gdenv_version().unwrap(), "4.6-beta2");
+    }
+}
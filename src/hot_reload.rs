@@ -0,0 +1,96 @@
+//! An opt-in lint for setups known to break GDExtension hot reloading,
+//! see `GodotRunner::lint_hot_reload`.
+//!
+//! Godot reloads a `reloadable` extension by unloading and loading the library again. This
+//! crashes or silently keeps the old code in a few common setups:
+//! - Thread-local storage with destructors (`thread_local!`): glibc refuses to unload a library
+//!   which registered TLS destructors, so the rebuilt library is never loaded.
+//! - Fat LTO: inlining across crates makes reloads more likely to leave dangling function
+//!   pointers behind in the engine.
+//!
+//! The checks are heuristics, so they only produce warnings. The library's symbols don't reveal
+//! whether TLS destructors are used, since every Rust library links std's destructor
+//! registration, so the sources are scanned instead.
+use crate::class_names::find_rust_files;
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// The suggestion appended to every warning.
+const SUGGESTION: &str = "Consider `GdExtensionConfig::reloadable(false)`.";
+
+/// Warnings for the `[profile.<profile>]` section of a `Cargo.toml` manifest.
+pub fn lint_profile(manifest: &str, profile: &str) -> Vec<String> {
+    let header = format!("[profile.{profile}]");
+    let mut in_profile = false;
+    let mut warnings = vec![];
+    for line in manifest.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_profile = line == header;
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.split('#').next().unwrap_or_default().trim();
+        if in_profile && key.trim() == "lto" && matches!(value, "true" | "\"fat\"") {
+            warnings.push(format!(
+                "Hot reload: the `{profile}` profile uses fat LTO (`lto = {value}`), \
+                which often crashes when Godot reloads the extension. {SUGGESTION}"
+            ));
+        }
+    }
+    warnings
+}
+
+/// Warnings for the Rust sources below `src_dir`.
+pub fn lint_sources(src_dir: &Path) -> Result<Vec<String>> {
+    let mut files = vec![];
+    find_rust_files(src_dir, &mut files)?;
+    files.sort();
+
+    let mut warnings = vec![];
+    for file in files {
+        let source = std::fs::read_to_string(&file)
+            .with_context(|| format!("Failed to read source file: {file:?}"))?;
+        if source.contains("thread_local!") {
+            warnings.push(format!(
+                "Hot reload: {file:?} declares `thread_local!` statics. Their destructors prevent \
+                the library from being unloaded, so Godot keeps running the old code. {SUGGESTION}"
+            ));
+        }
+    }
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint() {
+        let manifest = r#"
+            [package]
+            name = "game"
+
+            [profile.release]
+            lto = "fat" # smaller binaries
+
+            [profile.dev]
+            lto = "thin"
+        "#;
+        assert_eq!(lint_profile(manifest, "release").len(), 1);
+        assert!(lint_profile(manifest, "dev").is_empty());
+        assert!(lint_profile("[profile.dist]\nlto = true", "dist")[0].contains("lto = true"));
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "mod cache;").unwrap();
+        std::fs::write(
+            dir.path().join("cache.rs"),
+            "thread_local! { static CACHE: RefCell<Vec<u8>> = RefCell::default(); }",
+        )
+        .unwrap();
+        let warnings = lint_sources(dir.path()).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("cache.rs"));
+    }
+}
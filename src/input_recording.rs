@@ -0,0 +1,97 @@
+//! Recording the input events of a running game to a file and replaying them in a later run,
+//! over the `remote` channel, e.g. for scripted end-to-end gameplay tests.
+//!
+//! Events are recorded as Godot `var_to_str` strings with their time since the recording
+//! started, before the game handles them. Playback feeds them to `Input.parse_input_event` at the
+//! same times. Mouse positions are in window coordinates, so replay with the same resolution,
+//! e.g. `GodotRunner::arg_resolution`.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! // Record a run played by hand.
+//! let (mut remote, process) = runner.spawn_remote(0)?;
+//! remote.start_recording()?;
+//! process.wait()?; // ... or quit when done
+//! remote.stop_recording()?.save(Path::new("tests/inputs/level1.json"))?;
+//!
+//! // Replay it in a test.
+//! let (mut remote, process) = runner.spawn_remote(0)?;
+//! remote.play(&InputRecording::load(Path::new("tests/inputs/level1.json"))?)?;
+//! assert_eq!(remote.eval("get_tree().current_scene.name")?, "Level2");
+//! remote.quit(0)?;
+//! ```
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+/// An input event of an `InputRecording`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedInput {
+    /// Seconds since the recording started.
+    pub time: f64,
+    /// The `InputEvent` serialized with `var_to_str`.
+    pub event: String,
+}
+
+/// Recorded input events, see `GodotRemote::stop_recording`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputRecording {
+    pub events: Vec<RecordedInput>,
+}
+
+impl InputRecording {
+    /// Load a recording saved with `save`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read input recording: {path:?}"))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse input recording: {path:?}"))
+    }
+
+    /// Save the recording to `path` as JSON.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {parent:?}"))?;
+        }
+        let contents = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, contents).with_context(|| format!("Failed to write {path:?}"))
+    }
+
+    /// The time of the last event.
+    pub fn duration(&self) -> Duration {
+        self.events
+            .iter()
+            .map(|input| Duration::from_secs_f64(input.time.max(0.0)))
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_recording() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("inputs/level1.json");
+        let recording = InputRecording {
+            events: vec![
+                RecordedInput {
+                    time: 0.5,
+                    event: "Object(InputEventKey,\"keycode\":32,\"pressed\":true)".into(),
+                },
+                RecordedInput {
+                    time: 1.25,
+                    event: "Object(InputEventKey,\"keycode\":32,\"pressed\":false)".into(),
+                },
+            ],
+        };
+        recording.save(&path).unwrap();
+        assert_eq!(InputRecording::load(&path).unwrap(), recording);
+        assert_eq!(recording.duration(), Duration::from_millis(1250));
+        assert!(InputRecording::load(&dir.path().join("missing.json")).is_err());
+    }
+}
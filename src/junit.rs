@@ -0,0 +1,553 @@
+//! JUnit XML and GitHub Actions annotations for the results of runs, scene validation, script and
+//! shader checks, audits, doctor checks and visual tests, so CI systems display failures natively.
+//!
+//! Results are converted to `TestSuite`s with `From`, e.g. from a `RunReport` whose script errors
+//! become failed test cases. `res://` paths of failures are written relative to the repository,
+//! given the path of the godot project in it.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let suites = [
+//!     TestSuite::from(&runner.execute_with_report()?),
+//!     TestSuite::from(&audit::audit(Path::new("godot"))?),
+//! ];
+//! junit::write_junit_xml(Path::new("target/junit.xml"), &suites, Path::new("godot"))?;
+//! if std::env::var_os("GITHUB_ACTIONS").is_some() {
+//!     print!("{}", junit::github_annotations(&suites, Path::new("godot")));
+//! }
+//! ```
+use crate::audit::AuditReport;
+use crate::doctor::{CheckStatus, DoctorReport};
+use crate::godot_commands::{SceneProblem, ScriptDiagnostic, ShaderDiagnostic};
+use crate::output::GodotError;
+use crate::report::RunReport;
+#[cfg(feature = "visual-test")]
+use crate::visual_test::VisualDiff;
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::time::Duration;
+
+/// Why a test case failed.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TestFailure {
+    pub message: String,
+    pub details: Option<String>,
+    /// The file which caused the failure, e.g. `res://main.gd`.
+    pub file: Option<String>,
+    pub line: Option<u32>,
+}
+
+/// A single result of a `TestSuite`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TestCase {
+    pub name: String,
+    pub duration: Option<Duration>,
+    pub failure: Option<TestFailure>,
+    /// A problem which doesn't fail the test case, reported as output and as a warning
+    /// annotation.
+    pub warning: Option<String>,
+}
+
+impl TestCase {
+    pub fn passed(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn failed(name: impl Into<String>, failure: TestFailure) -> Self {
+        Self {
+            name: name.into(),
+            failure: Some(failure),
+            ..Self::default()
+        }
+    }
+
+    pub fn warning(name: impl Into<String>, warning: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            warning: Some(warning.into()),
+            ..Self::default()
+        }
+    }
+}
+
+/// Named test cases, e.g. of one run.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TestSuite {
+    pub name: String,
+    pub cases: Vec<TestCase>,
+}
+
+impl TestSuite {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            cases: vec![],
+        }
+    }
+
+    /// Add a test case.
+    pub fn case(mut self, case: TestCase) -> Self {
+        self.cases.push(case);
+        self
+    }
+
+    pub fn failures(&self) -> usize {
+        self.cases
+            .iter()
+            .filter(|case| case.failure.is_some())
+            .count()
+    }
+}
+
+impl From<&RunReport> for TestSuite {
+    /// A case for the exit status and a failed case for every error Godot reported.
+    fn from(report: &RunReport) -> Self {
+        let mut exit = if report.exit_status.is_success() {
+            TestCase::passed("exit status")
+        } else {
+            let details = report
+                .crash_dump
+                .as_ref()
+                .map(|crash_dump| format!("Crash dump: {crash_dump:?}"));
+            TestCase::failed(
+                "exit status",
+                TestFailure {
+                    message: report.exit_status.to_string(),
+                    details,
+                    ..TestFailure::default()
+                },
+            )
+        };
+        exit.duration = Some(report.duration);
+        let mut suite = TestSuite::new("godot").case(exit);
+        for error in &report.errors {
+            suite = suite.case(TestCase::failed(
+                error.to_string(),
+                error_failure(error, None),
+            ));
+        }
+        suite
+    }
+}
+
+impl From<&[SceneProblem]> for TestSuite {
+    /// A failed case for every scene problem of `validate_scenes`.
+    fn from(problems: &[SceneProblem]) -> Self {
+        let mut suite = TestSuite::new("scenes");
+        for problem in problems {
+            suite = suite.case(TestCase::failed(
+                problem.to_string(),
+                TestFailure {
+                    message: problem.problem.clone(),
+                    file: Some(problem.scene.clone()),
+                    ..TestFailure::default()
+                },
+            ));
+        }
+        if suite.cases.is_empty() {
+            suite = suite.case(TestCase::passed("scenes"));
+        }
+        suite
+    }
+}
+
+impl From<&[ScriptDiagnostic]> for TestSuite {
+    /// A failed case for every error of `check_scripts`.
+    fn from(diagnostics: &[ScriptDiagnostic]) -> Self {
+        let mut suite = TestSuite::new("scripts");
+        for diagnostic in diagnostics {
+            suite = suite.case(TestCase::failed(
+                diagnostic.to_string(),
+                error_failure(&diagnostic.error, Some(&diagnostic.script)),
+            ));
+        }
+        if suite.cases.is_empty() {
+            suite = suite.case(TestCase::passed("scripts"));
+        }
+        suite
+    }
+}
+
+impl From<&[ShaderDiagnostic]> for TestSuite {
+    /// A failed case for every error of `validate_shaders`.
+    fn from(diagnostics: &[ShaderDiagnostic]) -> Self {
+        let mut suite = TestSuite::new("shaders");
+        for diagnostic in diagnostics {
+            suite = suite.case(TestCase::failed(
+                diagnostic.to_string(),
+                error_failure(&diagnostic.error, Some(&diagnostic.shader)),
+            ));
+        }
+        if suite.cases.is_empty() {
+            suite = suite.case(TestCase::passed("shaders"));
+        }
+        suite
+    }
+}
+
+impl From<&AuditReport> for TestSuite {
+    /// A failed case for every missing reference and a warning for every orphaned asset.
+    fn from(report: &AuditReport) -> Self {
+        let mut suite = TestSuite::new("audit");
+        for missing in &report.missing {
+            suite = suite.case(TestCase::failed(
+                missing.to_string(),
+                TestFailure {
+                    message: format!("Missing {}", missing.reference),
+                    file: Some(missing.file.clone()),
+                    ..TestFailure::default()
+                },
+            ));
+        }
+        for orphaned in &report.orphaned {
+            suite = suite.case(TestCase::warning(
+                format!("{orphaned} is orphaned"),
+                format!("No file references {orphaned}"),
+            ));
+        }
+        if suite.cases.is_empty() {
+            suite = suite.case(TestCase::passed("references"));
+        }
+        suite
+    }
+}
+
+impl From<&DoctorReport> for TestSuite {
+    /// A case for every check, with the suggested fix as details.
+    fn from(report: &DoctorReport) -> Self {
+        let mut suite = TestSuite::new("doctor");
+        for check in &report.checks {
+            suite = suite.case(match check.status {
+                CheckStatus::Ok => TestCase::passed(check.name),
+                CheckStatus::Warning => TestCase::warning(check.name, &check.message),
+                CheckStatus::Error => TestCase::failed(
+                    check.name,
+                    TestFailure {
+                        message: check.message.clone(),
+                        details: check.fix.clone(),
+                        ..TestFailure::default()
+                    },
+                ),
+            });
+        }
+        suite
+    }
+}
+
+#[cfg(feature = "visual-test")]
+impl From<&VisualDiff> for TestCase {
+    /// A case named after the golden image.
+    fn from(diff: &VisualDiff) -> Self {
+        let name = diff
+            .golden
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        if diff.passed() {
+            TestCase::passed(name)
+        } else {
+            TestCase::failed(
+                name,
+                TestFailure {
+                    message: diff.to_string(),
+                    ..TestFailure::default()
+                },
+            )
+        }
+    }
+}
+
+/// The failure of a Godot `error`, located at its `location`, or in `file` if it has none.
+fn error_failure(error: &GodotError, file: Option<&str>) -> TestFailure {
+    let (location_file, line) = error
+        .location
+        .as_deref()
+        .map(parse_location)
+        .unwrap_or_default();
+    TestFailure {
+        message: error.message.clone(),
+        details: error.location.clone(),
+        file: location_file.or(file.map(str::to_string)),
+        line,
+    }
+}
+
+/// The file and line of a Godot error location, e.g. `GDScript::reload (res://main.gd:5)`.
+fn parse_location(location: &str) -> (Option<String>, Option<u32>) {
+    let Some(start) = location.find("res://") else {
+        return (None, None);
+    };
+    let path = location[start..].trim_end_matches(')');
+    match path.rsplit_once(':') {
+        Some((file, line)) if line.parse::<u32>().is_ok() => {
+            (Some(file.to_string()), line.parse().ok())
+        }
+        _ => (Some(path.to_string()), None),
+    }
+}
+
+/// `file` relative to the repository if it is a `res://` path.
+fn repository_path(file: &str, godot_project_path: &Path) -> String {
+    match file.strip_prefix("res://") {
+        Some(file) => godot_project_path
+            .join(file)
+            .to_string_lossy()
+            .replace('\\', "/"),
+        None => file.to_string(),
+    }
+}
+
+/// `text` escaped for XML, without the control characters XML doesn't allow.
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The suites as a JUnit XML document.
+pub fn junit_xml(suites: &[TestSuite], godot_project_path: &Path) -> String {
+    let tests: usize = suites.iter().map(|suite| suite.cases.len()).sum();
+    let failures: usize = suites.iter().map(TestSuite::failures).sum();
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites tests=\"{tests}\" failures=\"{failures}\">\n"
+    );
+    for suite in suites {
+        let time: Duration = suite.cases.iter().filter_map(|case| case.duration).sum();
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+            escape_xml(&suite.name),
+            suite.cases.len(),
+            suite.failures(),
+            time.as_secs_f64()
+        ));
+        for case in &suite.cases {
+            xml.push_str(&format!(
+                "    <testcase name=\"{}\" classname=\"{}\"",
+                escape_xml(&case.name),
+                escape_xml(&suite.name)
+            ));
+            if let Some(duration) = case.duration {
+                xml.push_str(&format!(" time=\"{:.3}\"", duration.as_secs_f64()));
+            }
+            if let Some(failure) = &case.failure {
+                if let Some(file) = &failure.file {
+                    let file = repository_path(file, godot_project_path);
+                    xml.push_str(&format!(" file=\"{}\"", escape_xml(&file)));
+                }
+                if let Some(line) = failure.line {
+                    xml.push_str(&format!(" line=\"{line}\""));
+                }
+            }
+            if case.failure.is_none() && case.warning.is_none() {
+                xml.push_str("/>\n");
+                continue;
+            }
+            xml.push_str(">\n");
+            if let Some(failure) = &case.failure {
+                xml.push_str(&format!(
+                    "      <failure message=\"{}\">{}</failure>\n",
+                    escape_xml(&failure.message),
+                    escape_xml(failure.details.as_deref().unwrap_or_default())
+                ));
+            }
+            if let Some(warning) = &case.warning {
+                xml.push_str(&format!(
+                    "      <system-out>{}</system-out>\n",
+                    escape_xml(warning)
+                ));
+            }
+            xml.push_str("    </testcase>\n");
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+/// Write `junit_xml` of the suites to `path`.
+pub fn write_junit_xml(path: &Path, suites: &[TestSuite], godot_project_path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {parent:?}"))?;
+    }
+    std::fs::write(path, junit_xml(suites, godot_project_path))
+        .with_context(|| format!("Failed to write {path:?}"))
+}
+
+/// `text` escaped for a GitHub Actions workflow command, also escaping `:` and `,` in
+/// `property` values.
+fn escape_annotation(text: &str, property: bool) -> String {
+    let escaped = text
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A");
+    if property {
+        escaped.replace(':', "%3A").replace(',', "%2C")
+    } else {
+        escaped
+    }
+}
+
+/// The failures and warnings of the suites as GitHub Actions workflow commands, one per line,
+/// e.g. `::error file=godot/main.gd,line=5,title=godot::Invalid call.`
+pub fn github_annotations(suites: &[TestSuite], godot_project_path: &Path) -> String {
+    let mut annotations = String::new();
+    for suite in suites {
+        for case in &suite.cases {
+            let title = format!("{}: {}", suite.name, case.name);
+            if let Some(failure) = &case.failure {
+                let mut properties = vec![];
+                if let Some(file) = &failure.file {
+                    let file = repository_path(file, godot_project_path);
+                    properties.push(format!("file={}", escape_annotation(&file, true)));
+                }
+                if let Some(line) = failure.line {
+                    properties.push(format!("line={line}"));
+                }
+                properties.push(format!("title={}", escape_annotation(&title, true)));
+                let mut message = failure.message.clone();
+                if let Some(details) = &failure.details {
+                    message.push_str(&format!("\n{details}"));
+                }
+                annotations.push_str(&format!(
+                    "::error {}::{}\n",
+                    properties.join(","),
+                    escape_annotation(&message, false)
+                ));
+            }
+            if let Some(warning) = &case.warning {
+                annotations.push_str(&format!(
+                    "::warning title={}::{}\n",
+                    escape_annotation(&title, true),
+                    escape_annotation(warning, false)
+                ));
+            }
+        }
+    }
+    annotations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::MissingReference;
+    use crate::exit_status::GodotExitStatus;
+    use crate::output::{GodotError, GodotErrorKind};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_junit() {
+        let report = RunReport {
+            binary: PathBuf::from("godot"),
+            version: None,
+            args: vec![],
+            duration: Duration::from_millis(1500),
+            exit_status: GodotExitStatus::ScriptError(1),
+            errors: vec![GodotError {
+                kind: GodotErrorKind::ScriptError,
+                message: "Invalid call. Nonexistent function 'jump' in base \"Nil\".".to_string(),
+                location: Some("_ready (res://player.gd:7)".to_string()),
+            }],
+            crash_dump: None,
+            backtrace: vec![],
+            performance: None,
+            leaks: None,
+            written_files: vec![],
+            warnings: vec![],
+        };
+        let audit = AuditReport {
+            missing: vec![MissingReference {
+                file: "res://main.tscn".to_string(),
+                reference: "res://gone.png".to_string(),
+            }],
+            orphaned: vec!["res://unused.png".to_string()],
+            ..AuditReport::default()
+        };
+        let suites = [TestSuite::from(&report), TestSuite::from(&audit)];
+        assert_eq!(suites[0].failures(), 2);
+        assert_eq!(suites[0].cases[1].failure.as_ref().unwrap().line, Some(7));
+
+        let xml = junit_xml(&suites, Path::new("godot"));
+        assert!(xml.contains("<testsuites tests=\"4\" failures=\"3\">"));
+        assert!(
+            xml.contains("<testsuite name=\"godot\" tests=\"2\" failures=\"2\" time=\"1.500\">")
+        );
+        assert!(xml.contains("file=\"godot/player.gd\" line=\"7\""));
+        assert!(xml.contains("base &quot;Nil&quot;."));
+        assert!(xml.contains("<system-out>No file references res://unused.png</system-out>"));
+
+        let annotations = github_annotations(&suites, Path::new("godot"));
+        let lines: Vec<_> = annotations.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(
+            lines[1]
+                .starts_with("::error file=godot/player.gd,line=7,title=godot%3A SCRIPT ERROR%3A")
+        );
+        assert!(lines[1].ends_with("::Invalid call. Nonexistent function 'jump' in base \"Nil\".%0A_ready (res://player.gd:7)"));
+        assert_eq!(
+            lines[3],
+            "::warning title=audit%3A res%3A//unused.png is orphaned::No file references res://unused.png"
+        );
+        let scenes = TestSuite::from(
+            &[SceneProblem {
+                scene: "res://main.tscn".to_string(),
+                problem: "missing dependency res://player.png".to_string(),
+            }][..],
+        );
+        let failure = scenes.cases[0].failure.as_ref().unwrap();
+        assert_eq!(failure.file.as_deref(), Some("res://main.tscn"));
+        assert_eq!(failure.message, "missing dependency res://player.png");
+        assert_eq!(
+            TestSuite::from(&[][..] as &[SceneProblem]).cases,
+            [TestCase::passed("scenes")]
+        );
+
+        let error = GodotError {
+            kind: GodotErrorKind::ScriptError,
+            message: "Parse Error: Unexpected \"}\".".to_string(),
+            location: Some("GDScript::reload (res://broken.gd:3)".to_string()),
+        };
+        let scripts = TestSuite::from(
+            &[ScriptDiagnostic {
+                script: "res://broken.gd".to_string(),
+                error: error.clone(),
+            }][..],
+        );
+        let failure = scripts.cases[0].failure.as_ref().unwrap();
+        assert_eq!(failure.file.as_deref(), Some("res://broken.gd"));
+        assert_eq!(failure.line, Some(3));
+
+        let shaders = TestSuite::from(
+            &[ShaderDiagnostic {
+                shader: "res://water.gdshader".to_string(),
+                error: GodotError {
+                    kind: GodotErrorKind::ShaderError,
+                    location: None,
+                    ..error
+                },
+            }][..],
+        );
+        assert_eq!(shaders.name, "shaders");
+        let failure = shaders.cases[0].failure.as_ref().unwrap();
+        assert_eq!(failure.file.as_deref(), Some("res://water.gdshader"));
+        assert_eq!(failure.line, None);
+        assert!(
+            github_annotations(&[scripts], Path::new("godot"))
+                .starts_with("::error file=godot/broken.gd,line=3,")
+        );
+    }
+}
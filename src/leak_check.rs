@@ -0,0 +1,190 @@
+//! Checking for objects, RIDs and resources Godot reports as leaked at exit, see
+//! `GodotRunner::check_leaks`, e.g. to catch a missing `free()` in the extension.
+//!
+//! Godot is run with `--verbose`, so that it lists every leaked instance and resource instead of
+//! only warning about them. The leaks are listed in the `RunReport`, and `GodotRunner::execute`
+//! fails if there are more than `LeakCheck::max_leaks`.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let runner = GodotRunner::create("game", Path::new("godot"))
+//!     .frame_limit(300)
+//!     .check_leaks(LeakCheck::default());
+//! runner.execute()?;
+//! ```
+use crate::output::OutputCallback;
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
+
+/// RID allocations of one type leaked at exit.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct RidLeak {
+    /// The type of the allocations, e.g. `CanvasItem`.
+    pub kind: String,
+    pub count: usize,
+}
+
+/// The leaks Godot reported at exit.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct LeakReport {
+    /// The leaked instances as `Class:id`, e.g. `Node2D:9223372061282190600`.
+    pub instances: Vec<String>,
+    /// True if Godot warned about leaked instances. Release builds don't list them.
+    pub object_db_leaked: bool,
+    pub rids: Vec<RidLeak>,
+    /// The resources still in use at exit, e.g. `res://enemy.tres (PackedScene)`.
+    pub resources: Vec<String>,
+}
+
+impl LeakReport {
+    /// The number of leaked instances, RIDs and resources.
+    pub fn total(&self) -> usize {
+        let instances = self.instances.len().max(usize::from(self.object_db_leaked));
+        instances + self.rids.iter().map(|rid| rid.count).sum::<usize>() + self.resources.len()
+    }
+
+    /// Record the leak reported by `line`, if any.
+    fn scan_line(&mut self, line: &str) {
+        let line = line.trim();
+        if let Some(instance) = line.strip_prefix("Leaked instance: ") {
+            self.instances.push(instance.to_string());
+        } else if line.contains("ObjectDB instances leaked at exit") {
+            self.object_db_leaked = true;
+        } else if let Some(resource) = line.strip_prefix("Resource still in use: ") {
+            self.resources.push(resource.to_string());
+        } else if let Some(leak) = parse_rid_leak(line) {
+            self.rids.push(leak);
+        }
+    }
+}
+
+impl Display for LeakReport {
+    /// One leak per line.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut lines = vec![];
+        if self.object_db_leaked && self.instances.is_empty() {
+            lines.push("ObjectDB instances leaked, run a debug build to list them".to_string());
+        }
+        lines.extend(
+            self.instances
+                .iter()
+                .map(|instance| format!("Leaked instance: {instance}")),
+        );
+        lines.extend(
+            self.rids
+                .iter()
+                .map(|rid| format!("{} leaked RID allocations of type {}", rid.count, rid.kind)),
+        );
+        lines.extend(
+            self.resources
+                .iter()
+                .map(|resource| format!("Resource still in use: {resource}")),
+        );
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// Parses `ERROR: 3 RID allocations of type 'CanvasItem' were leaked at exit.`
+fn parse_rid_leak(line: &str) -> Option<RidLeak> {
+    let message = line.strip_prefix("ERROR: ").unwrap_or(line);
+    let (count, rest) = message.split_once(" RID allocations of type ")?;
+    let kind = rest.strip_suffix(" were leaked at exit.")?;
+    Some(RidLeak {
+        kind: kind.trim_matches('\'').to_string(),
+        count: count.trim().parse().ok()?,
+    })
+}
+
+/// Options for `GodotRunner::check_leaks`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LeakCheck {
+    max_leaks: Option<usize>,
+}
+
+impl Default for LeakCheck {
+    fn default() -> Self {
+        Self { max_leaks: Some(0) }
+    }
+}
+
+impl LeakCheck {
+    /// Fail the run if there are more leaks, or only report them if `None`. Default: `Some(0)`.
+    pub fn max_leaks(self, max_leaks: Option<usize>) -> Self {
+        Self { max_leaks }
+    }
+
+    /// Whether `report` has more leaks than allowed.
+    pub fn exceeded(&self, report: &LeakReport) -> bool {
+        self.max_leaks
+            .is_some_and(|max_leaks| report.total() > max_leaks)
+    }
+}
+
+/// Collects the leaks from Godot's output.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct LeakCollector {
+    report: Arc<Mutex<LeakReport>>,
+}
+
+impl LeakCollector {
+    pub(crate) fn callback(&self) -> OutputCallback {
+        let report = self.report.clone();
+        Arc::new(move |line| {
+            if let Ok(mut report) = report.lock() {
+                report.scan_line(line);
+            }
+        })
+    }
+
+    pub(crate) fn take(&self) -> LeakReport {
+        std::mem::take(&mut *self.report.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leak_check() {
+        let collector = LeakCollector::default();
+        let callback = collector.callback();
+        for line in [
+            "WARNING: ObjectDB instances leaked at exit (run with --verbose for details).",
+            "     at: cleanup (core/object/object.cpp:2284)",
+            "Leaked instance: Node2D:9223372061282190600",
+            "Leaked instance: Timer:9223372061299017817",
+            "ERROR: 3 RID allocations of type 'N10RendererRD12CanvasItemE' were leaked at exit.",
+            "ERROR: Resources still in use at exit (run with --verbose for details).",
+            "Resource still in use: res://enemy.tres (PackedScene)",
+        ] {
+            callback(line);
+        }
+        let report = collector.take();
+        assert_eq!(
+            report.instances,
+            ["Node2D:9223372061282190600", "Timer:9223372061299017817"]
+        );
+        assert_eq!(
+            report.rids,
+            [RidLeak {
+                kind: "N10RendererRD12CanvasItemE".to_string(),
+                count: 3
+            }]
+        );
+        assert_eq!(report.resources, ["res://enemy.tres (PackedScene)"]);
+        assert_eq!(report.total(), 6);
+        assert!(LeakCheck::default().exceeded(&report));
+        assert!(!LeakCheck::default().max_leaks(Some(6)).exceeded(&report));
+        assert!(!LeakCheck::default().max_leaks(None).exceeded(&report));
+
+        let release = LeakReport {
+            object_db_leaked: true,
+            ..LeakReport::default()
+        };
+        assert_eq!(release.total(), 1);
+        assert!(release.to_string().starts_with("ObjectDB instances leaked"));
+        assert_eq!(collector.take().total(), 0);
+    }
+}
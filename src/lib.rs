@@ -1,139 +1,2330 @@
+pub mod addons;
+pub mod android;
+#[cfg(feature = "asset-tools")]
+pub mod asset_tools;
+pub mod audit;
+pub mod autoload;
+pub mod benchmark;
+#[cfg(feature = "bundle")]
+pub mod bundle;
+pub mod cargo;
+pub mod class_names;
+pub mod codesign;
+pub mod crash_dump;
+pub mod cross_build;
+pub mod debug;
+pub mod deploy;
+pub mod docs;
+pub mod doctor;
+pub mod dotnet;
+pub mod editor_lock;
+pub mod editor_plugin;
+pub mod exit_status;
+pub mod export;
+pub mod export_templates;
+pub mod extension_api;
 pub mod gdextension_config;
+#[cfg(feature = "gdnative")]
+pub mod gdnative;
+pub mod generated_files;
+pub mod godot_args;
 pub mod godot_commands;
+pub mod godot_locator;
+pub mod godot_lock;
+pub mod hot_reload;
+#[cfg(feature = "remote")]
+pub mod input_recording;
+pub mod junit;
+pub mod leak_check;
+pub mod lifecycle;
+pub mod localization;
+pub mod log_file;
+pub mod log_records;
+pub mod movie;
+pub mod notify;
+pub mod output;
+pub mod paths;
+pub mod performance;
+pub mod pipeline;
+pub mod profiler;
+pub mod project_config;
+pub mod project_lock;
+pub mod project_overrides;
+pub mod projects;
+pub mod provenance;
+pub mod readiness;
+#[cfg(feature = "remote")]
+pub mod remote;
+pub mod report;
+pub mod scaffold;
+pub mod state;
+pub mod symbolicate;
+#[cfg(feature = "symbol-check")]
+pub mod symbols;
+pub mod test_context;
+pub mod upload;
+pub mod user_dir;
+pub mod verbosity;
+pub mod version_stamp;
+#[cfg(feature = "visual-test")]
+pub mod visual_test;
+pub mod web;
 
-use crate::gdextension_config::GdExtensionConfig;
-use crate::godot_commands::{run_godot, run_godot_import_if_needed};
-use anyhow::{Context, Result};
+pub use crate::doctor::doctor;
+pub use crate::exit_status::GodotExitStatus;
+pub use crate::report::RunReport;
+pub use crate::test_context::GodotTestContext;
+
+use crate::addons::Addon;
+use crate::autoload::TemporaryAutoload;
+use crate::benchmark::{BenchmarkOptions, BenchmarkReport, OutputTimer, benchmark_file_path};
+use crate::cargo::{CargoBuild, CdylibArtifact};
+use crate::codesign::Codesign;
+use crate::crash_dump::CrashDumps;
+use crate::debug::{DebugConfig, LanguageServer, resolve_port, wait_for_port};
+use crate::deploy::Deploy;
+use crate::docs::DocsUpdate;
+use crate::dotnet::Dotnet;
+use crate::editor_plugin::EditorPlugin;
+use crate::gdextension_config::{GENERATED_HEADER, GdExtensionConfig, ValidGdExtensionConfig};
+use crate::generated_files::{CleanReport, GeneratedFile};
+use crate::godot_commands::{
+    CommandHook, GodotBinary, GodotProcess, GodotVersion, ImportOptions, detect_godot_version,
+    godot_command, godot_process_command, run_godot_import_with_options, spawn_command,
+};
+use crate::godot_locator::{GodotLocator, LocatedGodot};
+use crate::godot_lock::{GodotLock, GodotLockMode};
+use crate::leak_check::{LeakCheck, LeakCollector, LeakReport};
+use crate::lifecycle::{AfterExitHook, BeforeLaunchHook, LaunchContext};
+use crate::log_records::LogRecord;
+use crate::notify::BuildNotification;
+use crate::output::{GodotError, GodotErrorKind, OutputCallback, summarize};
+use crate::paths::CanonicalizeMode;
+use crate::performance::{PerformanceMonitor, PerformanceReport, SampleCollector};
+use crate::pipeline::Pipeline;
+use crate::profiler::{CaptureProcess, Profiler};
+use crate::project_config::ProjectConfig;
+use crate::project_lock::ProjectLock;
+use crate::project_overrides::ProjectOverrides;
+use crate::projects::{GodotProject, ProjectSelector};
+use crate::readiness::{Readiness, ReadyProbe, ReadyProcess};
+use crate::state::{BuildState, BuildStatus, RunState, build_state_path};
+use crate::symbolicate::SymbolicatedFrame;
+use crate::user_dir::IsolatedUserDir;
+use crate::verbosity::Verbosity;
+use crate::version_stamp::VersionStamp;
+use anyhow::{Context, Result, anyhow};
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
+/// The outcome of `GodotRunner::prepare`.
+pub(crate) struct Prepared {
+    pub(crate) godot_project_path: PathBuf,
+    import_status: GodotExitStatus,
+    written_files: Vec<PathBuf>,
+    warnings: Vec<String>,
+    /// The library files of the written `.gdextension` files.
+    libraries: Vec<PathBuf>,
+}
+
+/// The outcome of `GodotRunner::run_prepared`.
+struct Finished {
+    status: GodotExitStatus,
+    errors: Vec<GodotError>,
+    /// The collected crash dump, or why none was found, see `GodotRunner::crash_dumps`.
+    crash_dump: Option<Result<PathBuf>>,
+    backtrace: Vec<SymbolicatedFrame>,
+    /// The samples and violations of `GodotRunner::collect_performance`.
+    performance: Option<PerformanceReport>,
+    /// The leaks reported with `GodotRunner::check_leaks`.
+    leaks: Option<LeakReport>,
+}
+
+/// Godot CLI flags and their values added by `GodotRunner::ci_defaults`.
+const CI_ARGUMENTS: [&[&str]; 3] = [
+    &["--headless"],
+    &["--rendering-driver", "dummy"],
+    &["--audio-driver", "Dummy"],
+];
+
+/// The fixed frame rate of `GodotRunner::deterministic` runs.
+const DETERMINISTIC_FPS: u32 = 60;
+
+/// The environment variable holding the seed of `GodotRunner::deterministic` runs.
+pub const SEED_ENV_VAR: &str = "CARGO_GODOT_SEED";
+
+/// How long `GodotRunner::with_language_server` waits for the language server to start.
+const LANGUAGE_SERVER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Customizes the default configuration of a generated `.gdextension` file.
+type GdExtensionConfigFn =
+    Box<dyn Fn(GdExtensionConfig) -> GdExtensionConfig + Send + Sync + 'static>;
+
+pub struct GodotRunner {
+    crate_name: String,
+    godot_project_path: PathBuf,
+    create_project_if_missing: bool,
+    additional_projects: Vec<GodotProject>,
+    project_selector: ProjectSelector,
+    verbosity: Verbosity,
+    build_notification: Option<BuildNotification>,
+    cargo_manifest_path: PathBuf,
+    gdextension_config: GdExtensionConfigFn,
+    additional_gdextension_configs: Vec<GdExtensionConfigFn>,
+    write_gdextension_config: bool,
+    auto_compatability_version: bool,
+    pre_import: bool,
+    import_options: ImportOptions,
+    godot_cli_arguments: Vec<String>,
+    wrapper_command: Vec<String>,
+    profiler: Option<Profiler>,
+    crash_dumps: Option<CrashDumps>,
+    symbolicate_backtraces: bool,
+    performance_monitor: Option<PerformanceMonitor>,
+    leak_check: Option<LeakCheck>,
+    log_file: Option<PathBuf>,
+    log_retention: usize,
+    godot_version: Option<String>,
+    godot_binary_path: Option<PathBuf>,
+    godot_lock: Option<GodotLockMode>,
+    /// The `gdenv` version installed by `GodotLockMode::Install`.
+    locked_godot_version: OnceLock<String>,
+    dotnet: Option<Dotnet>,
+    /// The .NET Godot binary found for `dotnet`, resolved on first use.
+    dotnet_binary: OnceLock<Option<PathBuf>>,
+    godot_locator: Option<GodotLocator>,
+    /// The Godot binary found by `godot_locator`, resolved on first use.
+    located_godot: OnceLock<Option<LocatedGodot>>,
+    debug: Option<DebugConfig>,
+    force_editor_launch: bool,
+    scan_output_errors: bool,
+    strip_ansi: bool,
+    log_records: Option<Sender<LogRecord>>,
+    project_overrides: ProjectOverrides,
+    ci_defaults: bool,
+    envs: Vec<(OsString, OsString)>,
+    isolated_user_dir: bool,
+    keep_user_dir: bool,
+    deterministic_seed: Option<u64>,
+    frame_limit: Option<u32>,
+    temporary_autoloads: Vec<TemporaryAutoload>,
+    class_names: Vec<String>,
+    discover_class_names: bool,
+    resolve_artifact_dir: bool,
+    canonicalize_mode: CanonicalizeMode,
+    cargo_build: Option<CargoBuild>,
+    cargo_features: Vec<String>,
+    no_default_features: bool,
+    all_features: bool,
+    codesign: Option<Codesign>,
+    deploy: Option<Deploy>,
+    update_gitignore: bool,
+    lint_hot_reload: bool,
+    editor_plugin: Option<EditorPlugin>,
+    version_stamp: Option<VersionStamp>,
+    addons: Vec<Addon>,
+    pipeline: Option<Pipeline>,
+    #[cfg(feature = "symbol-check")]
+    check_entry_symbol: bool,
+    command_hooks: Vec<CommandHook>,
+    before_launch_hooks: Vec<BeforeLaunchHook>,
+    after_exit_hooks: Vec<AfterExitHook>,
+}
+
+impl GodotRunner {
+    /// Example usage:
+    /// ```rust,ignore
+    /// let runner = cargo_godot_lib::GodotRunner::create(
+    ///     env!("CARGO_PKG_NAME"),
+    ///     &std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../godot"),
+    /// );
+    /// if let Err(e) = runner.execute() {
+    ///     eprintln!("{e:?}");
+    ///     std::process::exit(1);
+    /// }
+    /// ```
+    pub fn create(crate_name: &str, godot_project_path: &Path) -> Self {
+        Self {
+            crate_name: crate_name.to_string(),
+            godot_project_path: godot_project_path.into(),
+            create_project_if_missing: false,
+            additional_projects: vec![],
+            project_selector: ProjectSelector::default(),
+            verbosity: Verbosity::default(),
+            build_notification: None,
+            cargo_manifest_path: Path::new("./Cargo.toml").into(),
+            gdextension_config: Box::new(|config| config),
+            additional_gdextension_configs: vec![],
+            write_gdextension_config: true,
+            auto_compatability_version: false,
+            pre_import: true,
+            import_options: ImportOptions::default(),
+            godot_cli_arguments: vec![],
+            wrapper_command: vec![],
+            profiler: None,
+            crash_dumps: None,
+            symbolicate_backtraces: false,
+            performance_monitor: None,
+            leak_check: None,
+            log_file: None,
+            log_retention: 0,
+            godot_version: None,
+            godot_binary_path: None,
+            godot_lock: None,
+            locked_godot_version: OnceLock::new(),
+            dotnet: None,
+            dotnet_binary: OnceLock::new(),
+            godot_locator: None,
+            located_godot: OnceLock::new(),
+            debug: None,
+            force_editor_launch: false,
+            scan_output_errors: false,
+            strip_ansi: false,
+            log_records: None,
+            project_overrides: ProjectOverrides::default(),
+            ci_defaults: false,
+            envs: vec![],
+            isolated_user_dir: false,
+            keep_user_dir: false,
+            deterministic_seed: None,
+            frame_limit: None,
+            temporary_autoloads: vec![],
+            class_names: vec![],
+            discover_class_names: false,
+            resolve_artifact_dir: false,
+            canonicalize_mode: CanonicalizeMode::default(),
+            cargo_build: None,
+            cargo_features: vec![],
+            no_default_features: false,
+            all_features: false,
+            codesign: None,
+            deploy: None,
+            update_gitignore: false,
+            lint_hot_reload: false,
+            editor_plugin: None,
+            version_stamp: None,
+            addons: vec![],
+            pipeline: None,
+            #[cfg(feature = "symbol-check")]
+            check_entry_symbol: false,
+            command_hooks: vec![],
+            before_launch_hooks: vec![],
+            after_exit_hooks: vec![],
+        }
+    }
+
+    /// Run Godot with the current configuration and wait for it to exit.
+    /// Returns an error if Godot could not be launched or exited unsuccessfully.
+    /// With `scan_output_errors`, errors found in Godot's output are also reported as an error.
+    /// With `crash_dumps`, a crash is reported as an error including the path of the dump.
+    pub fn execute(&self) -> Result<()> {
+        self.execute_status()?.into_result()
+    }
+
+    /// Run Godot like `execute`, but return how Godot exited instead of failing on an
+    /// unsuccessful exit, e.g. to pass its exit code on.
+    pub fn execute_status(&self) -> Result<GodotExitStatus> {
+        let prepared = self.prepare()?;
+        self.execute_prepared(&prepared)
+    }
+
+    /// Run Godot for the `prepared` project, reporting failures like `execute`.
+    fn execute_prepared(&self, prepared: &Prepared) -> Result<GodotExitStatus> {
+        let Finished {
+            status,
+            errors,
+            crash_dump,
+            performance,
+            leaks,
+            ..
+        } = self.run_prepared(prepared)?;
+        let failed = !status.is_success()
+            || errors
+                .iter()
+                .any(|error| error.kind == GodotErrorKind::GdExtension);
+        if let Some(crash_dump) = crash_dump {
+            let crash_dump = match crash_dump {
+                Ok(path) => format!("Crash dump: {path:?}"),
+                Err(e) => format!("No crash dump collected: {e:#}"),
+            };
+            let errors = if errors.is_empty() {
+                String::new()
+            } else {
+                format!("\n{}", summarize(&errors))
+            };
+            return Err(anyhow!("{status}\n{crash_dump}{errors}"));
+        }
+        if failed && !errors.is_empty() {
+            return Err(anyhow!("{status}\n{}", summarize(&errors)));
+        }
+        if let Some(performance) = performance
+            && !performance.violations.is_empty()
+        {
+            return Err(anyhow!(
+                "Performance thresholds violated:\n{}",
+                performance.violations.join("\n")
+            ));
+        }
+        if let (Some(leak_check), Some(leaks)) = (&self.leak_check, leaks)
+            && leak_check.exceeded(&leaks)
+        {
+            return Err(anyhow!(
+                "Godot reported {} leaks at exit:\n{leaks}",
+                leaks.total()
+            ));
+        }
+        Ok(status)
+    }
+
+    /// Run Godot like `execute`, but skip the launch if neither the godot project nor the
+    /// extension library changed since the last successful run, e.g. when an editor plugin
+    /// calls the runner on every build. The project is still prepared as configured, including
+    /// the `pre_import`. Returns false if the run was skipped.
+    /// See `state` for how changes are detected.
+    pub fn execute_if_changed(&self) -> Result<bool> {
+        let prepared = self.prepare()?;
+        let state_path = state::state_path(&self.cargo_target_directory()?);
+
+        let state = RunState::fingerprint(&prepared.godot_project_path, &prepared.libraries)?;
+        if prepared.import_status.is_success()
+            && RunState::load(&state_path).as_ref() == Some(&state)
+        {
+            self.verbosity.log(
+                Verbosity::Normal,
+                "Nothing changed since the last successful Godot run, skipping.",
+            );
+            return Ok(false);
+        }
+
+        self.execute_prepared(&prepared)?.into_result()?;
+        // Hash again, the run may have changed project files, e.g. in the editor.
+        RunState::fingerprint(&prepared.godot_project_path, &prepared.libraries)?
+            .save(&state_path)?;
+        Ok(true)
+    }
+
+    /// Run Godot like `execute` and return a machine-readable `RunReport` of the run.
+    /// Unlike `execute`, errors found by `scan_output_errors` are only listed in the report.
+    pub fn execute_with_report(&self) -> Result<RunReport> {
+        let start = Instant::now();
+        let prepared = self.prepare()?;
+        let finished = self.run_prepared(&prepared)?;
+        let duration = start.elapsed();
+
+        Ok(RunReport {
+            binary: godot_command(self.godot_binary())?.get_program().into(),
+            version: self
+                .detected_godot_version()
+                .ok()
+                .map(|version| version.to_string()),
+            args: self.godot_arguments(),
+            duration,
+            exit_status: finished.status,
+            errors: finished.errors,
+            crash_dump: finished.crash_dump.and_then(Result::ok),
+            backtrace: finished.backtrace,
+            performance: finished.performance,
+            leaks: finished.leaks,
+            written_files: prepared.written_files,
+            warnings: prepared.warnings,
+        })
+    }
+
+    /// Launch Godot after a successful import and wait for it to exit.
+    /// Collects the crash dump if Godot crashed and `crash_dumps` is configured,
+    /// and symbolicates backtrace frames if `symbolicate_backtraces` is configured.
+    fn run_prepared(&self, prepared: &Prepared) -> Result<Finished> {
+        if !prepared.import_status.is_success() {
+            return Ok(Finished {
+                status: prepared.import_status,
+                errors: vec![],
+                crash_dump: None,
+                backtrace: vec![],
+                performance: None,
+                leaks: None,
+            });
+        }
+        let mut callbacks: Vec<OutputCallback> = vec![];
+        let frame_lines = Arc::new(Mutex::new(vec![]));
+        if self.symbolicate_backtraces {
+            let frame_lines = frame_lines.clone();
+            callbacks.push(Arc::new(move |line: &str| {
+                if symbolicate::parse_frame(line).is_some()
+                    && let Ok(mut frame_lines) = frame_lines.lock()
+                {
+                    frame_lines.push(line.to_string());
+                }
+            }));
+        }
+        let samples = SampleCollector::default();
+        if self.performance_monitor.is_some() {
+            callbacks.push(samples.callback());
+        }
+        let leaks = LeakCollector::default();
+        if self.leak_check.is_some() {
+            callbacks.push(leaks.callback());
+        }
+        let on_line = output::combine(callbacks);
+        let autoloads: Vec<_> = self
+            .performance_monitor
+            .iter()
+            .map(PerformanceMonitor::autoload)
+            .collect();
+        let launched_at = SystemTime::now();
+        let process = self.launch_configured(&prepared.godot_project_path, on_line, &autoloads)?;
+        let pid = process.id();
+        let (status, errors) = process.wait_with_errors()?;
+        let frame_lines =
+            std::mem::take(&mut *frame_lines.lock().unwrap_or_else(|e| e.into_inner()));
+        let backtrace = symbolicate::symbolicate(&frame_lines, &prepared.libraries);
+        if !backtrace.is_empty() {
+            let frames: Vec<String> = backtrace.iter().map(ToString::to_string).collect();
+            self.verbosity.log(
+                Verbosity::Normal,
+                format!(
+                    "Symbolicated backtrace of the extension:\n{}",
+                    frames.join("\n")
+                ),
+            );
+        }
+        let crash_dump = match (&self.crash_dumps, status) {
+            (Some(crash_dumps), GodotExitStatus::Crashed(_)) => {
+                Some(crash_dumps.collect(pid, launched_at, &prepared.godot_project_path))
+            }
+            _ => None,
+        };
+        let performance = self
+            .performance_monitor
+            .as_ref()
+            .map(|monitor| monitor.report(samples.take()));
+        Ok(Finished {
+            status,
+            errors,
+            crash_dump,
+            backtrace,
+            performance,
+            leaks: self.leak_check.as_ref().map(|_| leaks.take()),
+        })
+    }
+
+    /// Launch Godot with profiling and startup timing flags and measure how long it takes
+    /// to start, e.g. to track startup performance regressions of the extension.
+    /// See `BenchmarkOptions` for how the first frame is detected.
+    ///
+    /// Example usage:
+    /// ```rust,ignore
+    /// let report = runner.benchmark(&BenchmarkOptions::default())?;
+    /// println!("First frame after {:?}", report.time_to_first_frame);
+    /// ```
+    pub fn benchmark(&self, options: &BenchmarkOptions) -> Result<BenchmarkReport> {
+        let godot_project_path = self.prepare_checked()?.godot_project_path;
+        let benchmark_file = benchmark_file_path();
+        let args = self.godot_arguments_with(options.cli_arguments(&benchmark_file));
+
+        let timer = OutputTimer::start();
+        let on_line = timer.callback(options);
+        let process = self.launch_watched(&godot_project_path, &args, Some(on_line))?;
+        let exit_status = process.wait()?;
+        timer.finish(exit_status, &benchmark_file)
+    }
+
+    /// Run Godot in Movie Maker mode, recording every frame to `output_path` at `fps` frames
+    /// per second until Godot exits, e.g. for automated gameplay capture. The extension of
+    /// `output_path` selects the format, see `MovieFormat`. Combine with `frame_limit` to stop
+    /// recording after a number of frames. Returns the path of the (first) recorded file.
+    ///
+    /// Example usage:
+    /// ```rust,ignore
+    /// let movie = runner.frame_limit(600).record_movie(Path::new("target/run.avi"), 60)?;
+    /// ```
+    pub fn record_movie(&self, output_path: &Path, fps: u32) -> Result<PathBuf> {
+        let (output_path, format) = movie::prepare_output_path(output_path)?;
+        let godot_project_path = self.prepare_checked()?.godot_project_path;
+
+        let args = self.godot_arguments_with(movie::cli_arguments(&output_path, fps));
+        self.launch(&godot_project_path, &args)?
+            .wait()?
+            .into_result()
+            .with_context(|| format!("Failed to record movie to {output_path:?}"))?;
+
+        let recorded = format.first_file(&output_path);
+        if !recorded.exists() {
+            return Err(anyhow!(
+                "Godot finished recording but {recorded:?} was not created.\n\
+                Movie Maker mode requires a rendering driver, so it doesn't work with `--headless`."
+            ));
+        }
+        Ok(recorded)
+    }
+
+    /// Generate the XML class reference of the classes registered by the extension
+    /// (`--doctool --gdextension-docs`, Godot 4.3+) and update the files in `doc_dir`,
+    /// usually `docs::DEFAULT_DOC_DIR` in the godot project, so the editor help stays current.
+    ///
+    /// Example usage:
+    /// ```rust,ignore
+    /// let update = runner.generate_docs(&godot_project_path.join(docs::DEFAULT_DOC_DIR))?;
+    /// println!("Updated {} class reference files", update.updated.len());
+    /// ```
+    pub fn generate_docs(&self, doc_dir: &Path) -> Result<DocsUpdate> {
+        let godot_project_path = self.prepare_checked()?.godot_project_path;
+        let generated_dir = tempfile::tempdir().context("Failed to create temporary directory")?;
+
+        self.launch(
+            &godot_project_path,
+            &docs::cli_arguments(generated_dir.path()),
+        )?
+        .wait()?
+        .into_result()
+        .context("Failed to generate the extension class reference")?;
+        docs::update_doc_dir(generated_dir.path(), doc_dir)
+    }
+
+    /// The files and directories written into the godot project with the current configuration,
+    /// e.g. to review them before committing. Files outside the project, such as the state of
+    /// `execute_if_changed` in the cargo target directory, are not listed.
+    pub fn generated_files_manifest(&self) -> Vec<GeneratedFile> {
+        let mut files: Vec<GeneratedFile> = self
+            .gdextension_configs()
+            .map(|config| GeneratedFile {
+                path: config(GdExtensionConfig::default()).file_name().to_string(),
+                description: "The GDExtension configuration, rewritten before every launch",
+                temporary: false,
+                gitignore: true,
+            })
+            .collect();
+        if let Some(deploy) = &self.deploy {
+            let dir = deploy.dir(Path::new(""));
+            files.push(GeneratedFile {
+                path: format!("{}/", dir.to_string_lossy().replace('\\', "/")),
+                description: "The deployed extension library and its debug symbols",
+                temporary: false,
+                gitignore: true,
+            });
+        }
+        if self.editor_plugin.is_some() {
+            files.push(GeneratedFile {
+                path: format!("{}/", editor_plugin::PLUGIN_DIR),
+                description: "The editor plugin, meant to be committed for teammates",
+                temporary: false,
+                gitignore: false,
+            });
+        }
+        files.extend(self.addons.iter().map(|addon| GeneratedFile {
+            path: format!("addons/{}/", addon.name()),
+            description: "A fetched addon dependency",
+            temporary: false,
+            gitignore: true,
+        }));
+        if self.godot_lock.is_some() {
+            files.push(GeneratedFile {
+                path: godot_lock::LOCK_FILE_NAME.to_string(),
+                description: "The locked Godot build, meant to be committed",
+                temporary: false,
+                gitignore: false,
+            });
+        }
+        if let Some(VersionStamp::Autoload { path, .. }) = &self.version_stamp {
+            files.push(GeneratedFile {
+                path: path.clone(),
+                description: "The crate version autoload, part of exports",
+                temporary: false,
+                gitignore: false,
+            });
+        }
+        files.extend([
+            GeneratedFile {
+                path: format!("{}/", autoload::GENERATED_DIR),
+                description: "Temporary autoloads and scripts, and cached engine data",
+                temporary: true,
+                gitignore: true,
+            },
+            GeneratedFile {
+                path: "override.cfg".to_string(),
+                description: "Project setting overrides, restored or removed after every run",
+                temporary: true,
+                gitignore: false,
+            },
+            GeneratedFile {
+                path: project_overrides::BACKUP_FILE_NAME.to_string(),
+                description: "The backup of an existing `override.cfg` during a run",
+                temporary: true,
+                gitignore: true,
+            },
+        ]);
+        files
+    }
+
+    /// Remove everything this crate wrote into the godot projects with the current configuration:
+    /// the `.gdextension` files, restoring the hand-written files they replaced, deployed
+    /// libraries, generated `.gdnlib` and `.gdns` files, the `godot.lock`, temporary autoloads,
+    /// leftover `override.cfg` backups and `.gitignore` entries, as well as the state of
+    /// `execute_if_changed`. Fetched addons are kept, the project may depend on them.
+    /// Useful when switching a project away from this crate.
+    pub fn clean(&self) -> Result<CleanReport> {
+        let godot_project_path = self.validated_project_path()?;
+        let mut report = CleanReport::default();
+
+        let mut project_paths = vec![godot_project_path.clone()];
+        for project in self.godot_projects() {
+            // Projects which do not exist (anymore) contain nothing to clean.
+            if let Ok(path) = project.path.canonicalize()
+                && !project_paths.contains(&path)
+            {
+                project_paths.push(path);
+            }
+        }
+        for project_path in &project_paths {
+            self.clean_project_extension(project_path, &mut report)?;
+        }
+        if self.godot_lock.is_some() {
+            report.remove(&GodotLock::path(&godot_project_path))?;
+        }
+        report.remove(&godot_project_path.join(autoload::GENERATED_DIR))?;
+        if editor_plugin::uninstall(&godot_project_path)? {
+            report
+                .removed
+                .push(godot_project_path.join(editor_plugin::PLUGIN_DIR));
+        }
+        if let Some(version_stamp) = &self.version_stamp {
+            for file in version_stamp.remove(&godot_project_path)? {
+                if file.exists() {
+                    report.edited.push(file);
+                } else {
+                    report.removed.push(file);
+                }
+            }
+        }
+        if project_overrides::restore_backup(&godot_project_path)? {
+            report
+                .restored
+                .push(project_overrides::override_path(&godot_project_path));
+        }
+        if generated_files::remove_gitignore_entries(
+            &godot_project_path,
+            &self.generated_files_manifest(),
+        )? {
+            report.edited.push(godot_project_path.join(".gitignore"));
+        }
+        if let Ok(target_directory) = self.cargo_target_directory() {
+            report.remove(&target_directory.join(state::STATE_DIR))?;
+        }
+        Ok(report)
+    }
+
+    /// Remove the `.gdextension` files, deployed libraries and generated GDNative files of one
+    /// canonicalized godot project for `clean`.
+    fn clean_project_extension(&self, project_path: &Path, report: &mut CleanReport) -> Result<()> {
+        for config in self.gdextension_configs() {
+            let config = config(GdExtensionConfig::default());
+            let path = project_path.join(config.file_name());
+            // Hand-written files, e.g. restored by a previous `clean`, are kept.
+            if std::fs::read_to_string(&path)
+                .is_ok_and(|contents| contents.starts_with(GENERATED_HEADER))
+            {
+                report.remove(&path)?;
+            }
+            if !path.exists()
+                && let Some(backup) = gdextension_config::latest_backup(&path)
+            {
+                std::fs::rename(&backup, &path)
+                    .with_context(|| format!("Failed to restore {path:?} from {backup:?}"))?;
+                report.restored.push(path);
+            }
+        }
+        if let Some(deploy) = &self.deploy {
+            let library_name = self.crate_name.replace('-', "_");
+            for file in deploy.deployed_files(project_path, &library_name) {
+                report.remove(&file)?;
+            }
+            let dir = deploy.dir(project_path);
+            if std::fs::read_dir(&dir).is_ok_and(|mut entries| entries.next().is_none()) {
+                report.remove(&dir)?;
+            }
+        }
+        #[cfg(feature = "gdnative")]
+        for file in gdnative::generated_files(project_path)? {
+            report.remove(&file)?;
+        }
+        Ok(())
+    }
+
+    /// Launch Godot with the current configuration without waiting for it to exit.
+    /// Useful for attaching external tools to the running instance, see `debug`.
+    pub fn spawn(&self) -> Result<GodotProcess> {
+        let prepared = self.prepare_checked()?;
+        self.launch_configured(&prepared.godot_project_path, None, &[])
+    }
+
+    /// Launch Godot like `spawn`, watching its output for the readiness check. Call
+    /// `ReadyProcess::wait_until_ready` before sending input or attaching tools.
+    pub fn spawn_ready(&self, readiness: Readiness) -> Result<ReadyProcess> {
+        let prepared = self.prepare_checked()?;
+        let probe = ReadyProbe::default();
+        let process = self.launch_with(
+            &prepared.godot_project_path,
+            &self.godot_arguments(),
+            Some(probe.callback(readiness.marker())),
+            &readiness.autoloads(),
+        )?;
+        Ok(ReadyProcess::new(process, probe))
+    }
+
+    /// Launch the game with the remote control autoload listening on the local `port`, and
+    /// connect to it once it listens. A `port` of `0` picks a free local port. See `remote`.
+    #[cfg(feature = "remote")]
+    pub fn spawn_remote(&self, port: u16) -> Result<(remote::GodotRemote, GodotProcess)> {
+        let prepared = self.prepare_checked()?;
+        let port = resolve_port(port);
+        let host = "127.0.0.1";
+        let token = remote::generate_token()?;
+        let process = self.launch_with(
+            &prepared.godot_project_path,
+            &self.godot_arguments(),
+            None,
+            &[remote::autoload(port, &token)],
+        )?;
+        let remote = wait_for_port(host, port, remote::REMOTE_START_TIMEOUT)
+            .and_then(|()| remote::GodotRemote::connect((host, port), &token));
+        match remote {
+            Ok(remote) => Ok((remote, process)),
+            Err(e) => {
+                process.kill()?;
+                Err(e.context("The Godot remote control did not start"))
+            }
+        }
+    }
+
+    /// Launch Godot with the configured arguments and debugger, injecting `extra_autoloads`
+    /// and calling `on_line` for every line of Godot's output if given.
+    fn launch_configured(
+        &self,
+        godot_project_path: &Path,
+        on_line: Option<OutputCallback>,
+        extra_autoloads: &[TemporaryAutoload],
+    ) -> Result<GodotProcess> {
+        if let Some(debug) = &self.debug {
+            debug.wait_before_launch()?;
+        }
+
+        let process = self.launch_with(
+            godot_project_path,
+            &self.godot_arguments(),
+            on_line,
+            extra_autoloads,
+        )?;
+
+        if let Some(debug) = &self.debug
+            && let Err(e) = debug.wait_after_launch()
+        {
+            process.kill()?;
+            return Err(e);
+        }
+
+        Ok(process)
+    }
+
+    /// Launch the Godot editor with the GDScript language server listening on `port`
+    /// and wait until it accepts connections. A `port` of `0` picks a free local port.
+    ///
+    /// Example usage:
+    /// ```rust,ignore
+    /// let lsp = runner.with_language_server(6005)?;
+    /// println!("GDScript language server: {}", lsp.address());
+    /// lsp.wait()?;
+    /// ```
+    pub fn with_language_server(&self, port: u16) -> Result<LanguageServer> {
+        let godot_project_path = self.prepare_checked()?.godot_project_path;
+        let port = resolve_port(port);
+        let host = "127.0.0.1";
+
+        let mut args = vec![
+            "--editor".to_string(),
+            "--lsp-port".to_string(),
+            port.to_string(),
+        ];
+        args.extend(self.godot_cli_arguments.iter().cloned());
+        let process = self.launch(&godot_project_path, &args)?;
+
+        if let Err(e) = wait_for_port(host, port, LANGUAGE_SERVER_TIMEOUT) {
+            process.kill()?;
+            return Err(e.context("GDScript language server did not start"));
+        }
+
+        Ok(LanguageServer::new(host, port, process))
+    }
+
+    /// Spawn Godot, guarding against launching a second editor on the same project.
+    fn launch(&self, godot_project_path: &Path, args: &[String]) -> Result<GodotProcess> {
+        self.launch_watched(godot_project_path, args, None)
+    }
+
+    /// Like `launch`, calling `on_line` for every line of Godot's output.
+    fn launch_watched(
+        &self,
+        godot_project_path: &Path,
+        args: &[String],
+        on_line: Option<OutputCallback>,
+    ) -> Result<GodotProcess> {
+        self.launch_with(godot_project_path, args, on_line, &[])
+    }
+
+    /// Like `launch_watched`, injecting `extra_autoloads` in addition to the configured ones.
+    pub(crate) fn launch_with(
+        &self,
+        godot_project_path: &Path,
+        args: &[String],
+        on_line: Option<OutputCallback>,
+        extra_autoloads: &[TemporaryAutoload],
+    ) -> Result<GodotProcess> {
+        let log_args;
+        let args = match &self.log_file {
+            Some(log_file) if !args.iter().any(|arg| arg == "--log-file") => {
+                log_file::rotate(log_file, self.log_retention)?;
+                log_args = [log_file::cli_arguments(log_file)?, args.to_vec()].concat();
+                &log_args
+            }
+            _ => args,
+        };
+        godot_args::validate(args)?;
+        let is_editor = editor_lock::is_editor_launch(args);
+        if is_editor && !self.force_editor_launch {
+            editor_lock::ensure_no_running_editor(godot_project_path)?;
+        }
+
+        let mut callbacks: Vec<OutputCallback> = on_line.into_iter().collect();
+        if let Some(sender) = &self.log_records {
+            callbacks.push(log_records::callback(sender.clone()));
+        }
+        if callbacks.is_empty() && (self.scan_output_errors || self.strip_ansi) {
+            callbacks.push(Arc::new(|_: &str| {}));
+        }
+        let on_line = output::combine(callbacks);
+        let autoloads = [self.temporary_autoloads.as_slice(), extra_autoloads].concat();
+        let (autoload_overrides, autoload_files) =
+            autoload::install(&autoloads, godot_project_path)?;
+        let project_overrides = self
+            .effective_project_overrides()
+            .merge(&autoload_overrides);
+        let overrides = if project_overrides.is_empty() {
+            None
+        } else {
+            Some(project_overrides.apply(godot_project_path)?)
+        };
+        let mut envs = self.envs.clone();
+        let mut wrapper = match &self.crash_dumps {
+            Some(crash_dumps) => {
+                crash_dumps.setup(godot_command(self.godot_binary())?.get_program())?
+            }
+            None => vec![],
+        };
+        wrapper.extend(self.wrapper_command.iter().cloned());
+        let mut capture = None;
+        if let Some(profiler) = &self.profiler {
+            let setup = profiler.setup()?;
+            envs.extend(setup.envs);
+            wrapper.extend(setup.wrapper);
+            capture = setup.capture;
+        }
+        if let Some(seed) = self.deterministic_seed {
+            envs.push((SEED_ENV_VAR.into(), seed.to_string().into()));
+        }
+        let user_dir = if self.isolated_user_dir {
+            let user_dir = IsolatedUserDir::create(self.keep_user_dir)?;
+            if !user_dir.is_temporary() {
+                self.verbosity.log(
+                    Verbosity::Normal,
+                    format!("Godot user data directory: {:?}", user_dir.path()),
+                );
+            }
+            envs.extend(user_dir.environment());
+            Some(user_dir)
+        } else {
+            None
+        };
+
+        self.verbosity.log(
+            Verbosity::Verbose,
+            format!(
+                "Godot binary: {:?}",
+                godot_command(self.godot_binary())?.get_program()
+            ),
+        );
+        let command = godot_process_command(
+            godot_project_path,
+            self.godot_binary(),
+            &wrapper,
+            args,
+            &envs,
+            on_line.is_some(),
+            &self.command_hooks,
+        )?;
+        self.verbosity
+            .log(Verbosity::Debug, format!("Godot command: {command:?}"));
+        let context = LaunchContext {
+            crate_name: &self.crate_name,
+            godot_project_path,
+            args,
+            command: &command,
+        };
+        for hook in &self.before_launch_hooks {
+            hook(&context).context("A before_launch hook failed")?;
+        }
+        let mut process =
+            spawn_command(command, on_line, self.strip_ansi, self.scan_output_errors)?;
+        for hook in &self.after_exit_hooks {
+            let hook = hook.clone();
+            let crate_name = self.crate_name.clone();
+            let godot_project_path = godot_project_path.to_path_buf();
+            let args = args.to_vec();
+            process.on_exit(Box::new(move |command, status| {
+                let context = LaunchContext {
+                    crate_name: &crate_name,
+                    godot_project_path: &godot_project_path,
+                    args: &args,
+                    command,
+                };
+                hook(&context, status).context("An after_exit hook failed")
+            }));
+        }
+        if let Some(overrides) = overrides {
+            process.hold(overrides);
+        }
+        process.hold(autoload_files);
+        if let Some(capture) = capture {
+            process.hold(CaptureProcess::spawn(capture)?);
+        }
+        if let Some(user_dir) = user_dir {
+            process.hold(user_dir);
+        }
+        if is_editor {
+            let lock = editor_lock::write_lock(godot_project_path, process.id())?;
+            process.set_editor_lock(lock);
+        }
+        Ok(process)
+    }
+
+    /// Reimport only `files` of the godot project, see `godot_commands::reimport_files`.
+    pub fn reimport_files(&self, files: &[impl AsRef<Path>]) -> Result<GodotExitStatus> {
+        let godot_project_path = self.validated_project_path()?;
+        let _lock = ProjectLock::acquire(&godot_project_path, self.verbosity)?;
+        godot_commands::reimport_files(
+            &godot_project_path,
+            self.godot_binary(),
+            files,
+            self.verbosity,
+        )
+    }
+
+    /// Reimport the files in the `written` files and directories, skipping `.import` files.
+    fn reimport_written(&self, godot_project_path: &Path, written: &[PathBuf]) -> Result<()> {
+        let mut files = vec![];
+        for path in written {
+            if path.is_dir() {
+                state::find_project_files(path, &mut files)?;
+            } else if path.exists() {
+                files.push(path.clone());
+            }
+        }
+        files.retain(|file| {
+            file.extension()
+                .is_none_or(|extension| extension != "import")
+        });
+        godot_commands::reimport_files(
+            godot_project_path,
+            self.godot_binary(),
+            &files,
+            self.verbosity,
+        )?
+        .into_result()
+        .context("Failed to reimport the files written by the pipeline")
+    }
+
+    /// Import the project with `godot --import --headless` using the configured `import_options`,
+    /// writing the `.gdextension` file first if configured. Unlike `pre_import`, this also runs
+    /// when the `.godot` folder already exists if `ImportOptions::force` is set.
+    pub fn import(&self) -> Result<GodotExitStatus> {
+        let godot_project_path = self.validated_project_path()?;
+        let _lock = ProjectLock::acquire(&godot_project_path, self.verbosity)?;
+        if self.write_gdextension_config {
+            self.write_gdextension(&godot_project_path)?;
+        }
+        self.run_import(&godot_project_path)
+    }
+
+    /// Import the project with the configured `import_options`, recording the Godot version
+    /// in the `BuildState` if Godot imported it.
+    fn run_import(&self, godot_project_path: &Path) -> Result<GodotExitStatus> {
+        let imports = self.import_options.force || !godot_project_path.join(".godot").exists();
+        let status = run_godot_import_with_options(
+            godot_project_path,
+            self.godot_binary(),
+            &self.import_options.clone().verbosity(self.verbosity),
+        )?;
+        if imports && status.is_success() {
+            self.update_build_state(None, |state| {
+                let version = self.detected_godot_version()?;
+                state.record_import(&version.to_string());
+                Ok(())
+            });
+        }
+        Ok(status)
+    }
+
+    /// Update the `BuildState` in `target_directory`, or the cargo target directory.
+    /// Failures are only warned about, since the state is informational.
+    fn update_build_state(
+        &self,
+        target_directory: Option<&Path>,
+        f: impl FnOnce(&mut BuildState) -> Result<()>,
+    ) {
+        let target_directory = match target_directory {
+            Some(target_directory) => Ok(target_directory.to_path_buf()),
+            None => self.cargo_target_directory(),
+        };
+        let updated = target_directory.and_then(|target_directory| {
+            BuildState::update(&build_state_path(&target_directory), f)
+        });
+        if let Err(e) = updated {
+            eprintln!("Warning: Failed to update the build state: {e:#}");
+        }
+    }
+
+    /// What is stale according to the `BuildState` in the cargo target directory, comparing the
+    /// import with the configured Godot version. See `state::BuildState::status`.
+    pub fn build_status(&self) -> Result<BuildStatus> {
+        let state = BuildState::load(&build_state_path(&self.cargo_target_directory()?))
+            .unwrap_or_default();
+        let godot_version = self
+            .detected_godot_version()
+            .ok()
+            .map(|version| version.to_string());
+        state.status(godot_version.as_deref())
+    }
+
+    /// Write the `.gdextension` file and import the project as configured, holding the
+    /// `ProjectLock`. Returns the canonicalized godot project path, the status of the import,
+    /// and the files written along the way.
+    fn prepare(&self) -> Result<Prepared> {
+        let godot_project_path = self.validated_project_path()?;
+        let _lock = ProjectLock::acquire(&godot_project_path, self.verbosity)?;
+        let mut written_files = vec![];
+
+        if self.apply_godot_lock(&godot_project_path)? {
+            written_files.push(GodotLock::path(&godot_project_path));
+        }
+        let mut warnings = vec![];
+        let mut libraries = vec![];
+
+        if self.write_gdextension_config {
+            let configs = self.write_gdextension(&godot_project_path)?;
+            for config in &configs {
+                written_files.push(config.full_config_path());
+                warnings.extend(config.warnings().iter().cloned());
+                libraries.extend(config.library_files());
+            }
+            if self.lint_hot_reload && configs.iter().any(ValidGdExtensionConfig::reloadable) {
+                let lint_warnings = self.lint_hot_reload_setup();
+                for warning in &lint_warnings {
+                    eprintln!("Warning: {warning}");
+                }
+                warnings.extend(lint_warnings);
+            }
+        }
+
+        if self.update_gitignore {
+            let added = generated_files::update_gitignore(
+                &godot_project_path,
+                &self.generated_files_manifest(),
+            )?;
+            if !added.is_empty() {
+                written_files.push(godot_project_path.join(".gitignore"));
+            }
+        }
+
+        if let Some(plugin) = &self.editor_plugin {
+            written_files.extend(plugin.install(&godot_project_path)?);
+        }
+
+        if let Some(version_stamp) = &self.version_stamp {
+            let version =
+                version_stamp::crate_version(&self.cargo_manifest_path, &self.crate_name)?;
+            written_files.extend(version_stamp.stamp(
+                &godot_project_path,
+                &self.crate_name,
+                &version,
+            )?);
+        }
+
+        written_files.extend(addons::fetch(&self.addons, &godot_project_path)?);
+
+        if let Some(pipeline) = &self.pipeline {
+            let state_path = pipeline::pipeline_state_path(&self.cargo_target_directory()?);
+            let report = pipeline.run(&godot_project_path, &state_path)?;
+            if !report.ran.is_empty() {
+                self.verbosity.log(
+                    Verbosity::Normal,
+                    format!("Ran pipeline steps: {}", report.ran.join(", ")),
+                );
+            }
+            // Godot only imports changed files in the editor, so reimport what the steps wrote.
+            if !report.written.is_empty() && godot_project_path.join(".godot").exists() {
+                self.reimport_written(&godot_project_path, &report.written)?;
+            }
+            written_files.extend(report.written);
+        }
+
+        if let Some(dotnet) = &self.dotnet {
+            dotnet.prepare(&godot_project_path)?;
+            if !self
+                .detected_godot_version()
+                .is_ok_and(|version| version.mono)
+            {
+                let warning = "The Godot binary is not a .NET build, so C# scripts won't load. \
+                    Set the `GODOT` environment variable to a .NET build of Godot."
+                    .to_string();
+                eprintln!("Warning: {warning}");
+                warnings.push(warning);
+            }
+        }
+
+        if !self.class_names.is_empty() || self.discover_class_names {
+            let class_warnings = self.check_class_names_against_engine(&godot_project_path);
+            for warning in &class_warnings {
+                eprintln!("Warning: {warning}");
+            }
+            warnings.extend(class_warnings);
+        }
+
+        let import_status = if self.pre_import {
+            self.run_import(&godot_project_path)?
+        } else {
+            GodotExitStatus::Success
+        };
+
+        Ok(Prepared {
+            godot_project_path,
+            import_status,
+            written_files,
+            warnings,
+            libraries,
+        })
+    }
+
+    /// Check, install or update the Godot build of the `godot.lock` file as configured.
+    /// Returns true if the lock file was written.
+    fn apply_godot_lock(&self, godot_project_path: &Path) -> Result<bool> {
+        let Some(mode) = self.godot_lock else {
+            return Ok(false);
+        };
+        let current = match self.used_located_godot() {
+            Some(located) => GodotLock::located(located),
+            None => match (&self.godot_version, &self.godot_binary_path) {
+                (Some(version), _) => GodotLock::current(GodotBinary::Version(version)),
+                (None, Some(path)) => GodotLock::current(path.as_path()),
+                (None, None) => GodotLock::current(GodotBinary::Discover),
+            },
+        };
+        let lock = match GodotLock::load(godot_project_path)? {
+            Some(lock) => lock,
+            None => {
+                current?.save(godot_project_path)?;
+                return Ok(true);
+            }
+        };
+        match mode {
+            GodotLockMode::Verify => lock.check(&current?)?,
+            GodotLockMode::Install => {
+                if current.and_then(|current| lock.check(&current)).is_err() {
+                    lock.install()?;
+                    let version = lock.gdenv_version()?;
+                    lock.check(&GodotLock::current(GodotBinary::Version(&version))?)?;
+                    let _ = self.locked_godot_version.set(version);
+                }
+            }
+            GodotLockMode::Update => {
+                let updated = lock.updated(&current?);
+                if updated != lock {
+                    updated.save(godot_project_path)?;
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// The Godot binary to run: the `godot_version`, the version installed for the `godot_lock`,
+    /// the `godot_binary_path`, the .NET Godot binary preferred by `dotnet`, the binary cached by
+    /// the `godot_locator`, or the discovered binary.
+    fn godot_binary(&self) -> GodotBinary<'_> {
+        let version = self
+            .godot_version
+            .as_deref()
+            .or(self.locked_godot_version.get().map(String::as_str));
+        if let Some(version) = version {
+            return GodotBinary::Version(version);
+        }
+        if let Some(path) = &self.godot_binary_path {
+            return GodotBinary::Path(path);
+        }
+        let dotnet_binary = self.dotnet.as_ref().and_then(|dotnet| {
+            self.dotnet_binary
+                .get_or_init(|| {
+                    dotnet
+                        .prefers_dotnet_editor()
+                        .then(dotnet::find_dotnet_binary)
+                        .flatten()
+                })
+                .as_deref()
+        });
+        match dotnet_binary.or_else(|| Some(&self.located_godot()?.path)) {
+            Some(path) => GodotBinary::Path(path),
+            None => GodotBinary::Discover,
+        }
+    }
+
+    /// The Godot binary of the `godot_locator`. A failure is only warned about, falling back to
+    /// discovery on every launch.
+    fn located_godot(&self) -> Option<&LocatedGodot> {
+        let locator = self.godot_locator.as_ref()?;
+        self.located_godot
+            .get_or_init(|| match locator.locate() {
+                Ok(located) => Some(located),
+                Err(e) => {
+                    eprintln!("Warning: Failed to locate Godot: {e:#}");
+                    None
+                }
+            })
+            .as_ref()
+    }
+
+    /// The binary located by the `godot_locator` if the runner uses it.
+    fn used_located_godot(&self) -> Option<&LocatedGodot> {
+        let located = self.located_godot()?;
+        (self.godot_binary() == GodotBinary::Path(&located.path)).then_some(located)
+    }
+
+    /// The version of the Godot binary the runner uses, cached by the `godot_locator` if it
+    /// located the binary.
+    fn detected_godot_version(&self) -> Result<GodotVersion> {
+        match self.used_located_godot() {
+            Some(located) => located.godot_version(),
+            None => detect_godot_version(self.godot_binary()),
+        }
+    }
+
+    /// Warnings for extension class names which collide with engine classes.
+    /// A failure to run the check is reported as a warning as well.
+    fn check_class_names_against_engine(&self, godot_project_path: &Path) -> Vec<String> {
+        let check = || -> Result<Vec<String>> {
+            let mut names = self.class_names.clone();
+            if self.discover_class_names {
+                let src_dir = self
+                    .cargo_manifest_path
+                    .parent()
+                    .unwrap_or(Path::new("."))
+                    .join("src");
+                for name in class_names::discover_class_names(&src_dir)? {
+                    if !names.contains(&name) {
+                        names.push(name);
+                    }
+                }
+            }
+            let api = class_names::cached_extension_api(godot_project_path, self.godot_binary())?;
+            Ok(class_names::find_collisions(&api, &names)
+                .iter()
+                .map(|name| class_names::collision_warning(name))
+                .collect())
+        };
+        check().unwrap_or_else(|e| vec![format!("Skipped class name check: {e:#}")])
+    }
+
+    /// Warnings for setups known to break hot reloading, see `hot_reload`.
+    /// A failure to run the lint is reported as a warning as well.
+    fn lint_hot_reload_setup(&self) -> Vec<String> {
+        let lint = || -> Result<Vec<String>> {
+            let metadata = cargo_metadata::MetadataCommand::new()
+                .manifest_path(&self.cargo_manifest_path)
+                .no_deps()
+                .exec()
+                .context("Failed to read cargo metadata")?;
+            let manifest_path = metadata.workspace_root.join("Cargo.toml");
+            let manifest = std::fs::read_to_string(&manifest_path)
+                .with_context(|| format!("Failed to read {manifest_path:?}"))?;
+            let profiles = match &self.cargo_build {
+                Some(cargo_build) => vec![cargo_build.profile_name()],
+                None => vec!["dev", "release"],
+            };
+            let mut warnings: Vec<String> = profiles
+                .into_iter()
+                .flat_map(|profile| hot_reload::lint_profile(&manifest, profile))
+                .collect();
+            let src_dir = self
+                .cargo_manifest_path
+                .parent()
+                .unwrap_or(Path::new("."))
+                .join("src");
+            warnings.extend(hot_reload::lint_sources(&src_dir)?);
+            Ok(warnings)
+        };
+        lint().unwrap_or_else(|e| vec![format!("Skipped hot reload lint: {e:#}")])
+    }
+
+    /// Like `prepare`, but fails if the import failed.
+    pub(crate) fn prepare_checked(&self) -> Result<Prepared> {
+        let prepared = self.prepare()?;
+        prepared.import_status.into_result()?;
+        Ok(prepared)
+    }
+
+    /// The main and the additional godot projects.
+    fn godot_projects(&self) -> Vec<GodotProject> {
+        std::iter::once(GodotProject::main(&self.godot_project_path))
+            .chain(self.additional_projects.iter().cloned())
+            .collect()
+    }
+
+    /// The godot project chosen with `select_project`.
+    fn selected_project(&self) -> Result<GodotProject> {
+        self.project_selector
+            .select(&self.godot_projects())
+            .cloned()
+    }
+
+    /// Returns the canonicalized path of the selected godot project after checking it contains a
+    /// Godot 4 project.
+    fn validated_project_path(&self) -> Result<PathBuf> {
+        self.validate_project_path(&self.selected_project()?.path)
+    }
+
+    /// Returns the canonicalized `godot_project_path` after checking it contains a Godot 4
+    /// project, creating one first with `create_project_if_missing`.
+    fn validate_project_path(&self, godot_project_path: &Path) -> Result<PathBuf> {
+        if self.create_project_if_missing && !ProjectConfig::path(godot_project_path).exists() {
+            let version = self
+                .detected_godot_version()
+                .ok()
+                .map(|version| version.compatibility());
+            scaffold::create_project(godot_project_path, &self.crate_name, version.as_deref())?;
+            self.verbosity.log(
+                Verbosity::Normal,
+                format!("Created a godot project at {godot_project_path:?}"),
+            );
+        }
+        let canonical_path = paths::canonicalize(godot_project_path, self.canonicalize_mode)
+            .with_context(|| {
+                format!("Failed to canonicalize godot project path: {godot_project_path:?}")
+            })?;
+        ProjectConfig::load(&canonical_path)?.validate()?;
+        Ok(canonical_path)
+    }
+
+    /// The `cargo_build` with the feature options, or the default `CargoBuild` if the library
+    /// needs to be built anyway.
+    fn effective_cargo_build(&self) -> Option<CargoBuild> {
+        let feature_options =
+            !self.cargo_features.is_empty() || self.no_default_features || self.all_features;
+        let mut cargo_build = self.cargo_build.clone().or_else(|| {
+            (self.resolve_artifact_dir || self.deploy.is_some() || feature_options)
+                .then(CargoBuild::default)
+        })?;
+        cargo_build = cargo_build.features(self.cargo_features.iter().cloned());
+        if self.no_default_features {
+            cargo_build = cargo_build.no_default_features(true);
+        }
+        if self.all_features {
+            cargo_build = cargo_build.all_features(true);
+        }
+        Some(cargo_build)
+    }
+
+    /// The main and the additional `.gdextension` configurations.
+    fn gdextension_configs(&self) -> impl Iterator<Item = &GdExtensionConfigFn> {
+        std::iter::once(&self.gdextension_config).chain(&self.additional_gdextension_configs)
+    }
+
+    /// Generate and write the `.gdextension` files into the selected godot project at
+    /// `godot_project_path` and every other registered project. Returns the configs written into
+    /// the selected project, starting with the main config.
+    fn write_gdextension(&self, godot_project_path: &Path) -> Result<Vec<ValidGdExtensionConfig>> {
+        let cargo_build = self.effective_cargo_build();
+        let library = match &cargo_build {
+            Some(cargo_build) => Some(self.build_library(cargo_build)?),
+            None => None,
+        };
+        let target_directory = match &library {
+            Some(library) => library
+                .target_directory()
+                .with_context(|| format!("Unexpected library path: {:?}", library.path))?
+                .to_path_buf(),
+            None => self.cargo_target_directory()?,
+        };
+        let selected = self.selected_project()?;
+        let configs = self.write_project_gdextension(
+            &selected.path,
+            godot_project_path,
+            cargo_build.as_ref(),
+            library.as_ref(),
+            &target_directory,
+        )?;
+        for project in self.godot_projects() {
+            let canonical_path = self
+                .validate_project_path(&project.path)
+                .with_context(|| format!("Invalid godot project {:?}", project.name))?;
+            // The selected project may be configured again under another path, e.g. `./godot`.
+            if canonical_path == godot_project_path {
+                continue;
+            }
+            let _lock = ProjectLock::acquire(&canonical_path, self.verbosity)?;
+            self.write_project_gdextension(
+                &project.path,
+                &canonical_path,
+                cargo_build.as_ref(),
+                library.as_ref(),
+                &target_directory,
+            )?;
+        }
+        Ok(configs)
+    }
+
+    /// Generate and write the `.gdextension` files of one godot project, given as configured and
+    /// canonicalized. Returns the written configs, starting with the main config.
+    fn write_project_gdextension(
+        &self,
+        project_path: &Path,
+        godot_project_path: &Path,
+        cargo_build: Option<&CargoBuild>,
+        library: Option<&CdylibArtifact>,
+        target_directory: &Path,
+    ) -> Result<Vec<ValidGdExtensionConfig>> {
+        let mut default_config =
+            GdExtensionConfig::start(&self.crate_name, project_path, target_directory)
+                .canonicalize_mode(self.canonicalize_mode);
+        if let (Some(cargo_build), Some(library), Some(deploy)) =
+            (cargo_build, library, &self.deploy)
+        {
+            let deployed = deploy
+                .deploy(&library.path, godot_project_path)
+                .context("Failed to deploy the extension library")?;
+            default_config = default_config.library_file(
+                cargo_build.gdextension_build(),
+                &library.target,
+                &deployed.library,
+            );
+        } else if let (Some(cargo_build), Some(library)) = (cargo_build, library) {
+            default_config = default_config.library_file(
+                cargo_build.gdextension_build(),
+                &library.target,
+                &library.path,
+            );
+        }
+        if self.auto_compatability_version
+            && let Some(version) = self.detect_compatability_version(godot_project_path)
+        {
+            default_config = default_config.compatability_version(&version);
+        }
+        let mut configs: Vec<ValidGdExtensionConfig> = vec![];
+        for config in self.gdextension_configs() {
+            let config = config(default_config.clone())
+                .build()
+                .context("Failed to build .gdextension config")?;
+            if configs
+                .iter()
+                .any(|other| other.full_config_path() == config.full_config_path())
+            {
+                return Err(anyhow!(
+                    "Multiple .gdextension configs are written to {:?}. \
+                    Set a distinct `config_file_name` for each additional config.",
+                    config.full_config_path()
+                ));
+            }
+            for warning in config.warnings() {
+                eprintln!("Warning: {warning}");
+            }
+            configs.push(config);
+        }
+        #[cfg(feature = "symbol-check")]
+        if self.check_entry_symbol {
+            for config in &configs {
+                for library in config.library_files().iter().filter(|file| file.exists()) {
+                    symbols::check_entry_symbol(library, config.entry_symbol())?;
+                }
+            }
+        }
+        if let Some(codesign) = &self.codesign {
+            let mut libraries: Vec<PathBuf> = vec![];
+            for library in configs
+                .iter()
+                .flat_map(ValidGdExtensionConfig::library_files)
+            {
+                if !libraries.contains(&library) {
+                    libraries.push(library);
+                }
+            }
+            codesign
+                .sign_dylibs(&libraries)
+                .context("Failed to codesign the extension library")?;
+        }
+        for config in &configs {
+            let backup = config
+                .write()
+                .with_context(|| format!("Failed to write {:?}", config.full_config_path()))?;
+            if let Some(backup) = backup {
+                eprintln!(
+                    "Warning: {:?} was not generated by cargo-godot-lib, backed it up to {backup:?}. \
+                    Use `GdExtensionConfig::force` to overwrite it without a backup.",
+                    config.full_config_path()
+                );
+            }
+            self.verbosity.log(
+                Verbosity::Verbose,
+                format!("Wrote {:?}", config.full_config_path()),
+            );
+        }
+        Ok(configs)
+    }
+
+    /// The cargo target directory of the crate according to `cargo metadata`.
+    fn cargo_target_directory(&self) -> Result<PathBuf> {
+        Ok(cargo_metadata::MetadataCommand::new()
+            .manifest_path(&self.cargo_manifest_path)
+            .exec()?
+            .target_directory
+            .into_std_path_buf())
+    }
+
+    /// Build the library with `cargo_build` and return the cdylib cargo produced.
+    fn build_library(&self, cargo_build: &CargoBuild) -> Result<CdylibArtifact> {
+        let library = cargo_build
+            .build(&self.cargo_manifest_path)
+            .and_then(|artifacts| {
+                cargo::find_library(&artifacts, &self.crate_name)
+                    .cloned()
+                    .with_context(|| {
+                        format!(
+                            "cargo build did not produce a cdylib for {:?}. \
+                            Is `crate-type = [\"cdylib\"]` set in {:?}?",
+                            self.crate_name, self.cargo_manifest_path
+                        )
+                    })
+            });
+        if let Some(notification) = self.build_notification {
+            notification.send(&self.crate_name, library.as_ref().err());
+        }
+        let library = library?;
+        self.update_build_state(library.target_directory(), |state| {
+            state.record_artifact(&library)
+        });
+        Ok(library)
+    }
+
+    /// Detect the `major.minor` Godot version from `project.godot`'s `config/features`,
+    /// falling back to the version of the Godot binary.
+    fn detect_compatability_version(&self, godot_project_path: &Path) -> Option<String> {
+        ProjectConfig::load(godot_project_path)
+            .ok()
+            .and_then(|config| config.engine_version())
+            .or_else(|| {
+                self.detected_godot_version()
+                    .ok()
+                    .map(|version| version.compatibility())
+            })
+    }
+
+    /// The full list of arguments passed to Godot.
+    fn godot_arguments(&self) -> Vec<String> {
+        self.godot_arguments_with(vec![])
+    }
+
+    /// The full list of arguments passed to Godot, adding `extra` flags with their values.
+    /// Extra flags replace the runner's default flags, but not flags set by the user.
+    pub(crate) fn godot_arguments_with(&self, extra: Vec<Vec<String>>) -> Vec<String> {
+        let mut flags = extra;
+        if self.ci_defaults {
+            flags.extend(
+                CI_ARGUMENTS
+                    .iter()
+                    .map(|flag| flag.iter().map(|arg| arg.to_string()).collect()),
+            );
+        }
+        if self.deterministic_seed.is_some() {
+            flags.push(vec![
+                "--fixed-fps".to_string(),
+                DETERMINISTIC_FPS.to_string(),
+            ]);
+            flags.push(vec!["--disable-vsync".to_string()]);
+        }
+        if let Some(frames) = self.frame_limit {
+            flags.push(vec!["--quit-after".to_string(), frames.to_string()]);
+        }
+        if self.leak_check.is_some() {
+            flags.push(vec!["--verbose".to_string()]);
+        }
+
+        let mut args: Vec<String> = vec![];
+        // Skip flags the user already set, e.g. a different `--rendering-driver`.
+        for flag in flags {
+            if !self.godot_cli_arguments.contains(&flag[0]) && !args.contains(&flag[0]) {
+                args.extend(flag);
+            }
+        }
+        if let Some(debug) = &self.debug {
+            args.extend(debug.cli_arguments());
+        }
+        args.extend(self.godot_cli_arguments.iter().cloned());
+        if let Some(seed) = self.deterministic_seed {
+            push_user_argument(&mut args, format!("--seed={seed}"));
+        }
+        args
+    }
+
+    /// The project overrides applied while Godot runs, including those of `ci_defaults`.
+    fn effective_project_overrides(&self) -> ProjectOverrides {
+        if self.ci_defaults {
+            ProjectOverrides::default()
+                .dummy_audio()
+                .setting("application/boot_splash/show_image", false)
+                .merge(&self.project_overrides)
+        } else {
+            self.project_overrides.clone()
+        }
+    }
+
+    /// Specify the path to the cargo manifest. Default: `./Cargo.toml`.
+    pub fn cargo_manifest_path(self, cargo_manifest_path: &Path) -> Self {
+        Self {
+            cargo_manifest_path: cargo_manifest_path.to_path_buf(),
+            ..self
+        }
+    }
+
+    /// Write the `.gdextension` config file before launching Godot. Default: true.
+    /// See also: `gdextension_config`.
+    pub fn write_gdextension_config(self, write_gdextension_config: bool) -> Self {
+        Self {
+            write_gdextension_config,
+            ..self
+        }
+    }
+
+    /// Derive `compatibility_minimum` of the generated `.gdextension` file from the engine version
+    /// in `project.godot` (`config/features`), or from the Godot binary's version if that fails.
+    /// Falls back to the `GdExtensionConfig` default when detection fails. Default: false.
+    pub fn auto_compatability_version(self, auto_compatability_version: bool) -> Self {
+        Self {
+            auto_compatability_version,
+            ..self
+        }
+    }
+
+    /// Replace the default configuration for the `.gdextension` file which is generated before Godot launch.
+    /// See also: `write_gdextension_config`.
+    pub fn gdextension_config(
+        mut self,
+        f: impl Fn(GdExtensionConfig) -> GdExtensionConfig + Send + Sync + 'static,
+    ) -> Self {
+        self.gdextension_config = Box::new(f);
+        self
+    }
+
+    /// Generate another `.gdextension` file from the same default configuration, e.g. for a
+    /// feature-gated build of the crate with its own entry symbol and library. Each additional
+    /// configuration needs a distinct `config_file_name`.
+    ///
+    /// Example usage:
+    /// ```rust,ignore
+    /// runner.additional_gdextension_config(|config| {
+    ///     config
+    ///         .config_file_name("tools.gdextension")
+    ///         .entry_symbol("tools_init")
+    ///         .library_name("my_crate_tools")
+    /// })
+    /// ```
+    pub fn additional_gdextension_config(
+        mut self,
+        f: impl Fn(GdExtensionConfig) -> GdExtensionConfig + Send + Sync + 'static,
+    ) -> Self {
+        self.additional_gdextension_configs.push(Box::new(f));
+        self
+    }
+
+    /// Run `godot --import --headless` before launching Godot to create a `.godot` folder
+    /// if it doesn't exist. Default: true.
+    pub fn pre_import(self, pre_import: bool) -> Self {
+        Self { pre_import, ..self }
+    }
+
+    /// Configure how the project is imported by `pre_import` and `import`.
+    /// See `ImportOptions` for details.
+    pub fn import_options(self, import_options: ImportOptions) -> Self {
+        Self {
+            import_options,
+            ..self
+        }
+    }
+
+    /// Set additional arguments to the Godot CLI.
+    /// See https://docs.godotengine.org/en/stable/tutorials/editor/command_line_tutorial.html
+    /// for a list of available arguments.
+    pub fn godot_cli_arguments(self, args: Vec<impl Into<String>>) -> Self {
+        Self {
+            godot_cli_arguments: args.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    /// Add `--resolution <width>x<height>`, the window size.
+    pub fn arg_resolution(mut self, width: u32, height: u32) -> Self {
+        self.godot_cli_arguments
+            .extend(godot_args::resolution(width, height));
+        self
+    }
+
+    /// Add `--position <x>,<y>`, the window position.
+    pub fn arg_position(mut self, x: i32, y: i32) -> Self {
+        self.godot_cli_arguments.extend(godot_args::position(x, y));
+        self
+    }
+
+    /// Add `--fullscreen`, which excludes `--maximized` and `--windowed`.
+    pub fn arg_fullscreen(mut self) -> Self {
+        self.godot_cli_arguments.push("--fullscreen".to_string());
+        self
+    }
+
+    /// Add `--verbose`, which excludes `--quiet`.
+    pub fn arg_verbose(mut self) -> Self {
+        self.godot_cli_arguments.push("--verbose".to_string());
+        self
+    }
+
+    /// Add `--quit-after <frames>`. Unlike `frame_limit`, this fails if `--quit-after` is given
+    /// twice.
+    pub fn arg_quit_after(mut self, frames: u32) -> Self {
+        self.godot_cli_arguments
+            .extend(godot_args::quit_after(frames));
+        self
+    }
+
+    /// Add an argument without a typed builder. Unlike `godot_cli_arguments`, previously added
+    /// arguments are kept. Arguments are checked for conflicts before launch,
+    /// see `godot_args::validate`.
+    pub fn raw_arg(mut self, arg: impl Into<String>) -> Self {
+        self.godot_cli_arguments.push(arg.into());
+        self
+    }
+
+    /// Launch Godot through a wrapper program, e.g. `["gdb", "--args"]`, `["valgrind"]` or
+    /// `["renderdoccmd", "capture"]`, to debug crashes of the extension natively. The Godot
+    /// binary and its arguments are appended to the wrapper's arguments, and the working
+    /// directory and environment are kept. The import is not wrapped. Default: no wrapper.
+    pub fn wrapper_command<S: Into<String>>(self, wrapper: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            wrapper_command: wrapper.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    /// Attach RenderDoc or Tracy to the launched Godot instance, checking that it is installed
+    /// first. A wrapper program of the profiler runs inside the `wrapper_command`.
+    /// See `profiler::Profiler`. Default: no profiler.
+    pub fn profiler(self, profiler: Profiler) -> Self {
+        Self {
+            profiler: Some(profiler),
+            ..self
+        }
+    }
+
+    /// Collect a dump when Godot crashes and include its path in the error of `execute` and in
+    /// the `RunReport`, so crashes of the extension in CI can be debugged.
+    /// See `crash_dump::CrashDumps`. Default: no crash dumps.
+    pub fn crash_dumps(self, crash_dumps: CrashDumps) -> Self {
+        Self {
+            crash_dumps: Some(crash_dumps),
+            ..self
+        }
+    }
+
+    /// Capture Godot's output and resolve backtrace frames of the extension's libraries to
+    /// function names and source locations with `addr2line` once Godot exits, e.g. for crashes
+    /// of release builds. The frames are printed and listed in the `RunReport`. Needs
+    /// `write_gdextension_config` to know the libraries. See `symbolicate`. Default: false.
+    pub fn symbolicate_backtraces(self, symbolicate_backtraces: bool) -> Self {
+        Self {
+            symbolicate_backtraces,
+            ..self
+        }
+    }
+
+    /// Sample Godot's performance monitors while it runs, list them in the `RunReport` and fail
+    /// `execute` if a threshold is violated. See `performance`. Default: not collected.
+    pub fn collect_performance(self, performance_monitor: PerformanceMonitor) -> Self {
+        Self {
+            performance_monitor: Some(performance_monitor),
+            ..self
+        }
+    }
+
+    /// Run Godot with `--verbose` and collect the objects, RIDs and resources it reports as
+    /// leaked at exit. They are listed in the `RunReport`, and `execute` fails if there are more
+    /// than allowed. See `leak_check`. Default: not checked.
+    pub fn check_leaks(self, leak_check: LeakCheck) -> Self {
+        Self {
+            leak_check: Some(leak_check),
+            ..self
+        }
+    }
+
+    /// Write Godot's log to `path` with `--log-file` on every launch, unless the arguments
+    /// already set a log file. Relative paths are relative to the current directory.
+    /// See `log_file`. Default: Godot's own log location.
+    pub fn log_to_file(self, path: impl Into<PathBuf>) -> Self {
+        Self {
+            log_file: Some(path.into()),
+            ..self
+        }
+    }
+
+    /// How many old logs of `log_to_file` to keep, e.g. `godot.1.log` for the previous launch.
+    /// `0` overwrites the log on every launch. Default: 0.
+    pub fn log_retention(self, log_retention: usize) -> Self {
+        Self {
+            log_retention,
+            ..self
+        }
+    }
+
+    /// Specify the Godot version to use via `gdenv` (https://github.com/bytemeadow/gdenv).
+    /// If specified, the runner will use `gdenv run <version>` to invoke Godot.
+    pub fn godot_version(self, version: impl Into<String>) -> Self {
+        Self {
+            godot_version: Some(version.into()),
+            ..self
+        }
+    }
+
+    /// Run the Godot binary at `path` instead of discovering it, e.g. a custom engine build.
+    /// A `godot_version` takes precedence.
+    pub fn godot_binary_path(self, path: impl Into<PathBuf>) -> Self {
+        Self {
+            godot_binary_path: Some(path.into()),
+            ..self
+        }
+    }
+
+    /// Check the Godot binary against the project's `godot.lock` file before every launch,
+    /// so the whole team and CI run the same Godot build. A missing lock is written for the
+    /// current binary. See `godot_lock::GodotLockMode`. Default: no lock.
+    pub fn godot_lock(self, mode: GodotLockMode) -> Self {
+        Self {
+            godot_lock: Some(mode),
+            ..self
+        }
+    }
+
+    /// Cache the Godot binary found by discovery, its version and its hash for the `godot_lock`,
+    /// instead of probing the environment, running `godot --version` and hashing the binary on
+    /// every launch. Only used without a `godot_version` or `godot_binary_path`.
+    /// See `godot_locator::GodotLocator`. Default: no cache.
+    pub fn godot_locator(self, locator: GodotLocator) -> Self {
+        Self {
+            godot_locator: Some(locator),
+            ..self
+        }
+    }
+
+    /// Build the C# scripts of a C#/.NET hybrid project with `dotnet build` before every launch
+    /// and prefer a .NET build of Godot. Warns if the Godot binary is not a .NET build.
+    /// See `dotnet::Dotnet`. Default: no C# support.
+    pub fn dotnet(self, dotnet: Dotnet) -> Self {
+        Self {
+            dotnet: Some(dotnet),
+            ..self
+        }
+    }
+
+    /// Launch the editor even if another editor launched by this crate is still running
+    /// on the project. See `editor_lock` for details. Default: false.
+    pub fn force_editor_launch(self, force_editor_launch: bool) -> Self {
+        Self {
+            force_editor_launch,
+            ..self
+        }
+    }
+
+    /// Scan Godot's output for `ERROR:` and `SCRIPT ERROR:` lines while passing it through.
+    /// If Godot exits unsuccessfully or a GDExtension library fails to load, `execute` returns
+    /// an error listing the errors found, so CI logs point directly at the problem. Default: false.
+    pub fn scan_output_errors(self, scan_output_errors: bool) -> Self {
+        Self {
+            scan_output_errors,
+            ..self
+        }
+    }
+
+    /// Remove ANSI escape codes such as colors from Godot's output while passing it through,
+    /// e.g. for CI logs. Default: false.
+    pub fn strip_ansi(self, strip_ansi: bool) -> Self {
+        Self { strip_ansi, ..self }
+    }
+
+    /// Send every line of Godot's output as a structured `LogRecord` to `sender` while passing
+    /// it through, for every launch. See `log_records`. Default: none.
+    pub fn log_records(self, sender: Sender<LogRecord>) -> Self {
+        Self {
+            log_records: Some(sender),
+            ..self
+        }
+    }
+
+    /// Temporarily add custom feature tags or project setting overrides while Godot runs,
+    /// e.g. to run the project in an "integration-test" configuration without editing
+    /// `project.godot`. See `ProjectOverrides` for details. Default: no overrides.
+    pub fn project_overrides(self, project_overrides: ProjectOverrides) -> Self {
+        Self {
+            project_overrides,
+            ..self
+        }
+    }
+
+    /// Configure Godot for headless CI machines such as GitHub Actions runners:
+    /// `--headless --rendering-driver dummy --audio-driver Dummy`, and no boot splash.
+    /// Flags already set with `godot_cli_arguments` are kept.
+    pub fn ci_defaults(self) -> Self {
+        Self {
+            ci_defaults: true,
+            ..self
+        }
+    }
+
+    /// Set an environment variable for the Godot process.
+    pub fn env(mut self, key: impl Into<OsString>, value: impl Into<OsString>) -> Self {
+        self.envs.push((key.into(), value.into()));
+        self
+    }
+
+    /// Give every run a fresh temporary data directory, so `user://`, editor settings and caches
+    /// don't depend on or pollute the real ones. See `user_dir` for details. Default: false.
+    /// Note that with `godot_version`, `gdenv` runs with the same environment.
+    pub fn isolated_user_dir(self, isolated_user_dir: bool) -> Self {
+        Self {
+            isolated_user_dir,
+            ..self
+        }
+    }
+
+    /// Keep the directory of `isolated_user_dir` after Godot exits instead of deleting it,
+    /// e.g. to inspect save files. Its path is printed on launch. Default: false.
+    pub fn keep_user_dir(self, keep_user_dir: bool) -> Self {
+        Self {
+            keep_user_dir,
+            ..self
+        }
+    }
+
+    /// Make runs reproducible, e.g. to reproduce simulation bugs: Godot runs with
+    /// `--fixed-fps 60 --disable-vsync`, so every frame has the same delta regardless of load.
+    /// Godot has no global seed flag, so `seed` is passed as the user argument `--seed=<seed>`
+    /// (see `OS.get_cmdline_user_args()`) and as the `CARGO_GODOT_SEED` environment variable,
+    /// for the project to seed its random number generators with.
+    pub fn deterministic(self, seed: u64) -> Self {
+        Self {
+            deterministic_seed: Some(seed),
+            ..self
+        }
+    }
+
+    /// Quit after the given number of frames (`--quit-after`). Default: no limit.
+    pub fn frame_limit(self, frames: u32) -> Self {
+        Self {
+            frame_limit: Some(frames),
+            ..self
+        }
+    }
+
+    /// Register an autoload while Godot runs, e.g. a test driver which inspects the main scene
+    /// and quits, without adding it to the project. See `TemporaryAutoload`. Default: none.
+    pub fn temporary_autoload(mut self, autoload: TemporaryAutoload) -> Self {
+        self.temporary_autoloads.push(autoload);
+        self
+    }
+
+    /// Warn before launching if any of these extension class names is already used by a built-in
+    /// Godot class, which would fail to register with a confusing error. See `class_names`.
+    pub fn check_class_names<S: Into<String>>(self, names: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            class_names: names.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    /// Also check the names of the classes found in the crate's `src` directory, next to
+    /// `cargo_manifest_path`, like `check_class_names`. Default: false.
+    pub fn discover_class_names(self, discover_class_names: bool) -> Self {
+        Self {
+            discover_class_names,
+            ..self
+        }
+    }
+
+    /// How the godot project path is made absolute, for the runner and the `.gdextension` files.
+    /// Use `CanonicalizeMode::Logical` if the project is reached through a symlinked directory.
+    /// See `paths::CanonicalizeMode`. Default: `CanonicalizeMode::Physical`.
+    pub fn canonicalize_mode(self, canonicalize_mode: CanonicalizeMode) -> Self {
+        Self {
+            canonicalize_mode,
+            ..self
+        }
+    }
+
+    /// Locate the target directory from the library cargo actually builds, using
+    /// `cargo build --message-format=json`, instead of `cargo metadata`'s `target_directory`.
+    /// Needed when the library isn't built into the workspace target directory, e.g. with
+    /// per-crate target directories. Builds the library before every launch. Default: false.
+    pub fn resolve_artifact_dir(self, resolve_artifact_dir: bool) -> Self {
+        Self {
+            resolve_artifact_dir,
+            ..self
+        }
+    }
+
+    /// Build the library with `cargo_build` before every launch and write the exact path of the
+    /// built library into the `.gdextension` file, e.g. for custom profiles or target triples.
+    /// See `cargo::CargoBuild`. Default: no build.
+    pub fn cargo_build(self, cargo_build: CargoBuild) -> Self {
+        Self {
+            cargo_build: Some(cargo_build),
+            ..self
+        }
+    }
+
+    /// Enable cargo features of the extension (`--features`), e.g. to launch a feature-gated
+    /// variant. Builds with `cargo_build`, or the default `CargoBuild`. Default: none.
+    pub fn cargo_features<S: Into<String>>(
+        mut self,
+        features: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.cargo_features
+            .extend(features.into_iter().map(Into::into));
+        self
+    }
+
+    /// Disable the default features of the extension (`--no-default-features`).
+    /// Builds with `cargo_build`, or the default `CargoBuild`. Default: false.
+    pub fn no_default_features(self, no_default_features: bool) -> Self {
+        Self {
+            no_default_features,
+            ..self
+        }
+    }
 
-pub struct GodotRunner {
-    crate_name: String,
-    godot_project_path: PathBuf,
-    cargo_manifest_path: PathBuf,
-    gdextension_config: Box<dyn Fn(GdExtensionConfig) -> GdExtensionConfig + Send + Sync + 'static>,
-    write_gdextension_config: bool,
-    pre_import: bool,
-    godot_cli_arguments: Vec<String>,
-    godot_version: Option<String>,
-}
+    /// Enable all features of the extension (`--all-features`).
+    /// Builds with `cargo_build`, or the default `CargoBuild`. Default: false.
+    pub fn all_features(self, all_features: bool) -> Self {
+        Self {
+            all_features,
+            ..self
+        }
+    }
 
-impl GodotRunner {
-    /// Example usage:
-    /// ```rust,ignore
-    /// let runner = cargo_godot_lib::GodotRunner::create(
-    ///     env!("CARGO_PKG_NAME"),
-    ///     &std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../godot"),
-    /// );
-    /// if let Err(e) = runner.execute() {
-    ///     eprintln!("{e:?}");
-    ///     std::process::exit(1);
-    /// }
-    /// ```
-    pub fn create(crate_name: &str, godot_project_path: &Path) -> Self {
+    /// Sign the extension's macOS libraries with `codesign` before every launch, so Gatekeeper
+    /// doesn't block them once the project is exported. Only existing `.dylib` files of the
+    /// `.gdextension` file are signed. See `codesign::Codesign`. Default: no signing.
+    pub fn codesign(self, codesign: Codesign) -> Self {
         Self {
-            crate_name: crate_name.to_string(),
-            godot_project_path: godot_project_path.into(),
-            cargo_manifest_path: Path::new("./Cargo.toml").into(),
-            gdextension_config: Box::new(|config| config),
-            write_gdextension_config: true,
-            pre_import: true,
-            godot_cli_arguments: vec![],
-            godot_version: None,
+            codesign: Some(codesign),
+            ..self
         }
     }
 
-    /// Run Godot with the current configuration.
-    pub fn execute(&self) -> Result<()> {
-        let godot_project_path = self.godot_project_path.canonicalize().with_context(|| {
-            format!(
-                "Failed to canonicalize godot project path: {:?}",
-                self.godot_project_path
-            )
-        })?;
+    /// Copy the built library and its debug symbols into the godot project before every launch
+    /// and point the `.gdextension` file at the copy, e.g. to ship it with an export.
+    /// Builds with `cargo_build`, or the default `CargoBuild`. See `deploy::Deploy`.
+    /// Default: the library is loaded from the cargo target directory.
+    pub fn deploy(self, deploy: Deploy) -> Self {
+        Self {
+            deploy: Some(deploy),
+            ..self
+        }
+    }
 
-        if self.write_gdextension_config {
-            let metadata = cargo_metadata::MetadataCommand::new()
-                .manifest_path(&self.cargo_manifest_path)
-                .exec()?;
-            let default_config = GdExtensionConfig::start(
-                &self.crate_name,
-                &self.godot_project_path,
-                metadata.target_directory.as_std_path(),
-            );
-            (self.gdextension_config)(default_config)
-                .build()
-                .context("Failed to build .gdextension config")?
-                .write()
-                .context("Failed to write .gdextension file")?;
+    /// Check that every existing library of the `.gdextension` files exports the configured
+    /// entry symbol before launching Godot, instead of failing to load it inside Godot.
+    /// See `symbols::check_entry_symbol`. Default: false.
+    #[cfg(feature = "symbol-check")]
+    pub fn check_entry_symbol(self, check_entry_symbol: bool) -> Self {
+        Self {
+            check_entry_symbol,
+            ..self
         }
+    }
 
-        if self.pre_import {
-            run_godot_import_if_needed(&godot_project_path, self.godot_version.as_deref())?;
+    /// Warn about setups known to break hot reloading of a `reloadable` extension, such as
+    /// `thread_local!` statics or fat LTO. See `hot_reload`. Default: false.
+    pub fn lint_hot_reload(self, lint_hot_reload: bool) -> Self {
+        Self {
+            lint_hot_reload,
+            ..self
         }
+    }
 
-        run_godot(
-            &godot_project_path,
-            self.godot_version.as_deref(),
-            &self.godot_cli_arguments,
-        )
+    /// Install or update an editor plugin with a toolbar button that rebuilds the extension
+    /// before every launch. See `editor_plugin::EditorPlugin`. `clean` removes it again.
+    /// Default: no plugin.
+    pub fn editor_plugin(self, plugin: EditorPlugin) -> Self {
+        Self {
+            editor_plugin: Some(plugin),
+            ..self
+        }
     }
 
-    /// Specify the path to the cargo manifest. Default: `./Cargo.toml`.
-    pub fn cargo_manifest_path(self, cargo_manifest_path: &Path) -> Self {
+    /// Write the crate version from `cargo metadata` into the godot project before every launch,
+    /// so the game can show the exact build it runs with. Unlike `project_overrides`, the stamp
+    /// is part of exports. See `version_stamp::VersionStamp`. Default: no stamp.
+    pub fn version_stamp(self, version_stamp: VersionStamp) -> Self {
         Self {
-            cargo_manifest_path: cargo_manifest_path.to_path_buf(),
+            version_stamp: Some(version_stamp),
             ..self
         }
     }
 
-    /// Write the `.gdextension` config file before launching Godot. Default: true.
-    /// See also: `gdextension_config`.
-    pub fn write_gdextension_config(self, write_gdextension_config: bool) -> Self {
+    /// Fetch a GDScript addon dependency into `addons/` before every import, if its source
+    /// changed or its folder is missing. See `addons::Addon`.
+    pub fn addon(mut self, addon: Addon) -> Self {
+        self.addons.push(addon);
+        self
+    }
+
+    /// Run custom asset pipeline steps before every import and launch, skipping unchanged steps.
+    /// See `pipeline::Pipeline`.
+    pub fn pipeline(self, pipeline: Pipeline) -> Self {
         Self {
-            write_gdextension_config,
+            pipeline: Some(pipeline),
             ..self
         }
     }
 
-    /// Replace the default configuration for the `.gdextension` file which is generated before Godot launch.
-    /// See also: `write_gdextension_config`.
-    pub fn gdextension_config(
+    /// Append the entries of the `generated_files_manifest` missing from the project's
+    /// `.gitignore` before every launch, creating it if needed. Default: false.
+    pub fn update_gitignore(self, update_gitignore: bool) -> Self {
+        Self {
+            update_gitignore,
+            ..self
+        }
+    }
+
+    /// Create a minimal godot project named after the crate if the godot project path has no
+    /// `project.godot`, instead of failing. See `scaffold::create_project`. Default: false.
+    pub fn create_project_if_missing(self, create_project_if_missing: bool) -> Self {
+        Self {
+            create_project_if_missing,
+            ..self
+        }
+    }
+
+    /// Register another godot project using the extension. Its `.gdextension` file is written
+    /// whenever the selected project's is, see `projects`.
+    pub fn additional_project(
         mut self,
-        f: impl Fn(GdExtensionConfig) -> GdExtensionConfig + Send + Sync + 'static,
+        name: impl Into<String>,
+        godot_project_path: &Path,
     ) -> Self {
-        self.gdextension_config = Box::new(f);
+        self.additional_projects
+            .push(GodotProject::new(name, godot_project_path));
         self
     }
 
-    /// Run `godot --import --headless` before launching Godot to create a `.godot` folder
-    /// if it doesn't exist. Default: true.
-    pub fn pre_import(self, pre_import: bool) -> Self {
-        Self { pre_import, ..self }
+    /// Choose the godot project to import and launch by index or name, see `projects`.
+    /// Default: the main project.
+    pub fn select_project(self, selector: impl Into<ProjectSelector>) -> Self {
+        Self {
+            project_selector: selector.into(),
+            ..self
+        }
     }
 
-    /// Set additional arguments to the Godot CLI.
-    /// See https://docs.godotengine.org/en/stable/tutorials/editor/command_line_tutorial.html
-    /// for a list of available arguments.
-    pub fn godot_cli_arguments(self, args: Vec<impl Into<String>>) -> Self {
+    /// How much the runner itself prints, e.g. `Verbosity::Debug` for the full Godot command
+    /// line. Godot's own output is not affected, see `arg_verbose`. Default: `Verbosity::Normal`.
+    pub fn verbosity(self, verbosity: Verbosity) -> Self {
+        Self { verbosity, ..self }
+    }
+
+    /// Notify when the library built with `cargo_build` is ready or failed to build, e.g. to
+    /// switch back to the editor after a rebuild. See `notify::BuildNotification`.
+    pub fn notify_build(self, notification: BuildNotification) -> Self {
         Self {
-            godot_cli_arguments: args.into_iter().map(Into::into).collect(),
+            build_notification: Some(notification),
             ..self
         }
     }
 
-    /// Specify the Godot version to use via `gdenv` (https://github.com/bytemeadow/gdenv).
-    /// If specified, the runner will use `gdenv run <version>` to invoke Godot.
-    pub fn godot_version(self, version: impl Into<String>) -> Self {
+    /// Customize the Godot `Command` before every launch, as an escape hatch for options this
+    /// crate doesn't provide, e.g. process groups, niceness or platform-specific flags.
+    /// Hooks run after the runner configured the command, in the order they were added.
+    /// Imports are not affected. Redirecting stdout or stderr disables `scan_output_errors`.
+    ///
+    /// Example usage:
+    /// ```rust,ignore
+    /// use std::os::unix::process::CommandExt;
+    /// let runner = runner.configure_command(|command| {
+    ///     command.process_group(0);
+    /// });
+    /// ```
+    pub fn configure_command(
+        mut self,
+        hook: impl Fn(&mut Command) + Send + Sync + 'static,
+    ) -> Self {
+        self.command_hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Run `hook` right before every Godot launch, after the project is prepared.
+    /// An error aborts the launch. See `lifecycle`.
+    pub fn before_launch(
+        mut self,
+        hook: impl Fn(&LaunchContext) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.before_launch_hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Run `hook` with the exit status once a launched Godot exited. An error is returned
+    /// instead of the exit status. See `lifecycle`.
+    pub fn after_exit(
+        mut self,
+        hook: impl Fn(&LaunchContext, GodotExitStatus) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.after_exit_hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Launch Godot so that a debugger or IDE can attach to it.
+    /// See `DebugConfig` for the available modes.
+    pub fn debug(self, debug: DebugConfig) -> Self {
         Self {
-            godot_version: Some(version.into()),
+            debug: Some(debug),
             ..self
         }
     }
 }
 
+/// Append a user argument, which Godot passes on to `OS.get_cmdline_user_args()`.
+/// User arguments follow `--` or `++`.
+pub(crate) fn push_user_argument(args: &mut Vec<String>, arg: String) {
+    if !args.iter().any(|arg| arg == "--" || arg == "++") {
+        args.push("--".to_string());
+    }
+    args.push(arg);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::pipeline::PipelineStep;
     use std::fs;
     use tempfile::tempdir;
 
@@ -147,9 +2338,61 @@ mod tests {
         assert_eq!(runner.godot_project_path, godot_project_path);
         assert_eq!(runner.cargo_manifest_path, PathBuf::from("./Cargo.toml"));
         assert!(runner.write_gdextension_config);
+        assert!(!runner.auto_compatability_version);
         assert!(runner.pre_import);
+        assert_eq!(runner.import_options, ImportOptions::default());
         assert!(runner.godot_cli_arguments.is_empty());
+        assert!(runner.wrapper_command.is_empty());
+        assert!(runner.profiler.is_none());
+        assert!(runner.crash_dumps.is_none());
+        assert!(!runner.symbolicate_backtraces);
+        assert!(runner.performance_monitor.is_none());
+        assert!(runner.leak_check.is_none());
+        assert!(runner.log_file.is_none());
+        assert_eq!(runner.log_retention, 0);
         assert!(runner.godot_version.is_none());
+        assert!(runner.godot_binary_path.is_none());
+        assert!(runner.godot_lock.is_none());
+        assert!(runner.godot_locator.is_none());
+        assert!(runner.debug.is_none());
+        assert!(!runner.force_editor_launch);
+        assert!(!runner.scan_output_errors);
+        assert!(!runner.strip_ansi);
+        assert!(runner.log_records.is_none());
+        assert!(runner.project_overrides.is_empty());
+        assert!(!runner.ci_defaults);
+        assert!(runner.envs.is_empty());
+        assert!(!runner.isolated_user_dir);
+        assert!(!runner.keep_user_dir);
+        assert!(runner.deterministic_seed.is_none());
+        assert!(runner.frame_limit.is_none());
+        assert!(runner.temporary_autoloads.is_empty());
+        assert!(runner.class_names.is_empty());
+        assert!(!runner.discover_class_names);
+        assert!(!runner.resolve_artifact_dir);
+        assert_eq!(runner.canonicalize_mode, CanonicalizeMode::Physical);
+        assert!(runner.cargo_build.is_none());
+        assert!(runner.cargo_features.is_empty());
+        assert!(!runner.no_default_features);
+        assert!(!runner.all_features);
+        assert_eq!(runner.effective_cargo_build(), None);
+        assert!(runner.codesign.is_none());
+        assert!(runner.deploy.is_none());
+        assert!(!runner.update_gitignore);
+        assert!(!runner.lint_hot_reload);
+        assert!(runner.editor_plugin.is_none());
+        assert!(runner.version_stamp.is_none());
+        assert!(runner.addons.is_empty());
+        assert!(runner.dotnet.is_none());
+        assert!(runner.command_hooks.is_empty());
+        assert!(runner.pipeline.is_none());
+        assert!(runner.before_launch_hooks.is_empty());
+        assert!(runner.after_exit_hooks.is_empty());
+        assert!(!runner.create_project_if_missing);
+        assert!(runner.additional_projects.is_empty());
+        assert_eq!(runner.project_selector, ProjectSelector::Index(0));
+        assert_eq!(runner.verbosity, Verbosity::Normal);
+        assert_eq!(runner.build_notification, None);
     }
 
     #[test]
@@ -157,23 +2400,280 @@ mod tests {
         let runner = GodotRunner::create("a", Path::new("b"))
             .cargo_manifest_path(Path::new("custom/Cargo.toml"))
             .write_gdextension_config(false)
+            .auto_compatability_version(true)
             .gdextension_config(|config| config)
             .pre_import(false)
+            .import_options(ImportOptions::default().force(true))
             .godot_cli_arguments(vec!["--hello", "world"])
-            .godot_version("4.6");
+            .wrapper_command(["gdb", "--args"])
+            .profiler(Profiler::tracy(8086))
+            .crash_dumps(CrashDumps::new(Path::new("dumps")))
+            .symbolicate_backtraces(true)
+            .collect_performance(PerformanceMonitor::default().min_fps(30.0))
+            .check_leaks(LeakCheck::default().max_leaks(Some(2)))
+            .log_to_file("target/logs/godot.log")
+            .log_retention(3)
+            .godot_version("4.6")
+            .godot_binary_path("/opt/godot/godot")
+            .godot_lock(GodotLockMode::Verify)
+            .godot_locator(GodotLocator::new("target"))
+            .dotnet(Dotnet::default().configuration("Release"))
+            .debug(DebugConfig::remote("localhost", 6007))
+            .force_editor_launch(true)
+            .scan_output_errors(true)
+            .strip_ansi(true)
+            .log_records(std::sync::mpsc::channel().0)
+            .project_overrides(ProjectOverrides::default().feature_tag("ci"))
+            .env("RUST_LOG", "debug")
+            .isolated_user_dir(true)
+            .keep_user_dir(true)
+            .temporary_autoload(TemporaryAutoload::res_path("Driver", "res://driver.gd"))
+            .check_class_names(["Player"])
+            .discover_class_names(true)
+            .resolve_artifact_dir(true)
+            .canonicalize_mode(CanonicalizeMode::Logical)
+            .cargo_features(["tracing"])
+            .no_default_features(true)
+            .all_features(true)
+            .cargo_build(CargoBuild::default().release())
+            .codesign(Codesign::ad_hoc())
+            .deploy(Deploy::new("bin"))
+            .update_gitignore(true)
+            .lint_hot_reload(true)
+            .editor_plugin(EditorPlugin::new(Path::new("Cargo.toml")))
+            .version_stamp(VersionStamp::default())
+            .addon(Addon::git(
+                "gut",
+                "https://github.com/bitwes/Gut.git",
+                "v9.5.0",
+            ))
+            .pipeline(Pipeline::default().step(PipelineStep::new("sprites", |_| Ok(()))))
+            .create_project_if_missing(true)
+            .additional_project("demo", Path::new("demo"))
+            .select_project("demo")
+            .verbosity(Verbosity::Quiet)
+            .notify_build(BuildNotification::Bell)
+            .configure_command(|command| {
+                command.env("HOOK", "1");
+            })
+            .before_launch(|ctx| {
+                assert_eq!(ctx.crate_name, "a");
+                Ok(())
+            })
+            .after_exit(|_, status| status.into_result());
 
         assert_eq!(
             runner.cargo_manifest_path,
             PathBuf::from("custom/Cargo.toml")
         );
         assert!(!runner.write_gdextension_config);
+        assert!(runner.auto_compatability_version);
         assert_eq!(
             (runner.gdextension_config)(GdExtensionConfig::default()),
             GdExtensionConfig::default()
         );
         assert!(!runner.pre_import);
+        assert_eq!(runner.import_options, ImportOptions::default().force(true));
         assert_eq!(runner.godot_cli_arguments, vec!["--hello", "world"]);
+        assert_eq!(runner.wrapper_command, vec!["gdb", "--args"]);
+        assert_eq!(runner.profiler, Some(Profiler::tracy(8086)));
+        assert_eq!(
+            runner.crash_dumps,
+            Some(CrashDumps::new(Path::new("dumps")))
+        );
+        assert!(runner.symbolicate_backtraces);
+        assert_eq!(
+            runner.performance_monitor,
+            Some(PerformanceMonitor::default().min_fps(30.0))
+        );
+        assert_eq!(
+            runner.leak_check,
+            Some(LeakCheck::default().max_leaks(Some(2)))
+        );
+        assert_eq!(
+            runner.log_file,
+            Some(PathBuf::from("target/logs/godot.log"))
+        );
+        assert_eq!(runner.log_retention, 3);
         assert_eq!(runner.godot_version, Some("4.6".to_string()));
+        assert_eq!(
+            runner.godot_binary_path,
+            Some(PathBuf::from("/opt/godot/godot"))
+        );
+        assert_eq!(runner.godot_lock, Some(GodotLockMode::Verify));
+        assert_eq!(runner.godot_locator, Some(GodotLocator::new("target")));
+        assert_eq!(
+            runner.dotnet,
+            Some(Dotnet::default().configuration("Release"))
+        );
+        assert!(runner.force_editor_launch);
+        assert!(runner.scan_output_errors);
+        assert!(runner.strip_ansi);
+        assert!(runner.log_records.is_some());
+        assert_eq!(
+            runner.project_overrides,
+            ProjectOverrides::default().feature_tag("ci")
+        );
+        assert_eq!(runner.envs, vec![("RUST_LOG".into(), "debug".into())]);
+        assert!(runner.isolated_user_dir);
+        assert!(runner.keep_user_dir);
+        assert_eq!(
+            runner.temporary_autoloads,
+            vec![TemporaryAutoload::res_path("Driver", "res://driver.gd")]
+        );
+        assert_eq!(runner.class_names, vec!["Player"]);
+        assert!(runner.discover_class_names);
+        assert!(runner.resolve_artifact_dir);
+        assert_eq!(runner.canonicalize_mode, CanonicalizeMode::Logical);
+        assert_eq!(runner.cargo_build, Some(CargoBuild::default().release()));
+        assert_eq!(runner.cargo_features, ["tracing"]);
+        assert!(runner.no_default_features);
+        assert!(runner.all_features);
+        assert_eq!(
+            runner.effective_cargo_build(),
+            Some(
+                CargoBuild::default()
+                    .release()
+                    .features(["tracing"])
+                    .no_default_features(true)
+                    .all_features(true)
+            )
+        );
+        assert_eq!(runner.codesign, Some(Codesign::ad_hoc()));
+        assert_eq!(runner.deploy, Some(Deploy::new("bin")));
+        assert!(runner.update_gitignore);
+        assert!(runner.lint_hot_reload);
+        assert_eq!(
+            runner.editor_plugin,
+            Some(EditorPlugin::new(Path::new("Cargo.toml")))
+        );
+        assert_eq!(runner.version_stamp, Some(VersionStamp::default()));
+        assert_eq!(
+            runner.addons,
+            vec![Addon::git(
+                "gut",
+                "https://github.com/bitwes/Gut.git",
+                "v9.5.0"
+            )]
+        );
+        assert_eq!(
+            runner.pipeline.as_ref().unwrap().order().unwrap()[0].name(),
+            "sprites"
+        );
+        assert!(runner.create_project_if_missing);
+        assert_eq!(
+            runner.additional_projects,
+            vec![GodotProject::new("demo", Path::new("demo"))]
+        );
+        assert_eq!(runner.project_selector, ProjectSelector::from("demo"));
+        assert_eq!(runner.verbosity, Verbosity::Quiet);
+        assert_eq!(runner.build_notification, Some(BuildNotification::Bell));
+        let mut command = Command::new("godot");
+        (runner.command_hooks[0])(&mut command);
+        assert_eq!(
+            command.get_envs().collect::<Vec<_>>(),
+            vec![("HOOK".as_ref(), Some("1".as_ref()))]
+        );
+        let context = LaunchContext {
+            crate_name: "a",
+            godot_project_path: Path::new("godot"),
+            args: &[],
+            command: &command,
+        };
+        (runner.before_launch_hooks[0])(&context).unwrap();
+        (runner.after_exit_hooks[0])(&context, GodotExitStatus::Success).unwrap();
+        assert!((runner.after_exit_hooks[0])(&context, GodotExitStatus::Crashed(11)).is_err());
+        assert_eq!(
+            runner.godot_arguments(),
+            vec![
+                "--verbose",
+                "--remote-debug",
+                "tcp://localhost:6007",
+                "--hello",
+                "world"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ci_defaults() {
+        let runner = GodotRunner::create("a", Path::new("b"))
+            .godot_cli_arguments(vec!["--rendering-driver", "opengl3", "--quit-after", "1"])
+            .ci_defaults();
+        assert_eq!(
+            runner.godot_arguments(),
+            vec![
+                "--headless",
+                "--audio-driver",
+                "Dummy",
+                "--rendering-driver",
+                "opengl3",
+                "--quit-after",
+                "1"
+            ]
+        );
+        assert!(
+            runner
+                .effective_project_overrides()
+                .create()
+                .contains("boot_splash/show_image=false")
+        );
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let runner = GodotRunner::create("a", Path::new("b"))
+            .deterministic(42)
+            .frame_limit(600);
+        assert_eq!(
+            runner.godot_arguments(),
+            vec![
+                "--fixed-fps",
+                "60",
+                "--disable-vsync",
+                "--quit-after",
+                "600",
+                "--",
+                "--seed=42"
+            ]
+        );
+
+        let runner = runner.godot_cli_arguments(vec!["--fixed-fps", "30", "++", "--level=2"]);
+        assert_eq!(
+            runner.godot_arguments(),
+            vec![
+                "--disable-vsync",
+                "--quit-after",
+                "600",
+                "--fixed-fps",
+                "30",
+                "++",
+                "--level=2",
+                "--seed=42"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_godot_arguments_with() {
+        let runner = GodotRunner::create("a", Path::new("b"))
+            .deterministic(7)
+            .godot_cli_arguments(vec!["--quit-after", "10"]);
+        assert_eq!(
+            runner.godot_arguments_with(vec![
+                vec!["--fixed-fps".to_string(), "30".to_string()],
+                vec!["--quit-after".to_string(), "1".to_string()],
+            ]),
+            vec![
+                "--fixed-fps",
+                "30",
+                "--disable-vsync",
+                "--quit-after",
+                "10",
+                "--",
+                "--seed=7"
+            ]
+        );
     }
 
     #[test]
@@ -186,6 +2686,331 @@ mod tests {
             .gdextension_config(|config| config.reloadable(false));
     }
 
+    #[test]
+    fn test_generated_files_manifest() {
+        let runner = GodotRunner::create("my_crate", Path::new("godot"))
+            .gdextension_config(|config| config.config_file_name("game.gdextension"))
+            .deploy(Deploy::new("bin"));
+        let files = runner.generated_files_manifest();
+        let entries: Vec<_> = files
+            .iter()
+            .filter(|file| file.gitignore)
+            .map(GeneratedFile::gitignore_entry)
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                "/game.gdextension",
+                "/bin/",
+                "/.godot/cargo_godot_lib/",
+                "/override.cfg.cargo-godot-lib.bak"
+            ]
+        );
+        assert!(files.iter().any(|file| file.path == "override.cfg"));
+    }
+
+    #[test]
+    fn test_additional_gdextension_configs() {
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("godot");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(project.join("project.godot"), "config_version=5").unwrap();
+
+        let runner =
+            GodotRunner::create("my-crate", &project).additional_gdextension_config(|config| {
+                config
+                    .config_file_name("tools.gdextension")
+                    .entry_symbol("tools_init")
+                    .library_name("my-crate-tools")
+            });
+        let configs = runner.write_gdextension(&project).unwrap();
+        assert_eq!(configs.len(), 2);
+        let tools = fs::read_to_string(project.join("tools.gdextension")).unwrap();
+        assert!(tools.contains("entry_symbol = \"tools_init\""));
+        assert!(tools.contains("libmy_crate_tools.so"));
+        let main = fs::read_to_string(project.join("rust.gdextension")).unwrap();
+        assert!(main.contains("entry_symbol = \"gdext_rust_init\""));
+        assert_eq!(
+            runner.generated_files_manifest()[1].path,
+            "tools.gdextension"
+        );
+
+        let runner = GodotRunner::create("my-crate", &project)
+            .additional_gdextension_config(|config| config.entry_symbol("tools_init"));
+        assert!(runner.write_gdextension(&project).is_err());
+    }
+
+    #[test]
+    fn test_typed_arguments() {
+        let runner = GodotRunner::create("my_crate", Path::new("godot"))
+            .raw_arg("--headless")
+            .arg_resolution(1280, 720)
+            .arg_position(0, 10)
+            .arg_fullscreen()
+            .arg_verbose()
+            .arg_quit_after(60)
+            .frame_limit(5);
+        let args = runner.godot_arguments();
+        assert_eq!(
+            args,
+            [
+                "--headless",
+                "--resolution",
+                "1280x720",
+                "--position",
+                "0,10",
+                "--fullscreen",
+                "--verbose",
+                "--quit-after",
+                "60"
+            ]
+        );
+        godot_args::validate(&args).unwrap();
+
+        let runner = runner.raw_arg("--windowed");
+        let error = godot_args::validate(&runner.godot_arguments()).unwrap_err();
+        assert!(error.to_string().contains("mutually exclusive"));
+    }
+
+    #[test]
+    fn test_additional_projects() {
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("godot");
+        let demo = dir.path().join("demos/demo");
+        for path in [&project, &demo] {
+            fs::create_dir_all(path).unwrap();
+            fs::write(path.join("project.godot"), "config_version=5").unwrap();
+        }
+
+        let runner = GodotRunner::create("my-crate", &project)
+            .additional_project("demo", &demo)
+            .select_project("demo");
+        let demo_path = runner.validated_project_path().unwrap();
+        assert_eq!(demo_path, demo.canonicalize().unwrap());
+        let configs = runner.write_gdextension(&demo_path).unwrap();
+        assert_eq!(configs[0].full_config_path(), demo.join("rust.gdextension"));
+        let library = |path: &Path| {
+            let config = fs::read_to_string(path.join("rust.gdextension")).unwrap();
+            config
+                .lines()
+                .find(|line| line.starts_with("linux"))
+                .map(str::to_string)
+        };
+        assert!(library(&project).unwrap().contains("res://../"));
+        assert!(library(&demo).unwrap().contains("res://../../"));
+
+        let runner = runner.select_project(2);
+        assert!(runner.validated_project_path().is_err());
+
+        // The selected project configured again under another path is skipped, as it is locked.
+        let runner = GodotRunner::create("my-crate", &project)
+            .additional_project("same", &dir.path().join("demos/../godot"));
+        let project_path = runner.validated_project_path().unwrap();
+        let _lock = ProjectLock::acquire(&project_path, Verbosity::Quiet).unwrap();
+        assert_eq!(runner.write_gdextension(&project_path).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_write_project_gdextension() {
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("godot");
+        let target = dir.path().join("target");
+        fs::create_dir_all(&project).unwrap();
+        fs::create_dir_all(target.join("x86_64-unknown-linux-gnu/release")).unwrap();
+        fs::write(project.join("project.godot"), "config_version=5").unwrap();
+
+        // The library of the effective build is configured without an explicit `cargo_build`.
+        let runner = GodotRunner::create("my-crate", &project).cargo_features(["tracing"]);
+        let library = CdylibArtifact {
+            name: "my_crate".to_string(),
+            path: target.join("x86_64-unknown-linux-gnu/release/libmy_crate.so"),
+            target: "x86_64-unknown-linux-gnu".to_string(),
+        };
+        fs::write(&library.path, "").unwrap();
+        let project_path = runner.validated_project_path().unwrap();
+        runner
+            .write_project_gdextension(
+                &project,
+                &project_path,
+                runner.effective_cargo_build().as_ref(),
+                Some(&library),
+                &target,
+            )
+            .unwrap();
+        let config = fs::read_to_string(project.join("rust.gdextension")).unwrap();
+        assert!(config.contains("x86_64-unknown-linux-gnu/release/libmy_crate.so"));
+    }
+
+    #[test]
+    fn test_clean() {
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("godot");
+        fs::create_dir_all(project.join(".godot/cargo_godot_lib/autoloads")).unwrap();
+        fs::create_dir_all(project.join("bin")).unwrap();
+        fs::write(project.join("project.godot"), "config_version=5").unwrap();
+        fs::write(project.join("rust.gdextension"), GENERATED_HEADER).unwrap();
+        fs::write(project.join("bin/libmy_crate.so"), "").unwrap();
+        fs::write(project.join("bin/icon.png"), "").unwrap();
+        fs::write(project.join("override.cfg"), "[application]").unwrap();
+        fs::write(
+            project.join(project_overrides::BACKUP_FILE_NAME),
+            "[display]",
+        )
+        .unwrap();
+        fs::write(project.join(".gitignore"), "/rust.gdextension\n").unwrap();
+        fs::write(project.join("godot.lock"), "").unwrap();
+        fs::create_dir_all(project.join("addons/gut")).unwrap();
+        fs::write(project.join("addons/gut/plugin.cfg"), "[plugin]").unwrap();
+        let demo = dir.path().join("demo");
+        fs::create_dir_all(&demo).unwrap();
+        fs::write(demo.join("rust.gdextension"), GENERATED_HEADER).unwrap();
+        fs::write(demo.join("rust.gdextension.bak"), "hand-written").unwrap();
+
+        let runner = GodotRunner::create("my-crate", &project)
+            .cargo_manifest_path(&dir.path().join("missing/Cargo.toml"))
+            .deploy(Deploy::new("bin"))
+            .additional_project("demo", &demo)
+            .additional_project("missing", &dir.path().join("missing"))
+            .godot_lock(GodotLockMode::Verify)
+            .addon(Addon::git(
+                "gut",
+                "https://github.com/bitwes/Gut.git",
+                "v9.5.0",
+            ));
+        let report = runner.clean().unwrap();
+        let project = project.canonicalize().unwrap();
+        let demo = demo.canonicalize().unwrap();
+        assert_eq!(
+            report.removed,
+            vec![
+                project.join("rust.gdextension"),
+                project.join("bin/libmy_crate.so"),
+                demo.join("rust.gdextension"),
+                project.join("godot.lock"),
+                project.join(".godot/cargo_godot_lib"),
+            ]
+        );
+        assert_eq!(
+            report.restored,
+            vec![demo.join("rust.gdextension"), project.join("override.cfg")]
+        );
+        assert_eq!(
+            fs::read_to_string(demo.join("rust.gdextension")).unwrap(),
+            "hand-written"
+        );
+        assert!(!demo.join("rust.gdextension.bak").exists());
+        assert_eq!(report.edited, vec![project.join(".gitignore")]);
+        assert!(project.join("bin/icon.png").exists());
+        assert!(project.join("addons/gut/plugin.cfg").exists());
+        assert_eq!(
+            fs::read_to_string(project.join("override.cfg")).unwrap(),
+            "[display]"
+        );
+        assert!(runner.clean().unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_output_errors() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempdir().unwrap();
+        let godot = dir.path().join("godot");
+        fs::write(&godot, "#!/bin/sh\necho 'ERROR: broken'\n").unwrap();
+        fs::set_permissions(&godot, fs::Permissions::from_mode(0o755)).unwrap();
+        let launch = |runner: GodotRunner| {
+            let process = runner
+                .godot_binary_path(&godot)
+                .launch_with(dir.path(), &[], None, &[])
+                .unwrap();
+            process.wait_with_errors().unwrap().1
+        };
+        let runner = GodotRunner::create("my_crate", dir.path());
+        assert!(launch(runner.strip_ansi(true)).is_empty());
+        let runner = GodotRunner::create("my_crate", dir.path());
+        assert_eq!(launch(runner.scan_output_errors(true)).len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_execute_if_changed() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("godot");
+        for path in [
+            &dir.path().join("src"),
+            &dir.path().join("target"),
+            &project,
+        ] {
+            fs::create_dir_all(path).unwrap();
+        }
+        fs::write(project.join("project.godot"), "config_version=5").unwrap();
+        fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"my-crate\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+        let godot = dir.path().join("godot.sh");
+        let launches = dir.path().join("launches");
+        fs::write(&godot, format!("#!/bin/sh\necho \"$@\" >> {launches:?}\n")).unwrap();
+        fs::set_permissions(&godot, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let runner = GodotRunner::create("my-crate", &project)
+            .cargo_manifest_path(&dir.path().join("Cargo.toml"))
+            .godot_binary_path(&godot)
+            .pre_import(false);
+        assert!(runner.execute_if_changed().unwrap());
+        assert!(!runner.execute_if_changed().unwrap());
+        fs::write(project.join("main.gd"), "extends Node").unwrap();
+        assert!(runner.execute_if_changed().unwrap());
+        assert_eq!(fs::read_to_string(&launches).unwrap().lines().count(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_execute_status() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("godot");
+        for path in [
+            &dir.path().join("src"),
+            &dir.path().join("target"),
+            &project,
+        ] {
+            fs::create_dir_all(path).unwrap();
+        }
+        fs::write(project.join("project.godot"), "config_version=5").unwrap();
+        fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"my-crate\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+        let godot = dir.path().join("godot.sh");
+        fs::write(&godot, "#!/bin/sh\nexit 42\n").unwrap();
+        fs::set_permissions(&godot, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let runner = GodotRunner::create("my-crate", &project)
+            .cargo_manifest_path(&dir.path().join("Cargo.toml"))
+            .godot_binary_path(&godot)
+            .pre_import(false);
+        assert_eq!(
+            runner.execute_status().unwrap(),
+            GodotExitStatus::Custom(42)
+        );
+        assert!(runner.execute().is_err());
+        assert!(runner.execute_if_changed().is_err());
+    }
+
+    #[test]
+    fn test_detect_compatability_version() {
+        let runner = GodotRunner::create("my_crate", Path::new("mock_godot_project"));
+        assert_eq!(
+            runner.detect_compatability_version(Path::new("mock_godot_project")),
+            Some("4.5".to_string())
+        );
+    }
+
     #[test]
     fn test_execute_failure_invalid_project_path() {
         let runner = GodotRunner::create("my_crate", Path::new("non_existent_path"));
@@ -199,6 +3024,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_create_project_if_missing() {
+        let dir = tempdir().unwrap();
+        let godot_project_path = dir.path().join("godot");
+        let runner =
+            GodotRunner::create("my_crate", &godot_project_path).create_project_if_missing(true);
+        let path = runner.validated_project_path().unwrap();
+        assert_eq!(path, godot_project_path.canonicalize().unwrap());
+        let contents = fs::read_to_string(godot_project_path.join("project.godot")).unwrap();
+        assert!(contents.contains("config/name=\"my_crate\""));
+        // An existing project is left as it is.
+        runner.validated_project_path().unwrap();
+    }
+
     #[test]
     fn test_execute() {
         let dir = tempdir().unwrap();
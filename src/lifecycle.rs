@@ -0,0 +1,41 @@
+//! Hooks running custom steps around every Godot launch, see `GodotRunner::before_launch` and
+//! `GodotRunner::after_exit`, e.g. cooking assets or reporting telemetry.
+//!
+//! `before_launch` hooks run after the project is prepared and the command is built, right
+//! before Godot is spawned. An error aborts the launch. `after_exit` hooks run once Godot exited
+//! and the runner cleaned up, when the process is waited for or killed. An error is returned
+//! instead of the exit status.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let runner = GodotRunner::create("game", Path::new("godot"))
+//!     .before_launch(|ctx| cook_assets(&ctx.godot_project_path.join("assets")))
+//!     .after_exit(|ctx, status| {
+//!         eprintln!("{:?} exited: {status}", ctx.command.get_program());
+//!         Ok(())
+//!     });
+//! ```
+use crate::exit_status::GodotExitStatus;
+use anyhow::Result;
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+
+/// What the lifecycle hooks get to know about a launch.
+#[derive(Debug)]
+pub struct LaunchContext<'a> {
+    pub crate_name: &'a str,
+    /// The canonicalized path of the launched godot project.
+    pub godot_project_path: &'a Path,
+    /// The Godot arguments, without the `wrapper_command`.
+    pub args: &'a [String],
+    /// The command Godot is spawned with, including the wrapper, environment and
+    /// `configure_command` changes.
+    pub command: &'a Command,
+}
+
+/// A hook of `GodotRunner::before_launch`.
+pub type BeforeLaunchHook = Box<dyn Fn(&LaunchContext) -> Result<()> + Send + Sync>;
+
+/// A hook of `GodotRunner::after_exit`.
+pub type AfterExitHook = Arc<dyn Fn(&LaunchContext, GodotExitStatus) -> Result<()> + Send + Sync>;
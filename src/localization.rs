@@ -0,0 +1,471 @@
+//! Localization checks of a godot project without running Godot: collecting translatable
+//! strings into a `.pot` template and finding strings missing from the `.po` and `.csv`
+//! translations.
+//!
+//! Strings are collected like Godot's POT generation from the files listed in the project
+//! setting `internationalization/locale/translations_pot_files`, or from all scenes and scripts
+//! if it's empty. Scripts contribute the first argument of `tr`, `tr_n`, `atr` and `atr_n`
+//! called with a string literal, scenes the text properties of their nodes, e.g. `text` and
+//! `tooltip_text`. Plural forms and contexts are not collected.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! localization::write_pot(Path::new("godot"), Path::new("godot/locale/messages.pot"))?;
+//! for missing in localization::check_translations(Path::new("godot"))? {
+//!     eprintln!("{missing}");
+//! }
+//! ```
+use crate::audit::find_files;
+use crate::paths;
+use crate::project_config::{ProjectConfig, unescape};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+/// Functions whose string literal argument is translatable.
+const TRANSLATION_FUNCTIONS: [&str; 4] = ["tr", "tr_n", "atr", "atr_n"];
+
+/// Node properties in scenes whose strings are translatable.
+const TRANSLATABLE_PROPERTIES: [&str; 5] = [
+    "text",
+    "tooltip_text",
+    "placeholder_text",
+    "title",
+    "dialog_text",
+];
+
+/// A string which a translation doesn't translate.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize)]
+pub struct MissingTranslation {
+    /// The `res://` path of the `.po` or `.csv` file.
+    pub file: String,
+    /// The locale of the translation, e.g. `de`.
+    pub locale: String,
+    /// The untranslated string or key.
+    pub key: String,
+}
+
+impl Display for MissingTranslation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}) doesn't translate {:?}",
+            self.file, self.locale, self.key
+        )
+    }
+}
+
+/// The entries of one locale of a `.po` or `.csv` file.
+struct Translation {
+    file: String,
+    locale: String,
+    entries: BTreeMap<String, String>,
+}
+
+/// Collect the translatable strings of the godot project, mapped to the `res://` paths of the
+/// files using them.
+pub fn collect_strings(godot_project_path: &Path) -> Result<BTreeMap<String, BTreeSet<String>>> {
+    let config = ProjectConfig::load(godot_project_path)?;
+    let mut sources = config
+        .get_string_array("internationalization", "locale/translations_pot_files")
+        .unwrap_or_default();
+    if sources.is_empty() {
+        find_files(godot_project_path, "", &mut sources)?;
+        sources.retain(|file| file.ends_with(".gd") || file.ends_with(".tscn"));
+    }
+    let mut strings: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for source in sources {
+        let found = if source.ends_with(".gd") {
+            script_strings
+        } else if source.ends_with(".tscn") {
+            scene_strings
+        } else {
+            continue;
+        };
+        let path = paths::from_res_path(godot_project_path, &source)?;
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read file: {path:?}"))?;
+        for string in found(&contents) {
+            strings.entry(string).or_default().insert(source.clone());
+        }
+    }
+    Ok(strings)
+}
+
+/// The `.pot` template of the translatable `strings`, see `collect_strings`.
+pub fn pot(strings: &BTreeMap<String, BTreeSet<String>>) -> String {
+    let mut pot =
+        String::from("msgid \"\"\nmsgstr \"\"\n\"Content-Type: text/plain; charset=UTF-8\\n\"\n");
+    for (string, files) in strings {
+        pot.push('\n');
+        for file in files {
+            pot.push_str(&format!("#: {file}\n"));
+        }
+        pot.push_str(&format!("msgid \"{}\"\nmsgstr \"\"\n", escape_po(string)));
+    }
+    pot
+}
+
+/// Write the `.pot` template of the godot project's translatable strings to `pot_path`.
+/// Returns the number of strings.
+pub fn write_pot(godot_project_path: &Path, pot_path: &Path) -> Result<usize> {
+    let strings = collect_strings(godot_project_path)?;
+    if let Some(parent) = pot_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {parent:?}"))?;
+    }
+    std::fs::write(pot_path, pot(&strings))
+        .with_context(|| format!("Failed to write file: {pot_path:?}"))?;
+    Ok(strings.len())
+}
+
+/// Find the translatable strings which the `.po` files and the `.csv` files imported as
+/// translations don't translate. Fuzzy `.po` entries count as untranslated, like in Godot.
+pub fn check_translations(godot_project_path: &Path) -> Result<Vec<MissingTranslation>> {
+    let strings = collect_strings(godot_project_path)?;
+    let mut missing = vec![];
+    for translation in translations(godot_project_path)? {
+        for key in strings.keys() {
+            if translation.entries.get(key).is_none_or(String::is_empty) {
+                missing.push(MissingTranslation {
+                    file: translation.file.clone(),
+                    locale: translation.locale.clone(),
+                    key: key.clone(),
+                });
+            }
+        }
+    }
+    Ok(missing)
+}
+
+/// The translations of the project's `.po` files and `.csv` files imported as translations.
+fn translations(godot_project_path: &Path) -> Result<Vec<Translation>> {
+    let mut files = vec![];
+    find_files(godot_project_path, "", &mut files)?;
+    let mut translations = vec![];
+    for file in files {
+        let path = paths::from_res_path(godot_project_path, &file)?;
+        if file.ends_with(".po") {
+            let contents = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read file: {path:?}"))?;
+            let (language, entries) = parse_po(&contents);
+            let locale = language.unwrap_or_else(|| {
+                path.file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            });
+            translations.push(Translation {
+                file,
+                locale,
+                entries,
+            });
+        } else if file.ends_with(".csv") {
+            let import = std::fs::read_to_string(path.with_extension("csv.import"))
+                .ok()
+                .and_then(|contents| ProjectConfig::parse(&contents).ok());
+            let Some(import) = import.filter(|import| {
+                import.get_string("remap", "importer").as_deref() == Some("csv_translation")
+            }) else {
+                continue;
+            };
+            let delimiter = match import.get("params", "delimiter") {
+                Some("1") => ';',
+                Some("2") => '\t',
+                _ => ',',
+            };
+            let contents = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read file: {path:?}"))?;
+            translations.extend(csv_translations(&file, &contents, delimiter));
+        }
+    }
+    Ok(translations)
+}
+
+/// The strings passed to translation functions in a GDScript file.
+fn script_strings(contents: &str) -> Vec<String> {
+    let mut strings = vec![];
+    for line in contents.lines() {
+        for function in TRANSLATION_FUNCTIONS {
+            for (index, _) in line.match_indices(&format!("{function}(")) {
+                let before = &line[..index];
+                if before.contains('#')
+                    || before.ends_with(|c: char| c.is_alphanumeric() || c == '_')
+                {
+                    continue;
+                }
+                let argument = line[index + function.len() + 1..].trim_start();
+                if let Some((string, _)) = string_literal(argument) {
+                    strings.push(string);
+                }
+            }
+        }
+    }
+    strings.retain(|string| !string.is_empty());
+    strings
+}
+
+/// The values of translatable node properties in a `.tscn` file.
+fn scene_strings(contents: &str) -> Vec<String> {
+    let mut strings = vec![];
+    let mut offset = 0;
+    for line in contents.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let Some((property, _)) = line.split_once(" = \"") else {
+            continue;
+        };
+        if TRANSLATABLE_PROPERTIES.contains(&property) {
+            // Strings in scenes may span several lines.
+            let value = &contents[start + property.len() + 3..];
+            if let Some((string, _)) = string_literal(value) {
+                strings.push(string);
+            }
+        }
+    }
+    strings.retain(|string| !string.is_empty());
+    strings
+}
+
+/// Parse the quoted string at the start of `text`.
+/// Returns the unescaped string and the text after it.
+fn string_literal(text: &str) -> Option<(String, &str)> {
+    let inner = text.strip_prefix('"')?;
+    let mut escaped = false;
+    for (index, c) in inner.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some((unescape(&inner[..index]), &inner[index + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Escape a string for use inside a quoted `.po` string.
+fn escape_po(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum PoField {
+    None,
+    Id,
+    Str,
+    PluralStr,
+    Other,
+}
+
+#[derive(Default)]
+struct PoEntry {
+    msgid: Option<String>,
+    msgstr: String,
+    fuzzy: bool,
+}
+
+/// The `Language` of the header and the translated entries of a `.po` file.
+/// Fuzzy entries are translated to an empty string.
+fn parse_po(contents: &str) -> (Option<String>, BTreeMap<String, String>) {
+    let mut language = None;
+    let mut entries = BTreeMap::new();
+    let mut flush = |entry: PoEntry| match entry.msgid {
+        Some(msgid) if msgid.is_empty() => {
+            language = entry
+                .msgstr
+                .lines()
+                .find_map(|line| line.strip_prefix("Language:"))
+                .map(|locale| locale.trim().to_string())
+                .filter(|locale| !locale.is_empty());
+        }
+        Some(msgid) => {
+            let msgstr = if entry.fuzzy {
+                String::new()
+            } else {
+                entry.msgstr
+            };
+            entries.insert(msgid, msgstr);
+        }
+        None => {}
+    };
+
+    let mut entry = PoEntry::default();
+    let mut field = PoField::None;
+    for line in contents.lines() {
+        let line = line.trim();
+        // A new entry starts with a blank line, or with comments or a msgid after a msgstr.
+        let after_msgstr = matches!(field, PoField::Str | PoField::PluralStr);
+        let starts_entry =
+            line.starts_with('#') || line.starts_with("msgctxt ") || line.starts_with("msgid ");
+        if line.is_empty() || (after_msgstr && starts_entry) {
+            flush(std::mem::take(&mut entry));
+            field = PoField::None;
+        }
+        if line.starts_with("#,") && line.contains("fuzzy") {
+            entry.fuzzy = true;
+        }
+        let (next, text) = match line.split_once(' ') {
+            Some(("msgid", text)) => (PoField::Id, text),
+            Some(("msgstr" | "msgstr[0]", text)) => (PoField::Str, text),
+            Some((keyword, text)) if keyword.starts_with("msgstr[") => (PoField::PluralStr, text),
+            Some(("msgctxt" | "msgid_plural", text)) => (PoField::Other, text),
+            _ if line.starts_with('"') => (field, line),
+            _ => continue,
+        };
+        field = next;
+        let Some((text, _)) = string_literal(text.trim()) else {
+            continue;
+        };
+        match field {
+            PoField::Id => entry.msgid.get_or_insert_default().push_str(&text),
+            PoField::Str => entry.msgstr.push_str(&text),
+            _ => {}
+        }
+    }
+    flush(entry);
+    (language, entries)
+}
+
+/// The translations of a `.csv` file with a `keys,<locale>,...` header. Like in Godot,
+/// columns whose header is empty or starts with `_` are ignored.
+fn csv_translations(file: &str, contents: &str, delimiter: char) -> Vec<Translation> {
+    let mut records = parse_csv(contents, delimiter).into_iter();
+    let Some(header) = records.next() else {
+        return vec![];
+    };
+    let records: Vec<_> = records
+        .filter(|record| record.first().is_some_and(|key| !key.is_empty()))
+        .collect();
+    header
+        .iter()
+        .enumerate()
+        .skip(1)
+        .filter(|(_, locale)| !locale.is_empty() && !locale.starts_with('_'))
+        .map(|(column, locale)| Translation {
+            file: file.to_string(),
+            locale: locale.clone(),
+            entries: records
+                .iter()
+                .map(|record| {
+                    let value = record.get(column).cloned().unwrap_or_default();
+                    (record[0].clone(), value)
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Parse CSV records, with fields optionally quoted with `"`.
+fn parse_csv(contents: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = contents.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            c if quoted => field.push(c),
+            '\r' => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            c if c == delimiter => record.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_translations() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path();
+        std::fs::create_dir_all(project.join("locale")).unwrap();
+        let files = [
+            ("project.godot", "config_version=5\n"),
+            (
+                "main.tscn",
+                "[gd_scene format=3]\n\n[node name=\"Title\" type=\"Label\"]\n\
+                text = \"Hello \\\"World\\\"\nand more\"\n\
+                tooltip_text = \"Greeting\"\nname_hint = \"Ignored\"\n",
+            ),
+            (
+                "player.gd",
+                "extends Node\n\nfunc _ready():\n\
+                \tprint(tr(\"Jump\"), str(\"Ignored\"))\n\
+                \t# tr(\"Commented\")\n\
+                \tvar n = tr_n(\"%d coin\", \"%d coins\", 2)\n",
+            ),
+            (
+                "locale/de.po",
+                "msgid \"\"\nmsgstr \"\"\n\"Language: de_DE\\n\"\n\n\
+                msgid \"Jump\"\nmsgstr \"Springen\"\n\n\
+                #, fuzzy\nmsgid \"Greeting\"\nmsgstr \"Gruß\"\n\
+                msgid \"%d coin\"\nmsgid_plural \"%d coins\"\nmsgstr[0] \"%d Münze\"\n\
+                msgstr[1] \"%d Münzen\"\n",
+            ),
+            (
+                "locale/text.csv",
+                "keys,fr,_comment\n\"Hello \"\"World\"\"\nand more\",Bonjour,x\nJump,,\n",
+            ),
+            (
+                "locale/text.csv.import",
+                "[remap]\n\nimporter=\"csv_translation\"\n\n[params]\n\ndelimiter=0\n",
+            ),
+            ("data.csv", "keys,en\nJump,\n"),
+        ];
+        for (file, contents) in files {
+            std::fs::write(project.join(file), contents).unwrap();
+        }
+
+        let strings = collect_strings(project).unwrap();
+        assert_eq!(
+            strings.keys().collect::<Vec<_>>(),
+            ["%d coin", "Greeting", "Hello \"World\"\nand more", "Jump"]
+        );
+        assert_eq!(
+            strings["Jump"],
+            BTreeSet::from(["res://player.gd".to_string()])
+        );
+
+        let pot_path = project.join("locale/messages.pot");
+        assert_eq!(write_pot(project, &pot_path).unwrap(), 4);
+        let pot = std::fs::read_to_string(pot_path).unwrap();
+        assert!(pot.contains("#: res://main.tscn\nmsgid \"Hello \\\"World\\\"\\nand more\"\n"));
+
+        let missing = check_translations(project).unwrap();
+        let missing: Vec<_> = missing
+            .iter()
+            .map(|m| (m.file.as_str(), m.locale.as_str(), m.key.as_str()))
+            .collect();
+        assert_eq!(
+            missing,
+            [
+                ("res://locale/de.po", "de_DE", "Greeting"),
+                ("res://locale/de.po", "de_DE", "Hello \"World\"\nand more"),
+                ("res://locale/text.csv", "fr", "%d coin"),
+                ("res://locale/text.csv", "fr", "Greeting"),
+                ("res://locale/text.csv", "fr", "Jump"),
+            ]
+        );
+    }
+}
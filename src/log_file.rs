@@ -0,0 +1,92 @@
+//! Routing Godot's log to a file with Godot's `--log-file` flag, see `GodotRunner::log_to_file`,
+//! so long editor sessions and CI runs leave searchable logs.
+//!
+//! Before every launch the previous log is rotated to a numbered file next to it, e.g.
+//! `godot.log` to `godot.1.log`, keeping `GodotRunner::log_retention` old logs.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let runner = GodotRunner::create("game", Path::new("godot"))
+//!     .log_to_file("target/logs/godot.log")
+//!     .log_retention(10);
+//! ```
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// The path of the `n`th old log, e.g. `godot.2.log` for `godot.log`.
+pub fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{stem}.{n}.{}", extension.to_string_lossy()),
+        None => format!("{stem}.{n}"),
+    };
+    path.with_file_name(name)
+}
+
+/// Prepare `path` for a new log: create its directory, and shift the existing logs to keep
+/// `retention` old ones. With a `retention` of `0` the log is overwritten.
+pub(crate) fn rotate(path: &Path, retention: usize) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {parent:?}"))?;
+    }
+    if retention == 0 || !path.exists() {
+        return Ok(());
+    }
+    let oldest = rotated_path(path, retention);
+    if oldest.exists() {
+        std::fs::remove_file(&oldest).with_context(|| format!("Failed to remove {oldest:?}"))?;
+    }
+    for n in (1..retention).rev() {
+        let from = rotated_path(path, n);
+        if from.exists() {
+            let to = rotated_path(path, n + 1);
+            std::fs::rename(&from, &to)
+                .with_context(|| format!("Failed to rename {from:?} to {to:?}"))?;
+        }
+    }
+    let to = rotated_path(path, 1);
+    std::fs::rename(path, &to).with_context(|| format!("Failed to rename {path:?} to {to:?}"))
+}
+
+/// The `--log-file` flag for `path`, made absolute since Godot runs in the project directory.
+pub(crate) fn cli_arguments(path: &Path) -> Result<Vec<String>> {
+    let path = std::path::absolute(path)
+        .with_context(|| format!("Failed to resolve log file path: {path:?}"))?;
+    Ok(vec![
+        "--log-file".to_string(),
+        path.to_string_lossy().into_owned(),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotate() {
+        assert_eq!(
+            rotated_path(Path::new("logs/godot.log"), 2),
+            Path::new("logs/godot.2.log")
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs/godot.log");
+        rotate(&path, 2).unwrap();
+        assert!(dir.path().join("logs").is_dir());
+        for run in ["first", "second", "third"] {
+            rotate(&path, 2).unwrap();
+            std::fs::write(&path, run).unwrap();
+        }
+        let read = |n| std::fs::read_to_string(rotated_path(&path, n)).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "third");
+        assert_eq!(read(1), "second");
+        assert_eq!(read(2), "first");
+        rotate(&path, 2).unwrap();
+        assert_eq!(read(2), "second");
+        assert!(!rotated_path(&path, 3).exists());
+
+        let args = cli_arguments(Path::new("godot.log")).unwrap();
+        assert_eq!(args[0], "--log-file");
+        assert!(Path::new(&args[1]).is_absolute());
+    }
+}
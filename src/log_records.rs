@@ -0,0 +1,193 @@
+//! Godot's output lines parsed into structured records with a severity, message and source,
+//! e.g. to annotate CI runs. See `GodotRunner::log_records` to receive the records of every
+//! launch on a channel, and `parse_log` for captured output.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let (sender, records) = std::sync::mpsc::channel();
+//! let runner = GodotRunner::create("game", Path::new("godot"))
+//!     .strip_ansi(true)
+//!     .log_records(sender);
+//! runner.execute()?;
+//! for record in records.try_iter().filter(|record| record.severity == LogSeverity::Error) {
+//!     println!("::error::{}", record.message);
+//! }
+//! ```
+use crate::output::{self, OutputCallback};
+use serde::Serialize;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+
+/// Prefixes of Godot's warning lines.
+const WARNING_PREFIXES: [&str; 4] = [
+    "WARNING:",
+    "USER WARNING:",
+    "SCRIPT WARNING:",
+    "USER SCRIPT WARNING:",
+];
+
+/// Prefixes of Godot's error lines.
+const ERROR_PREFIXES: [&str; 5] = [
+    "ERROR:",
+    "USER ERROR:",
+    "SCRIPT ERROR:",
+    "USER SCRIPT ERROR:",
+    "SHADER ERROR:",
+];
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum LogSeverity {
+    /// Regular output, e.g. of `print`.
+    Info,
+    Warning,
+    Error,
+}
+
+/// A line of Godot's output.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct LogRecord {
+    pub severity: LogSeverity,
+    /// The line without ANSI escape codes and the severity prefix such as `ERROR:`.
+    pub message: String,
+    /// Where a warning or error was raised, taken from the `at: ...` line following it.
+    pub source: Option<String>,
+}
+
+/// Turns lines into records, holding back warnings and errors until their source is known.
+#[derive(Debug, Default)]
+struct LogParser {
+    pending: Option<LogRecord>,
+}
+
+impl LogParser {
+    /// Parse `line`, returning the records which are complete.
+    fn push(&mut self, line: &str) -> Vec<LogRecord> {
+        let line = output::strip_ansi(line);
+        let trimmed = line.trim();
+        if let Some(source) = trimmed.strip_prefix("at:")
+            && let Some(mut record) = self.pending.take()
+        {
+            record.source = Some(source.trim().to_string());
+            return vec![record];
+        }
+        let mut records: Vec<_> = self.pending.take().into_iter().collect();
+        if trimmed.is_empty() {
+            return records;
+        }
+        let prefixed = |prefixes: &[&str]| {
+            prefixes
+                .iter()
+                .find_map(|prefix| trimmed.strip_prefix(prefix))
+        };
+        if let Some(message) = prefixed(&ERROR_PREFIXES) {
+            self.pending = Some(record(LogSeverity::Error, message));
+        } else if let Some(message) = prefixed(&WARNING_PREFIXES) {
+            self.pending = Some(record(LogSeverity::Warning, message));
+        } else {
+            records.push(record(LogSeverity::Info, line.trim_end()));
+        }
+        records
+    }
+
+    /// The record still held back once the output ended.
+    fn finish(&mut self) -> Option<LogRecord> {
+        self.pending.take()
+    }
+}
+
+fn record(severity: LogSeverity, message: &str) -> LogRecord {
+    LogRecord {
+        severity,
+        message: message.trim().to_string(),
+        source: None,
+    }
+}
+
+/// Parse complete output text into records, skipping empty lines.
+pub fn parse_log(output: &str) -> Vec<LogRecord> {
+    let mut parser = LogParser::default();
+    let mut records: Vec<_> = output.lines().flat_map(|line| parser.push(line)).collect();
+    records.extend(parser.finish());
+    records
+}
+
+/// Sends the records to a channel, and the last one once the output ended and the callback is
+/// dropped.
+struct ChannelParser {
+    parser: LogParser,
+    sender: Sender<LogRecord>,
+}
+
+impl Drop for ChannelParser {
+    fn drop(&mut self) {
+        if let Some(record) = self.parser.finish() {
+            let _ = self.sender.send(record);
+        }
+    }
+}
+
+/// A callback sending the records of the lines to `sender`. Records are dropped once the
+/// receiver is gone.
+pub(crate) fn callback(sender: Sender<LogRecord>) -> OutputCallback {
+    let parser = Mutex::new(ChannelParser {
+        parser: LogParser::default(),
+        sender,
+    });
+    Arc::new(move |line| {
+        if let Ok(mut parser) = parser.lock() {
+            for record in parser.parser.push(line) {
+                let _ = parser.sender.send(record);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_records() {
+        let output = "Godot Engine v4.5.1.stable.official\n\
+            \n\
+            \x1b[1;33mWARNING:\x1b[0m Unused variable\n\
+            \x20  at: GDScript::reload (res://main.gd:3)\n\
+            ERROR: Failed loading resource: res://missing.tscn.\n\
+            Hello\n\
+            SCRIPT ERROR: Invalid call.\n\
+            \x20  at: _ready (res://main.gd:7)\n";
+        let records = parse_log(output);
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| record.severity)
+                .collect::<Vec<_>>(),
+            [
+                LogSeverity::Info,
+                LogSeverity::Warning,
+                LogSeverity::Error,
+                LogSeverity::Info,
+                LogSeverity::Error
+            ]
+        );
+        assert_eq!(records[1].message, "Unused variable");
+        assert_eq!(
+            records[1].source.as_deref(),
+            Some("GDScript::reload (res://main.gd:3)")
+        );
+        assert_eq!(records[2].source, None);
+        assert_eq!(
+            records[4].source.as_deref(),
+            Some("_ready (res://main.gd:7)")
+        );
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let on_line = callback(sender);
+        on_line("Hello");
+        on_line("ERROR: Last line");
+        assert_eq!(receiver.try_iter().count(), 1);
+        drop(on_line);
+        let last: Vec<_> = receiver.iter().collect();
+        assert_eq!(last, [record(LogSeverity::Error, "Last line")]);
+    }
+}
@@ -0,0 +1,100 @@
+//! Helpers for Godot's Movie Maker mode (`--write-movie`), see `GodotRunner::record_movie`.
+//!
+//! In Movie Maker mode Godot renders every frame at a fixed frame rate regardless of how long
+//! rendering takes, and writes the frames to a video file or an image sequence. It requires a
+//! rendering driver, so it doesn't work with `--headless`.
+use anyhow::{Context, Result, anyhow};
+use std::path::{Path, PathBuf};
+
+/// The output format of Movie Maker mode, selected by the output file extension.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MovieFormat {
+    /// An MJPEG `.avi` video with audio.
+    Avi,
+    /// A sequence of `.png` images numbered by frame, with the audio in a separate `.wav` file.
+    Png,
+}
+
+impl MovieFormat {
+    /// The format of `output_path`, or an error if Godot can't write it.
+    pub fn from_path(output_path: &Path) -> Result<Self> {
+        match output_path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_lowercase)
+            .as_deref()
+        {
+            Some("avi") => Ok(MovieFormat::Avi),
+            Some("png") => Ok(MovieFormat::Png),
+            _ => Err(anyhow!(
+                "Movie output path must end in `.avi` or `.png`: {output_path:?}"
+            )),
+        }
+    }
+
+    /// The first file Godot writes for `output_path`.
+    /// Image sequences are numbered from `00000000`, e.g. `frames00000000.png` for `frames.png`.
+    pub fn first_file(&self, output_path: &Path) -> PathBuf {
+        match self {
+            MovieFormat::Avi => output_path.to_path_buf(),
+            MovieFormat::Png => {
+                let stem = output_path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy())
+                    .unwrap_or_default();
+                output_path.with_file_name(format!("{stem}00000000.png"))
+            }
+        }
+    }
+}
+
+/// Check the output path, resolve it against the current directory and create its parent.
+/// Godot would resolve relative paths against the project directory instead.
+pub(crate) fn prepare_output_path(output_path: &Path) -> Result<(PathBuf, MovieFormat)> {
+    let format = MovieFormat::from_path(output_path)?;
+    let output_path = std::path::absolute(output_path)
+        .with_context(|| format!("Failed to resolve movie path: {output_path:?}"))?;
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create movie directory: {parent:?}"))?;
+    }
+    Ok((output_path, format))
+}
+
+/// The Godot CLI flags and their values to record a movie at `fps` frames per second.
+pub(crate) fn cli_arguments(output_path: &Path, fps: u32) -> Vec<Vec<String>> {
+    vec![
+        vec![
+            "--write-movie".to_string(),
+            output_path.to_string_lossy().into_owned(),
+        ],
+        vec!["--fixed-fps".to_string(), fps.to_string()],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_movie_format() {
+        assert_eq!(
+            MovieFormat::from_path(Path::new("out/run.AVI")).unwrap(),
+            MovieFormat::Avi
+        );
+        assert!(
+            MovieFormat::from_path(Path::new("out/run.mp4"))
+                .unwrap_err()
+                .to_string()
+                .contains("must end in `.avi` or `.png`")
+        );
+        assert_eq!(
+            MovieFormat::Png.first_file(Path::new("out/frames.png")),
+            Path::new("out/frames00000000.png")
+        );
+        assert_eq!(
+            cli_arguments(Path::new("run.avi"), 30).concat(),
+            vec!["--write-movie", "run.avi", "--fixed-fps", "30"]
+        );
+    }
+}
@@ -0,0 +1,77 @@
+//! Notifications when the runner's cargo build finishes or fails, see
+//! `GodotRunner::notify_build`, e.g. to switch back to the editor once a rebuild is done.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let runner = GodotRunner::create("game", Path::new("godot"))
+//!     .cargo_build(CargoBuild::default())
+//!     .notify_build(BuildNotification::Desktop);
+//! ```
+use anyhow::Error;
+
+/// How to notify about a finished build.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BuildNotification {
+    /// Print the outcome and ring the terminal bell, which most terminals turn into a visual or
+    /// taskbar alert.
+    Bell,
+    /// Show a desktop notification with the outcome.
+    #[cfg(feature = "notify")]
+    Desktop,
+}
+
+impl BuildNotification {
+    /// Notify about the build of `crate_name`, which failed with the error if given.
+    pub fn send(self, crate_name: &str, error: Option<&Error>) {
+        let (summary, body) = message(crate_name, error);
+        match self {
+            Self::Bell => eprintln!("{summary}\x07"),
+            #[cfg(feature = "notify")]
+            Self::Desktop => {
+                if let Err(e) = notify_rust::Notification::new()
+                    .summary(&summary)
+                    .body(&body)
+                    .appname("cargo-godot-lib")
+                    .show()
+                {
+                    eprintln!("Warning: Failed to show a desktop notification: {e}");
+                }
+            }
+        }
+        #[cfg(not(feature = "notify"))]
+        let _ = body;
+    }
+}
+
+/// The summary and body of the notification about the build of `crate_name`.
+fn message(crate_name: &str, error: Option<&Error>) -> (String, String) {
+    match error {
+        None => (
+            format!("Built {crate_name}"),
+            "The extension is ready".to_string(),
+        ),
+        Some(error) => (
+            format!("Failed to build {crate_name}"),
+            format!("{error:#}"),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_message() {
+        assert_eq!(message("game", None).0, "Built game");
+        let error = anyhow!("error[E0308]: mismatched types").context("cargo build failed");
+        assert_eq!(
+            message("game", Some(&error)),
+            (
+                "Failed to build game".to_string(),
+                "cargo build failed: error[E0308]: mismatched types".to_string()
+            )
+        );
+    }
+}
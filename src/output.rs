@@ -0,0 +1,317 @@
+//! Scanning of Godot's console output for reported errors.
+//!
+//! Godot keeps running after most errors, e.g. when a GDExtension library fails to load,
+//! so the exit status alone doesn't tell whether a run went well. When output scanning is
+//! enabled, Godot's stdout and stderr are passed through to the console while lines such as
+//! `ERROR: ...` and `SCRIPT ERROR: ...` are collected. ANSI escape codes are removed from the
+//! scanned lines, and from the passed through output with `GodotRunner::strip_ansi`.
+use serde::Serialize;
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+/// Called with every line of Godot's output, see `godot_commands::spawn_godot_watched`.
+pub type OutputCallback = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// The kind of error reported by Godot.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum GodotErrorKind {
+    /// An engine error (`ERROR:`), e.g. a missing resource.
+    Error,
+    /// A GDScript error (`SCRIPT ERROR:`), e.g. a parse error.
+    ScriptError,
+    /// A shader compilation error (`SHADER ERROR:`).
+    ShaderError,
+    /// An error loading a GDExtension library, e.g. a missing or incompatible library file.
+    GdExtension,
+}
+
+/// An error printed by Godot.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct GodotError {
+    pub kind: GodotErrorKind,
+    pub message: String,
+    /// Where the error was raised, taken from the `at: ...` line following the error.
+    pub location: Option<String>,
+}
+
+impl Display for GodotError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            GodotErrorKind::Error => "ERROR",
+            GodotErrorKind::ScriptError => "SCRIPT ERROR",
+            GodotErrorKind::ShaderError => "SHADER ERROR",
+            GodotErrorKind::GdExtension => "GDEXTENSION ERROR",
+        };
+        write!(f, "{kind}: {}", self.message)?;
+        if let Some(location) = &self.location {
+            write!(f, " (at: {location})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects errors from Godot's output one line at a time.
+#[derive(Clone, Debug, Default)]
+pub struct OutputScanner {
+    errors: Vec<GodotError>,
+    /// True if the previous line was an error, so that an `at:` line belongs to it.
+    in_error: bool,
+}
+
+impl OutputScanner {
+    /// Scan a single line of output.
+    pub fn scan_line(&mut self, line: &str) {
+        let trimmed = line.trim();
+        if self.in_error
+            && let Some(location) = trimmed.strip_prefix("at:")
+            && let Some(error) = self.errors.last_mut()
+        {
+            error.location = Some(location.trim().to_string());
+            self.in_error = false;
+            return;
+        }
+
+        self.in_error = false;
+        let (kind, message) = if let Some(message) = trimmed
+            .strip_prefix("SCRIPT ERROR:")
+            .or_else(|| trimmed.strip_prefix("USER SCRIPT ERROR:"))
+        {
+            (GodotErrorKind::ScriptError, message)
+        } else if let Some(message) = trimmed.strip_prefix("SHADER ERROR:") {
+            (GodotErrorKind::ShaderError, message)
+        } else if let Some(message) = trimmed
+            .strip_prefix("ERROR:")
+            .or_else(|| trimmed.strip_prefix("USER ERROR:"))
+        {
+            let kind = if is_gdextension_error(message) {
+                GodotErrorKind::GdExtension
+            } else {
+                GodotErrorKind::Error
+            };
+            (kind, message)
+        } else {
+            return;
+        };
+
+        self.errors.push(GodotError {
+            kind,
+            message: message.trim().to_string(),
+            location: None,
+        });
+        self.in_error = true;
+    }
+
+    /// The errors found so far.
+    pub fn errors(&self) -> &[GodotError] {
+        &self.errors
+    }
+
+    /// Consume the scanner, returning the errors found.
+    pub fn into_errors(self) -> Vec<GodotError> {
+        self.errors
+    }
+}
+
+/// Scan complete output text for errors.
+pub fn scan_output(output: &str) -> Vec<GodotError> {
+    let mut scanner = OutputScanner::default();
+    output.lines().for_each(|line| scanner.scan_line(line));
+    scanner.into_errors()
+}
+
+/// Summarize `errors` for an error message, listing GDExtension errors first.
+pub fn summarize(errors: &[GodotError]) -> String {
+    let mut sorted = errors.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|error| error.kind != GodotErrorKind::GdExtension);
+    let mut summary = format!("Godot reported {} error(s):", errors.len());
+    for error in sorted {
+        summary.push_str(&format!("\n  - {error}"));
+    }
+    summary
+}
+
+fn is_gdextension_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("gdextension") || message.contains("dynamic library")
+}
+
+/// Remove ANSI escape codes such as colors from `text`.
+pub fn strip_ansi(text: &str) -> Cow<'_, str> {
+    if !text.contains('\x1b') {
+        return Cow::Borrowed(text);
+    }
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+            continue;
+        }
+        match chars.next() {
+            // Control sequences end with a byte in `@` to `~`, e.g. `\x1b[1;31m`.
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // Operating system commands end with BEL or `\x1b\`, e.g. hyperlinks.
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Cow::Owned(stripped)
+}
+
+/// A callback passing every line to all `callbacks`, or `None` if there are none.
+pub(crate) fn combine(callbacks: Vec<OutputCallback>) -> Option<OutputCallback> {
+    (!callbacks.is_empty()).then(|| {
+        Arc::new(move |line: &str| callbacks.iter().for_each(|callback| callback(line)))
+            as OutputCallback
+    })
+}
+
+/// Pass `reader` through to `writer` line by line on a background thread, without ANSI escape
+/// codes if `strip_ansi`, passing each line to `on_line`. The lines are scanned for errors if
+/// `scan`, otherwise no errors are returned.
+pub(crate) fn tee(
+    reader: impl Read + Send + 'static,
+    mut writer: impl Write + Send + 'static,
+    on_line: OutputCallback,
+    strip_ansi: bool,
+    scan: bool,
+) -> JoinHandle<Vec<GodotError>> {
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+        let mut scanner = OutputScanner::default();
+        let mut line = vec![];
+        while reader.read_until(b'\n', &mut line).is_ok_and(|n| n > 0) {
+            let text = String::from_utf8_lossy(&line);
+            let stripped = self::strip_ansi(&text);
+            let written = if strip_ansi {
+                stripped.as_bytes()
+            } else {
+                &line
+            };
+            let _ = writer.write_all(written).and_then(|_| writer.flush());
+            if scan {
+                scanner.scan_line(&stripped);
+            }
+            on_line(stripped.trim_end());
+            line.clear();
+        }
+        scanner.into_errors()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_output() {
+        let errors = scan_output(
+            "Godot Engine v4.5.1.stable.official - https://godotengine.org\n\
+            ERROR: Can't open dynamic library: /tmp/libmy_crate.so.\n\
+            \x20  at: open_dynamic_library (drivers/unix/os_unix.cpp:1011)\n\
+            SCRIPT ERROR: Parse Error: Identifier \"foo\" not declared in the current scope.\n\
+            \x20         at: GDScript::reload (res://main.gd:5)\n\
+            WARNING: Not an error\n\
+            ERROR: Failed loading resource: res://missing.tscn.\n\
+            SHADER ERROR: Expected ';' after statement.\n\
+            \x20         at: (null) (res://water.gdshader:7)\n",
+        );
+        assert_eq!(errors.len(), 4);
+        assert_eq!(errors[0].kind, GodotErrorKind::GdExtension);
+        assert_eq!(
+            errors[0].location.as_deref(),
+            Some("open_dynamic_library (drivers/unix/os_unix.cpp:1011)")
+        );
+        assert_eq!(errors[1].kind, GodotErrorKind::ScriptError);
+        assert_eq!(
+            errors[1].location.as_deref(),
+            Some("GDScript::reload (res://main.gd:5)")
+        );
+        assert_eq!(errors[2].kind, GodotErrorKind::Error);
+        assert_eq!(errors[2].location, None);
+        assert_eq!(errors[3].kind, GodotErrorKind::ShaderError);
+        assert_eq!(
+            errors[3].location.as_deref(),
+            Some("(null) (res://water.gdshader:7)")
+        );
+
+        let summary = summarize(&errors);
+        assert!(summary.starts_with("Godot reported 4 error(s):\n  - GDEXTENSION ERROR"));
+    }
+
+    #[test]
+    fn test_tee() {
+        let output = b"line\nERROR: broken\n".to_vec();
+        let lines = Arc::new(std::sync::Mutex::new(vec![]));
+        let on_line = {
+            let lines = lines.clone();
+            Arc::new(move |line: &str| lines.lock().unwrap().push(line.to_string()))
+        };
+        let errors = tee(
+            std::io::Cursor::new(output),
+            std::io::sink(),
+            on_line.clone(),
+            false,
+            true,
+        )
+        .join()
+        .unwrap();
+        assert_eq!(*lines.lock().unwrap(), vec!["line", "ERROR: broken"]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "broken");
+        let unscanned = tee(
+            std::io::Cursor::new(b"ERROR: broken\n".to_vec()),
+            std::io::sink(),
+            on_line,
+            false,
+            false,
+        )
+        .join()
+        .unwrap();
+        assert!(unscanned.is_empty());
+
+        assert_eq!(
+            strip_ansi(
+                "\x1b[1;31mERROR:\x1b[0m broken \x1b]8;;https://godotengine.org\x07link\x1b]8;;\x1b\\"
+            ),
+            "ERROR: broken link"
+        );
+        let written = Arc::new(std::sync::Mutex::new(vec![]));
+        struct Shared(Arc<std::sync::Mutex<Vec<u8>>>);
+        impl Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let output = b"\x1b[1;31mERROR:\x1b[0m colored\n".to_vec();
+        let errors = tee(
+            std::io::Cursor::new(output),
+            Shared(written.clone()),
+            Arc::new(|_: &str| {}),
+            true,
+            true,
+        )
+        .join()
+        .unwrap();
+        assert_eq!(*written.lock().unwrap(), b"ERROR: colored\n");
+        assert_eq!(errors[0].message, "colored");
+    }
+}
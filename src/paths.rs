@@ -0,0 +1,302 @@
+//! Converting between filesystem paths and Godot's `res://` and `user://` paths.
+//!
+//! Godot paths always use forward slashes. Paths are canonicalized before they are compared, so
+//! symlinks and the Windows `\\?\` verbatim prefix don't produce unexpected `..` components, and
+//! paths on different drives or UNC shares, which can't be made relative, are reported as errors.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let res_path = paths::to_res_path(project, &project.join("scenes/main.tscn"))?;
+//! assert_eq!(res_path, "res://scenes/main.tscn");
+//! let save = paths::from_user_path(&paths::user_data_dir(project)?, "user://saves/1.save")?;
+//! ```
+use crate::project_config::ProjectConfig;
+use anyhow::{Context, Result, anyhow};
+use pathdiff::diff_paths;
+use std::path::{Component, Path, PathBuf, Prefix};
+
+/// How paths are made absolute before relative paths are computed from them.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CanonicalizeMode {
+    /// Keep symlinks and only remove `.` and `..` components lexically, like `pwd -L`. Use this
+    /// when the godot project is reached through a symlinked directory and relative paths should
+    /// be computed from the symlink.
+    Logical,
+    /// Resolve symlinks with `std::fs::canonicalize`.
+    #[default]
+    Physical,
+}
+
+/// The absolute form of the existing `path` according to `mode`.
+pub fn canonicalize(path: &Path, mode: CanonicalizeMode) -> std::io::Result<PathBuf> {
+    match mode {
+        CanonicalizeMode::Physical => path.canonicalize(),
+        CanonicalizeMode::Logical => {
+            let path = normalize_lexically(&std::path::absolute(path)?);
+            // Fail for missing paths like `canonicalize` does.
+            std::fs::metadata(&path)?;
+            Ok(path)
+        }
+    }
+}
+
+/// Remove `.` and `..` components without accessing the filesystem.
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// The `res://` path of `path`, relative to the godot project. Paths outside of the project get
+/// `..` components, which Godot accepts e.g. for `.gdextension` library paths.
+pub fn to_res_path(godot_project_path: &Path, path: &Path) -> Result<String> {
+    Ok(format!("res://{}", relative_to(path, godot_project_path)?))
+}
+
+/// The filesystem path of the `res://` path `res_path` in the godot project.
+pub fn from_res_path(godot_project_path: &Path, res_path: &str) -> Result<PathBuf> {
+    let relative = res_path
+        .strip_prefix("res://")
+        .with_context(|| format!("Not a res:// path: {res_path:?}"))?;
+    Ok(join_godot_path(godot_project_path, relative))
+}
+
+/// The `user://` path of `path`, relative to the user data directory `user_dir`,
+/// see `user_data_dir`.
+pub fn to_user_path(user_dir: &Path, path: &Path) -> Result<String> {
+    Ok(format!("user://{}", relative_to(path, user_dir)?))
+}
+
+/// The filesystem path of the `user://` path `user_path` in the user data directory `user_dir`.
+pub fn from_user_path(user_dir: &Path, user_path: &str) -> Result<PathBuf> {
+    let relative = user_path
+        .strip_prefix("user://")
+        .with_context(|| format!("Not a user:// path: {user_path:?}"))?;
+    Ok(join_godot_path(user_dir, relative))
+}
+
+/// The default `user://` directory of the godot project, derived from its `config/name` like
+/// Godot does: `app_userdata/<name>` in Godot's data directory. Projects with
+/// `application/config/use_custom_user_dir` set are not supported.
+pub fn user_data_dir(godot_project_path: &Path) -> Result<PathBuf> {
+    let name = ProjectConfig::load(godot_project_path)?
+        .name()
+        .unwrap_or_else(|| "[unnamed project]".to_string());
+    Ok(godot_data_dir()?.join("app_userdata").join(name))
+}
+
+/// Godot's data directory, which respects `XDG_DATA_HOME` on Linux and macOS.
+fn godot_data_dir() -> Result<PathBuf> {
+    let env_dir = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty());
+    if cfg!(windows) {
+        let app_data = env_dir("APPDATA").context("APPDATA is not set")?;
+        return Ok(PathBuf::from(app_data).join("Godot"));
+    }
+    if let Some(data_home) = env_dir("XDG_DATA_HOME") {
+        return Ok(PathBuf::from(data_home).join("godot"));
+    }
+    let home = PathBuf::from(env_dir("HOME").context("HOME is not set")?);
+    Ok(if cfg!(target_os = "macos") {
+        home.join("Library/Application Support/Godot")
+    } else {
+        home.join(".local/share/godot")
+    })
+}
+
+/// `path` relative to `base` as a forward slash string, after canonicalizing both.
+fn relative_to(path: &Path, base: &Path) -> Result<String> {
+    let path = canonicalize_lenient(path)?;
+    let base = canonicalize_lenient(base)?;
+    if path_root(&path) != path_root(&base) {
+        return Err(anyhow!(
+            "{path:?} and {base:?} are on different drives, so no relative path exists"
+        ));
+    }
+    project_path_string(&path, &base, false)
+}
+
+/// Canonicalize `path`, or its longest existing ancestor for paths which don't exist yet.
+fn canonicalize_lenient(path: &Path) -> Result<PathBuf> {
+    let absolute =
+        std::path::absolute(path).with_context(|| format!("Failed to make {path:?} absolute"))?;
+    let mut existing = absolute.as_path();
+    let mut rest = vec![];
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return Ok(rest
+                .iter()
+                .rev()
+                .fold(canonical, |path, name| path.join(name)));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => return Ok(absolute),
+        }
+    }
+}
+
+/// Join the forward slash path `relative` to `base`.
+fn join_godot_path(base: &Path, relative: &str) -> PathBuf {
+    relative
+        .split('/')
+        .filter(|component| !component.is_empty())
+        .fold(base.to_path_buf(), |path, component| path.join(component))
+}
+
+/// The absolute `path`, or `path` relative to the godot project, as a forward slash string.
+pub(crate) fn project_path_string(
+    path: &Path,
+    godot_project_path: &Path,
+    absolute: bool,
+) -> Result<String> {
+    if absolute {
+        return absolute_path_string(path);
+    }
+    Ok(diff_paths(path, godot_project_path)
+        .with_context(|| {
+            format!(
+                "Failed to calculate relative target path: target={:?} -> godot_project={:?}",
+                path, godot_project_path
+            )
+        })?
+        .to_str()
+        .context("Failed to convert relative target path to string")?
+        .to_string()
+        .replace('\\', "/")) // Godot res:// paths are always forward slashes.
+}
+
+/// The drive or UNC share of a Windows path, normalized for comparison. `None` on other platforms.
+pub(crate) fn path_root(path: &Path) -> Option<String> {
+    match path.components().next()? {
+        Component::Prefix(prefix) => Some(match prefix.kind() {
+            Prefix::Disk(drive) | Prefix::VerbatimDisk(drive) => {
+                format!("{}:", drive.to_ascii_uppercase() as char)
+            }
+            Prefix::UNC(server, share) | Prefix::VerbatimUNC(server, share) => format!(
+                r"\\{}\{}",
+                server.to_string_lossy().to_lowercase(),
+                share.to_string_lossy().to_lowercase()
+            ),
+            _ => prefix.as_os_str().to_string_lossy().to_string(),
+        }),
+        _ => None,
+    }
+}
+
+/// Convert an absolute path to a forward slash string, without the Windows `\\?\` verbatim prefix
+/// that `canonicalize` adds. Verbatim UNC paths become `//server/share/...`.
+pub(crate) fn absolute_path_string(path: &Path) -> Result<String> {
+    let path = path
+        .to_str()
+        .with_context(|| format!("Failed to convert path to string: {path:?}"))?;
+    let path = match path.strip_prefix(r"\\?\UNC\") {
+        Some(unc) => format!(r"\\{unc}"),
+        None => path.strip_prefix(r"\\?\").unwrap_or(path).to_string(),
+    };
+    Ok(path.replace('\\', "/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_res_and_user_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("godot");
+        std::fs::create_dir_all(project.join("scenes")).unwrap();
+        std::fs::write(
+            project.join("project.godot"),
+            "config_version=5\n\n[application]\n\nconfig/name=\"My Game\"\n",
+        )
+        .unwrap();
+
+        let scene = project.join("scenes/main.tscn");
+        assert_eq!(
+            to_res_path(&project, &scene).unwrap(),
+            "res://scenes/main.tscn"
+        );
+        assert_eq!(
+            from_res_path(&project, "res://scenes/main.tscn").unwrap(),
+            scene
+        );
+        assert_eq!(
+            to_res_path(&project, &dir.path().join("target/libgame.so")).unwrap(),
+            "res://../target/libgame.so"
+        );
+        assert!(from_res_path(&project, "user://save").is_err());
+
+        let user_dir = user_data_dir(&project).unwrap();
+        assert!(user_dir.ends_with("app_userdata/My Game"));
+        let save = from_user_path(&user_dir, "user://saves/1.save").unwrap();
+        assert_eq!(save, user_dir.join("saves").join("1.save"));
+        assert_eq!(
+            to_user_path(&user_dir, &save).unwrap(),
+            "user://saves/1.save"
+        );
+
+        #[cfg(unix)]
+        {
+            let link = dir.path().join("link");
+            std::os::unix::fs::symlink(&project, &link).unwrap();
+            assert_eq!(
+                to_res_path(&link, &scene).unwrap(),
+                "res://scenes/main.tscn"
+            );
+        }
+    }
+
+    #[test]
+    fn test_canonicalize_modes() {
+        let dir = tempfile::tempdir().unwrap();
+        let real = dir.path().canonicalize().unwrap().join("real");
+        std::fs::create_dir_all(real.join("sub")).unwrap();
+        let dotted = real.join("sub/../sub/.");
+        assert_eq!(
+            canonicalize(&dotted, CanonicalizeMode::Logical).unwrap(),
+            real.join("sub")
+        );
+        assert!(canonicalize(&real.join("missing"), CanonicalizeMode::Logical).is_err());
+
+        #[cfg(unix)]
+        {
+            let link = real.parent().unwrap().join("link");
+            std::os::unix::fs::symlink(&real, &link).unwrap();
+            assert_eq!(
+                canonicalize(&link.join("sub"), CanonicalizeMode::Logical).unwrap(),
+                link.join("sub")
+            );
+            assert_eq!(
+                canonicalize(&link.join("sub"), CanonicalizeMode::Physical).unwrap(),
+                real.join("sub")
+            );
+        }
+    }
+
+    #[test]
+    fn test_absolute_path_string() {
+        assert_eq!(
+            absolute_path_string(Path::new(r"\\?\C:\project\lib.dll")).unwrap(),
+            "C:/project/lib.dll"
+        );
+        assert_eq!(
+            absolute_path_string(Path::new(r"\\?\UNC\server\share\lib.dll")).unwrap(),
+            "//server/share/lib.dll"
+        );
+        assert_eq!(
+            absolute_path_string(Path::new("/home/user/lib.so")).unwrap(),
+            "/home/user/lib.so"
+        );
+    }
+}
@@ -0,0 +1,278 @@
+//! Collecting Godot's performance monitors during a run, see `GodotRunner::collect_performance`,
+//! with thresholds for CI performance gates.
+//!
+//! A temporary autoload prints the monitors as a JSON line every `interval`. The samples and the
+//! violated thresholds are listed in the `RunReport`, and `GodotRunner::execute` fails if a
+//! threshold was violated. Memory is only tracked by debug builds of Godot.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let runner = GodotRunner::create("game", Path::new("godot"))
+//!     .frame_limit(600)
+//!     .collect_performance(
+//!         PerformanceMonitor::default()
+//!             .warmup(Duration::from_secs(2))
+//!             .min_fps(55.0)
+//!             .max_objects(50_000),
+//!     );
+//! let report = runner.execute_with_report()?;
+//! println!("{:?}", report.performance);
+//! ```
+use crate::autoload::TemporaryAutoload;
+use crate::output::OutputCallback;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The prefix of the output lines with a sample.
+const SAMPLE_PREFIX: &str = "cargo-godot-lib: performance ";
+
+const PERFORMANCE_SCRIPT: &str = r#"extends Node
+
+# Injected by cargo-godot-lib to print performance monitors.
+
+const INTERVAL := 1.0
+
+func _ready() -> void:
+	process_mode = Node.PROCESS_MODE_ALWAYS
+	var timer := Timer.new()
+	timer.wait_time = INTERVAL
+	timer.timeout.connect(_sample)
+	add_child(timer)
+	timer.start()
+
+func _sample() -> void:
+	print("cargo-godot-lib: performance ", JSON.stringify({
+		"time": Time.get_ticks_msec() / 1000.0,
+		"fps": Performance.get_monitor(Performance.TIME_FPS),
+		"frame_time": Performance.get_monitor(Performance.TIME_PROCESS),
+		"physics_frame_time": Performance.get_monitor(Performance.TIME_PHYSICS_PROCESS),
+		"memory": int(Performance.get_monitor(Performance.MEMORY_STATIC)),
+		"objects": int(Performance.get_monitor(Performance.OBJECT_COUNT)),
+		"nodes": int(Performance.get_monitor(Performance.OBJECT_NODE_COUNT)),
+		"orphan_nodes": int(Performance.get_monitor(Performance.OBJECT_ORPHAN_NODE_COUNT)),
+	}))
+"#;
+
+/// The performance monitors at one point of a run.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PerformanceSample {
+    /// Seconds since Godot started.
+    pub time: f64,
+    pub fps: f64,
+    /// Seconds spent processing the last frame.
+    pub frame_time: f64,
+    /// Seconds spent processing the last physics frame.
+    pub physics_frame_time: f64,
+    /// Static memory in bytes.
+    pub memory: u64,
+    pub objects: u64,
+    pub nodes: u64,
+    /// Nodes outside of the scene tree, which often are leaks.
+    pub orphan_nodes: u64,
+}
+
+/// The samples of a run and the thresholds they violated.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct PerformanceReport {
+    pub samples: Vec<PerformanceSample>,
+    /// A message for every violated threshold.
+    pub violations: Vec<String>,
+}
+
+/// Options and thresholds for `GodotRunner::collect_performance`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PerformanceMonitor {
+    interval: Duration,
+    warmup: Duration,
+    min_fps: Option<f64>,
+    max_frame_time: Option<Duration>,
+    max_memory: Option<u64>,
+    max_objects: Option<u64>,
+}
+
+impl Default for PerformanceMonitor {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            warmup: Duration::ZERO,
+            min_fps: None,
+            max_frame_time: None,
+            max_memory: None,
+            max_objects: None,
+        }
+    }
+}
+
+impl PerformanceMonitor {
+    /// How often to sample the monitors. Default: every second.
+    pub fn interval(self, interval: Duration) -> Self {
+        Self { interval, ..self }
+    }
+
+    /// Ignore samples taken before this time since Godot started for the thresholds, e.g. while
+    /// loading the main scene. Default: zero.
+    pub fn warmup(self, warmup: Duration) -> Self {
+        Self { warmup, ..self }
+    }
+
+    /// Fail if a sample is below this many frames per second. Default: none.
+    pub fn min_fps(self, min_fps: f64) -> Self {
+        Self {
+            min_fps: Some(min_fps),
+            ..self
+        }
+    }
+
+    /// Fail if processing a frame took longer. Default: none.
+    pub fn max_frame_time(self, max_frame_time: Duration) -> Self {
+        Self {
+            max_frame_time: Some(max_frame_time),
+            ..self
+        }
+    }
+
+    /// Fail if the static memory exceeds this many bytes. Default: none.
+    pub fn max_memory(self, max_memory: u64) -> Self {
+        Self {
+            max_memory: Some(max_memory),
+            ..self
+        }
+    }
+
+    /// Fail if there are more objects. Default: none.
+    pub fn max_objects(self, max_objects: u64) -> Self {
+        Self {
+            max_objects: Some(max_objects),
+            ..self
+        }
+    }
+
+    /// The autoload printing the samples.
+    pub(crate) fn autoload(&self) -> TemporaryAutoload {
+        TemporaryAutoload::script(
+            "CargoGodotLibPerformance",
+            PERFORMANCE_SCRIPT.replace(
+                "const INTERVAL := 1.0",
+                &format!("const INTERVAL := {:?}", self.interval.as_secs_f64()),
+            ),
+        )
+    }
+
+    /// A message for every threshold a sample after the warmup violated.
+    pub fn violations(&self, samples: &[PerformanceSample]) -> Vec<String> {
+        let mut violations = vec![];
+        for sample in samples
+            .iter()
+            .filter(|sample| sample.time >= self.warmup.as_secs_f64())
+        {
+            let at = format!("at {:.1}s", sample.time);
+            if let Some(min_fps) = self.min_fps
+                && sample.fps < min_fps
+            {
+                violations.push(format!("{} FPS {at}, below {min_fps}", sample.fps));
+            }
+            if let Some(max_frame_time) = self.max_frame_time
+                && sample.frame_time > max_frame_time.as_secs_f64()
+            {
+                violations.push(format!(
+                    "Frame time of {:?} {at}, above {max_frame_time:?}",
+                    Duration::from_secs_f64(sample.frame_time)
+                ));
+            }
+            if let Some(max_memory) = self.max_memory
+                && sample.memory > max_memory
+            {
+                violations.push(format!(
+                    "{} bytes of memory {at}, above {max_memory}",
+                    sample.memory
+                ));
+            }
+            if let Some(max_objects) = self.max_objects
+                && sample.objects > max_objects
+            {
+                violations.push(format!(
+                    "{} objects {at}, above {max_objects}",
+                    sample.objects
+                ));
+            }
+        }
+        violations
+    }
+
+    /// The report of the collected `samples`.
+    pub(crate) fn report(&self, samples: Vec<PerformanceSample>) -> PerformanceReport {
+        PerformanceReport {
+            violations: self.violations(&samples),
+            samples,
+        }
+    }
+}
+
+/// Collects the samples printed by the autoload of a `PerformanceMonitor`.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SampleCollector {
+    samples: Arc<Mutex<Vec<PerformanceSample>>>,
+}
+
+impl SampleCollector {
+    pub(crate) fn callback(&self) -> OutputCallback {
+        let samples = self.samples.clone();
+        Arc::new(move |line| {
+            if let Some(sample) = parse_sample(line)
+                && let Ok(mut samples) = samples.lock()
+            {
+                samples.push(sample);
+            }
+        })
+    }
+
+    pub(crate) fn take(&self) -> Vec<PerformanceSample> {
+        std::mem::take(&mut *self.samples.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+/// The sample of an output line of the autoload.
+fn parse_sample(line: &str) -> Option<PerformanceSample> {
+    let json = line.trim().strip_prefix(SAMPLE_PREFIX)?;
+    serde_json::from_str(json).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_performance_monitor() {
+        let collector = SampleCollector::default();
+        let callback = collector.callback();
+        callback("Godot Engine v4.5.1.stable.official");
+        callback(
+            r#"cargo-godot-lib: performance {"fps":12.0,"frame_time":0.08,"memory":1000,"nodes":40,"objects":900,"orphan_nodes":0,"physics_frame_time":0.001,"time":0.5}"#,
+        );
+        callback(
+            r#"cargo-godot-lib: performance {"fps":60.0,"frame_time":0.004,"memory":2000,"nodes":40,"objects":1200,"orphan_nodes":1,"physics_frame_time":0.001,"time":1.5}"#,
+        );
+        let samples = collector.take();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[1].objects, 1200);
+
+        let monitor = PerformanceMonitor::default()
+            .warmup(Duration::from_secs(1))
+            .min_fps(30.0)
+            .max_frame_time(Duration::from_millis(10))
+            .max_objects(1000);
+        assert_eq!(
+            monitor.violations(&samples),
+            ["1200 objects at 1.5s, above 1000"]
+        );
+        let report = monitor.warmup(Duration::ZERO).report(samples);
+        assert_eq!(report.violations.len(), 3);
+        assert!(
+            PerformanceMonitor::default()
+                .interval(Duration::from_millis(250))
+                .autoload()
+                != PerformanceMonitor::default().autoload()
+        );
+    }
+}
@@ -0,0 +1,317 @@
+//! Custom asset pipeline steps run before every import and launch, see
+//! `GodotRunner::pipeline`, e.g. exporting Aseprite files or generating tilemaps.
+//!
+//! Steps run in the order they were added, except that a step always runs after the steps it
+//! `depends_on`. A step with `inputs` is skipped if the SHA-256 hashes of its input files are
+//! unchanged since it last succeeded, all its `outputs` exist and none of its dependencies ran.
+//! Steps without inputs always run. The hashes are kept in
+//! `<target>/.cargo-godot-lib/pipeline.json`. Paths are relative to the godot project.
+//! `GodotRunner` reimports the `outputs` of the steps which ran if the project was imported
+//! before, since only the editor notices changed assets.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let pipeline = Pipeline::default()
+//!     .step(
+//!         PipelineStep::new("aseprite", |project| export_sprites(&project.join("art")))
+//!             .inputs(["art"])
+//!             .outputs(["sprites"]),
+//!     )
+//!     .step(
+//!         PipelineStep::new("tilemaps", |project| generate_tilemaps(project))
+//!             .depends_on("aseprite")
+//!             .inputs(["sprites", "levels"]),
+//!     );
+//! let runner = GodotRunner::create("game", Path::new("godot")).pipeline(pipeline);
+//! ```
+use crate::state::{self, STATE_DIR};
+use anyhow::{Context, Result, anyhow};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// The function of a step, called with the canonicalized godot project path.
+type StepFn = Box<dyn Fn(&Path) -> Result<()> + Send + Sync>;
+
+/// The input hashes of every step which last succeeded, keyed by step name.
+type PipelineState = BTreeMap<String, BTreeMap<String, String>>;
+
+/// The path of the pipeline state in the cargo `target_directory`.
+pub fn pipeline_state_path(target_directory: &Path) -> PathBuf {
+    target_directory.join(STATE_DIR).join("pipeline.json")
+}
+
+/// A named step of a `Pipeline`.
+pub struct PipelineStep {
+    name: String,
+    dependencies: Vec<String>,
+    inputs: Vec<PathBuf>,
+    outputs: Vec<PathBuf>,
+    run: StepFn,
+}
+
+impl PipelineStep {
+    pub fn new(
+        name: impl Into<String>,
+        run: impl Fn(&Path) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            dependencies: vec![],
+            inputs: vec![],
+            outputs: vec![],
+            run: Box::new(run),
+        }
+    }
+
+    /// Run after the step named `name`, and whenever it ran.
+    pub fn depends_on(mut self, name: impl Into<String>) -> Self {
+        self.dependencies.push(name.into());
+        self
+    }
+
+    /// Add files or directories whose contents decide whether the step needs to run.
+    /// Hidden files in directories are ignored.
+    pub fn inputs<P: Into<PathBuf>>(mut self, inputs: impl IntoIterator<Item = P>) -> Self {
+        self.inputs.extend(inputs.into_iter().map(Into::into));
+        self
+    }
+
+    /// Add files or directories the step writes. The step runs if one of them is missing.
+    pub fn outputs<P: Into<PathBuf>>(mut self, outputs: impl IntoIterator<Item = P>) -> Self {
+        self.outputs.extend(outputs.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl std::fmt::Debug for PipelineStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PipelineStep")
+            .field("name", &self.name)
+            .field("dependencies", &self.dependencies)
+            .field("inputs", &self.inputs)
+            .field("outputs", &self.outputs)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The steps ran and skipped by `Pipeline::run`, in the order they were visited.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PipelineReport {
+    pub ran: Vec<String>,
+    pub skipped: Vec<String>,
+    /// The outputs of the steps which ran.
+    pub written: Vec<PathBuf>,
+}
+
+/// Steps with dependencies, see the module documentation.
+#[derive(Debug, Default)]
+pub struct Pipeline {
+    steps: Vec<PipelineStep>,
+}
+
+impl Pipeline {
+    /// Add a step. Step names must be unique.
+    pub fn step(mut self, step: PipelineStep) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    /// The steps in the order they run. Fails for duplicate names, unknown dependencies and
+    /// dependency cycles.
+    pub fn order(&self) -> Result<Vec<&PipelineStep>> {
+        let index = |name: &str| self.steps.iter().position(|step| step.name == name);
+        for (i, step) in self.steps.iter().enumerate() {
+            if index(&step.name) != Some(i) {
+                return Err(anyhow!("Multiple pipeline steps are named {:?}", step.name));
+            }
+            if let Some(dependency) = step.dependencies.iter().find(|name| index(name).is_none()) {
+                return Err(anyhow!(
+                    "The pipeline step {:?} depends on the unknown step {dependency:?}",
+                    step.name
+                ));
+            }
+        }
+        // Depth-first, so that steps keep their order unless a dependency comes later.
+        fn visit<'a>(
+            pipeline: &'a Pipeline,
+            i: usize,
+            visiting: &mut Vec<usize>,
+            order: &mut Vec<&'a PipelineStep>,
+        ) -> Result<()> {
+            let step = &pipeline.steps[i];
+            if order.iter().any(|visited| visited.name == step.name) {
+                return Ok(());
+            }
+            if visiting.contains(&i) {
+                return Err(anyhow!(
+                    "The pipeline steps {:?} depend on each other",
+                    visiting
+                        .iter()
+                        .map(|&i| pipeline.steps[i].name.as_str())
+                        .collect::<Vec<_>>()
+                ));
+            }
+            visiting.push(i);
+            for dependency in &step.dependencies {
+                let dependency = pipeline
+                    .steps
+                    .iter()
+                    .position(|step| step.name == *dependency)
+                    .expect("dependencies are checked");
+                visit(pipeline, dependency, visiting, order)?;
+            }
+            visiting.pop();
+            order.push(step);
+            Ok(())
+        }
+        let mut order = vec![];
+        for i in 0..self.steps.len() {
+            visit(self, i, &mut vec![], &mut order)?;
+        }
+        Ok(order)
+    }
+
+    /// Run the steps in order against the project at `godot_project_path`, skipping unchanged
+    /// steps, and save the hashes of the steps which succeeded to `state_path`.
+    pub fn run(&self, godot_project_path: &Path, state_path: &Path) -> Result<PipelineReport> {
+        let order = self.order()?;
+        let mut saved: PipelineState = std::fs::read_to_string(state_path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        let mut report = PipelineReport::default();
+        let mut result = Ok(());
+        for step in order {
+            let hashes = fingerprint(godot_project_path, &step.inputs)?;
+            let unchanged = !step.inputs.is_empty()
+                && saved.get(&step.name) == Some(&hashes)
+                && step
+                    .outputs
+                    .iter()
+                    .all(|output| godot_project_path.join(output).exists())
+                && !step
+                    .dependencies
+                    .iter()
+                    .any(|dependency| report.ran.contains(dependency));
+            if unchanged {
+                report.skipped.push(step.name.clone());
+                continue;
+            }
+            saved.remove(&step.name);
+            result = (step.run)(godot_project_path)
+                .with_context(|| format!("The pipeline step {:?} failed", step.name));
+            if result.is_err() {
+                break;
+            }
+            // Hash again, the step may write its own inputs, e.g. formatting them.
+            saved.insert(
+                step.name.clone(),
+                fingerprint(godot_project_path, &step.inputs)?,
+            );
+            report.ran.push(step.name.clone());
+            report.written.extend(
+                step.outputs
+                    .iter()
+                    .map(|output| godot_project_path.join(output)),
+            );
+        }
+        if let Some(parent) = state_path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {parent:?}"))?;
+        }
+        std::fs::write(state_path, serde_json::to_string_pretty(&saved)?)
+            .with_context(|| format!("Failed to write {state_path:?}"))?;
+        result.map(|()| report)
+    }
+}
+
+/// The hashes of the files of `inputs`, keyed by their path relative to the project.
+/// Missing inputs are recorded as `missing`.
+fn fingerprint(godot_project_path: &Path, inputs: &[PathBuf]) -> Result<BTreeMap<String, String>> {
+    let mut hashes = BTreeMap::new();
+    for input in inputs {
+        let path = godot_project_path.join(input);
+        let mut files = vec![];
+        if path.is_dir() {
+            state::find_project_files(&path, &mut files)?;
+        } else if path.exists() {
+            files.push(path.clone());
+        } else {
+            hashes.insert(input.to_string_lossy().replace('\\', "/"), "missing".into());
+        }
+        for file in files {
+            let key = file
+                .strip_prefix(godot_project_path)
+                .unwrap_or(&file)
+                .to_string_lossy()
+                .replace('\\', "/");
+            hashes.insert(key, state::hash_file(&file)?);
+        }
+    }
+    Ok(hashes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_pipeline() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("godot");
+        std::fs::create_dir_all(project.join("art")).unwrap();
+        std::fs::write(project.join("art/hero.aseprite"), "v1").unwrap();
+        let state_path = pipeline_state_path(&dir.path().join("target"));
+
+        let log = Arc::new(Mutex::new(vec![]));
+        let step = |name: &'static str| {
+            let log = log.clone();
+            PipelineStep::new(name, move |project| {
+                log.lock().unwrap().push(name);
+                std::fs::write(project.join(format!("{name}.out")), "").map_err(Into::into)
+            })
+        };
+        let pipeline = Pipeline::default()
+            .step(
+                step("tilemaps")
+                    .depends_on("sprites")
+                    .inputs(["sprites.out"]),
+            )
+            .step(step("sprites").inputs(["art"]).outputs(["sprites.out"]))
+            .step(step("always"));
+
+        let report = pipeline.run(&project, &state_path).unwrap();
+        assert_eq!(report.ran, ["sprites", "tilemaps", "always"]);
+        assert_eq!(report.written, [project.join("sprites.out")]);
+        let report = pipeline.run(&project, &state_path).unwrap();
+        assert_eq!(report.ran, ["always"]);
+        assert_eq!(report.skipped, ["sprites", "tilemaps"]);
+
+        std::fs::write(project.join("art/hero.aseprite"), "v2").unwrap();
+        let report = pipeline.run(&project, &state_path).unwrap();
+        assert_eq!(report.ran, ["sprites", "tilemaps", "always"]);
+        std::fs::remove_file(project.join("sprites.out")).unwrap();
+        let report = pipeline.run(&project, &state_path).unwrap();
+        assert_eq!(report.ran, ["sprites", "tilemaps", "always"]);
+        assert_eq!(log.lock().unwrap().len(), 10);
+
+        let cycle = Pipeline::default()
+            .step(step("a").depends_on("b"))
+            .step(step("b").depends_on("a"));
+        assert!(cycle.order().is_err());
+        assert!(
+            Pipeline::default()
+                .step(step("a").depends_on("c"))
+                .order()
+                .is_err()
+        );
+        let failing = Pipeline::default().step(PipelineStep::new("fail", |_| Err(anyhow!("no"))));
+        let error = failing.run(&project, &state_path).unwrap_err();
+        assert_eq!(error.to_string(), "The pipeline step \"fail\" failed");
+    }
+}
@@ -0,0 +1,284 @@
+//! Attaching RenderDoc or Tracy to a Godot instance launched by `GodotRunner`,
+//! see `GodotRunner::profiler`.
+//!
+//! RenderDoc is injected with `LD_PRELOAD` on Linux and launched through `renderdoccmd` on
+//! Windows; macOS is not supported by RenderDoc. Captures are written with the path prefix
+//! given to `Profiler::render_doc`, e.g. when pressing F12 in the game.
+//!
+//! Tracy needs the extension (and optionally Godot) to be built with the Tracy client, e.g. the
+//! `tracy-client` crate. The runner sets the client's port and can record the session with
+//! `tracy-capture` until Godot exits.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let runner = runner.profiler(Profiler::render_doc(Path::new("captures/frame")));
+//! let runner = runner.profiler(Profiler::tracy(8086).capture(Path::new("trace.tracy")));
+//! ```
+use anyhow::{Context, Result, anyhow};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use which::which;
+
+/// The default port of the Tracy client.
+pub const TRACY_DEFAULT_PORT: u16 = 8086;
+
+/// Where RenderDoc installs its capture library on Linux.
+const RENDER_DOC_LINUX_LIBRARIES: [&str; 5] = [
+    "/usr/lib/librenderdoc.so",
+    "/usr/lib64/librenderdoc.so",
+    "/usr/lib/x86_64-linux-gnu/librenderdoc.so",
+    "/usr/local/lib/librenderdoc.so",
+    "/opt/renderdoc/lib/librenderdoc.so",
+];
+
+/// Where RenderDoc is installed on Windows.
+const RENDER_DOC_WINDOWS_DIR: &str = r"C:\Program Files\RenderDoc";
+
+/// A graphics debugger or profiler attached to the launched Godot instance.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Profiler {
+    /// RenderDoc frame captures.
+    RenderDoc {
+        capture_path: PathBuf,
+        library: Option<PathBuf>,
+    },
+    /// A Tracy client in the extension listening on `port`.
+    Tracy {
+        port: u16,
+        no_exit: bool,
+        capture_path: Option<PathBuf>,
+    },
+}
+
+/// The changes to the Godot launch needed to attach a `Profiler`.
+#[derive(Debug, Default)]
+pub struct ProfilerSetup {
+    /// Environment variables of the Godot process.
+    pub envs: Vec<(OsString, OsString)>,
+    /// A program and its arguments Godot is launched through, see `GodotRunner::wrapper_command`.
+    pub wrapper: Vec<String>,
+    /// A command recording the session, started after Godot.
+    pub capture: Option<Command>,
+}
+
+impl Profiler {
+    /// Capture frames with RenderDoc, writing them with the path prefix `capture_path`.
+    pub fn render_doc(capture_path: &Path) -> Self {
+        Self::RenderDoc {
+            capture_path: capture_path.to_path_buf(),
+            library: None,
+        }
+    }
+
+    /// Connect Tracy to the extension's Tracy client listening on `port`,
+    /// see `TRACY_DEFAULT_PORT`.
+    pub fn tracy(port: u16) -> Self {
+        Self::Tracy {
+            port,
+            no_exit: false,
+            capture_path: None,
+        }
+    }
+
+    /// Use the RenderDoc capture library (`librenderdoc.so` or `renderdoc.dll`) at `path`
+    /// instead of searching the default install locations. Ignored for Tracy.
+    pub fn library(self, path: &Path) -> Self {
+        match self {
+            Self::RenderDoc { capture_path, .. } => Self::RenderDoc {
+                capture_path,
+                library: Some(path.to_path_buf()),
+            },
+            tracy => tracy,
+        }
+    }
+
+    /// Keep Godot running after it quits until Tracy received all data (`TRACY_NO_EXIT`).
+    /// Ignored for RenderDoc.
+    pub fn no_exit(self, no_exit: bool) -> Self {
+        match self {
+            Self::Tracy {
+                port, capture_path, ..
+            } => Self::Tracy {
+                port,
+                no_exit,
+                capture_path,
+            },
+            render_doc => render_doc,
+        }
+    }
+
+    /// Record the Tracy session into `path` with `tracy-capture`, which must be on the `PATH`.
+    /// Ignored for RenderDoc.
+    pub fn capture(self, path: &Path) -> Self {
+        match self {
+            Self::Tracy { port, no_exit, .. } => Self::Tracy {
+                port,
+                no_exit,
+                capture_path: Some(path.to_path_buf()),
+            },
+            render_doc => render_doc,
+        }
+    }
+
+    /// Check that the profiler is installed and return the changes to the Godot launch.
+    pub fn setup(&self) -> Result<ProfilerSetup> {
+        match self {
+            Self::RenderDoc {
+                capture_path,
+                library,
+            } => render_doc_setup(capture_path, library.as_deref()),
+            Self::Tracy {
+                port,
+                no_exit,
+                capture_path,
+            } => {
+                let mut setup = ProfilerSetup::default();
+                setup
+                    .envs
+                    .push(("TRACY_PORT".into(), port.to_string().into()));
+                if *no_exit {
+                    setup.envs.push(("TRACY_NO_EXIT".into(), "1".into()));
+                }
+                if let Some(capture_path) = capture_path {
+                    let tracy_capture = which("tracy-capture").context(
+                        "Failed to find tracy-capture, which is required to record Tracy \
+                        sessions (https://github.com/wolfpld/tracy)",
+                    )?;
+                    let mut command = Command::new(tracy_capture);
+                    command
+                        .arg("-o")
+                        .arg(capture_path)
+                        .args(["-a", "127.0.0.1", "-p", &port.to_string(), "-f"])
+                        .stdin(Stdio::null())
+                        .stdout(Stdio::null());
+                    setup.capture = Some(command);
+                }
+                Ok(setup)
+            }
+        }
+    }
+}
+
+fn render_doc_setup(capture_path: &Path, library: Option<&Path>) -> Result<ProfilerSetup> {
+    if cfg!(target_os = "macos") {
+        return Err(anyhow!("RenderDoc does not support macOS"));
+    }
+    let capture_path = std::path::absolute(capture_path)
+        .with_context(|| format!("Failed to make {capture_path:?} absolute"))?;
+    if let Some(parent) = capture_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {parent:?}"))?;
+    }
+    let library = find_render_doc_library(library)?;
+    let mut setup = ProfilerSetup::default();
+    if cfg!(windows) {
+        let renderdoccmd = library.with_file_name("renderdoccmd.exe");
+        setup.wrapper = vec![
+            renderdoccmd.to_string_lossy().to_string(),
+            "capture".to_string(),
+            "--wait-for-exit".to_string(),
+            "--capture-file".to_string(),
+            capture_path.to_string_lossy().to_string(),
+        ];
+    } else {
+        setup.envs = vec![
+            ("LD_PRELOAD".into(), library.into()),
+            ("ENABLE_VULKAN_RENDERDOC_CAPTURE".into(), "1".into()),
+            ("RENDERDOC_CAPFILE".into(), capture_path.into()),
+        ];
+    }
+    Ok(setup)
+}
+
+/// The RenderDoc capture library at `library` or in the default install locations.
+fn find_render_doc_library(library: Option<&Path>) -> Result<PathBuf> {
+    let candidates: Vec<PathBuf> = match library {
+        Some(library) => vec![library.to_path_buf()],
+        None if cfg!(windows) => vec![Path::new(RENDER_DOC_WINDOWS_DIR).join("renderdoc.dll")],
+        None => RENDER_DOC_LINUX_LIBRARIES
+            .iter()
+            .map(PathBuf::from)
+            .collect(),
+    };
+    candidates
+        .iter()
+        .find(|candidate| candidate.is_file())
+        .cloned()
+        .with_context(|| {
+            format!(
+                "Couldn't find the RenderDoc capture library, searched {candidates:?}. \
+                Install RenderDoc (https://renderdoc.org) or set its path with \
+                `Profiler::library`."
+            )
+        })
+}
+
+/// A running `tracy-capture`, waited for once the Godot process exits so the trace is complete.
+#[derive(Debug)]
+pub(crate) struct CaptureProcess(Child);
+
+impl CaptureProcess {
+    /// Start the capture `command` of a `ProfilerSetup`.
+    pub(crate) fn spawn(mut command: Command) -> Result<Self> {
+        command
+            .spawn()
+            .map(Self)
+            .with_context(|| format!("Failed to start capture: {command:?}"))
+    }
+}
+
+impl Drop for CaptureProcess {
+    fn drop(&mut self) {
+        let _ = self.0.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_setup() {
+        let setup = Profiler::tracy(TRACY_DEFAULT_PORT)
+            .no_exit(true)
+            .setup()
+            .unwrap();
+        assert_eq!(
+            setup.envs,
+            vec![
+                ("TRACY_PORT".into(), "8086".into()),
+                ("TRACY_NO_EXIT".into(), "1".into())
+            ]
+        );
+        assert!(setup.wrapper.is_empty() && setup.capture.is_none());
+
+        let dir = tempfile::tempdir().unwrap();
+        let missing = Profiler::render_doc(&dir.path().join("capture"))
+            .library(&dir.path().join("librenderdoc.so"))
+            .setup();
+        assert!(missing.is_err());
+
+        #[cfg(target_os = "linux")]
+        {
+            let library = dir.path().join("librenderdoc.so");
+            std::fs::write(&library, "").unwrap();
+            let capture_path = dir.path().join("captures/frame");
+            let setup = Profiler::render_doc(&capture_path)
+                .library(&library)
+                .setup()
+                .unwrap();
+            assert!(capture_path.parent().unwrap().is_dir());
+            assert!(
+                setup
+                    .envs
+                    .contains(&("LD_PRELOAD".into(), library.into_os_string()))
+            );
+            assert!(
+                setup
+                    .envs
+                    .contains(&("RENDERDOC_CAPFILE".into(), capture_path.into_os_string()))
+            );
+        }
+    }
+}
@@ -0,0 +1,552 @@
+//! A lightweight reader for Godot's `project.godot` file (ConfigFile format).
+use anyhow::{Context, Result, anyhow};
+use std::path::{Path, PathBuf};
+
+/// A parsed `project.godot` file.
+///
+/// Values are kept as raw Godot variant text, e.g. `"My Game"` (with quotes) or
+/// `PackedStringArray("4.5", "Forward Plus")`. Use `get_string` and `get_string_array`
+/// to decode the common value types.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ProjectConfig {
+    /// Comment lines before the first entry.
+    header: Vec<String>,
+    /// Sections in file order. Keys before the first `[section]` belong to the section named `""`.
+    sections: Vec<ConfigSection>,
+}
+
+/// A typed project setting value, written in Godot's variant text format.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SettingValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    /// A value already in Godot's variant text format, e.g. `Vector2i(1280, 720)`.
+    Raw(String),
+}
+
+impl SettingValue {
+    /// The value in Godot's variant text format, e.g. `"My Game"` for a string.
+    pub fn to_variant_string(&self) -> String {
+        match self {
+            Self::Bool(value) => value.to_string(),
+            Self::Int(value) => value.to_string(),
+            Self::Float(value) if value.fract() == 0.0 && value.is_finite() => {
+                format!("{value:.1}")
+            }
+            Self::Float(value) => value.to_string(),
+            Self::String(value) => format!("\"{}\"", escape(value)),
+            Self::Raw(value) => value.clone(),
+        }
+    }
+}
+
+impl From<bool> for SettingValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i64> for SettingValue {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<i32> for SettingValue {
+    fn from(value: i32) -> Self {
+        Self::Int(value.into())
+    }
+}
+
+impl From<u32> for SettingValue {
+    fn from(value: u32) -> Self {
+        Self::Int(value.into())
+    }
+}
+
+impl From<f64> for SettingValue {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<&str> for SettingValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for SettingValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct ConfigSection {
+    name: String,
+    entries: Vec<(String, String)>,
+}
+
+impl ProjectConfig {
+    /// Read `project.godot` from the given godot project directory.
+    pub fn load(godot_project_path: &Path) -> Result<Self> {
+        let path = Self::path(godot_project_path);
+        let contents = std::fs::read_to_string(&path).with_context(|| {
+            format!(
+                "Not a Godot project, failed to read `project.godot` in {:?}",
+                godot_project_path
+            )
+        })?;
+        Self::parse(&contents).with_context(|| format!("Failed to parse {path:?}"))
+    }
+
+    /// The path of the `project.godot` file of a godot project directory.
+    pub fn path(godot_project_path: &Path) -> PathBuf {
+        godot_project_path.join("project.godot")
+    }
+
+    /// Parse the contents of a `project.godot` file.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut config = Self::default();
+        let mut section = ConfigSection::default();
+        let mut lines = contents.lines().enumerate();
+
+        while let Some((index, line)) = lines.next() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            if trimmed.starts_with(';') {
+                if config.sections.is_empty() && section.entries.is_empty() {
+                    config.header.push(line.to_string());
+                }
+                continue;
+            }
+            if let Some(name) = trimmed.strip_prefix('[') {
+                let name = name.strip_suffix(']').with_context(|| {
+                    format!("Unterminated section header on line {}", index + 1)
+                })?;
+                config.push_section(std::mem::take(&mut section));
+                section.name = name.to_string();
+                continue;
+            }
+
+            let (key, value) = trimmed
+                .split_once('=')
+                .with_context(|| format!("Expected `key=value` on line {}", index + 1))?;
+            let mut value = value.trim().to_string();
+            // Arrays, dictionaries and strings may span multiple lines.
+            while !is_complete_value(&value) {
+                let (_, next) = lines
+                    .next()
+                    .with_context(|| format!("Unterminated value for key `{}`", key.trim()))?;
+                value.push('\n');
+                value.push_str(next);
+            }
+            section.entries.push((key.trim().to_string(), value));
+        }
+        config.push_section(section);
+
+        Ok(config)
+    }
+
+    fn push_section(&mut self, section: ConfigSection) {
+        if !section.name.is_empty() || !section.entries.is_empty() {
+            self.sections.push(section);
+        }
+    }
+
+    /// The raw value of `key` in `section`. Use `""` for keys before the first section.
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.sections
+            .iter()
+            .filter(|s| s.name == section)
+            .flat_map(|s| s.entries.iter())
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The value of `key` in `section` decoded as a string.
+    pub fn get_string(&self, section: &str, key: &str) -> Option<String> {
+        self.get(section, key).and_then(parse_string)
+    }
+
+    /// The value of `key` in `section` decoded as a `PackedStringArray` or `Array` of strings.
+    pub fn get_string_array(&self, section: &str, key: &str) -> Option<Vec<String>> {
+        let value = self.get(section, key)?;
+        let start = value.find(['(', '['])?;
+        Some(quoted_strings(&value[start..]))
+    }
+
+    /// The keys of `section` in file order.
+    pub fn keys<'a>(&'a self, section: &'a str) -> impl Iterator<Item = &'a str> {
+        self.sections
+            .iter()
+            .filter(move |s| s.name == section)
+            .flat_map(|s| s.entries.iter())
+            .map(|(key, _)| key.as_str())
+    }
+
+    /// The names of all sections in file order.
+    pub fn sections(&self) -> impl Iterator<Item = &str> {
+        self.sections
+            .iter()
+            .map(|s| s.name.as_str())
+            .filter(|name| !name.is_empty())
+    }
+
+    /// The `config_version` of the file. Godot 4 projects use version `5`.
+    pub fn config_version(&self) -> Option<u32> {
+        self.get("", "config_version")?.parse().ok()
+    }
+
+    /// The project name (`application/config/name`).
+    pub fn name(&self) -> Option<String> {
+        self.get_string("application", "config/name")
+    }
+
+    /// The main scene (`application/run/main_scene`), either a `res://` or `uid://` path.
+    pub fn main_scene(&self) -> Option<String> {
+        self.get_string("application", "run/main_scene")
+    }
+
+    /// The project feature tags (`application/config/features`), e.g. `["4.5", "Forward Plus"]`.
+    pub fn features(&self) -> Vec<String> {
+        self.get_string_array("application", "config/features")
+            .unwrap_or_default()
+    }
+
+    /// The engine version the project was last saved with, inferred from its feature tags, e.g. `4.5`.
+    pub fn engine_version(&self) -> Option<String> {
+        self.features().into_iter().find(|feature| {
+            let mut parts = feature.split('.');
+            parts.next().is_some_and(|p| p.parse::<u32>().is_ok())
+                && parts.next().is_some_and(|p| p.parse::<u32>().is_ok())
+                && parts.next().is_none()
+        })
+    }
+
+    /// Check that this is a Godot 4 project.
+    pub fn validate(&self) -> Result<()> {
+        match self.config_version() {
+            Some(version) if version >= 5 => Ok(()),
+            Some(version) => Err(anyhow!(
+                "`project.godot` has config_version={version}, which is a Godot 3 project.\n\
+                GDExtension requires Godot 4 (config_version=5)."
+            )),
+            None => Err(anyhow!("`project.godot` is missing `config_version`")),
+        }
+    }
+}
+
+/// Permanently set `key` in `section` of the project's `project.godot` to `value`, or remove it
+/// if `value` is `None`, keeping the rest of the file as is. Use `""` for keys before the first
+/// section. Returns true if the file changed.
+///
+/// Prefer `ProjectOverrides` for settings that only apply to a single run.
+pub fn write_setting(
+    godot_project_path: &Path,
+    section: &str,
+    key: &str,
+    value: Option<&SettingValue>,
+) -> Result<bool> {
+    let path = ProjectConfig::path(godot_project_path);
+    let contents =
+        std::fs::read_to_string(&path).with_context(|| format!("Failed to read {path:?}"))?;
+    let value = value.map(SettingValue::to_variant_string);
+    let updated = set_setting(&contents, section, key, value.as_deref());
+    if updated == contents {
+        return Ok(false);
+    }
+    std::fs::write(&path, updated).with_context(|| format!("Failed to write {path:?}"))?;
+    Ok(true)
+}
+
+/// Set or remove `key` in `section` of the ConfigFile `contents`, see `write_setting`.
+fn set_setting(contents: &str, section: &str, key: &str, value: Option<&str>) -> String {
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut current = "";
+    // The index after the last entry of `section`, where a new key is inserted.
+    let mut section_end = None;
+    let mut index = 0;
+    while index < lines.len() {
+        let trimmed = lines[index].trim();
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|n| n.strip_suffix(']')) {
+            current = name;
+            if current == section {
+                section_end = Some(index + 1);
+            }
+            index += 1;
+            continue;
+        }
+        let Some((k, v)) = trimmed
+            .split_once('=')
+            .filter(|_| !trimmed.starts_with(';'))
+        else {
+            index += 1;
+            continue;
+        };
+        let mut end = index + 1;
+        let mut full_value = v.trim().to_string();
+        while !is_complete_value(&full_value) && end < lines.len() {
+            full_value.push('\n');
+            full_value.push_str(lines[end]);
+            end += 1;
+        }
+        if current == section {
+            if k.trim() == key {
+                let mut updated: Vec<String> =
+                    lines[..index].iter().map(|l| l.to_string()).collect();
+                if let Some(value) = value {
+                    updated.push(format!("{key}={value}"));
+                }
+                updated.extend(lines[end..].iter().map(|l| l.to_string()));
+                return updated.join("\n") + "\n";
+            }
+            section_end = Some(end);
+        }
+        index = end;
+    }
+
+    let Some(value) = value else {
+        return contents.to_string();
+    };
+    let entry = format!("{key}={value}");
+    let mut updated: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+    match section_end {
+        Some(end) => {
+            // Godot separates a section header from its keys with an empty line.
+            let header_only = end > 0 && lines[end - 1].trim().starts_with('[');
+            if header_only {
+                updated.splice(end..end, [String::new(), entry]);
+            } else {
+                updated.insert(end, entry);
+            }
+        }
+        None if section.is_empty() => updated.insert(0, entry),
+        None => {
+            while updated.last().is_some_and(|line| line.trim().is_empty()) {
+                updated.pop();
+            }
+            updated.extend([String::new(), format!("[{section}]"), String::new(), entry]);
+        }
+    }
+    updated.join("\n") + "\n"
+}
+
+/// Returns true if brackets and quotes in `value` are balanced.
+fn is_complete_value(value: &str) -> bool {
+    let mut depth = 0i32;
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in value.chars() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else {
+            match c {
+                '"' => in_string = true,
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+    !in_string && depth <= 0
+}
+
+/// Decode a quoted Godot string such as `"My \"Game\""`.
+fn parse_string(value: &str) -> Option<String> {
+    let value = value.trim();
+    let inner = value.strip_prefix('"')?.strip_suffix('"')?;
+    Some(unescape(inner))
+}
+
+/// Extract all quoted strings from `value` in order.
+fn quoted_strings(value: &str) -> Vec<String> {
+    let mut strings = vec![];
+    let mut current: Option<String> = None;
+    let mut escaped = false;
+    for c in value.chars() {
+        match current.as_mut() {
+            Some(string) if escaped => {
+                string.push('\\');
+                string.push(c);
+                escaped = false;
+            }
+            Some(_) if c == '\\' => escaped = true,
+            Some(_) if c == '"' => strings.push(unescape(&current.take().unwrap_or_default())),
+            Some(string) => string.push(c),
+            None if c == '"' => current = Some(String::new()),
+            None => {}
+        }
+    }
+    strings
+}
+
+/// Escape a string for use inside a quoted Godot string.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+pub(crate) fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some(other) => result.push(other),
+                None => result.push('\\'),
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_mock_project() {
+        let config = ProjectConfig::load(Path::new("mock_godot_project")).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.config_version(), Some(5));
+        assert_eq!(config.name(), Some("Mock Godot Project".to_string()));
+        assert_eq!(config.main_scene(), Some("uid://bbikqg5gdb6ih".to_string()));
+        assert_eq!(config.features(), vec!["4.5", "Forward Plus"]);
+        assert_eq!(config.engine_version(), Some("4.5".to_string()));
+        assert_eq!(config.sections().collect::<Vec<_>>(), vec!["application"]);
+        assert_eq!(
+            config.keys("application").collect::<Vec<_>>(),
+            vec![
+                "config/name",
+                "run/main_scene",
+                "config/features",
+                "config/icon"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_multiline_values() {
+        let config = ProjectConfig::parse(
+            r#"
+config_version=5
+
+[input]
+
+jump={
+"deadzone": 0.5,
+"events": []
+}
+
+[application]
+config/name="Say \"hi\""
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.get("input", "jump"),
+            Some("{\n\"deadzone\": 0.5,\n\"events\": []\n}")
+        );
+        assert_eq!(config.name(), Some("Say \"hi\"".to_string()));
+    }
+
+    #[test]
+    fn test_setting_value() {
+        assert_eq!(SettingValue::from(true).to_variant_string(), "true");
+        assert_eq!(SettingValue::from(1280).to_variant_string(), "1280");
+        assert_eq!(SettingValue::from(2.0).to_variant_string(), "2.0");
+        assert_eq!(SettingValue::from(0.25).to_variant_string(), "0.25");
+        assert_eq!(
+            SettingValue::from("Say \"hi\"").to_variant_string(),
+            r#""Say \"hi\"""#
+        );
+        let value = SettingValue::from("C:\\game").to_variant_string();
+        assert_eq!(parse_string(&value), Some("C:\\game".to_string()));
+    }
+
+    #[test]
+    fn test_set_setting() {
+        let contents = "config_version=5\n\n[application]\n\nconfig/name=\"Game\"\nconfig/features=PackedStringArray(\"4.5\",\n\"Forward Plus\")\n\n[rendering]\n\nx=1\n";
+        let updated = set_setting(contents, "application", "config/version", Some("\"1.2.0\""));
+        assert_eq!(
+            updated,
+            "config_version=5\n\n[application]\n\nconfig/name=\"Game\"\nconfig/features=PackedStringArray(\"4.5\",\n\"Forward Plus\")\nconfig/version=\"1.2.0\"\n\n[rendering]\n\nx=1\n"
+        );
+        let config = ProjectConfig::parse(&updated).unwrap();
+        assert_eq!(
+            config.get_string("application", "config/version").unwrap(),
+            "1.2.0"
+        );
+        assert_eq!(config.features(), vec!["4.5", "Forward Plus"]);
+
+        let replaced = set_setting(
+            &updated,
+            "application",
+            "config/features",
+            Some("PackedStringArray()"),
+        );
+        assert_eq!(
+            ProjectConfig::parse(&replaced)
+                .unwrap()
+                .get("application", "config/features"),
+            Some("PackedStringArray()")
+        );
+        assert_eq!(
+            ProjectConfig::parse(&replaced)
+                .unwrap()
+                .get_string("application", "config/name")
+                .unwrap(),
+            "Game"
+        );
+
+        let removed = set_setting(&updated, "application", "config/version", None);
+        assert_eq!(removed, contents);
+        assert_eq!(
+            set_setting(contents, "editor_plugins", "enabled", None),
+            contents
+        );
+
+        let added = set_setting(
+            contents,
+            "editor_plugins",
+            "enabled",
+            Some("PackedStringArray()"),
+        );
+        assert!(added.ends_with("x=1\n\n[editor_plugins]\n\nenabled=PackedStringArray()\n"));
+        let added = set_setting(
+            "[autoload]\n",
+            "autoload",
+            "Version",
+            Some("\"*res://version.gd\""),
+        );
+        assert_eq!(added, "[autoload]\n\nVersion=\"*res://version.gd\"\n");
+    }
+
+    #[test]
+    fn test_not_a_project() {
+        let result = ProjectConfig::load(Path::new("non_existent_path"));
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Not a Godot project")
+        );
+
+        let config = ProjectConfig::parse("config_version=4").unwrap();
+        assert!(config.validate().is_err());
+    }
+}
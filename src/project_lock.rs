@@ -0,0 +1,86 @@
+//! Advisory locking of a godot project while `GodotRunner` writes the `.gdextension` file and
+//! imports the project.
+//!
+//! Concurrent invocations, e.g. a `cargo run` while rust-analyzer runs a build script, would
+//! otherwise interleave writes of the config file and run two headless imports at once, which
+//! corrupts the `.godot` folder. The lock file is kept in the temporary directory, so the project
+//! gets no extra file. The lock is released when the `ProjectLock` is dropped or the process
+//! exits.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let _lock = ProjectLock::acquire(Path::new("godot"), Verbosity::Normal)?;
+//! // Write and import the project.
+//! ```
+use crate::verbosity::Verbosity;
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+
+/// An exclusive lock of a godot project, held until dropped.
+#[derive(Debug)]
+pub struct ProjectLock {
+    _file: File,
+}
+
+impl ProjectLock {
+    /// Lock the godot project, waiting for another process holding the lock. The wait is logged
+    /// with `verbosity`.
+    pub fn acquire(godot_project_path: &Path, verbosity: Verbosity) -> Result<Self> {
+        let path = lock_path(godot_project_path);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open lock file: {path:?}"))?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                verbosity.log(
+                    Verbosity::Normal,
+                    format!(
+                        "Waiting for another process preparing the godot project \
+                        {godot_project_path:?}..."
+                    ),
+                );
+                file.lock()
+                    .with_context(|| format!("Failed to lock {path:?}"))?;
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("Failed to lock {path:?}"));
+            }
+        }
+        Ok(Self { _file: file })
+    }
+}
+
+/// The path of the lock file of a godot project, named after the hash of its canonical path.
+pub fn lock_path(godot_project_path: &Path) -> PathBuf {
+    let canonical = godot_project_path
+        .canonicalize()
+        .unwrap_or_else(|_| godot_project_path.to_path_buf());
+    let hash: String = Sha256::digest(canonical.to_string_lossy().as_bytes())
+        .iter()
+        .take(8)
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    std::env::temp_dir().join(format!("cargo-godot-lib-{hash}.lock"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock = ProjectLock::acquire(dir.path(), Verbosity::Quiet).unwrap();
+        let other = File::open(lock_path(dir.path())).unwrap();
+        assert!(matches!(other.try_lock(), Err(TryLockError::WouldBlock)));
+        drop(lock);
+        assert!(other.try_lock().is_ok());
+        assert_ne!(lock_path(dir.path()), lock_path(&dir.path().join("other")));
+    }
+}
@@ -0,0 +1,385 @@
+//! Temporary project setting overrides written to the project's `override.cfg`.
+//!
+//! Godot loads `override.cfg` from the project directory after `project.godot`, so settings in it
+//! replace the project's settings without editing `project.godot`. An existing `override.cfg` is
+//! kept: the overrides are added to it and the original file is restored after the run.
+use crate::project_config::SettingValue;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// The rendering method of a project (`rendering/renderer/rendering_method`).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Renderer {
+    /// Forward+, the default for desktop projects. Requires a Vulkan, D3D12 or Metal GPU.
+    ForwardPlus,
+    /// Mobile, a simpler Vulkan/D3D12/Metal renderer.
+    Mobile,
+    /// Compatibility, an OpenGL renderer which runs on most CI machines with software rendering.
+    Compatibility,
+}
+
+impl Renderer {
+    /// The name of the rendering method in `project.godot`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Renderer::ForwardPlus => "forward_plus",
+            Renderer::Mobile => "mobile",
+            Renderer::Compatibility => "gl_compatibility",
+        }
+    }
+}
+
+/// Project settings and custom feature tags to apply for a single run.
+///
+/// Example usage:
+/// ```rust,ignore
+/// let runner = runner.project_overrides(
+///     ProjectOverrides::default()
+///         .feature_tag("integration-test")
+///         .renderer(Renderer::Compatibility)
+///         .dummy_audio()
+///         .setting("application/run/max_fps", 30),
+/// );
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProjectOverrides {
+    feature_tags: Vec<String>,
+    settings: Vec<(String, SettingValue)>,
+}
+
+impl ProjectOverrides {
+    /// Add a custom feature tag, checked with `OS.has_feature(tag)` and used by
+    /// `setting.tag=value` feature overrides in `project.godot`.
+    pub fn feature_tag(mut self, tag: impl Into<String>) -> Self {
+        self.feature_tags.push(tag.into());
+        self
+    }
+
+    /// Override a project setting by its full path, e.g. `display/window/size/viewport_width`.
+    pub fn setting(mut self, name: impl Into<String>, value: impl Into<SettingValue>) -> Self {
+        let name = name.into();
+        let value = value.into();
+        match self.settings.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, existing)) => *existing = value,
+            None => self.settings.push((name, value)),
+        }
+        self
+    }
+
+    /// Override the initial window size (`display/window/size/viewport_width` and `viewport_height`).
+    pub fn window_size(self, width: u32, height: u32) -> Self {
+        self.setting("display/window/size/viewport_width", width)
+            .setting("display/window/size/viewport_height", height)
+    }
+
+    /// Override the rendering method. The Forward+ renderer fails on most headless CI machines,
+    /// `Renderer::Compatibility` works with software OpenGL.
+    pub fn renderer(self, renderer: Renderer) -> Self {
+        self.setting("rendering/renderer/rendering_method", renderer.name())
+    }
+
+    /// Override the audio driver (`audio/driver/driver`), e.g. `"Dummy"` or `"PulseAudio"`.
+    pub fn audio_driver(self, driver: impl Into<String>) -> Self {
+        self.setting("audio/driver/driver", driver.into())
+    }
+
+    /// Use the dummy audio driver, for machines without a sound card.
+    pub fn dummy_audio(self) -> Self {
+        self.audio_driver("Dummy")
+    }
+
+    /// Override a setting of the project's own `[section]`, e.g.
+    /// `custom_setting("my_game", "server/url", "http://localhost")` for `my_game/server/url`.
+    pub fn custom_setting(self, section: &str, key: &str, value: impl Into<SettingValue>) -> Self {
+        self.setting(format!("{section}/{key}"), value)
+    }
+
+    /// Register an autoload named `name` for the script or scene at `res_path`,
+    /// e.g. `autoload("TestDriver", "res://tests/driver.gd")`.
+    pub fn autoload(self, name: &str, res_path: &str) -> Self {
+        self.setting(format!("autoload/{name}"), format!("*{res_path}"))
+    }
+
+    /// Add the feature tags and settings of `other`, replacing settings which are set in both.
+    pub fn merge(self, other: &ProjectOverrides) -> Self {
+        let mut merged = other.settings.iter().fold(self, |merged, (name, value)| {
+            merged.setting(name.clone(), value.clone())
+        });
+        for tag in &other.feature_tags {
+            if !merged.feature_tags.contains(tag) {
+                merged.feature_tags.push(tag.clone());
+            }
+        }
+        merged
+    }
+
+    /// Returns true if there is nothing to override.
+    pub fn is_empty(&self) -> bool {
+        self.feature_tags.is_empty() && self.settings.is_empty()
+    }
+
+    /// The overrides in `override.cfg` format.
+    pub fn create(&self) -> String {
+        let (root, sections) = self.create_parts();
+        root + &sections
+    }
+
+    /// The keys before the first section header and the sections in `override.cfg` format.
+    fn create_parts(&self) -> (String, String) {
+        let mut root = String::new();
+        if !self.feature_tags.is_empty() {
+            let tags = SettingValue::from(self.feature_tags.join(","));
+            root.push_str(&format!("_custom_features={}\n", tags.to_variant_string()));
+        }
+
+        // The first part of a setting path is its section, e.g. `[display]`.
+        let mut sections: Vec<(&str, Vec<(&str, &SettingValue)>)> = vec![];
+        for (name, value) in &self.settings {
+            let (section, key) = name.split_once('/').unwrap_or(("", name));
+            match sections.iter_mut().find(|(s, _)| *s == section) {
+                Some((_, entries)) => entries.push((key, value)),
+                None => sections.push((section, vec![(key, value)])),
+            }
+        }
+        let mut contents = String::new();
+        for (section, entries) in sections {
+            let target = if section.is_empty() {
+                &mut root
+            } else {
+                contents.push_str(&format!("\n[{section}]\n\n"));
+                &mut contents
+            };
+            for (key, value) in entries {
+                target.push_str(&format!("{key}={}\n", value.to_variant_string()));
+            }
+        }
+        (root, contents)
+    }
+
+    /// Permanently write the overrides to the `override.cfg` of the godot project,
+    /// replacing an existing file. Use `apply` to remove them again after a run.
+    pub fn write(&self, godot_project_path: &Path) -> Result<()> {
+        let path = override_path(godot_project_path);
+        std::fs::write(&path, self.create()).with_context(|| format!("Failed to write {path:?}"))
+    }
+
+    /// Write the overrides to the `override.cfg` of the godot project.
+    /// The returned guard restores the previous `override.cfg` when dropped.
+    pub fn apply(&self, godot_project_path: &Path) -> Result<OverrideGuard> {
+        let path = override_path(godot_project_path);
+        let backup_path = backup_path(godot_project_path);
+
+        // A backup left behind by an interrupted run holds the user's original file.
+        restore_backup(godot_project_path)?;
+
+        let original = if path.exists() {
+            std::fs::copy(&path, &backup_path)
+                .with_context(|| format!("Failed to back up {path:?}"))?;
+            Some(
+                std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {path:?}"))?,
+            )
+        } else {
+            None
+        };
+
+        // Keys without a section must come before the first section header of the original file.
+        let contents = match &original {
+            Some(original) => {
+                let (root, sections) = self.create_parts();
+                format!("{root}{original}\n; Added by cargo-godot-lib\n{sections}")
+            }
+            None => self.create(),
+        };
+        std::fs::write(&path, contents).with_context(|| format!("Failed to write {path:?}"))?;
+
+        Ok(OverrideGuard {
+            path,
+            backup_path: original.map(|_| backup_path),
+        })
+    }
+}
+
+/// Restores the project's original `override.cfg` when dropped. See `ProjectOverrides::apply`.
+#[derive(Debug)]
+pub struct OverrideGuard {
+    path: PathBuf,
+    backup_path: Option<PathBuf>,
+}
+
+impl OverrideGuard {
+    /// The path of the written `override.cfg`.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for OverrideGuard {
+    fn drop(&mut self) {
+        let result = match &self.backup_path {
+            Some(backup_path) => restore(&self.path, backup_path),
+            None => std::fs::remove_file(&self.path)
+                .with_context(|| format!("Failed to remove {:?}", self.path)),
+        };
+        if let Err(e) = result {
+            eprintln!("Warning: {e:#}");
+        }
+    }
+}
+
+/// Restore the `override.cfg` of the godot project from a backup left behind by an interrupted
+/// run. Returns true if there was a backup.
+pub fn restore_backup(godot_project_path: &Path) -> Result<bool> {
+    let backup_path = backup_path(godot_project_path);
+    if !backup_path.exists() {
+        return Ok(false);
+    }
+    restore(&override_path(godot_project_path), &backup_path)?;
+    Ok(true)
+}
+
+/// The path of the `override.cfg` file of a godot project.
+pub fn override_path(godot_project_path: &Path) -> PathBuf {
+    godot_project_path.join("override.cfg")
+}
+
+/// The name of the backup of an existing `override.cfg` during a run.
+pub(crate) const BACKUP_FILE_NAME: &str = "override.cfg.cargo-godot-lib.bak";
+
+fn backup_path(godot_project_path: &Path) -> PathBuf {
+    godot_project_path.join(BACKUP_FILE_NAME)
+}
+
+fn restore(path: &Path, backup_path: &Path) -> Result<()> {
+    std::fs::rename(backup_path, path)
+        .with_context(|| format!("Failed to restore {path:?} from {backup_path:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides() -> ProjectOverrides {
+        ProjectOverrides::default()
+            .feature_tag("integration-test")
+            .feature_tag("ci")
+            .setting("display/window/size/viewport_width", 640)
+            .setting("application/run/max_fps", 30)
+            .setting("display/window/size/viewport_width", 320)
+            .setting("config_version", 5)
+    }
+
+    #[test]
+    fn test_create() {
+        assert!(ProjectOverrides::default().is_empty());
+        assert_eq!(
+            overrides().create(),
+            r#"_custom_features="integration-test,ci"
+config_version=5
+
+[display]
+
+window/size/viewport_width=320
+
+[application]
+
+run/max_fps=30
+"#
+        );
+    }
+
+    #[test]
+    fn test_typed_setters() {
+        let overrides = ProjectOverrides::default()
+            .window_size(1280, 720)
+            .renderer(Renderer::Compatibility)
+            .dummy_audio()
+            .custom_setting("my_game", "server/url", "http://localhost")
+            .autoload("Driver", "res://driver.gd");
+        assert_eq!(
+            overrides.create(),
+            r#"
+[display]
+
+window/size/viewport_width=1280
+window/size/viewport_height=720
+
+[rendering]
+
+renderer/rendering_method="gl_compatibility"
+
+[audio]
+
+driver/driver="Dummy"
+
+[my_game]
+
+server/url="http://localhost"
+
+[autoload]
+
+Driver="*res://driver.gd"
+"#
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        overrides.write(dir.path()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(override_path(dir.path())).unwrap(),
+            overrides.create()
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let merged = ProjectOverrides::default()
+            .feature_tag("ci")
+            .dummy_audio()
+            .window_size(640, 480)
+            .merge(
+                &ProjectOverrides::default()
+                    .feature_tag("ci")
+                    .feature_tag("integration-test")
+                    .audio_driver("PulseAudio"),
+            );
+        assert_eq!(
+            merged,
+            ProjectOverrides::default()
+                .feature_tag("ci")
+                .feature_tag("integration-test")
+                .audio_driver("PulseAudio")
+                .window_size(640, 480)
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = override_path(dir.path());
+
+        let guard = overrides().apply(dir.path()).unwrap();
+        assert_eq!(guard.path(), path);
+        assert!(
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .contains("integration-test")
+        );
+        drop(guard);
+        assert!(!path.exists());
+
+        std::fs::write(&path, "[audio]\n\ndriver/driver=\"Dummy\"\n").unwrap();
+        let guard = overrides().apply(dir.path()).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(
+            contents
+                .starts_with("_custom_features=\"integration-test,ci\"\nconfig_version=5\n[audio]")
+        );
+        assert!(contents.contains("run/max_fps=30"));
+        drop(guard);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "[audio]\n\ndriver/driver=\"Dummy\"\n"
+        );
+        assert!(!backup_path(dir.path()).exists());
+    }
+}
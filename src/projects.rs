@@ -0,0 +1,136 @@
+//! Running the same extension against several godot projects, e.g. a game and its demo
+//! projects, see `GodotRunner::additional_project`.
+//!
+//! The `.gdextension` file is written into every registered project, while only the project
+//! chosen with a `ProjectSelector` is imported and launched. The main project passed to
+//! `GodotRunner::create` has index 0 and is named after its directory, the additional projects
+//! follow in the order they were added.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let runner = GodotRunner::create("game", Path::new("godot"))
+//!     .additional_project("physics", Path::new("demos/physics"))
+//!     .additional_project("ui", Path::new("demos/ui"))
+//!     .select_project("ui");
+//! ```
+use anyhow::{Result, anyhow};
+use std::path::{Path, PathBuf};
+
+/// A named godot project.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GodotProject {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl GodotProject {
+    pub fn new(name: impl Into<String>, path: &Path) -> Self {
+        Self {
+            name: name.into(),
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// The main project, named after the last component of its path.
+    pub(crate) fn main(path: &Path) -> Self {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Self::new(name, path)
+    }
+}
+
+/// Chooses the godot project to launch, see the module documentation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ProjectSelector {
+    /// The project at this index, 0 being the main project.
+    Index(usize),
+    /// The project with this name.
+    Name(String),
+}
+
+impl Default for ProjectSelector {
+    fn default() -> Self {
+        Self::Index(0)
+    }
+}
+
+impl From<usize> for ProjectSelector {
+    fn from(index: usize) -> Self {
+        Self::Index(index)
+    }
+}
+
+impl From<&str> for ProjectSelector {
+    fn from(name: &str) -> Self {
+        Self::Name(name.to_string())
+    }
+}
+
+impl From<String> for ProjectSelector {
+    fn from(name: String) -> Self {
+        Self::Name(name)
+    }
+}
+
+impl ProjectSelector {
+    /// The selected project of `projects`.
+    pub fn select<'a>(&self, projects: &'a [GodotProject]) -> Result<&'a GodotProject> {
+        let names = || {
+            projects
+                .iter()
+                .map(|project| project.name.as_str())
+                .collect::<Vec<_>>()
+        };
+        match self {
+            Self::Index(index) => projects.get(*index).ok_or_else(|| {
+                anyhow!(
+                    "No godot project at index {index}, the projects are {:?}",
+                    names()
+                )
+            }),
+            Self::Name(name) => {
+                let mut matches = projects.iter().filter(|project| project.name == *name);
+                match (matches.next(), matches.next()) {
+                    (Some(project), None) => Ok(project),
+                    (Some(_), Some(_)) => Err(anyhow!(
+                        "Multiple godot projects are named {name:?}, select one by index"
+                    )),
+                    (None, _) => Err(anyhow!(
+                        "No godot project named {name:?}, the projects are {:?}",
+                        names()
+                    )),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select() {
+        let projects = [
+            GodotProject::main(Path::new("games/godot")),
+            GodotProject::new("demo", Path::new("demos/demo")),
+            GodotProject::new("ui", Path::new("demos/ui")),
+        ];
+        assert_eq!(projects[0].name, "godot");
+        let select = |selector: ProjectSelector| selector.select(&projects).map(|p| &p.path);
+        assert_eq!(
+            select(ProjectSelector::default()).unwrap(),
+            Path::new("games/godot")
+        );
+        assert_eq!(select(2.into()).unwrap(), Path::new("demos/ui"));
+        assert_eq!(select("demo".into()).unwrap(), Path::new("demos/demo"));
+        let error = select(3.into()).unwrap_err().to_string();
+        assert!(error.contains(r#"["godot", "demo", "ui"]"#), "{error}");
+        assert!(select("other".into()).is_err());
+
+        let duplicates = [projects[1].clone(), projects[1].clone()];
+        assert!(ProjectSelector::from("demo").select(&duplicates).is_err());
+    }
+}
@@ -0,0 +1,261 @@
+//! Provenance metadata for distributed builds: `SHA256SUMS` checksum files and CycloneDX
+//! software bills of materials (SBOMs), see `Bundle::checksums` and `Bundle::sbom`.
+//!
+//! The SBOM lists the crates the extension is built from, according to `cargo metadata`
+//! without dev-dependencies, and the Godot version the game is exported with.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let sbom = Sbom::from_cargo(Path::new("Cargo.toml"), "game")?.godot_version("4.5.1");
+//! sbom.write(Path::new("dist/sbom.cdx.json"))?;
+//! provenance::write_sha256sums(Path::new("dist"), &["game-1.2.0-linux.zip".to_string()])?;
+//! ```
+use crate::state::hash_file;
+use anyhow::{Context, Result, anyhow};
+use cargo_metadata::DependencyKind;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+
+/// The name of the checksum file written by `write_sha256sums`.
+pub const SHA256SUMS: &str = "SHA256SUMS";
+
+/// The package URL prefix of Godot, which is told apart from the `godot` crate by it.
+const GODOT_PURL_PREFIX: &str = "pkg:github/godotengine/godot@";
+
+/// The checksums of the `files` in `dir` in the format of `sha256sum`, so they can be verified
+/// with `sha256sum -c SHA256SUMS`.
+pub fn sha256sums(dir: &Path, files: &[String]) -> Result<String> {
+    let mut sums = String::new();
+    for file in files {
+        sums.push_str(&format!("{}  {file}\n", hash_file(&dir.join(file))?));
+    }
+    Ok(sums)
+}
+
+/// Write the checksums of the `files` in `dir` into `dir/SHA256SUMS`.
+/// Returns the path of the checksum file.
+pub fn write_sha256sums(dir: &Path, files: &[String]) -> Result<PathBuf> {
+    let path = dir.join(SHA256SUMS);
+    std::fs::write(&path, sha256sums(dir, files)?)
+        .with_context(|| format!("Failed to write file: {path:?}"))?;
+    Ok(path)
+}
+
+/// A component listed in an `Sbom`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct SbomComponent {
+    pub name: String,
+    pub version: String,
+    /// The package URL identifying the component, e.g. `pkg:cargo/serde@1.0.228`.
+    pub purl: String,
+    /// The SPDX license expression, if known.
+    pub license: Option<String>,
+    /// The `purl`s of the components this component depends on.
+    pub depends_on: Vec<String>,
+}
+
+/// A software bill of materials of a game, written in the CycloneDX JSON format.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Sbom {
+    /// The extension crate.
+    pub root: SbomComponent,
+    /// The crates the extension depends on, and Godot if its version is set.
+    pub components: Vec<SbomComponent>,
+}
+
+impl Sbom {
+    /// The SBOM of the crate `crate_name` in the workspace of `cargo_manifest_path`, listing
+    /// its normal and build dependencies.
+    pub fn from_cargo(cargo_manifest_path: &Path, crate_name: &str) -> Result<Self> {
+        let metadata = cargo_metadata::MetadataCommand::new()
+            .manifest_path(cargo_manifest_path)
+            .exec()
+            .context("Failed to read cargo metadata")?;
+        let packages: BTreeMap<_, _> = metadata
+            .packages
+            .iter()
+            .map(|package| (&package.id, package))
+            .collect();
+        let root = metadata
+            .packages
+            .iter()
+            .find(|package| package.name.replace('-', "_") == crate_name.replace('-', "_"))
+            .ok_or_else(|| {
+                anyhow!("Package {crate_name:?} not found in {cargo_manifest_path:?}")
+            })?;
+        let nodes: BTreeMap<_, _> = metadata
+            .resolve
+            .as_ref()
+            .context("cargo metadata returned no dependency graph")?
+            .nodes
+            .iter()
+            .map(|node| (&node.id, node))
+            .collect();
+
+        let mut components = BTreeMap::new();
+        let mut queue = VecDeque::from([&root.id]);
+        while let Some(id) = queue.pop_front() {
+            if components.contains_key(id) {
+                continue;
+            }
+            let package = packages
+                .get(id)
+                .with_context(|| format!("Package {id} missing from cargo metadata"))?;
+            let dependencies: Vec<_> = nodes
+                .get(id)
+                .map(|node| node.deps.as_slice())
+                .unwrap_or_default()
+                .iter()
+                .filter(|dep| {
+                    dep.dep_kinds.is_empty()
+                        || dep
+                            .dep_kinds
+                            .iter()
+                            .any(|kind| kind.kind != DependencyKind::Development)
+                })
+                .map(|dep| &dep.pkg)
+                .collect();
+            queue.extend(dependencies.iter().copied());
+            let purl = |name: &str, version: &str| format!("pkg:cargo/{name}@{version}");
+            components.insert(
+                id,
+                SbomComponent {
+                    name: package.name.to_string(),
+                    version: package.version.to_string(),
+                    purl: purl(&package.name, &package.version.to_string()),
+                    license: package.license.clone(),
+                    depends_on: dependencies
+                        .iter()
+                        .filter_map(|dep| packages.get(dep))
+                        .map(|dep| purl(&dep.name, &dep.version.to_string()))
+                        .collect(),
+                },
+            );
+        }
+
+        let root = components
+            .remove(&root.id)
+            .context("The root package is missing from the SBOM")?;
+        let mut components: Vec<_> = components.into_values().collect();
+        components.sort_by(|a, b| a.purl.cmp(&b.purl));
+        Ok(Self { root, components })
+    }
+
+    /// Add the Godot `version` the game is exported with, e.g. `4.5.1`.
+    pub fn godot_version(mut self, version: &str) -> Self {
+        let godot = SbomComponent {
+            name: "godot".to_string(),
+            version: version.to_string(),
+            purl: format!("{GODOT_PURL_PREFIX}{version}"),
+            license: Some("MIT".to_string()),
+            depends_on: vec![],
+        };
+        self.components
+            .retain(|component| !component.purl.starts_with(GODOT_PURL_PREFIX));
+        self.root
+            .depends_on
+            .retain(|purl| !purl.starts_with(GODOT_PURL_PREFIX));
+        self.root.depends_on.push(godot.purl.clone());
+        self.components.push(godot);
+        self
+    }
+
+    /// The SBOM as a CycloneDX 1.5 JSON document. It has no timestamp, so it is reproducible.
+    pub fn to_cyclonedx(&self) -> serde_json::Value {
+        let component = |component: &SbomComponent, kind: &str| {
+            let mut value = json!({
+                "type": kind,
+                "bom-ref": component.purl,
+                "name": component.name,
+                "version": component.version,
+                "purl": component.purl,
+            });
+            if let Some(license) = &component.license {
+                value["licenses"] = json!([{ "expression": license }]);
+            }
+            value
+        };
+        let components: Vec<_> = self
+            .components
+            .iter()
+            .map(|c| {
+                component(
+                    c,
+                    if c.purl.starts_with(GODOT_PURL_PREFIX) {
+                        "framework"
+                    } else {
+                        "library"
+                    },
+                )
+            })
+            .collect();
+        let dependencies: Vec<_> = std::iter::once(&self.root)
+            .chain(&self.components)
+            .map(|c| json!({ "ref": c.purl, "dependsOn": c.depends_on }))
+            .collect();
+        json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "version": 1,
+            "metadata": {
+                "tools": {
+                    "components": [{
+                        "type": "library",
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION"),
+                    }],
+                },
+                "component": component(&self.root, "application"),
+            },
+            "components": components,
+            "dependencies": dependencies,
+        })
+    }
+
+    /// Write the CycloneDX JSON document to `path`, e.g. `sbom.cdx.json`.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(&self.to_cyclonedx())?;
+        std::fs::write(path, json).with_context(|| format!("Failed to write file: {path:?}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provenance() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("game.pck"), "abc").unwrap();
+        let path = write_sha256sums(dir.path(), &["game.pck".to_string()]).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  game.pck\n"
+        );
+
+        let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        let sbom = Sbom::from_cargo(&manifest, "cargo_godot_lib")
+            .unwrap()
+            .godot_version("4.5.1");
+        assert_eq!(sbom.root.name, "cargo-godot-lib");
+        assert!(sbom.components.iter().any(|c| c.name == "anyhow"));
+        assert!(
+            sbom.root
+                .depends_on
+                .contains(&"pkg:github/godotengine/godot@4.5.1".to_string())
+        );
+
+        let json = sbom.to_cyclonedx();
+        assert_eq!(json["bomFormat"], "CycloneDX");
+        assert_eq!(json["metadata"]["component"]["name"], "cargo-godot-lib");
+        let godot = json["components"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|c| c["type"] == "framework")
+            .unwrap();
+        assert_eq!(godot["purl"], "pkg:github/godotengine/godot@4.5.1");
+    }
+}
@@ -0,0 +1,193 @@
+//! Waiting until a launched Godot is actually up, see `GodotRunner::spawn_ready`, so automation
+//! knows when to send input or attach tools.
+//!
+//! Godot's output is watched for a marker line. With `Readiness::Autoload`, a temporary autoload
+//! prints `READY_MARKER` once the main loop processed its first frame. Autoloads don't run in the
+//! editor, so editor launches need a `Readiness::Marker` printed by an editor plugin instead.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let mut game = runner.spawn_ready(Readiness::Autoload)?;
+//! game.wait_until_ready(Duration::from_secs(30))?;
+//! send_input()?;
+//! game.into_process().wait()?;
+//! ```
+use crate::autoload::TemporaryAutoload;
+use crate::godot_commands::GodotProcess;
+use crate::output::OutputCallback;
+use anyhow::{Result, anyhow};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// The line printed by the autoload of `Readiness::Autoload`.
+pub const READY_MARKER: &str = "cargo-godot-lib: ready";
+
+/// How often `wait_until_ready` checks whether Godot exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+const READY_SCRIPT: &str = r#"extends Node
+
+# Injected by cargo-godot-lib to report that the main loop started.
+
+const MARKER := ""
+
+func _ready() -> void:
+	await get_tree().process_frame
+	print(MARKER)
+"#;
+
+/// The `READY_SCRIPT` printing `READY_MARKER`.
+fn ready_script() -> String {
+    READY_SCRIPT.replace(
+        "const MARKER := \"\"",
+        &format!("const MARKER := {READY_MARKER:?}"),
+    )
+}
+
+/// How `GodotRunner::spawn_ready` detects that Godot is up.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Readiness {
+    /// Inject an autoload printing `READY_MARKER` after the first frame.
+    Autoload,
+    /// Wait for an output line containing this marker.
+    Marker(String),
+}
+
+impl Readiness {
+    pub(crate) fn marker(&self) -> &str {
+        match self {
+            Self::Autoload => READY_MARKER,
+            Self::Marker(marker) => marker,
+        }
+    }
+
+    /// The autoloads to inject for this readiness check.
+    pub(crate) fn autoloads(&self) -> Vec<TemporaryAutoload> {
+        match self {
+            Self::Autoload => vec![TemporaryAutoload::script(
+                "CargoGodotLibReady",
+                ready_script(),
+            )],
+            Self::Marker(_) => vec![],
+        }
+    }
+}
+
+/// Records whether the marker line was printed.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ReadyProbe {
+    ready: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl ReadyProbe {
+    /// A callback marking the probe ready once a line contains `marker`.
+    pub(crate) fn callback(&self, marker: &str) -> OutputCallback {
+        let ready = self.ready.clone();
+        let marker = marker.to_string();
+        Arc::new(move |line| {
+            if line.contains(&marker) {
+                let (lock, condvar) = &*ready;
+                *lock.lock().unwrap_or_else(|e| e.into_inner()) = true;
+                condvar.notify_all();
+            }
+        })
+    }
+
+    /// Wait up to `timeout` for the marker. Returns whether it was printed.
+    fn wait(&self, timeout: Duration) -> bool {
+        let (lock, condvar) = &*self.ready;
+        let ready = lock.lock().unwrap_or_else(|e| e.into_inner());
+        let (ready, _) = condvar
+            .wait_timeout_while(ready, timeout, |ready| !*ready)
+            .unwrap_or_else(|e| e.into_inner());
+        *ready
+    }
+}
+
+/// A Godot process launched by `GodotRunner::spawn_ready`.
+#[derive(Debug)]
+pub struct ReadyProcess {
+    process: GodotProcess,
+    probe: ReadyProbe,
+}
+
+impl ReadyProcess {
+    pub(crate) fn new(process: GodotProcess, probe: ReadyProbe) -> Self {
+        Self { process, probe }
+    }
+
+    /// Wait until Godot printed the readiness marker. Fails if Godot exits before, or if it
+    /// isn't ready after `timeout`. Returns immediately once Godot was ready.
+    pub fn wait_until_ready(&mut self, timeout: Duration) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if self.probe.wait(POLL_INTERVAL.min(remaining)) {
+                return Ok(());
+            }
+            if let Some(status) = self.process.try_wait()? {
+                // The output may still be scanned after the exit.
+                if self.probe.wait(POLL_INTERVAL) {
+                    return Ok(());
+                }
+                return Err(anyhow!("Godot exited with `{status}` before it was ready"));
+            }
+            if remaining.is_zero() {
+                return Err(anyhow!("Godot was not ready after {timeout:?}"));
+            }
+        }
+    }
+
+    /// The running process.
+    pub fn process(&self) -> &GodotProcess {
+        &self.process
+    }
+
+    /// The running process, e.g. to wait for it to exit.
+    pub fn into_process(self) -> GodotProcess {
+        self.process
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    #[test]
+    fn test_wait_until_ready() {
+        use super::*;
+        use crate::godot_commands::spawn_command;
+        use std::process::Command;
+
+        let spawn = |script: &str| {
+            let probe = ReadyProbe::default();
+            let mut command = Command::new("sh");
+            command
+                .args(["-c", script])
+                .stdout(std::process::Stdio::piped());
+            let process = spawn_command(
+                command,
+                Some(probe.callback(Readiness::Autoload.marker())),
+                false,
+                false,
+            )
+            .unwrap();
+            ReadyProcess::new(process, probe)
+        };
+        assert!(ready_script().contains("const MARKER := \"cargo-godot-lib: ready\""));
+        let mut ready = spawn("sleep 0.2; echo 'cargo-godot-lib: ready'; exec sleep 5");
+        ready.wait_until_ready(Duration::from_secs(5)).unwrap();
+        ready.wait_until_ready(Duration::ZERO).unwrap();
+        ready.into_process().kill().unwrap();
+
+        let mut exited = spawn("exit 3");
+        let error = exited.wait_until_ready(Duration::from_secs(5)).unwrap_err();
+        assert!(error.to_string().contains("before it was ready"), "{error}");
+
+        let mut slow = spawn("exec sleep 5");
+        let error = slow
+            .wait_until_ready(Duration::from_millis(200))
+            .unwrap_err();
+        assert!(error.to_string().contains("not ready after"), "{error}");
+        slow.into_process().kill().unwrap();
+    }
+}
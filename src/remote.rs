@@ -0,0 +1,348 @@
+//! A JSON command channel to the running game, see `GodotRunner::spawn_remote`, so tests and
+//! tools can drive it programmatically.
+//!
+//! The autoload of `autoload` listens on a local TCP port. Commands and responses are JSON
+//! objects, one per line: `{"id": 1, "token": "...", "command": "eval", "args": {"expression":
+//! "1 + 1"}}` is answered with `{"id": 1, "result": 2}`, or `{"id": 1, "error": "..."}` if it
+//! failed. `GodotRemote` is the client for it.
+//!
+//! Since commands can run arbitrary code in the game, every request must carry the random token
+//! the autoload was created with, see `generate_token`. A connection is closed on the first line
+//! which isn't a request with the token, e.g. from another local process or a web page.
+//! Autoloads don't run in the editor.
+//!
+//! Input can be recorded and replayed, see `input_recording`.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let (mut remote, process) = runner.spawn_remote(0)?;
+//! remote.eval("get_tree().current_scene.get_node('Player').position = Vector2(0, 0)")?;
+//! remote.screenshot(&std::env::current_dir()?.join("start.png"))?;
+//! remote.quit(0)?;
+//! process.wait()?;
+//! ```
+use crate::autoload::TemporaryAutoload;
+use crate::input_recording::InputRecording;
+use anyhow::{Context, Result, anyhow};
+use serde_json::{Value, json};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::time::Duration;
+
+/// How long `GodotRunner::spawn_remote` waits for the game to listen.
+pub(crate) const REMOTE_START_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long `GodotRemote` waits for a response by default.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+const REMOTE_SCRIPT: &str = r#"extends Node
+
+# Injected by cargo-godot-lib to accept commands of `GodotRemote`.
+
+const PORT := 0
+const TOKEN := ""
+
+var _server := TCPServer.new()
+var _peers: Array[StreamPeerTCP] = []
+var _buffers := {}
+var _recording := []
+var _recording_start := -1
+
+func _ready() -> void:
+	process_mode = Node.PROCESS_MODE_ALWAYS
+	get_tree().root.window_input.connect(_on_window_input)
+	var error := _server.listen(PORT, "127.0.0.1")
+	if error != OK:
+		push_error("cargo-godot-lib: Remote control failed to listen on port %d: %s" % [PORT, error_string(error)])
+
+func _process(_delta: float) -> void:
+	while _server.is_connection_available():
+		_peers.append(_server.take_connection())
+	for peer in _peers.duplicate():
+		peer.poll()
+		if peer.get_status() != StreamPeerTCP.STATUS_CONNECTED:
+			_peers.erase(peer)
+			_buffers.erase(peer)
+			continue
+		var buffer: PackedByteArray = _buffers.get(peer, PackedByteArray())
+		if peer.get_available_bytes() > 0:
+			buffer.append_array(peer.get_data(peer.get_available_bytes())[1])
+		var end := buffer.find(10)
+		while end != -1:
+			var request = _authenticated(buffer.slice(0, end).get_string_from_utf8())
+			if request == null:
+				_close(peer)
+				break
+			_handle(peer, request)
+			buffer = buffer.slice(end + 1)
+			end = buffer.find(10)
+		if peer in _peers:
+			_buffers[peer] = buffer
+
+func _authenticated(line: String) -> Variant:
+	var request = JSON.parse_string(line)
+	if request is Dictionary and request.get("token") is String and request["token"] == TOKEN:
+		return request
+	return null
+
+func _close(peer: StreamPeerTCP) -> void:
+	peer.disconnect_from_host()
+	_peers.erase(peer)
+	_buffers.erase(peer)
+
+func _on_window_input(event: InputEvent) -> void:
+	if _recording_start >= 0:
+		var time := (Time.get_ticks_usec() - _recording_start) / 1000000.0
+		_recording.append({"time": time, "event": var_to_str(event)})
+
+func _handle(peer: StreamPeerTCP, request: Dictionary) -> void:
+	var id = request.get("id")
+	var args: Dictionary = request.get("args", {})
+	match request.get("command"):
+		"quit":
+			_reply(peer, id, {"result": null})
+			get_tree().quit.call_deferred(int(args.get("exit_code", 0)))
+		"reload_scene":
+			_reply_status(peer, id, get_tree().reload_current_scene())
+		"screenshot":
+			await RenderingServer.frame_post_draw
+			var image := get_viewport().get_texture().get_image()
+			if image == null:
+				_reply(peer, id, {"error": "No rendered frame, the game may run headless"})
+			else:
+				_reply_status(peer, id, image.save_png(args.get("path", "")))
+		"eval":
+			var expression := Expression.new()
+			var error := expression.parse(args.get("expression", ""))
+			if error != OK:
+				_reply(peer, id, {"error": expression.get_error_text()})
+				return
+			var result = expression.execute([], get_tree().current_scene, false)
+			if expression.has_execute_failed():
+				_reply(peer, id, {"error": expression.get_error_text()})
+			else:
+				_reply(peer, id, {"result": result})
+		"start_recording":
+			_recording = []
+			_recording_start = Time.get_ticks_usec()
+			_reply(peer, id, {"result": null})
+		"stop_recording":
+			_recording_start = -1
+			_reply(peer, id, {"result": {"events": _recording}})
+		"play_input":
+			var start := Time.get_ticks_usec()
+			for input in args.get("events", []):
+				var delay: float = input["time"] - (Time.get_ticks_usec() - start) / 1000000.0
+				if delay > 0:
+					await get_tree().create_timer(delay, true, false, true).timeout
+				Input.parse_input_event(str_to_var(input["event"]))
+			_reply(peer, id, {"result": null})
+		var command:
+			_reply(peer, id, {"error": "Unknown command: %s" % command})
+
+func _reply_status(peer: StreamPeerTCP, id, error: Error) -> void:
+	if error == OK:
+		_reply(peer, id, {"result": null})
+	else:
+		_reply(peer, id, {"error": error_string(error)})
+
+func _reply(peer: StreamPeerTCP, id, response: Dictionary) -> void:
+	response["id"] = id
+	peer.put_data((JSON.stringify(response) + "\n").to_utf8_buffer())
+"#;
+
+/// A random token for `autoload` and `GodotRemote::connect`.
+pub fn generate_token() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes)
+        .map_err(|e| anyhow!("Failed to generate a remote control token: {e}"))?;
+    Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// The autoload accepting remote commands with `token` on the local `port`.
+pub fn autoload(port: u16, token: &str) -> TemporaryAutoload {
+    TemporaryAutoload::script(
+        "CargoGodotLibRemote",
+        REMOTE_SCRIPT
+            .replace("const PORT := 0", &format!("const PORT := {port}"))
+            .replace("const TOKEN := \"\"", &format!("const TOKEN := {token:?}")),
+    )
+}
+
+/// A connection to the remote control autoload of a running game.
+#[derive(Debug)]
+pub struct GodotRemote {
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+    token: String,
+    next_id: u64,
+}
+
+impl GodotRemote {
+    /// Connect to a game running the `autoload` with `token`, e.g. at `127.0.0.1:6010`.
+    pub fn connect(address: impl ToSocketAddrs, token: &str) -> Result<Self> {
+        let stream =
+            TcpStream::connect(address).context("Failed to connect to the Godot remote control")?;
+        stream.set_read_timeout(Some(DEFAULT_TIMEOUT))?;
+        let reader = BufReader::new(stream.try_clone()?);
+        Ok(Self {
+            stream,
+            reader,
+            token: token.to_string(),
+            next_id: 1,
+        })
+    }
+
+    /// How long to wait for a response. Default: 30 seconds.
+    pub fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.stream
+            .set_read_timeout(Some(timeout))
+            .context("Failed to set the Godot remote control timeout")
+    }
+
+    /// Send `command` with the JSON object `args` and return its result.
+    pub fn request(&mut self, command: &str, args: Value) -> Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let request = json!({"id": id, "token": self.token, "command": command, "args": args});
+        writeln!(self.stream, "{request}")
+            .with_context(|| format!("Failed to send the remote command `{command}`"))?;
+        loop {
+            let mut line = String::new();
+            let read = self
+                .reader
+                .read_line(&mut line)
+                .with_context(|| format!("No response to the remote command `{command}`"))?;
+            if read == 0 {
+                return Err(anyhow!(
+                    "Godot closed the remote control before responding to `{command}`"
+                ));
+            }
+            let mut response: Value = serde_json::from_str(&line)
+                .with_context(|| format!("Invalid remote control response: {line:?}"))?;
+            // Godot parses JSON numbers as floats. Responses to earlier commands which timed
+            // out are skipped.
+            if response["id"].as_f64() != Some(id as f64) {
+                continue;
+            }
+            if let Some(error) = response.get("error") {
+                let error = error.as_str().map_or_else(|| error.to_string(), Into::into);
+                return Err(anyhow!("The remote command `{command}` failed: {error}"));
+            }
+            return Ok(response["result"].take());
+        }
+    }
+
+    /// Quit the game with `exit_code`.
+    pub fn quit(&mut self, exit_code: i32) -> Result<()> {
+        self.request("quit", json!({"exit_code": exit_code}))
+            .map(drop)
+    }
+
+    /// Reload the current scene.
+    pub fn reload_scene(&mut self) -> Result<()> {
+        self.request("reload_scene", json!({})).map(drop)
+    }
+
+    /// Save the next rendered frame as a PNG at `path`. Relative paths are relative to the godot
+    /// project. Fails for headless games.
+    pub fn screenshot(&mut self, path: &Path) -> Result<()> {
+        let path = path.to_string_lossy().replace('\\', "/");
+        self.request("screenshot", json!({"path": path})).map(drop)
+    }
+
+    /// Start recording the input events of the game, replacing a previous recording.
+    pub fn start_recording(&mut self) -> Result<()> {
+        self.request("start_recording", json!({})).map(drop)
+    }
+
+    /// Stop recording and return the events since `start_recording`.
+    pub fn stop_recording(&mut self) -> Result<InputRecording> {
+        let recording = self.request("stop_recording", json!({}))?;
+        serde_json::from_value(recording).context("Invalid input recording")
+    }
+
+    /// Replay `recording` and wait until its last event was sent. The response timeout is
+    /// extended by the duration of the recording.
+    pub fn play(&mut self, recording: &InputRecording) -> Result<()> {
+        let timeout = self.stream.read_timeout()?;
+        self.stream
+            .set_read_timeout(timeout.map(|timeout| timeout + recording.duration()))?;
+        let result = self.request("play_input", serde_json::to_value(recording)?);
+        self.stream.set_read_timeout(timeout)?;
+        result.map(drop)
+    }
+
+    /// Evaluate a GDScript `Expression` on the current scene. Values without a JSON equivalent,
+    /// e.g. `Vector2`, are returned as strings.
+    pub fn eval(&mut self, expression: &str) -> Result<Value> {
+        self.request("eval", json!({"expression": expression}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_godot_remote() {
+        assert_eq!(autoload(6010, "a").name(), "CargoGodotLibRemote");
+        assert!(autoload(6010, "a") != autoload(6010, "b"));
+        let token = generate_token().unwrap();
+        assert_eq!(token.len(), 32);
+        assert!(token != generate_token().unwrap());
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut requests = vec![];
+            for line in BufReader::new(stream).lines() {
+                let request: Value = serde_json::from_str(&line.unwrap()).unwrap();
+                let id = request["id"].clone();
+                let response = match request["command"].as_str().unwrap() {
+                    "eval" => json!({"id": id.as_f64(), "result": 2}),
+                    "reload_scene" => {
+                        // A late response to an earlier command comes first.
+                        writeln!(writer, "{}", json!({"id": 0, "result": null})).unwrap();
+                        json!({"id": id, "result": null})
+                    }
+                    "stop_recording" => json!({"id": id, "result": {"events": [
+                        {"time": 0.5, "event": "Object(InputEventKey,\"keycode\":32)"}
+                    ]}}),
+                    "play_input" => json!({"id": id, "result": null}),
+                    command => json!({"id": id, "error": format!("Unknown command: {command}")}),
+                };
+                writeln!(writer, "{response}").unwrap();
+                requests.push(request);
+            }
+            requests
+        });
+
+        let mut remote = GodotRemote::connect(address, &token).unwrap();
+        assert_eq!(remote.eval("1 + 1").unwrap(), json!(2));
+        remote.reload_scene().unwrap();
+        let error = remote.request("fly", json!({})).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The remote command `fly` failed: Unknown command: fly"
+        );
+        let recording = remote.stop_recording().unwrap();
+        assert_eq!(recording.events[0].time, 0.5);
+        remote.play(&recording).unwrap();
+        drop(remote);
+        let requests = server.join().unwrap();
+        assert_eq!(
+            requests[0],
+            json!({"id": 1, "token": token, "command": "eval", "args": {"expression": "1 + 1"}})
+        );
+        assert_eq!(
+            requests[4]["args"],
+            serde_json::to_value(&recording).unwrap()
+        );
+        assert_eq!(requests.len(), 5);
+    }
+}
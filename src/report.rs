@@ -0,0 +1,100 @@
+//! A machine-readable summary of a `GodotRunner` run.
+use crate::exit_status::GodotExitStatus;
+use crate::leak_check::LeakReport;
+use crate::output::GodotError;
+use crate::performance::PerformanceReport;
+use crate::symbolicate::SymbolicatedFrame;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// The result of `GodotRunner::execute_with_report`.
+///
+/// Implements `Serialize` so build orchestration tools can consume it, e.g. as JSON:
+/// ```rust,ignore
+/// let report = runner.execute_with_report()?;
+/// println!("{}", serde_json::to_string(&report)?);
+/// ```
+#[derive(Clone, Debug, Serialize)]
+pub struct RunReport {
+    /// The executable that was run, either the Godot binary or `gdenv`.
+    pub binary: PathBuf,
+    /// The Godot version reported by `godot --version`, if it could be detected.
+    pub version: Option<String>,
+    /// The arguments passed to Godot.
+    pub args: Vec<String>,
+    /// The wall-clock time of the run, including writing files and importing the project.
+    pub duration: Duration,
+    /// How Godot exited. `ImportFailed` if the pre-import step failed and Godot wasn't launched.
+    pub exit_status: GodotExitStatus,
+    /// Errors found in Godot's output. Only collected with `GodotRunner::scan_output_errors`.
+    pub errors: Vec<GodotError>,
+    /// The crash dump collected with `GodotRunner::crash_dumps` if Godot crashed.
+    pub crash_dump: Option<PathBuf>,
+    /// Backtrace frames of the extension resolved with `GodotRunner::symbolicate_backtraces`.
+    pub backtrace: Vec<SymbolicatedFrame>,
+    /// The performance samples collected with `GodotRunner::collect_performance`.
+    pub performance: Option<PerformanceReport>,
+    /// The leaks Godot reported at exit, collected with `GodotRunner::check_leaks`.
+    pub leaks: Option<LeakReport>,
+    /// Files written before launching Godot, e.g. the `.gdextension` file.
+    pub written_files: Vec<PathBuf>,
+    /// Warnings raised while preparing the run.
+    pub warnings: Vec<String>,
+}
+
+impl RunReport {
+    /// Returns true if Godot exited successfully without reporting errors or violating
+    /// performance thresholds.
+    pub fn is_success(&self) -> bool {
+        self.exit_status.is_success()
+            && self.errors.is_empty()
+            && self
+                .performance
+                .as_ref()
+                .is_none_or(|performance| performance.violations.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::GodotErrorKind;
+
+    #[test]
+    fn test_serialize() {
+        let report = RunReport {
+            binary: PathBuf::from("godot"),
+            version: Some("4.5.1.stable.official.f62fdbde1".to_string()),
+            args: vec!["--headless".to_string()],
+            duration: Duration::from_millis(1500),
+            exit_status: GodotExitStatus::Crashed(11),
+            errors: vec![GodotError {
+                kind: GodotErrorKind::GdExtension,
+                message: "Can't open dynamic library".to_string(),
+                location: None,
+            }],
+            crash_dump: Some(PathBuf::from("target/crash-dumps/core.42")),
+            backtrace: vec![SymbolicatedFrame {
+                line: "[3] libgame.so(+0x1a2b) [0x7f00001a2b]".to_string(),
+                function: "game::player::jump".to_string(),
+                location: Some("src/player.rs:42".to_string()),
+            }],
+            performance: None,
+            leaks: None,
+            written_files: vec![PathBuf::from("godot/rust.gdextension")],
+            warnings: vec![],
+        };
+        assert!(!report.is_success());
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["binary"], "godot");
+        assert_eq!(json["args"][0], "--headless");
+        assert_eq!(json["duration"]["secs"], 1);
+        assert_eq!(json["exit_status"]["Crashed"], 11);
+        assert_eq!(json["errors"][0]["kind"], "GdExtension");
+        assert_eq!(json["crash_dump"], "target/crash-dumps/core.42");
+        assert_eq!(json["backtrace"][0]["function"], "game::player::jump");
+        assert_eq!(json["written_files"][0], "godot/rust.gdextension");
+    }
+}
@@ -0,0 +1,79 @@
+//! Creating a minimal godot project for a new repository, see
+//! `GodotRunner::create_project_if_missing`.
+//!
+//! The project has only a `project.godot` with the project name and, if known, the Godot version
+//! in `config/features`, and the `.gitignore` Godot creates for new projects. The editor fills
+//! in everything else when the project is first opened.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! scaffold::create_project(Path::new("godot"), "My Game", Some("4.5"))?;
+//! ```
+use crate::project_config::{ProjectConfig, SettingValue};
+use anyhow::{Context, Result, anyhow};
+use std::path::{Path, PathBuf};
+
+/// The `.gitignore` of new Godot 4 projects.
+const GITIGNORE: &str = "# Godot 4+ specific ignores\n.godot/\n/android/\n";
+
+/// Create a godot project named `name` at `godot_project_path`, creating the directory if
+/// needed. `godot_version` is the `major.minor` version, e.g. `4.5`. Fails if the directory
+/// already has a `project.godot`. Returns the written files.
+pub fn create_project(
+    godot_project_path: &Path,
+    name: &str,
+    godot_version: Option<&str>,
+) -> Result<Vec<PathBuf>> {
+    let project_file = ProjectConfig::path(godot_project_path);
+    if project_file.exists() {
+        return Err(anyhow!("{project_file:?} already exists"));
+    }
+    std::fs::create_dir_all(godot_project_path)
+        .with_context(|| format!("Failed to create directory: {godot_project_path:?}"))?;
+
+    let mut contents = format!(
+        "config_version=5\n\n[application]\n\nconfig/name={}\n",
+        SettingValue::from(name).to_variant_string()
+    );
+    if let Some(version) = godot_version {
+        contents += &format!(
+            "config/features=PackedStringArray({})\n",
+            SettingValue::from(version).to_variant_string()
+        );
+    }
+    std::fs::write(&project_file, contents)
+        .with_context(|| format!("Failed to write {project_file:?}"))?;
+    let mut written = vec![project_file];
+
+    let gitignore = godot_project_path.join(".gitignore");
+    if !gitignore.exists() {
+        std::fs::write(&gitignore, GITIGNORE)
+            .with_context(|| format!("Failed to write {gitignore:?}"))?;
+        written.push(gitignore);
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_project() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("games/godot");
+        let written = create_project(&project, "My \"Game\"", Some("4.5")).unwrap();
+        assert_eq!(
+            written,
+            vec![project.join("project.godot"), project.join(".gitignore")]
+        );
+        let config = ProjectConfig::load(&project).unwrap();
+        config.validate().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(project.join("project.godot")).unwrap(),
+            "config_version=5\n\n[application]\n\nconfig/name=\"My \\\"Game\\\"\"\n\
+            config/features=PackedStringArray(\"4.5\")\n"
+        );
+        assert!(create_project(&project, "Game", None).is_err());
+    }
+}
@@ -0,0 +1,417 @@
+//! Change detection for `GodotRunner::execute_if_changed`, and the `BuildState` reporting what
+//! is stale.
+//!
+//! The inputs of a run are the files of the godot project, excluding hidden files and folders
+//! such as `.godot`, and the extension libraries. Their SHA-256 hashes are stored in
+//! `<target>/.cargo-godot-lib/state.json` after every successful run.
+//!
+//! The `BuildState` in `<target>/.cargo-godot-lib/build_state.json` records the libraries built
+//! by the runner, the Godot version the project was imported with, the Godot binary cached by a
+//! `GodotLocator` and, with `BuildState::record_exports`, when presets were exported.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let status = runner.build_status()?;
+//! for stale in &status.stale {
+//!     println!("{stale}");
+//! }
+//! ```
+use crate::cargo::CdylibArtifact;
+use crate::export::{ExportMode, ExportSummary};
+use crate::godot_locator::LocatedGodot;
+use crate::project_overrides::BACKUP_FILE_NAME;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The directory in the cargo target directory holding this crate's state.
+pub const STATE_DIR: &str = ".cargo-godot-lib";
+
+/// Files in the godot project which change during a run without being inputs.
+const IGNORED_FILES: &[&str] = &["override.cfg", BACKUP_FILE_NAME];
+
+/// The path of the state file in the cargo `target_directory`.
+pub fn state_path(target_directory: &Path) -> PathBuf {
+    target_directory.join(STATE_DIR).join("state.json")
+}
+
+/// The hashes of the inputs of a run.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RunState {
+    /// The godot project the state belongs to.
+    pub project: PathBuf,
+    /// SHA-256 hashes of the input files, keyed by their path relative to the project,
+    /// or their absolute path for files outside of the project.
+    pub inputs: BTreeMap<String, String>,
+}
+
+impl RunState {
+    /// Hash the files of the godot project and the `extra_files`, e.g. the extension libraries.
+    /// Missing extra files are recorded as `missing`.
+    pub fn fingerprint(godot_project_path: &Path, extra_files: &[PathBuf]) -> Result<Self> {
+        let mut files = vec![];
+        find_project_files(godot_project_path, &mut files)?;
+
+        let mut inputs = BTreeMap::new();
+        for file in files {
+            let key = file
+                .strip_prefix(godot_project_path)
+                .unwrap_or(&file)
+                .to_string_lossy()
+                .replace('\\', "/");
+            inputs.insert(key, hash_file(&file)?);
+        }
+        for file in extra_files {
+            let hash = if file.exists() {
+                hash_file(file)?
+            } else {
+                "missing".to_string()
+            };
+            inputs.insert(file.to_string_lossy().into_owned(), hash);
+        }
+        Ok(Self {
+            project: godot_project_path.to_path_buf(),
+            inputs,
+        })
+    }
+
+    /// Load the state saved at `path`. Returns `None` if there is none or it can't be parsed,
+    /// e.g. because it was written by an incompatible version.
+    pub fn load(path: &Path) -> Option<Self> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    /// Save the state to `path`.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {parent:?}"))?;
+        }
+        let contents = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, contents).with_context(|| format!("Failed to write {path:?}"))
+    }
+}
+
+/// The path of the build state file in the cargo `target_directory`.
+pub fn build_state_path(target_directory: &Path) -> PathBuf {
+    target_directory.join(STATE_DIR).join("build_state.json")
+}
+
+/// A library recorded in the `BuildState`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ArtifactState {
+    /// The library name, e.g. `my_crate`.
+    pub name: String,
+    /// The target triple the library was built for.
+    pub target: String,
+    pub path: PathBuf,
+    /// The SHA-256 hash of the library when it was built.
+    pub hash: String,
+    pub built: SystemTime,
+}
+
+/// An export recorded in the `BuildState`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ExportState {
+    pub preset: String,
+    pub mode: ExportMode,
+    pub output_path: PathBuf,
+    pub exported: SystemTime,
+}
+
+/// What was built, imported and exported, see the module documentation.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct BuildState {
+    /// The built libraries, keyed by `<target>/<name>`.
+    pub artifacts: BTreeMap<String, ArtifactState>,
+    /// The Godot version of the last import, e.g. `4.5.1.stable.official.f62fdbde1`.
+    pub import_godot_version: Option<String>,
+    /// The last successful exports, keyed by `<preset>/<mode>`.
+    pub exports: BTreeMap<String, ExportState>,
+    /// The Godot binary cached by a `GodotLocator`.
+    pub godot_binary: Option<LocatedGodot>,
+}
+
+impl BuildState {
+    /// Load the state saved at `path`. Returns `None` if there is none or it can't be parsed.
+    pub fn load(path: &Path) -> Option<Self> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    /// Save the state to `path`.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {parent:?}"))?;
+        }
+        let contents = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, contents).with_context(|| format!("Failed to write {path:?}"))
+    }
+
+    /// Load the state at `path`, apply `f` and save it.
+    pub fn update(path: &Path, f: impl FnOnce(&mut Self) -> Result<()>) -> Result<()> {
+        let mut state = Self::load(path).unwrap_or_default();
+        f(&mut state)?;
+        state.save(path)
+    }
+
+    /// Record a library which was just built.
+    pub fn record_artifact(&mut self, artifact: &CdylibArtifact) -> Result<()> {
+        self.artifacts.insert(
+            format!("{}/{}", artifact.target, artifact.name),
+            ArtifactState {
+                name: artifact.name.clone(),
+                target: artifact.target.clone(),
+                path: artifact.path.clone(),
+                hash: hash_file(&artifact.path)?,
+                built: SystemTime::now(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Record that the project was imported with Godot `version`.
+    pub fn record_import(&mut self, godot_version: &str) {
+        self.import_godot_version = Some(godot_version.to_string());
+    }
+
+    /// Record the successful exports of an `ExportMatrix` run.
+    pub fn record_exports(&mut self, summary: &ExportSummary) {
+        let exported = SystemTime::now();
+        for result in summary.results.iter().filter(|result| result.is_success()) {
+            let job = &result.job;
+            self.exports.insert(
+                format!("{}/{}", job.preset, job.mode.name()),
+                ExportState {
+                    preset: job.preset.clone(),
+                    mode: job.mode,
+                    output_path: job.output_path.clone(),
+                    exported,
+                },
+            );
+        }
+    }
+
+    /// What is stale: libraries which are missing or changed since they were built, an import
+    /// with another Godot version than `godot_version`, and exports which are missing or older
+    /// than a library.
+    pub fn status(&self, godot_version: Option<&str>) -> Result<BuildStatus> {
+        let mut stale = vec![];
+        let mut item = |item: String, reason: String| stale.push(StaleItem { item, reason });
+        for artifact in self.artifacts.values() {
+            let name = format!("library {} ({})", artifact.name, artifact.target);
+            if !artifact.path.exists() {
+                item(name, format!("{:?} is missing", artifact.path));
+            } else if hash_file(&artifact.path)? != artifact.hash {
+                item(
+                    name,
+                    format!("{:?} changed since it was built", artifact.path),
+                );
+            }
+        }
+        if let Some(current) = godot_version {
+            match &self.import_godot_version {
+                None => item("import".to_string(), "never imported".to_string()),
+                Some(imported) if imported != current => item(
+                    "import".to_string(),
+                    format!("imported with Godot {imported}, not {current}"),
+                ),
+                Some(_) => {}
+            }
+        }
+        let last_build = self.artifacts.values().map(|artifact| artifact.built).max();
+        for export in self.exports.values() {
+            let name = format!("export {} ({})", export.preset, export.mode.name());
+            if !export.output_path.exists() {
+                item(name, format!("{:?} is missing", export.output_path));
+            } else if last_build.is_some_and(|built| built > export.exported) {
+                item(name, "a library was built since the export".to_string());
+            }
+        }
+        Ok(BuildStatus { stale })
+    }
+}
+
+/// Something stale according to `BuildState::status`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct StaleItem {
+    /// What is stale, e.g. `import` or `export Linux (release)`.
+    pub item: String,
+    /// Why it is stale.
+    pub reason: String,
+}
+
+impl Display for StaleItem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.item, self.reason)
+    }
+}
+
+/// The result of `BuildState::status`.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct BuildStatus {
+    pub stale: Vec<StaleItem>,
+}
+
+impl BuildStatus {
+    /// Returns true if nothing is stale.
+    pub fn is_up_to_date(&self) -> bool {
+        self.stale.is_empty()
+    }
+}
+
+impl Display for BuildStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.stale.is_empty() {
+            return write!(f, "Everything is up to date");
+        }
+        for stale in &self.stale {
+            writeln!(f, "{stale}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The hex encoded SHA-256 hash of a file.
+pub fn hash_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {path:?}"))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .with_context(|| format!("Failed to read {path:?}"))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+pub(crate) fn find_project_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries =
+        std::fs::read_dir(dir).with_context(|| format!("Failed to read directory: {dir:?}"))?;
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with('.') || IGNORED_FILES.contains(&name.as_ref()) {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            find_project_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("godot");
+        std::fs::create_dir_all(project.join(".godot")).unwrap();
+        std::fs::create_dir_all(project.join("scenes")).unwrap();
+        std::fs::write(project.join("project.godot"), "config_version=5").unwrap();
+        std::fs::write(project.join("scenes/main.tscn"), "[gd_scene]").unwrap();
+        std::fs::write(project.join(".godot/uid_cache.bin"), "").unwrap();
+        std::fs::write(project.join("override.cfg"), "").unwrap();
+        let library = dir.path().join("libgame.so");
+        std::fs::write(&library, "library").unwrap();
+        let missing = dir.path().join("game.dll");
+
+        let state = RunState::fingerprint(&project, &[library.clone(), missing.clone()]).unwrap();
+        let mut keys: Vec<_> = state.inputs.keys().cloned().collect();
+        keys.sort();
+        let mut expected = vec![
+            "project.godot".to_string(),
+            "scenes/main.tscn".to_string(),
+            library.to_string_lossy().into_owned(),
+            missing.to_string_lossy().into_owned(),
+        ];
+        expected.sort();
+        assert_eq!(keys, expected);
+        assert_eq!(state.inputs[&*missing.to_string_lossy()], "missing");
+        assert_eq!(
+            state.inputs["project.godot"],
+            hash_file(&project.join("project.godot")).unwrap()
+        );
+
+        let path = state_path(&dir.path().join("target"));
+        assert_eq!(RunState::load(&path), None);
+        state.save(&path).unwrap();
+        assert_eq!(RunState::load(&path), Some(state.clone()));
+
+        std::fs::write(&library, "rebuilt").unwrap();
+        let changed = RunState::fingerprint(&project, &[library, missing]).unwrap();
+        assert_ne!(changed, state);
+    }
+
+    #[test]
+    fn test_build_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let library = dir.path().join("libgame.so");
+        std::fs::write(&library, "library").unwrap();
+        let path = build_state_path(&dir.path().join("target"));
+        BuildState::update(&path, |state| {
+            state.record_import("4.5.1.stable");
+            state.record_artifact(&CdylibArtifact {
+                name: "game".to_string(),
+                path: library.clone(),
+                target: "x86_64-unknown-linux-gnu".to_string(),
+            })
+        })
+        .unwrap();
+        let mut state = BuildState::load(&path).unwrap();
+        assert!(state.status(Some("4.5.1.stable")).unwrap().is_up_to_date());
+
+        state.exports.insert(
+            "Linux/release".to_string(),
+            ExportState {
+                preset: "Linux".to_string(),
+                mode: ExportMode::Release,
+                output_path: dir.path().join("game.x86_64"),
+                exported: SystemTime::UNIX_EPOCH,
+            },
+        );
+        std::fs::write(&library, "rebuilt").unwrap();
+        let status = state.status(Some("4.5.2.stable")).unwrap();
+        let items: Vec<_> = status
+            .stale
+            .iter()
+            .map(|stale| stale.item.as_str())
+            .collect();
+        assert_eq!(
+            items,
+            [
+                "library game (x86_64-unknown-linux-gnu)",
+                "import",
+                "export Linux (release)"
+            ]
+        );
+        assert!(
+            status
+                .to_string()
+                .contains("imported with Godot 4.5.1.stable")
+        );
+    }
+}
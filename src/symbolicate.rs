@@ -0,0 +1,176 @@
+//! Symbolicating backtrace frames of the extension library printed by Godot,
+//! see `GodotRunner::symbolicate_backtraces`.
+//!
+//! Godot's crash handler prints frames of other libraries as `[3] /path/libgame.so(+0x1a2b3c)
+//! [0x7f...]`, without function names for release builds of the extension. Such frames of the
+//! extension's libraries are resolved with `addr2line` from binutils against the built library,
+//! which needs debug info in the library, e.g. `debug = "line-tables-only"` in the profile.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let frames = symbolicate::symbolicate(&output_lines, &[PathBuf::from("target/release/libgame.so")]);
+//! for frame in frames {
+//!     eprintln!("{frame}");
+//! }
+//! ```
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// A frame of a library printed in a backtrace.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Frame {
+    /// The library as printed, e.g. `/path/libgame.so`.
+    pub library: String,
+    /// The offset of the address in the library.
+    pub offset: u64,
+}
+
+/// A backtrace frame resolved to its function and source location.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct SymbolicatedFrame {
+    /// The line of Godot's output the frame was taken from.
+    pub line: String,
+    /// The demangled function name.
+    pub function: String,
+    /// The source location, e.g. `src/lib.rs:42`, if known.
+    pub location: Option<String>,
+}
+
+impl Display for SymbolicatedFrame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}\n    {}", self.line, self.function)?;
+        if let Some(location) = &self.location {
+            write!(f, "\n        at {location}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a frame like `[3] /path/libgame.so(+0x1a2b3c) [0x7f3c2a1b3c]`.
+/// Frames relative to a symbol, like `libgame.so(godot_init+0x12)`, are not parsed.
+pub fn parse_frame(line: &str) -> Option<Frame> {
+    let open = line.find("(+0x")?;
+    let close = open + line[open..].find(')')?;
+    let offset = u64::from_str_radix(&line[open + 4..close], 16).ok()?;
+    let library = line[..open].rsplit(char::is_whitespace).next()?;
+    if library.is_empty() {
+        return None;
+    }
+    Some(Frame {
+        library: library.to_string(),
+        offset,
+    })
+}
+
+/// Symbolicate the frames in `lines` which belong to one of the `libraries`, matched by
+/// file name. Frames `addr2line` can't resolve are skipped, as are all frames if it isn't
+/// installed.
+pub fn symbolicate(lines: &[String], libraries: &[PathBuf]) -> Vec<SymbolicatedFrame> {
+    let mut frames_by_library: BTreeMap<&Path, Vec<(&str, u64)>> = BTreeMap::new();
+    for line in lines {
+        let Some(frame) = parse_frame(line) else {
+            continue;
+        };
+        let file_name = Path::new(&frame.library).file_name();
+        if let Some(library) = libraries
+            .iter()
+            .find(|library| library.file_name() == file_name && library.is_file())
+        {
+            frames_by_library
+                .entry(library)
+                .or_default()
+                .push((line.trim(), frame.offset));
+        }
+    }
+
+    let mut symbolicated = vec![];
+    for (library, frames) in frames_by_library {
+        let Some(resolved) = addr2line(library, frames.iter().map(|(_, offset)| *offset)) else {
+            continue;
+        };
+        for ((line, _), (function, location)) in frames.into_iter().zip(resolved) {
+            if function == "??" {
+                continue;
+            }
+            symbolicated.push(SymbolicatedFrame {
+                line: line.to_string(),
+                function,
+                location,
+            });
+        }
+    }
+    symbolicated
+}
+
+/// Resolve `offsets` in `library` to function names and locations with `addr2line`.
+fn addr2line(
+    library: &Path,
+    offsets: impl Iterator<Item = u64>,
+) -> Option<Vec<(String, Option<String>)>> {
+    let output = Command::new("addr2line")
+        .arg("-e")
+        .arg(library)
+        .args(["-f", "-C", "-p"])
+        .args(offsets.map(|offset| format!("{offset:#x}")))
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(parse_addr2line)
+            .collect(),
+    )
+}
+
+/// Parse a line of `addr2line -f -p`, e.g. `game::player::jump at src/player.rs:42`.
+fn parse_addr2line(line: &str) -> (String, Option<String>) {
+    match line.split_once(" at ") {
+        Some((function, location)) if !location.starts_with("??") => {
+            (function.to_string(), Some(location.to_string()))
+        }
+        Some((function, _)) => (function.to_string(), None),
+        None => (line.to_string(), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse_frame("[3] /home/me/game/target/release/libgame.so(+0x1a2b3c) [0x7f3c2a1b3c]"),
+            Some(Frame {
+                library: "/home/me/game/target/release/libgame.so".to_string(),
+                offset: 0x1a2b3c,
+            })
+        );
+        assert_eq!(
+            parse_frame("[4] libgame.so(godot_init+0x12) [0x7f3c2a1b3c]"),
+            None
+        );
+        assert_eq!(parse_frame("ERROR: Something (+0xzz)"), None);
+
+        assert_eq!(
+            parse_addr2line("game::player::jump at /home/me/game/src/player.rs:42"),
+            (
+                "game::player::jump".to_string(),
+                Some("/home/me/game/src/player.rs:42".to_string())
+            )
+        );
+        assert_eq!(
+            parse_addr2line("game::init at ??:0"),
+            ("game::init".to_string(), None)
+        );
+        assert!(symbolicate(&["[1] libother.so(+0x10) [0x1]".to_string()], &[]).is_empty());
+    }
+}
@@ -0,0 +1,83 @@
+//! Checking the symbols exported by the built extension library,
+//! see `GodotRunner::check_entry_symbol`.
+//!
+//! Godot looks up the `entry_symbol` of the `.gdextension` file when loading the library, and a
+//! mismatch, e.g. with a custom `#[gdextension(entry_symbol = ...)]`, only surfaces as a load error
+//! inside Godot. The ELF, PE and Mach-O export tables are read with the `object` crate.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! check_entry_symbol(Path::new("target/debug/libgame.so"), "gdext_rust_init")?;
+//! ```
+use anyhow::{Context, Result, anyhow};
+use object::Object;
+use std::path::Path;
+
+/// The names of the symbols exported by the shared library at `library`.
+/// The leading underscore of Mach-O symbol names is removed.
+pub fn exported_symbols(library: &Path) -> Result<Vec<String>> {
+    let data = std::fs::read(library).with_context(|| format!("Failed to read {library:?}"))?;
+    let file = object::File::parse(&*data)
+        .with_context(|| format!("Failed to parse shared library: {library:?}"))?;
+    let macho = file.format() == object::BinaryFormat::MachO;
+    Ok(file
+        .exports()
+        .with_context(|| format!("Failed to read the exports of {library:?}"))?
+        .iter()
+        .map(|export| {
+            let name = String::from_utf8_lossy(export.name());
+            match name.strip_prefix('_') {
+                Some(name) if macho => name.to_string(),
+                _ => name.into_owned(),
+            }
+        })
+        .collect())
+}
+
+/// Check that the shared library at `library` exports `entry_symbol`.
+pub fn check_entry_symbol(library: &Path, entry_symbol: &str) -> Result<()> {
+    let symbols = exported_symbols(library)?;
+    if symbols.iter().any(|symbol| symbol == entry_symbol) {
+        return Ok(());
+    }
+    Err(anyhow!(
+        "{library:?} does not export the entry symbol `{entry_symbol}`, so Godot will fail to \
+        load it.{}",
+        entry_symbol_hint(&symbols)
+    ))
+}
+
+/// Suggest the exported symbols which look like GDExtension entry symbols.
+fn entry_symbol_hint(symbols: &[String]) -> String {
+    let candidates: Vec<&str> = symbols
+        .iter()
+        .map(String::as_str)
+        .filter(|symbol| symbol.ends_with("_init") && !symbol.starts_with("__"))
+        .collect();
+    if candidates.is_empty() {
+        " Is `#[gdextension]` applied to an `ExtensionLibrary` impl in the crate?".to_string()
+    } else {
+        format!(
+            " Exported symbols which look like entry symbols: {}. \
+            Set the matching `GdExtensionConfig::entry_symbol`.",
+            candidates.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_entry_symbol() {
+        let dir = tempfile::tempdir().unwrap();
+        let library = dir.path().join("libgame.so");
+        std::fs::write(&library, "not a library").unwrap();
+        assert!(check_entry_symbol(&library, "gdext_rust_init").is_err());
+
+        let symbols = ["game_init", "__cxa_init", "rust_eh_personality"].map(String::from);
+        assert!(entry_symbol_hint(&symbols).contains(": game_init."));
+        assert!(entry_symbol_hint(&[]).contains("#[gdextension]"));
+    }
+}
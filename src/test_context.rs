@@ -0,0 +1,172 @@
+//! Running Godot-in-the-loop tests from `cargo test`, see `GodotTestContext` and `godot_test!`.
+//!
+//! A `GodotTestContext` wraps a `GodotRunner` configured with `ci_defaults` and
+//! `scan_output_errors`. The project is
+//! prepared once, i.e. the `.gdextension` file is written and the project imported, and every
+//! test then runs a scene or `SceneTree` script in its own headless Godot process with the
+//! extension loaded. A test passes if Godot exits successfully without printing errors.
+//! Godot processes of a context run one at a time, since they share the project folder.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! static GODOT: LazyLock<GodotTestContext> = LazyLock::new(|| {
+//!     GodotTestContext::new(GodotRunner::create("game", Path::new("../godot")))
+//! });
+//!
+//! godot_test!(GODOT, player_spawns, scene = "res://tests/player_spawns.tscn");
+//! godot_test!(GODOT, inventory, script = "res://tests/test_inventory.gd");
+//! ```
+use crate::GodotRunner;
+use crate::output::{OutputCallback, summarize};
+use anyhow::{Result, anyhow};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// A shared fixture running Godot for tests, see the module documentation.
+pub struct GodotTestContext {
+    runner: GodotRunner,
+    /// The prepared godot project path, or the error preparing it.
+    prepared: OnceLock<std::result::Result<PathBuf, String>>,
+    /// Held while Godot runs, so tests don't launch Godot on the project concurrently.
+    running: Mutex<()>,
+}
+
+impl GodotTestContext {
+    /// Create a context running Godot with `runner`, adding `ci_defaults` and
+    /// `scan_output_errors`.
+    pub fn new(runner: GodotRunner) -> Self {
+        Self {
+            runner: runner.ci_defaults().scan_output_errors(true),
+            prepared: OnceLock::new(),
+            running: Mutex::new(()),
+        }
+    }
+
+    /// Run the scene at `scene`, e.g. `res://tests/player.tscn`, until it quits.
+    /// Returns Godot's output, or an error if Godot failed or printed errors.
+    pub fn run_scene(&self, scene: &str) -> Result<Vec<String>> {
+        self.run(vec![scene.to_string()])
+    }
+
+    /// Run the `SceneTree` script at `script`, e.g. `res://tests/test_inventory.gd`, which must
+    /// `quit()` with a non-zero exit code to fail.
+    /// Returns Godot's output, or an error if Godot failed or printed errors.
+    pub fn run_script(&self, script: &str) -> Result<Vec<String>> {
+        self.run(vec!["--script".to_string(), script.to_string()])
+    }
+
+    fn run(&self, extra: Vec<String>) -> Result<Vec<String>> {
+        let godot_project_path = self
+            .prepared
+            .get_or_init(|| {
+                self.runner
+                    .prepare_checked()
+                    .map(|prepared| prepared.godot_project_path)
+                    .map_err(|e| format!("{e:#}"))
+            })
+            .as_ref()
+            .map_err(|e| anyhow!("Failed to prepare the godot project: {e}"))?;
+
+        let lines = Arc::new(Mutex::new(vec![]));
+        let on_line: OutputCallback = {
+            let lines = lines.clone();
+            Arc::new(move |line: &str| {
+                if let Ok(mut lines) = lines.lock() {
+                    lines.push(line.to_string());
+                }
+            })
+        };
+        let mut args = self.runner.godot_arguments();
+        args.extend(extra);
+
+        let _running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        let (status, errors) = self
+            .runner
+            .launch_watched(godot_project_path, &args, Some(on_line))?
+            .wait_with_errors()?;
+        let lines = std::mem::take(&mut *lines.lock().unwrap_or_else(|e| e.into_inner()));
+        if !status.is_success() || !errors.is_empty() {
+            return Err(anyhow!(
+                "{status}\n{}\nOutput:\n{}",
+                summarize(&errors),
+                lines.join("\n")
+            ));
+        }
+        Ok(lines)
+    }
+}
+
+/// Define a `#[test]` running a scene or script with a `GodotTestContext`.
+///
+/// Example usage:
+/// ```rust,ignore
+/// godot_test!(GODOT, player_spawns, scene = "res://tests/player_spawns.tscn");
+/// godot_test!(GODOT, inventory, script = "res://tests/test_inventory.gd");
+/// ```
+#[macro_export]
+macro_rules! godot_test {
+    ($context:expr, $name:ident, scene = $scene:expr) => {
+        #[test]
+        fn $name() {
+            if let Err(e) = $context.run_scene($scene) {
+                panic!("{e:?}");
+            }
+        }
+    };
+    ($context:expr, $name:ident, script = $script:expr) => {
+        #[test]
+        fn $name() {
+            if let Err(e) = $context.run_script($script) {
+                panic!("{e:?}");
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prepare_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let context =
+            GodotTestContext::new(GodotRunner::create("game", &dir.path().join("missing")));
+        let error = context.run_scene("res://main.tscn").unwrap_err();
+        assert!(format!("{error:#}").contains("Failed to prepare the godot project"));
+        // The failure is remembered instead of preparing again for every test.
+        assert!(context.run_script("res://test.gd").is_err());
+        assert!(
+            context
+                .prepared
+                .get()
+                .is_some_and(|prepared| prepared.is_err())
+        );
+
+        // Contexts are shared between test threads in a static.
+        fn assert_sync<T: Sync>() {}
+        assert_sync::<GodotTestContext>();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_errors() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("godot");
+        std::fs::create_dir_all(project.join(".godot")).unwrap();
+        std::fs::write(project.join("project.godot"), "config_version=5").unwrap();
+        // A fake Godot printing an error but exiting successfully.
+        let godot = dir.path().join("godot.sh");
+        std::fs::write(&godot, "#!/bin/sh\necho 'SCRIPT ERROR: Invalid call.'\n").unwrap();
+        std::fs::set_permissions(&godot, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let context = GodotTestContext::new(
+            GodotRunner::create("game", &project)
+                .godot_binary_path(&godot)
+                .write_gdextension_config(false),
+        );
+        let error = context.run_script("res://test.gd").unwrap_err();
+        assert!(error.to_string().contains("Invalid call."), "{error:#}");
+    }
+}
@@ -0,0 +1,588 @@
+//! Publishing exported games to storefronts with their command line tools: itch.io with
+//! `butler` and Steam with `steamcmd`, and to GitHub releases.
+//!
+//! Both tools must be installed and logged in: `butler` with `butler login` or the
+//! `BUTLER_API_KEY` environment variable, `steamcmd` with cached credentials of the build account.
+//! GitHub releases are uploaded with the token in the `GITHUB_TOKEN` or `GH_TOKEN` environment
+//! variable and require the `github-release` feature.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let bundle = export::export_project(godot, None, "Linux", ExportMode::Release, output)?;
+//! let publishers = [
+//!     Publisher::from(ItchButler::new("me", "my-game", "linux").user_version("1.2.0")),
+//!     Publisher::from(SteamPipe::new(480, 481).username("builder").set_live("beta")),
+//!     Publisher::from(GitHubRelease::new("me/my-game", "v1.2.0")),
+//! ];
+//! for publisher in &publishers {
+//!     publisher.publish(bundle.parent().unwrap())?;
+//! }
+//! ```
+use crate::state::hash_file;
+use anyhow::{Context, Result, anyhow};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use which::which;
+
+/// Uploads to an itch.io channel with `butler push`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ItchButler {
+    user: String,
+    game: String,
+    channel: String,
+    user_version: Option<String>,
+    if_changed: bool,
+}
+
+impl ItchButler {
+    /// Push to `user/game:channel`, e.g. `ItchButler::new("me", "my-game", "windows")`.
+    pub fn new(user: &str, game: &str, channel: &str) -> Self {
+        Self {
+            user: user.to_string(),
+            game: game.to_string(),
+            channel: channel.to_string(),
+            user_version: None,
+            if_changed: false,
+        }
+    }
+
+    /// The version shown on itch.io instead of butler's build number (`--userversion`).
+    pub fn user_version(self, version: &str) -> Self {
+        Self {
+            user_version: Some(version.to_string()),
+            ..self
+        }
+    }
+
+    /// Skip the upload if the artifact is unchanged since the last push (`--if-changed`).
+    /// Default: false.
+    pub fn if_changed(self, if_changed: bool) -> Self {
+        Self { if_changed, ..self }
+    }
+
+    /// The butler target, e.g. `me/my-game:windows`.
+    pub fn target(&self) -> String {
+        format!("{}/{}:{}", self.user, self.game, self.channel)
+    }
+
+    /// Push the exported folder or archive `artifact`.
+    pub fn upload(&self, artifact: &Path) -> Result<()> {
+        let butler = which("butler").context(
+            "Failed to find butler, which is required to upload to itch.io \
+            (https://itch.io/docs/butler)",
+        )?;
+        let mut command = Command::new(butler);
+        command.args(self.args(artifact)).stdin(Stdio::null());
+        run(command)
+    }
+
+    fn args(&self, artifact: &Path) -> Vec<OsString> {
+        let mut args: Vec<OsString> = vec!["push".into(), artifact.into(), self.target().into()];
+        if let Some(version) = &self.user_version {
+            args.extend(["--userversion".into(), version.into()]);
+        }
+        if self.if_changed {
+            args.push("--if-changed".into());
+        }
+        args
+    }
+}
+
+/// Uploads a build to a Steam depot with `steamcmd +run_app_build`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SteamPipe {
+    appid: u32,
+    depot: u32,
+    username: Option<String>,
+    description: Option<String>,
+    set_live: Option<String>,
+}
+
+impl SteamPipe {
+    /// Upload to the `depot` of the Steam app `appid`.
+    pub fn new(appid: u32, depot: u32) -> Self {
+        Self {
+            appid,
+            depot,
+            username: None,
+            description: None,
+            set_live: None,
+        }
+    }
+
+    /// The Steam account logged in with `steamcmd`, whose credentials must be cached.
+    /// Default: the `STEAM_USERNAME` environment variable.
+    pub fn username(self, username: &str) -> Self {
+        Self {
+            username: Some(username.to_string()),
+            ..self
+        }
+    }
+
+    /// The description of the build shown in Steamworks.
+    pub fn description(self, description: &str) -> Self {
+        Self {
+            description: Some(description.to_string()),
+            ..self
+        }
+    }
+
+    /// Set the build live on the beta `branch` after uploading. Steam doesn't allow setting
+    /// the `default` branch live this way.
+    pub fn set_live(self, branch: &str) -> Self {
+        Self {
+            set_live: Some(branch.to_string()),
+            ..self
+        }
+    }
+
+    /// Upload the exported folder or file `artifact` as the content of the depot.
+    pub fn upload(&self, artifact: &Path) -> Result<()> {
+        let username = match &self.username {
+            Some(username) => username.clone(),
+            None => std::env::var("STEAM_USERNAME").context(
+                "No Steam account to upload with, set it with `SteamPipe::username` or \
+                the STEAM_USERNAME environment variable",
+            )?,
+        };
+        let steamcmd = which("steamcmd")
+            .or_else(|_| which("steamcmd.sh"))
+            .context(
+                "Failed to find steamcmd, which is required to upload to Steam \
+                (https://developer.valvesoftware.com/wiki/SteamCMD)",
+            )?;
+
+        let artifact = std::path::absolute(artifact)
+            .with_context(|| format!("Failed to make {artifact:?} absolute"))?;
+        let (content_root, local_path) = if artifact.is_dir() {
+            (artifact.clone(), "*".to_string())
+        } else {
+            let file_name = artifact
+                .file_name()
+                .with_context(|| format!("Unexpected artifact path: {artifact:?}"))?;
+            (
+                artifact.parent().map(Path::to_path_buf).unwrap_or_default(),
+                file_name.to_string_lossy().to_string(),
+            )
+        };
+        let build_dir = tempfile::tempdir().context("Failed to create a temporary directory")?;
+        let script = build_dir
+            .path()
+            .join(format!("app_build_{}.vdf", self.appid));
+        std::fs::write(
+            &script,
+            self.app_build_script(&content_root, &local_path, &build_dir.path().join("output")),
+        )
+        .with_context(|| format!("Failed to write file: {script:?}"))?;
+
+        let mut command = Command::new(steamcmd);
+        command
+            .arg("+login")
+            .arg(username)
+            .arg("+run_app_build")
+            .arg(&script)
+            .arg("+quit")
+            .stdin(Stdio::null());
+        run(command)
+    }
+
+    /// The app build script uploading `local_path` in `content_root` to the depot.
+    fn app_build_script(
+        &self,
+        content_root: &Path,
+        local_path: &str,
+        build_output: &Path,
+    ) -> String {
+        let mut script = format!(
+            "\"AppBuild\"\n{{\n\t\"AppID\" \"{}\"\n\t\"Desc\" \"{}\"\n\
+            \t\"ContentRoot\" \"{}\"\n\t\"BuildOutput\" \"{}\"\n",
+            self.appid,
+            escape_vdf(self.description.as_deref().unwrap_or_default()),
+            escape_vdf(&content_root.to_string_lossy()),
+            escape_vdf(&build_output.to_string_lossy()),
+        );
+        if let Some(branch) = &self.set_live {
+            script.push_str(&format!("\t\"SetLive\" \"{}\"\n", escape_vdf(branch)));
+        }
+        script.push_str(&format!(
+            "\t\"Depots\"\n\t{{\n\t\t\"{}\"\n\t\t{{\n\t\t\t\"FileMapping\"\n\t\t\t{{\n\
+            \t\t\t\t\"LocalPath\" \"{}\"\n\t\t\t\t\"DepotPath\" \".\"\n\t\t\t\t\"recursive\" \"1\"\n\
+            \t\t\t}}\n\t\t}}\n\t}}\n}}\n",
+            self.depot,
+            escape_vdf(local_path),
+        ));
+        script
+    }
+}
+
+/// Uploads files to the GitHub release of a tag, creating the release if it doesn't exist.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GitHubRelease {
+    repository: String,
+    tag: String,
+    name: Option<String>,
+    body: Option<String>,
+    draft: bool,
+    prerelease: bool,
+    checksums: bool,
+}
+
+/// A file uploaded to a GitHub release.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum ReleaseAsset {
+    File { name: String, path: PathBuf },
+    Text { name: String, contents: String },
+}
+
+impl ReleaseAsset {
+    #[cfg_attr(not(feature = "github-release"), allow(dead_code))]
+    fn name(&self) -> &str {
+        match self {
+            Self::File { name, .. } | Self::Text { name, .. } => name,
+        }
+    }
+}
+
+impl GitHubRelease {
+    /// Upload to the release of `tag` in `repository`, e.g.
+    /// `GitHubRelease::new("me/my-game", "v1.2.0")`.
+    pub fn new(repository: &str, tag: &str) -> Self {
+        Self {
+            repository: repository.to_string(),
+            tag: tag.to_string(),
+            name: None,
+            body: None,
+            draft: false,
+            prerelease: false,
+            checksums: true,
+        }
+    }
+
+    /// The title of the release. Default: the tag.
+    pub fn name(self, name: &str) -> Self {
+        Self {
+            name: Some(name.to_string()),
+            ..self
+        }
+    }
+
+    /// The description of the release, e.g. the changelog.
+    pub fn body(self, body: &str) -> Self {
+        Self {
+            body: Some(body.to_string()),
+            ..self
+        }
+    }
+
+    /// Create the release as a draft. Ignored if the release exists. Default: false.
+    pub fn draft(self, draft: bool) -> Self {
+        Self { draft, ..self }
+    }
+
+    /// Create the release as a pre-release. Ignored if the release exists. Default: false.
+    pub fn prerelease(self, prerelease: bool) -> Self {
+        Self { prerelease, ..self }
+    }
+
+    /// Upload a `<file>.sha256` checksum file next to every file. Default: true.
+    pub fn checksums(self, checksums: bool) -> Self {
+        Self { checksums, ..self }
+    }
+
+    /// Upload the `files`, e.g. bundled zip archives, replacing assets with the same name.
+    /// The release is created if needed, and its name and body are updated if set.
+    pub fn upload(&self, files: &[PathBuf]) -> Result<()> {
+        let assets = self.assets(files)?;
+        upload_release_assets(self, &assets).with_context(|| {
+            format!(
+                "Failed to upload to release {} of {}",
+                self.tag, self.repository
+            )
+        })
+    }
+
+    /// The release assets of `files`: each file and, with `checksums`, its checksum file in the
+    /// format of `sha256sum`.
+    fn assets(&self, files: &[PathBuf]) -> Result<Vec<ReleaseAsset>> {
+        let mut assets = vec![];
+        for path in files {
+            if !path.is_file() {
+                return Err(anyhow!(
+                    "Only files can be uploaded to GitHub releases, e.g. a zip archive of a \
+                    bundle: {path:?}"
+                ));
+            }
+            let name = path
+                .file_name()
+                .with_context(|| format!("Unexpected file path: {path:?}"))?
+                .to_string_lossy()
+                .to_string();
+            let checksum = if self.checksums {
+                Some(ReleaseAsset::Text {
+                    name: format!("{name}.sha256"),
+                    contents: format!("{}  {name}\n", hash_file(path)?),
+                })
+            } else {
+                None
+            };
+            assets.push(ReleaseAsset::File {
+                name,
+                path: path.clone(),
+            });
+            assets.extend(checksum);
+        }
+        Ok(assets)
+    }
+}
+
+/// Create or update the release and upload the `assets` with the GitHub REST API.
+#[cfg(feature = "github-release")]
+fn upload_release_assets(release: &GitHubRelease, assets: &[ReleaseAsset]) -> Result<()> {
+    #[derive(serde::Deserialize)]
+    struct Release {
+        id: u64,
+        upload_url: String,
+        assets: Vec<Asset>,
+    }
+    #[derive(serde::Deserialize)]
+    struct Asset {
+        id: u64,
+        name: String,
+    }
+
+    let token = std::env::var("GITHUB_TOKEN")
+        .or_else(|_| std::env::var("GH_TOKEN"))
+        .context("Set the GITHUB_TOKEN or GH_TOKEN environment variable to upload to GitHub")?;
+    let authorization = format!("Bearer {token}");
+    // Set by GitHub Actions, also for GitHub Enterprise Server.
+    let api_url =
+        std::env::var("GITHUB_API_URL").unwrap_or_else(|_| "https://api.github.com".to_string());
+    let releases_url = format!("{api_url}/repos/{}/releases", release.repository);
+
+    let mut fields = serde_json::Map::new();
+    if let Some(name) = &release.name {
+        fields.insert("name".to_string(), name.clone().into());
+    }
+    if let Some(body) = &release.body {
+        fields.insert("body".to_string(), body.clone().into());
+    }
+    let tag_url = format!("{releases_url}/tags/{}", release.tag);
+    let existing = github_request(ureq::get(&tag_url), &authorization).call();
+    let mut response = match existing {
+        Ok(response) if fields.is_empty() => response,
+        Ok(mut response) => {
+            let existing: Release = serde_json::from_str(&response.body_mut().read_to_string()?)
+                .with_context(|| format!("Unexpected GitHub response: {tag_url}"))?;
+            let url = format!("{releases_url}/{}", existing.id);
+            github_request(ureq::patch(&url), &authorization)
+                .header("Content-Type", "application/json")
+                .send(serde_json::Value::Object(fields).to_string())
+                .with_context(|| format!("Failed to update the release: {url}"))?
+        }
+        Err(ureq::Error::StatusCode(404)) => {
+            fields.insert("tag_name".to_string(), release.tag.clone().into());
+            fields.insert("draft".to_string(), release.draft.into());
+            fields.insert("prerelease".to_string(), release.prerelease.into());
+            github_request(ureq::post(&releases_url), &authorization)
+                .header("Content-Type", "application/json")
+                .send(serde_json::Value::Object(fields).to_string())
+                .with_context(|| format!("Failed to create the release: {releases_url}"))?
+        }
+        Err(e) => return Err(e).with_context(|| format!("Failed to query the release: {tag_url}")),
+    };
+    let github_release: Release = serde_json::from_str(&response.body_mut().read_to_string()?)
+        .with_context(|| format!("Unexpected GitHub response: {tag_url}"))?;
+
+    // The upload URL is a template like `https://uploads.github.com/.../assets{?name,label}`.
+    let upload_url = github_release
+        .upload_url
+        .split('{')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    for asset in assets {
+        if let Some(existing) = github_release
+            .assets
+            .iter()
+            .find(|existing| existing.name == asset.name())
+        {
+            let url = format!("{releases_url}/assets/{}", existing.id);
+            github_request(ureq::delete(&url), &authorization)
+                .call()
+                .with_context(|| format!("Failed to replace the asset {}", asset.name()))?;
+        }
+        let contents = match asset {
+            ReleaseAsset::File { path, .. } => {
+                std::fs::read(path).with_context(|| format!("Failed to read {path:?}"))?
+            }
+            ReleaseAsset::Text { contents, .. } => contents.clone().into_bytes(),
+        };
+        let url = format!("{upload_url}?name={}", encode_query(asset.name()));
+        github_request(ureq::post(&url), &authorization)
+            .header("Content-Type", "application/octet-stream")
+            .send(&contents[..])
+            .with_context(|| format!("Failed to upload the asset {}", asset.name()))?;
+    }
+    Ok(())
+}
+
+/// Add the authorization and the headers GitHub's REST API requires to `request`.
+#[cfg(feature = "github-release")]
+fn github_request<B>(
+    request: ureq::RequestBuilder<B>,
+    authorization: &str,
+) -> ureq::RequestBuilder<B> {
+    request
+        .header("Authorization", authorization)
+        .header("Accept", "application/vnd.github+json")
+        .header("User-Agent", "cargo-godot-lib")
+}
+
+#[cfg(not(feature = "github-release"))]
+fn upload_release_assets(release: &GitHubRelease, _assets: &[ReleaseAsset]) -> Result<()> {
+    Err(anyhow!(
+        "Uploading to the GitHub release {} requires the `github-release` feature of \
+        cargo-godot-lib",
+        release.tag
+    ))
+}
+
+/// Percent-encode `value` for use in a URL query.
+#[cfg_attr(not(feature = "github-release"), allow(dead_code))]
+fn encode_query(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// A storefront exported games are published to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Publisher {
+    Itch(ItchButler),
+    Steam(SteamPipe),
+    GitHub(GitHubRelease),
+}
+
+impl Publisher {
+    /// Upload the exported folder or archive `artifact`.
+    /// GitHub releases only accept files, e.g. a zip archive of a bundle.
+    pub fn publish(&self, artifact: &Path) -> Result<()> {
+        match self {
+            Self::Itch(butler) => butler.upload(artifact),
+            Self::Steam(steam_pipe) => steam_pipe.upload(artifact),
+            Self::GitHub(release) => release.upload(&[artifact.to_path_buf()]),
+        }
+    }
+}
+
+impl From<ItchButler> for Publisher {
+    fn from(butler: ItchButler) -> Self {
+        Self::Itch(butler)
+    }
+}
+
+impl From<SteamPipe> for Publisher {
+    fn from(steam_pipe: SteamPipe) -> Self {
+        Self::Steam(steam_pipe)
+    }
+}
+
+impl From<GitHubRelease> for Publisher {
+    fn from(release: GitHubRelease) -> Self {
+        Self::GitHub(release)
+    }
+}
+
+/// Run an upload `command`, passing its output through.
+fn run(mut command: Command) -> Result<()> {
+    let status = command
+        .status()
+        .with_context(|| format!("Failed to run upload: {command:?}"))?;
+    if !status.success() {
+        return Err(anyhow!("Upload failed with status `{status}`: {command:?}"));
+    }
+    Ok(())
+}
+
+/// Escape a string for use inside a quoted VDF string.
+fn escape_vdf(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upload_commands() {
+        let butler = ItchButler::new("me", "my-game", "linux")
+            .user_version("1.2.0")
+            .if_changed(true);
+        assert_eq!(
+            butler.args(Path::new("build/linux")),
+            [
+                "push",
+                "build/linux",
+                "me/my-game:linux",
+                "--userversion",
+                "1.2.0",
+                "--if-changed"
+            ]
+        );
+
+        let script = SteamPipe::new(480, 481)
+            .description("Nightly \"build\"")
+            .set_live("beta")
+            .app_build_script(Path::new("/build/windows"), "*", Path::new("/tmp/out"));
+        assert!(script.starts_with("\"AppBuild\"\n{\n\t\"AppID\" \"480\"\n"));
+        assert!(script.contains("\t\"Desc\" \"Nightly \\\"build\\\"\"\n"));
+        assert!(script.contains("\t\"ContentRoot\" \"/build/windows\"\n"));
+        assert!(script.contains("\t\"SetLive\" \"beta\"\n"));
+        assert!(script.contains("\t\t\"481\"\n\t\t{\n\t\t\t\"FileMapping\""));
+        assert!(script.ends_with("\t\t\t}\n\t\t}\n\t}\n}\n"));
+    }
+
+    #[test]
+    fn test_release_assets() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("game linux.zip");
+        std::fs::write(&archive, "abc").unwrap();
+        let release = GitHubRelease::new("me/my-game", "v1.2.0");
+        assert_eq!(
+            release.assets(std::slice::from_ref(&archive)).unwrap(),
+            [
+                ReleaseAsset::File {
+                    name: "game linux.zip".to_string(),
+                    path: archive.clone(),
+                },
+                ReleaseAsset::Text {
+                    name: "game linux.zip.sha256".to_string(),
+                    contents: "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  \
+                        game linux.zip\n"
+                        .to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            release
+                .checksums(false)
+                .assets(std::slice::from_ref(&archive))
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(
+            GitHubRelease::new("me/my-game", "v1.2.0")
+                .assets(&[dir.path().to_path_buf()])
+                .is_err()
+        );
+        assert_eq!(encode_query("game linux.zip"), "game%20linux.zip");
+    }
+}
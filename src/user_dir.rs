@@ -0,0 +1,102 @@
+//! Temporary data directories isolating a Godot run from the user's real `user://` data,
+//! editor settings and caches.
+//!
+//! Godot derives these directories from the OS data, config and cache directories, so the runner
+//! points the corresponding environment variables at a temporary directory:
+//! `XDG_DATA_HOME`, `XDG_CONFIG_HOME` and `XDG_CACHE_HOME` on Linux and macOS, and `APPDATA`
+//! and `LOCALAPPDATA` on Windows.
+use anyhow::{Context, Result};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// A temporary directory holding Godot's data, config and cache directories for one run.
+/// The directory is deleted when dropped, unless it was created with `keep` set.
+#[derive(Debug)]
+pub struct IsolatedUserDir {
+    path: PathBuf,
+    temp_dir: Option<TempDir>,
+}
+
+impl IsolatedUserDir {
+    /// Create a new temporary directory. If `keep` is true it is not deleted when dropped.
+    pub fn create(keep: bool) -> Result<Self> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("cargo-godot-lib-user-")
+            .tempdir()
+            .context("Failed to create temporary user data directory")?;
+        let path = temp_dir.path().to_path_buf();
+        for (_, dir) in environment(&path) {
+            std::fs::create_dir_all(&dir)
+                .with_context(|| format!("Failed to create directory: {dir:?}"))?;
+        }
+        Ok(Self {
+            path,
+            temp_dir: if keep {
+                let _ = temp_dir.keep();
+                None
+            } else {
+                Some(temp_dir)
+            },
+        })
+    }
+
+    /// The root of the temporary directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The environment variables pointing Godot at this directory.
+    pub fn environment(&self) -> Vec<(OsString, OsString)> {
+        environment(&self.path)
+    }
+
+    /// Returns true if the directory is deleted when dropped.
+    pub fn is_temporary(&self) -> bool {
+        self.temp_dir.is_some()
+    }
+}
+
+#[cfg(not(windows))]
+fn environment(root: &Path) -> Vec<(OsString, OsString)> {
+    [
+        ("XDG_DATA_HOME", "data"),
+        ("XDG_CONFIG_HOME", "config"),
+        ("XDG_CACHE_HOME", "cache"),
+    ]
+    .into_iter()
+    .map(|(key, dir)| (key.into(), root.join(dir).into()))
+    .collect()
+}
+
+#[cfg(windows)]
+fn environment(root: &Path) -> Vec<(OsString, OsString)> {
+    [("APPDATA", "Roaming"), ("LOCALAPPDATA", "Local")]
+        .into_iter()
+        .map(|(key, dir)| (key.into(), root.join(dir).into()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_isolated_user_dir() {
+        let dir = IsolatedUserDir::create(false).unwrap();
+        let path = dir.path().to_path_buf();
+        assert!(dir.is_temporary());
+        for (_, value) in dir.environment() {
+            assert!(Path::new(&value).starts_with(&path));
+            assert!(Path::new(&value).is_dir());
+        }
+        drop(dir);
+        assert!(!path.exists());
+
+        let dir = IsolatedUserDir::create(true).unwrap();
+        let path = dir.path().to_path_buf();
+        drop(dir);
+        assert!(path.exists());
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
@@ -0,0 +1,30 @@
+//! How much `GodotRunner` itself prints, see `GodotRunner::verbosity`. This is independent of
+//! Godot's own output, which `--verbose` or `--quiet` controls.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let runner = GodotRunner::create("game", Path::new("godot")).verbosity(Verbosity::Debug);
+//! ```
+
+/// The levels of runner messages, each printing everything the previous one prints.
+#[derive(Clone, Copy, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Verbosity {
+    /// Only warnings.
+    Quiet,
+    /// Warnings and what the runner did, e.g. skipping an unchanged run.
+    #[default]
+    Normal,
+    /// Also the chosen Godot binary and the paths of generated files.
+    Verbose,
+    /// Also the full command line of every Godot launch.
+    Debug,
+}
+
+impl Verbosity {
+    /// Print `message` to stderr if this verbosity includes messages of `level`.
+    pub fn log(self, level: Verbosity, message: impl std::fmt::Display) {
+        if self >= level {
+            eprintln!("{message}");
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Stamping the crate version into the godot project, see `GodotRunner::version_stamp`.
+//!
+//! The version is written into `project.godot` or a generated autoload script rather than
+//! `override.cfg`, because exports don't include `override.cfg`. A stamped project can show the
+//! exact cargo build it runs with, e.g. `ProjectSettings.get_setting("application/config/version")`
+//! or `CargoVersion.VERSION`.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let version = version_stamp::crate_version(Path::new("rust/Cargo.toml"), "game")?;
+//! VersionStamp::autoload("CargoVersion", "version.gd").stamp(Path::new("godot"), "game", &version)?;
+//! export_project(Path::new("godot"), None, "Linux", ExportMode::Release, Path::new("build/game.x86_64"))?;
+//! ```
+use crate::project_config::{self, SettingValue};
+use anyhow::{Context, Result, anyhow};
+use std::path::{Path, PathBuf};
+
+/// The project setting written by `VersionStamp::default`.
+pub const DEFAULT_SETTING: &str = "application/config/version";
+
+const VERSION_SCRIPT: &str = r#"extends Node
+## Generated by cargo-godot-lib, changes are overwritten.
+
+const CRATE_NAME := {crate_name}
+const VERSION := {version}
+"#;
+
+/// Where the crate version is written.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum VersionStamp {
+    /// The project setting with this name, e.g. `application/config/version`.
+    Setting(String),
+    /// A script exposing `CRATE_NAME` and `VERSION` constants, registered as an autoload.
+    Autoload {
+        /// The name of the autoload, e.g. `CargoVersion`.
+        name: String,
+        /// The path of the script, relative to the godot project, e.g. `version.gd`.
+        path: String,
+    },
+}
+
+impl Default for VersionStamp {
+    /// Writes the `application/config/version` setting.
+    fn default() -> Self {
+        Self::setting(DEFAULT_SETTING)
+    }
+}
+
+impl VersionStamp {
+    /// Write the version into the project setting `name`, e.g. `application/config/version`.
+    pub fn setting(name: &str) -> Self {
+        Self::Setting(name.to_string())
+    }
+
+    /// Write the version into a script at `path`, relative to the godot project, and register
+    /// it as the autoload `name`.
+    pub fn autoload(name: &str, path: &str) -> Self {
+        Self::Autoload {
+            name: name.to_string(),
+            path: path.to_string(),
+        }
+    }
+
+    /// Write `version` into the godot project, updating files whose contents changed.
+    /// Returns the written files.
+    pub fn stamp(
+        &self,
+        godot_project_path: &Path,
+        crate_name: &str,
+        version: &str,
+    ) -> Result<Vec<PathBuf>> {
+        let project_file = project_config::ProjectConfig::path(godot_project_path);
+        let mut written = vec![];
+        match self {
+            Self::Setting(name) => {
+                let (section, key) = name.split_once('/').with_context(|| {
+                    format!("Project setting names have the form `section/key`, got {name:?}")
+                })?;
+                let value = SettingValue::from(version);
+                if project_config::write_setting(godot_project_path, section, key, Some(&value))? {
+                    written.push(project_file);
+                }
+            }
+            Self::Autoload { name, path } => {
+                let quote = |value: &str| SettingValue::from(value).to_variant_string();
+                let contents = VERSION_SCRIPT
+                    .replace("{crate_name}", &quote(crate_name))
+                    .replace("{version}", &quote(version));
+                let script = godot_project_path.join(path);
+                if !std::fs::read_to_string(&script).is_ok_and(|existing| existing == contents) {
+                    if let Some(parent) = script.parent() {
+                        std::fs::create_dir_all(parent)
+                            .with_context(|| format!("Failed to create directory: {parent:?}"))?;
+                    }
+                    std::fs::write(&script, contents)
+                        .with_context(|| format!("Failed to write {script:?}"))?;
+                    written.push(script);
+                }
+                let value = SettingValue::from(format!("*res://{path}"));
+                if project_config::write_setting(
+                    godot_project_path,
+                    "autoload",
+                    name,
+                    Some(&value),
+                )? {
+                    written.push(project_file);
+                }
+            }
+        }
+        Ok(written)
+    }
+
+    /// Remove the generated autoload script and its registration. Settings are kept.
+    /// Returns the removed or edited files.
+    pub fn remove(&self, godot_project_path: &Path) -> Result<Vec<PathBuf>> {
+        let Self::Autoload { name, path } = self else {
+            return Ok(vec![]);
+        };
+        let mut changed = vec![];
+        let script = godot_project_path.join(path);
+        if script.exists() {
+            std::fs::remove_file(&script)
+                .with_context(|| format!("Failed to remove {script:?}"))?;
+            changed.push(script);
+        }
+        if project_config::write_setting(godot_project_path, "autoload", name, None)? {
+            changed.push(project_config::ProjectConfig::path(godot_project_path));
+        }
+        Ok(changed)
+    }
+}
+
+/// The version of the package `crate_name` in the workspace of `cargo_manifest_path`,
+/// according to `cargo metadata`.
+pub fn crate_version(cargo_manifest_path: &Path, crate_name: &str) -> Result<String> {
+    let metadata = cargo_metadata::MetadataCommand::new()
+        .manifest_path(cargo_manifest_path)
+        .no_deps()
+        .exec()
+        .context("Failed to read cargo metadata")?;
+    metadata
+        .packages
+        .iter()
+        .find(|package| package.name.replace('-', "_") == crate_name.replace('-', "_"))
+        .map(|package| package.version.to_string())
+        .ok_or_else(|| anyhow!("Package {crate_name:?} not found in {cargo_manifest_path:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stamp() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path();
+        std::fs::write(
+            project.join("project.godot"),
+            "config_version=5\n\n[application]\n\nconfig/name=\"Game\"\n",
+        )
+        .unwrap();
+
+        let written = VersionStamp::default()
+            .stamp(project, "game", "1.2.0")
+            .unwrap();
+        assert_eq!(written, vec![project.join("project.godot")]);
+        let config = std::fs::read_to_string(project.join("project.godot")).unwrap();
+        assert!(config.contains("config/name=\"Game\"\nconfig/version=\"1.2.0\"\n"));
+        assert!(
+            VersionStamp::default()
+                .stamp(project, "game", "1.2.0")
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            VersionStamp::setting("version")
+                .stamp(project, "game", "1.2.0")
+                .is_err()
+        );
+
+        let autoload = VersionStamp::autoload("CargoVersion", "generated/version.gd");
+        let written = autoload.stamp(project, "game", "1.2.0").unwrap();
+        assert_eq!(written.len(), 2);
+        let script = std::fs::read_to_string(project.join("generated/version.gd")).unwrap();
+        assert!(script.contains("const VERSION := \"1.2.0\""));
+        let config = std::fs::read_to_string(project.join("project.godot")).unwrap();
+        assert!(config.contains("CargoVersion=\"*res://generated/version.gd\""));
+
+        assert_eq!(autoload.remove(project).unwrap().len(), 2);
+        assert!(!project.join("generated/version.gd").exists());
+        assert!(autoload.remove(project).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_crate_version() {
+        let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        assert_eq!(
+            crate_version(&manifest, env!("CARGO_PKG_NAME")).unwrap(),
+            env!("CARGO_PKG_VERSION")
+        );
+        assert!(crate_version(&manifest, "missing").is_err());
+    }
+}
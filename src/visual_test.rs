@@ -0,0 +1,411 @@
+//! Golden image testing of frames rendered by a Godot scene. Requires the `visual-test` feature.
+//!
+//! `run` launches a scene with a fixed frame rate, captures a frame through a small GDScript
+//! autoload which is injected for the run, and compares the frame to a golden PNG.
+//! The capture needs a rendering driver, so it doesn't work with `--headless`. On CI machines
+//! without a GPU, run Godot under a virtual display (e.g. `xvfb-run`) with
+//! `Renderer::Compatibility` and software OpenGL.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let test = VisualTest::new("res://tests/sprites.tscn", "tests/golden/sprites.png")
+//!     .resolution(640, 360)
+//!     .tolerance(0.001);
+//! let diff = cargo_godot_lib::visual_test::run(&runner, &test)?;
+//! assert!(diff.passed(), "{diff}");
+//! ```
+use crate::autoload::TemporaryAutoload;
+use crate::{GodotRunner, push_user_argument};
+use anyhow::{Context, Result, anyhow};
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+/// The name of the injected capture autoload.
+const CAPTURE_AUTOLOAD: &str = "CargoGodotLibVisualTest";
+
+/// Saves the viewport to `--visual-test-output` after `--visual-test-frames` frames and quits.
+const CAPTURE_SCRIPT: &str = r#"extends Node
+
+# Injected by cargo-godot-lib for golden image tests.
+
+func _ready() -> void:
+	var frames := 10
+	var output := ""
+	for arg in OS.get_cmdline_user_args():
+		if arg.begins_with("--visual-test-frames="):
+			frames = int(arg.trim_prefix("--visual-test-frames="))
+		elif arg.begins_with("--visual-test-output="):
+			output = arg.trim_prefix("--visual-test-output=")
+	for i in frames:
+		await RenderingServer.frame_post_draw
+	var error := get_viewport().get_texture().get_image().save_png(output)
+	get_tree().quit(0 if error == OK else 1)
+"#;
+
+/// A golden image test of a scene.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VisualTest {
+    scene: String,
+    golden: PathBuf,
+    frames: u32,
+    resolution: Option<(u32, u32)>,
+    tolerance: f64,
+    channel_tolerance: u8,
+    update_golden: bool,
+}
+
+impl VisualTest {
+    /// Compare the frame rendered by `scene` (a `res://` path) to the PNG at `golden`.
+    pub fn new(scene: impl Into<String>, golden: impl Into<PathBuf>) -> Self {
+        Self {
+            scene: scene.into(),
+            golden: golden.into(),
+            frames: 10,
+            resolution: None,
+            tolerance: 0.0,
+            channel_tolerance: 2,
+            update_golden: false,
+        }
+    }
+
+    /// Capture the frame after this many frames were drawn. Default: 10.
+    pub fn frames(self, frames: u32) -> Self {
+        Self {
+            frames: frames.max(1),
+            ..self
+        }
+    }
+
+    /// Render at the given window size (`--resolution`). Default: the project's window size.
+    pub fn resolution(self, width: u32, height: u32) -> Self {
+        Self {
+            resolution: Some((width, height)),
+            ..self
+        }
+    }
+
+    /// The fraction of pixels allowed to differ, from `0.0` to `1.0`. Default: 0.0.
+    pub fn tolerance(self, tolerance: f64) -> Self {
+        Self {
+            tolerance: tolerance.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+
+    /// How much a color channel may differ before a pixel counts as different,
+    /// to allow for small differences between GPUs and drivers. Default: 2.
+    pub fn channel_tolerance(self, channel_tolerance: u8) -> Self {
+        Self {
+            channel_tolerance,
+            ..self
+        }
+    }
+
+    /// Replace the golden image with the captured frame instead of comparing them,
+    /// or create it if it doesn't exist yet. Default: false.
+    pub fn update_golden(self, update_golden: bool) -> Self {
+        Self {
+            update_golden,
+            ..self
+        }
+    }
+}
+
+/// The result of comparing a captured frame to its golden image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VisualDiff {
+    /// The size of the captured frame.
+    pub size: (u32, u32),
+    /// The size of the golden image.
+    pub golden_size: (u32, u32),
+    /// The number of pixels which differ by more than the channel tolerance.
+    pub differing_pixels: u64,
+    /// The largest difference of a single color channel.
+    pub max_channel_difference: u8,
+    /// The allowed fraction of differing pixels.
+    pub tolerance: f64,
+    /// The golden image.
+    pub golden: PathBuf,
+    /// The captured frame, saved next to the golden image if the test failed.
+    pub actual: Option<PathBuf>,
+    /// An image highlighting the differing pixels in red, saved if the test failed.
+    pub diff_image: Option<PathBuf>,
+    /// True if the golden image was replaced by the captured frame.
+    pub updated_golden: bool,
+}
+
+impl VisualDiff {
+    /// The fraction of differing pixels.
+    pub fn difference(&self) -> f64 {
+        let pixels = u64::from(self.golden_size.0) * u64::from(self.golden_size.1);
+        if pixels == 0 {
+            return 0.0;
+        }
+        self.differing_pixels as f64 / pixels as f64
+    }
+
+    /// Returns true if the frame matches the golden image within the tolerance.
+    pub fn passed(&self) -> bool {
+        self.size == self.golden_size && self.difference() <= self.tolerance
+    }
+}
+
+impl Display for VisualDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.updated_golden {
+            return write!(f, "Updated golden image {:?}", self.golden);
+        }
+        if self.size != self.golden_size {
+            write!(
+                f,
+                "Captured frame is {}x{} but golden image {:?} is {}x{}",
+                self.size.0, self.size.1, self.golden, self.golden_size.0, self.golden_size.1
+            )?;
+        } else {
+            write!(
+                f,
+                "{} pixels ({:.4}%) differ from golden image {:?} (tolerance {:.4}%, max channel difference {})",
+                self.differing_pixels,
+                self.difference() * 100.0,
+                self.golden,
+                self.tolerance * 100.0,
+                self.max_channel_difference
+            )?;
+        }
+        if let Some(actual) = &self.actual {
+            write!(f, "\nCaptured frame: {actual:?}")?;
+        }
+        if let Some(diff_image) = &self.diff_image {
+            write!(f, "\nDifference: {diff_image:?}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Run `test` with the configuration of `runner` and compare the captured frame.
+/// Returns an error if the frame couldn't be captured, otherwise the comparison.
+pub fn run(runner: &GodotRunner, test: &VisualTest) -> Result<VisualDiff> {
+    let godot_project_path = runner.prepare_checked()?.godot_project_path;
+    let capture_dir = tempfile::tempdir().context("Failed to create capture directory")?;
+    let captured = capture_dir.path().join("capture.png");
+
+    let mut flags = vec![
+        vec![test.scene.clone()],
+        vec!["--fixed-fps".to_string(), "60".to_string()],
+        vec!["--disable-vsync".to_string()],
+    ];
+    if let Some((width, height)) = test.resolution {
+        flags.push(vec![
+            "--resolution".to_string(),
+            format!("{width}x{height}"),
+        ]);
+    }
+    let mut args = runner.godot_arguments_with(flags);
+    push_user_argument(&mut args, format!("--visual-test-frames={}", test.frames));
+    push_user_argument(
+        &mut args,
+        format!("--visual-test-output={}", captured.to_string_lossy()),
+    );
+    let capture = TemporaryAutoload::script(CAPTURE_AUTOLOAD, CAPTURE_SCRIPT);
+
+    runner
+        .launch_with(&godot_project_path, &args, None, &[capture])?
+        .wait()?
+        .into_result()
+        .with_context(|| format!("Failed to capture a frame of {}", test.scene))?;
+    if !captured.exists() {
+        return Err(anyhow!(
+            "Godot exited without capturing a frame of {}.\n\
+            Capturing requires a rendering driver, so it doesn't work with `--headless`.",
+            test.scene
+        ));
+    }
+
+    if test.update_golden || !test.golden.exists() {
+        if let Some(parent) = test.golden.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {parent:?}"))?;
+        }
+        std::fs::copy(&captured, &test.golden)
+            .with_context(|| format!("Failed to write golden image: {:?}", test.golden))?;
+        let image = load_rgba(&test.golden)?;
+        return Ok(VisualDiff {
+            size: image.size(),
+            golden_size: image.size(),
+            differing_pixels: 0,
+            max_channel_difference: 0,
+            tolerance: test.tolerance,
+            golden: test.golden.clone(),
+            actual: None,
+            diff_image: None,
+            updated_golden: true,
+        });
+    }
+
+    compare(
+        &captured,
+        &test.golden,
+        test.channel_tolerance,
+        test.tolerance,
+    )
+}
+
+/// Compare the PNG at `actual` to the PNG at `golden`. If they differ by more than `tolerance`,
+/// `actual` and an image of the differing pixels are saved next to `golden` as
+/// `<name>.actual.png` and `<name>.diff.png`.
+pub fn compare(
+    actual: &Path,
+    golden: &Path,
+    channel_tolerance: u8,
+    tolerance: f64,
+) -> Result<VisualDiff> {
+    let actual_image = load_rgba(actual)?;
+    let golden_image = load_rgba(golden)?;
+
+    let mut diff = VisualDiff {
+        size: actual_image.size(),
+        golden_size: golden_image.size(),
+        differing_pixels: 0,
+        max_channel_difference: 0,
+        tolerance,
+        golden: golden.to_path_buf(),
+        actual: None,
+        diff_image: None,
+        updated_golden: false,
+    };
+
+    let mut diff_image = RgbaImage {
+        width: golden_image.width,
+        height: golden_image.height,
+        pixels: vec![0; golden_image.pixels.len()],
+    };
+    if diff.size == diff.golden_size {
+        let pixels = actual_image
+            .pixels
+            .chunks_exact(4)
+            .zip(golden_image.pixels.chunks_exact(4))
+            .zip(diff_image.pixels.chunks_exact_mut(4));
+        for ((a, g), d) in pixels {
+            let difference = a.iter().zip(g).map(|(a, g)| a.abs_diff(*g)).max();
+            let difference = difference.unwrap_or_default();
+            diff.max_channel_difference = diff.max_channel_difference.max(difference);
+            if difference > channel_tolerance {
+                diff.differing_pixels += 1;
+                d.copy_from_slice(&[255, 0, 0, 255]);
+            } else {
+                // Dim the matching pixels so the differences stand out.
+                d.copy_from_slice(&[g[0] / 4, g[1] / 4, g[2] / 4, 255]);
+            }
+        }
+    } else {
+        diff.differing_pixels = u64::from(golden_image.width) * u64::from(golden_image.height);
+    }
+
+    if !diff.passed() {
+        let actual_copy = golden.with_extension("actual.png");
+        std::fs::copy(actual, &actual_copy)
+            .with_context(|| format!("Failed to save captured frame: {actual_copy:?}"))?;
+        diff.actual = Some(actual_copy);
+        if diff.size == diff.golden_size {
+            let diff_path = golden.with_extension("diff.png");
+            save_rgba(&diff_image, &diff_path)?;
+            diff.diff_image = Some(diff_path);
+        }
+    }
+    Ok(diff)
+}
+
+/// An 8-bit RGBA image.
+struct RgbaImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
+
+/// Load a PNG of any color type as 8-bit RGBA.
+fn load_rgba(path: &Path) -> Result<RgbaImage> {
+    let file = File::open(path).with_context(|| format!("Failed to open image: {path:?}"))?;
+    let mut decoder = png::Decoder::new(BufReader::new(file));
+    decoder.set_transformations(
+        png::Transformations::EXPAND | png::Transformations::STRIP_16 | png::Transformations::ALPHA,
+    );
+    let mut reader = decoder
+        .read_info()
+        .with_context(|| format!("Failed to read image: {path:?}"))?;
+    let mut buffer = vec![0; reader.output_buffer_size().context("Image is too large")?];
+    let info = reader
+        .next_frame(&mut buffer)
+        .with_context(|| format!("Failed to decode image: {path:?}"))?;
+    buffer.truncate(info.buffer_size());
+
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => buffer,
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        color_type => return Err(anyhow!("Unsupported color type {color_type:?}: {path:?}")),
+    };
+    Ok(RgbaImage {
+        width: info.width,
+        height: info.height,
+        pixels,
+    })
+}
+
+fn save_rgba(image: &RgbaImage, path: &Path) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("Failed to create image: {path:?}"))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), image.width, image.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&image.pixels))
+        .with_context(|| format!("Failed to write image: {path:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_image(path: &Path, pixels: &[[u8; 4]]) {
+        let image = RgbaImage {
+            width: 2,
+            height: 2,
+            pixels: pixels.concat(),
+        };
+        save_rgba(&image, path).unwrap();
+    }
+
+    #[test]
+    fn test_compare() {
+        let dir = tempfile::tempdir().unwrap();
+        let golden = dir.path().join("golden.png");
+        let actual = dir.path().join("capture.png");
+        let black = [0, 0, 0, 255];
+        write_image(&golden, &[black; 4]);
+
+        write_image(&actual, &[[1, 2, 0, 255], black, black, black]);
+        let diff = compare(&actual, &golden, 2, 0.0).unwrap();
+        assert!(diff.passed(), "{diff}");
+        assert_eq!(diff.max_channel_difference, 2);
+        assert_eq!(diff.actual, None);
+
+        write_image(&actual, &[[255, 255, 255, 255], black, black, black]);
+        let diff = compare(&actual, &golden, 2, 0.0).unwrap();
+        assert!(!diff.passed());
+        assert_eq!(diff.differing_pixels, 1);
+        assert_eq!(diff.difference(), 0.25);
+        assert!(dir.path().join("golden.actual.png").exists());
+        assert!(dir.path().join("golden.diff.png").exists());
+        assert!(compare(&actual, &golden, 2, 0.25).unwrap().passed());
+    }
+}
@@ -0,0 +1,192 @@
+//! Building the extension for web exports with Emscripten, see `WebBuild`.
+//!
+//! The library is built for `wasm32-unknown-emscripten` with a nightly toolchain and
+//! `-Zbuild-std`, and linked as an Emscripten side module as Godot's web export expects.
+//! Godot loads the module into its own Emscripten runtime, so the activated emsdk should have
+//! the version the export templates were built with, which `WebBuild::emscripten_version`
+//! checks. The library is added to the `web.*` entries of the `.gdextension` file with
+//! `CrossBuildOutput::gdextension_config`, which requires `Platform::Web`.
+//!
+//! Example usage:
+//! ```rust,ignore
+//! let output = WebBuild::new(CargoBuild::default().release())
+//!     .emscripten_version("3.1.64")
+//!     .build(Path::new("rust/Cargo.toml"))?;
+//! let runner = GodotRunner::create("game", Path::new("godot")).gdextension_config(move |config| {
+//!     let config = config.platforms(&[Platform::Linux, Platform::Web]);
+//!     output.gdextension_config("game", config)
+//! });
+//! ```
+use crate::cargo::{self, CargoBuild};
+use crate::cross_build::CrossBuildOutput;
+use anyhow::{Context, Result, anyhow};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// The target triple of web builds.
+pub const WEB_TRIPLE: &str = "wasm32-unknown-emscripten";
+
+/// Options for building the extension for the web, see the module documentation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WebBuild {
+    cargo_build: CargoBuild,
+    toolchain: String,
+    threads: bool,
+    emscripten_version: Option<String>,
+}
+
+impl WebBuild {
+    /// Build with the options of `cargo_build`. Its `target` is overridden. The rustflags of
+    /// the web build are appended to its `rustflags`, which like `RUSTFLAGS` replace the
+    /// rustflags of cargo's config.
+    pub fn new(cargo_build: CargoBuild) -> Self {
+        Self {
+            cargo_build: cargo_build.macos_universal(false),
+            toolchain: "nightly".to_string(),
+            threads: true,
+            emscripten_version: None,
+        }
+    }
+
+    /// The rustup toolchain to build with, which must support `-Zbuild-std`.
+    /// Default: `nightly`.
+    pub fn toolchain(self, toolchain: &str) -> Self {
+        Self {
+            toolchain: toolchain.to_string(),
+            ..self
+        }
+    }
+
+    /// Build with shared memory and atomics for exports with thread support. Without threads,
+    /// gdext's `experimental-wasm-nothreads` feature must be enabled. Default: true.
+    pub fn threads(self, threads: bool) -> Self {
+        Self { threads, ..self }
+    }
+
+    /// Fail unless the activated emsdk has this version, e.g. `3.1.64`, the version of the
+    /// Godot export templates. Default: any version.
+    pub fn emscripten_version(self, version: &str) -> Self {
+        Self {
+            emscripten_version: Some(version.to_string()),
+            ..self
+        }
+    }
+
+    /// Build the package at `manifest_path` for `wasm32-unknown-emscripten`.
+    /// Requires an activated emsdk and the `rust-src` component of the toolchain.
+    pub fn build(&self, manifest_path: &Path) -> Result<CrossBuildOutput> {
+        let version = emscripten_version()?;
+        if let Some(expected) = &self.emscripten_version
+            && *expected != version
+        {
+            return Err(anyhow!(
+                "The activated emsdk has Emscripten {version}, but {expected} is required. \
+                Run `emsdk install {expected} && emsdk activate {expected}`"
+            ));
+        }
+        let artifacts = cargo::run_build(self.command(manifest_path), "cargo build", WEB_TRIPLE)
+            .with_context(|| format!("Failed to build for {WEB_TRIPLE}"))?;
+        Ok(CrossBuildOutput {
+            build: self.cargo_build.gdextension_build(),
+            artifacts,
+        })
+    }
+
+    /// The `cargo build` command, with the rustflags of `cargo_build` and the web build.
+    fn command(&self, manifest_path: &Path) -> Command {
+        let cargo_build = self
+            .cargo_build
+            .clone()
+            .target(WEB_TRIPLE)
+            .rustflags(self.rustflags());
+        let mut command = Command::new("cargo");
+        command
+            .args(cargo_build.cli_arguments(manifest_path))
+            .arg("-Zbuild-std")
+            // `$CARGO` would bypass rustup, which selects the toolchain.
+            .env("RUSTUP_TOOLCHAIN", &self.toolchain)
+            .envs(cargo_build.env());
+        command
+    }
+
+    /// The rustflags linking the library as a side module for Godot.
+    fn rustflags(&self) -> Vec<&'static str> {
+        let mut flags = vec![];
+        if self.threads {
+            flags.extend(["-Clink-args=-pthread", "-Ctarget-feature=+atomics"]);
+        }
+        flags.extend([
+            "-Clink-args=-sSIDE_MODULE=2",
+            "-Zlink-native-libraries=no",
+            "-Cllvm-args=-enable-emscripten-cxx-exceptions=0",
+        ]);
+        flags
+    }
+}
+
+/// The version of the activated Emscripten from `emcc --version`, e.g. `3.1.64`.
+pub fn emscripten_version() -> Result<String> {
+    // `which` finds `emcc.bat` on Windows, which `Command` doesn't.
+    let emcc = which::which("emcc")
+        .context("emcc not found, install emsdk and activate it with `source ./emsdk_env.sh`")?;
+    let mut command = Command::new(emcc);
+    command.arg("--version").stdin(Stdio::null());
+    let output = command
+        .output()
+        .with_context(|| format!("Failed to run emcc: {command:?}"))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    parse_emcc_version(&stdout)
+        .with_context(|| format!("Failed to parse the version of `emcc --version`: {stdout}"))
+}
+
+/// The version in the first line of `emcc --version`, e.g.
+/// `emcc (Emscripten gcc/clang-like replacement + linker emulating GNU ld) 3.1.64 (a1fe3902)`.
+fn parse_emcc_version(output: &str) -> Option<String> {
+    output
+        .lines()
+        .next()?
+        .split_whitespace()
+        .find(|word| word.starts_with(|c: char| c.is_ascii_digit()) && word.contains('.'))
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_web_build() {
+        assert_eq!(
+            parse_emcc_version(
+                "emcc (Emscripten gcc/clang-like replacement + linker emulating GNU ld) 3.1.64 \
+                (fd61bacaf40131f74987e649a135f1dd559aff60)\nCopyright (C) 2014 ...\n"
+            )
+            .as_deref(),
+            Some("3.1.64")
+        );
+        assert_eq!(parse_emcc_version(""), None);
+
+        let build = WebBuild::new(CargoBuild::default());
+        assert!(build.rustflags().contains(&"-Ctarget-feature=+atomics"));
+        let flags = build.threads(false).rustflags();
+        assert!(!flags.contains(&"-Ctarget-feature=+atomics"));
+        assert!(flags.contains(&"-Clink-args=-sSIDE_MODULE=2"));
+
+        let command = WebBuild::new(CargoBuild::default().rustflags(["--cfg", "web_test"]))
+            .command(Path::new("Cargo.toml"));
+        let rustflags = command
+            .get_envs()
+            .find(|(key, _)| *key == "RUSTFLAGS")
+            .and_then(|(_, value)| value)
+            .unwrap()
+            .to_string_lossy();
+        assert!(rustflags.contains("--cfg web_test"));
+        assert!(rustflags.contains("-Clink-args=-sSIDE_MODULE=2"));
+        // Cargo ignores target specific rustflags when `RUSTFLAGS` is set.
+        assert!(
+            !command
+                .get_envs()
+                .any(|(key, _)| key.to_string_lossy().starts_with("CARGO_TARGET_"))
+        );
+    }
+}